@@ -0,0 +1,332 @@
+use super::Event;
+use crikey_irc_common::{
+    Casemapping, Command, Message, MessageBody, Nickname, Recipient, ReplyType, Sender,
+};
+use std::time::{Duration, Instant};
+
+/// Mark ourselves as away once there's been no activity for a while, and as
+/// back again as soon as there is.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AutoAway {
+    pub after: Duration,
+    pub message: String,
+}
+
+/// What we missed while we were away, given with `Event::Back`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct AwaySummary {
+    /// How long we were away for.
+    pub duration: Duration,
+    /// PRIVMSGs and NOTICEs sent to us directly.
+    pub private_messages: Vec<Message>,
+    /// Messages to channels that mentioned our nickname.
+    pub highlights: Vec<Message>,
+}
+
+/// Sets and clears our away message, either when asked to or automatically
+/// after a period of inactivity, and keeps the messages we miss while away.
+/// We're only considered away once the server has confirmed it with
+/// RPL_NOWAWAY, and back once it's confirmed that with RPL_UNAWAY.
+#[derive(Debug, Default)]
+pub struct Away {
+    /// The away message we want set, if we want to be away.
+    message: Option<String>,
+    /// Whether the away message was set by `AutoAway`, in which case any
+    /// activity clears it.
+    automatic: bool,
+    confirmed: bool,
+    registered: bool,
+    auto_away: Option<AutoAway>,
+    last_active: Option<Instant>,
+    since: Option<Instant>,
+    summary: AwaySummary,
+}
+
+impl Away {
+    pub fn set_auto_away(&mut self, auto_away: Option<AutoAway>) {
+        self.auto_away = auto_away;
+    }
+
+    /// Whether the server has confirmed that we're away.
+    pub fn is_away(&self) -> bool {
+        self.confirmed
+    }
+
+    /// Called once registration is complete, when the server is ready to
+    /// hear that we're away if we were asked to be before now.
+    pub fn start(&mut self, now: Instant) -> Vec<Command> {
+        self.registered = true;
+        self.last_active.get_or_insert(now);
+        match &self.message {
+            Some(message) => vec![away(Some(message.clone()))],
+            None => Vec::new(),
+        }
+    }
+
+    /// Set an away message, or clear it with `None`.
+    pub fn set_away(&mut self, message: Option<String>, now: Instant) -> Vec<Command> {
+        self.automatic = false;
+        self.change(message, now)
+    }
+
+    /// Called whenever the user does something, which brings us back if we
+    /// went away automatically.
+    pub fn active(&mut self, now: Instant) -> Vec<Command> {
+        self.last_active = Some(now);
+        if self.automatic {
+            self.automatic = false;
+            self.change(None, now)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Go away automatically if there's been no activity for long enough.
+    pub fn poll(&mut self, now: Instant) -> Vec<Command> {
+        let (auto_away, last_active) = match (&self.auto_away, self.last_active) {
+            (Some(auto_away), Some(last_active)) => (auto_away, last_active),
+            _ => return Vec::new(),
+        };
+
+        if self.message.is_some() || now.duration_since(last_active) < auto_away.after {
+            return Vec::new();
+        }
+
+        let message = auto_away.message.clone();
+        self.automatic = true;
+        self.change(Some(message), now)
+    }
+
+    /// Keep a message for the summary if we're away and it's for us.
+    pub fn handle_message(
+        &mut self,
+        message: &Message,
+        nickname: &Nickname,
+        casemapping: Casemapping,
+    ) {
+        if self.message.is_none() {
+            return;
+        }
+
+        let (recipients, text) = match &message.body {
+            MessageBody::Command(Command::Privmsg {
+                recipients,
+                message,
+            })
+            | MessageBody::Command(Command::Notice {
+                recipients,
+                message,
+            }) => (recipients, message),
+            _ => return,
+        };
+        match &message.sender {
+            Some(Sender::User { nickname: from, .. })
+                if !casemapping.eq(from.as_ref(), nickname.as_ref()) => {}
+            _ => return,
+        }
+
+        let private = recipients.iter().any(|recipient| match recipient {
+            Recipient::Nickname(recipient) | Recipient::NicknameUserHost(recipient, ..) => {
+                casemapping.eq(recipient.as_ref(), nickname.as_ref())
+            }
+            _ => false,
+        });
+        if private {
+            self.summary.private_messages.push(message.clone());
+        } else if mentions(text, nickname, casemapping) {
+            self.summary.highlights.push(message.clone());
+        }
+    }
+
+    pub fn handle_reply(&mut self, reply_type: &ReplyType, now: Instant) -> Vec<Event> {
+        match reply_type {
+            ReplyType::RplNowAway if !self.confirmed => {
+                self.confirmed = true;
+                vec![Event::Away]
+            }
+            ReplyType::RplUnAway if self.confirmed => {
+                self.confirmed = false;
+                let mut summary = std::mem::take(&mut self.summary);
+                if let Some(since) = self.since.take() {
+                    summary.duration = now.duration_since(since);
+                }
+                vec![Event::Back(summary)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn change(&mut self, message: Option<String>, now: Instant) -> Vec<Command> {
+        match (&self.message, &message) {
+            (None, Some(_)) => {
+                self.since = Some(now);
+                self.summary = AwaySummary::default();
+            }
+            (None, None) => return Vec::new(),
+            _ => (),
+        }
+        self.message = message.clone();
+
+        if self.registered {
+            vec![away(message)]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+fn away(message: Option<String>) -> Command {
+    Command::Away { message }
+}
+
+/// Whether our nickname appears in some text as a word of its own, so that
+/// "spudly" is mentioned by "spudly: hi" but not by "spudlyness".
+fn mentions(text: &str, nickname: &Nickname, casemapping: Casemapping) -> bool {
+    let text: Vec<char> = text.chars().map(|c| casemapping.fold_char(c)).collect();
+    let nickname: Vec<char> = nickname
+        .as_ref()
+        .chars()
+        .map(|c| casemapping.fold_char(c))
+        .collect();
+    let is_nickname_char = |c: &char| c.is_alphanumeric() || "[]\\`_^{|}-".contains(*c);
+
+    text.windows(nickname.len()).enumerate().any(|(i, window)| {
+        window == &nickname[..]
+            && !(i > 0 && is_nickname_char(&text[i - 1]))
+            && !text.get(i + nickname.len()).is_some_and(is_nickname_char)
+    })
+}
+
+#[cfg(test)]
+mod test_away {
+    use super::super::test_helpers::to_strings;
+    use super::*;
+
+    fn spudly() -> Nickname {
+        "spudly".parse().unwrap()
+    }
+
+    fn receive(away: &mut Away, raw: &str) {
+        away.handle_message(&raw.parse().unwrap(), &spudly(), Casemapping::Rfc1459);
+    }
+
+    #[test]
+    fn sets_away() {
+        let now = Instant::now();
+        let mut away = Away::default();
+        assert!(away
+            .set_away(Some("Gone fishing".to_string()), now)
+            .is_empty());
+        assert_eq!(vec!["AWAY :Gone fishing"], to_strings(away.start(now)));
+        assert!(!away.is_away());
+
+        assert_eq!(
+            vec![Event::Away],
+            away.handle_reply(&ReplyType::RplNowAway, now)
+        );
+        assert!(away.is_away());
+        assert!(away.active(now).is_empty());
+
+        let later = now + Duration::from_secs(90);
+        assert_eq!(vec!["AWAY"], to_strings(away.set_away(None, later)));
+        assert!(away.is_away());
+        assert_eq!(
+            vec![Event::Back(AwaySummary {
+                duration: Duration::from_secs(90),
+                ..AwaySummary::default()
+            })],
+            away.handle_reply(&ReplyType::RplUnAway, later)
+        );
+        assert!(!away.is_away());
+        assert!(away.handle_reply(&ReplyType::RplUnAway, later).is_empty());
+    }
+
+    #[test]
+    fn auto_away() {
+        let now = Instant::now();
+        let mut away = Away::default();
+        away.set_auto_away(Some(AutoAway {
+            after: Duration::from_secs(600),
+            message: "Idle".to_string(),
+        }));
+        assert!(away.poll(now).is_empty());
+        away.start(now);
+
+        assert!(away.poll(now + Duration::from_secs(599)).is_empty());
+        assert!(away.active(now + Duration::from_secs(300)).is_empty());
+        assert!(away.poll(now + Duration::from_secs(600)).is_empty());
+        assert_eq!(
+            vec!["AWAY Idle"],
+            to_strings(away.poll(now + Duration::from_secs(900)))
+        );
+        assert!(away.poll(now + Duration::from_secs(1000)).is_empty());
+        assert_eq!(
+            vec!["AWAY"],
+            to_strings(away.active(now + Duration::from_secs(1000)))
+        );
+
+        // Activity doesn't clear an away message that was set deliberately.
+        away.set_away(Some("Lunch".to_string()), now + Duration::from_secs(1100));
+        assert!(away.active(now + Duration::from_secs(1200)).is_empty());
+    }
+
+    #[test]
+    fn records_messages() {
+        let now = Instant::now();
+        let mut away = Away::default();
+        away.start(now);
+        receive(&mut away, ":Wiz!wiz@example.com PRIVMSG spudly :Before");
+
+        away.set_away(Some("Gone".to_string()), now);
+        away.handle_reply(&ReplyType::RplNowAway, now);
+        receive(&mut away, ":Wiz!wiz@example.com PRIVMSG spudly :Private");
+        receive(&mut away, ":Wiz!wiz@example.com NOTICE Spudly :Notice");
+        receive(
+            &mut away,
+            ":Wiz!wiz@example.com PRIVMSG #foo :SPUDLY: hello",
+        );
+        receive(&mut away, ":Wiz!wiz@example.com PRIVMSG #foo :spudlyness");
+        receive(&mut away, ":Wiz!wiz@example.com PRIVMSG #foo :unrelated");
+        receive(
+            &mut away,
+            ":spudly!pj@example.com PRIVMSG #foo :spudly is me",
+        );
+
+        away.set_away(None, now);
+        let summary = match away.handle_reply(&ReplyType::RplUnAway, now).pop() {
+            Some(Event::Back(summary)) => summary,
+            event => panic!("Unexpected event {:?}", event),
+        };
+        assert_eq!(
+            vec![
+                ":Wiz!wiz@example.com PRIVMSG spudly Private",
+                ":Wiz!wiz@example.com NOTICE Spudly Notice",
+            ],
+            summary
+                .private_messages
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![":Wiz!wiz@example.com PRIVMSG #foo :SPUDLY: hello"],
+            summary
+                .highlights
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn mentions_nickname() {
+        let nickname = "Wiz[away]".parse().unwrap();
+        let mentioned = |text| mentions(text, &nickname, Casemapping::Rfc1459);
+        assert!(mentioned("wiz{away}"));
+        assert!(mentioned("hi WIZ[AWAY], how are you"));
+        assert!(mentioned("<wiz[away]>"));
+        assert!(!mentioned("wiz[away]_"));
+        assert!(!mentioned("the wiz"));
+        assert!(!mentioned(""));
+    }
+}
@@ -0,0 +1,190 @@
+use crikey_irc_common::{Command, Message, MessageBody, MessageTags};
+use std::collections::HashMap;
+
+/// A group of messages that the server has asked us to treat as a unit, per
+/// the IRCv3 batch specification:
+///
+/// ```text
+/// batch-start  =  "BATCH" " " "+" reference " " type *( " " parameter )
+/// batch-end    =  "BATCH" " " "-" reference
+/// ```
+///
+/// Messages belonging to a batch carry a `batch=<reference>` tag. Batches may
+/// be nested, in which case the inner batch is collected into `batches`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Batch {
+    pub reference: String,
+    pub batch_type: String,
+    pub params: Vec<String>,
+    /// The tags of the BATCH command that opened the batch.
+    pub tags: MessageTags,
+    pub messages: Vec<Message>,
+    pub batches: Vec<Batch>,
+}
+
+/// What became of a message passed to `Batches::handle()`.
+#[derive(PartialEq, Debug)]
+pub enum Outcome {
+    /// The message wasn't part of a batch, and should be handled as usual.
+    Unbatched(Message),
+    /// The message has been set aside until its batch is complete.
+    Buffered,
+    /// The message closed a top-level batch, which is now ready to handle.
+    Complete(Batch),
+}
+
+/// Collects batched messages until their batch is closed.
+#[derive(Debug, Default)]
+pub struct Batches {
+    open: HashMap<String, (Option<String>, Batch)>,
+}
+
+impl Batches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle(&mut self, message: Message) -> Outcome {
+        let parent = message
+            .tags
+            .get("batch")
+            .filter(|parent| self.open.contains_key(*parent))
+            .map(|parent| parent.to_string());
+
+        match message.body {
+            MessageBody::Command(Command::BatchStart {
+                reference,
+                batch_type,
+                params,
+            }) => {
+                let batch = Batch {
+                    reference: reference.clone(),
+                    batch_type,
+                    params,
+                    tags: message.tags,
+                    messages: Vec::new(),
+                    batches: Vec::new(),
+                };
+                self.open.insert(reference, (parent, batch));
+                Outcome::Buffered
+            }
+            MessageBody::Command(Command::BatchEnd { reference }) => {
+                match self.open.remove(&reference) {
+                    Some((Some(parent), batch)) => {
+                        if let Some((_, parent)) = self.open.get_mut(&parent) {
+                            parent.batches.push(batch);
+                        }
+                        Outcome::Buffered
+                    }
+                    Some((None, batch)) => Outcome::Complete(batch),
+                    // Closing a batch we never saw open is meaningless.
+                    None => Outcome::Buffered,
+                }
+            }
+            body => {
+                let message = Message {
+                    tags: message.tags,
+                    sender: message.sender,
+                    body,
+                };
+                match parent {
+                    Some(parent) => {
+                        if let Some((_, batch)) = self.open.get_mut(&parent) {
+                            batch.messages.push(message);
+                        }
+                        Outcome::Buffered
+                    }
+                    None => Outcome::Unbatched(message),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_batches {
+    use super::*;
+
+    fn handle(batches: &mut Batches, raw: &str) -> Outcome {
+        batches.handle(raw.parse().unwrap())
+    }
+
+    #[test]
+    fn unbatched() {
+        let mut batches = Batches::new();
+        assert_eq!(
+            Outcome::Unbatched("PING irc.example.com".parse().unwrap()),
+            handle(&mut batches, "PING irc.example.com")
+        );
+        // A batch tag referring to an unknown batch is ignored.
+        assert_eq!(
+            Outcome::Unbatched("@batch=abc PING irc.example.com".parse().unwrap()),
+            handle(&mut batches, "@batch=abc PING irc.example.com")
+        );
+    }
+
+    #[test]
+    fn complete() {
+        let mut batches = Batches::new();
+        assert_eq!(
+            Outcome::Buffered,
+            handle(
+                &mut batches,
+                ":irc.host BATCH +sxtUfAeXBgNoD chathistory #channel"
+            )
+        );
+        assert_eq!(
+            Outcome::Buffered,
+            handle(
+                &mut batches,
+                "@batch=sxtUfAeXBgNoD;msgid=1 :nick!user@host PRIVMSG #channel :hi"
+            )
+        );
+        assert_eq!(
+            Outcome::Complete(Batch {
+                reference: "sxtUfAeXBgNoD".to_string(),
+                batch_type: "chathistory".to_string(),
+                params: vec!["#channel".to_string()],
+                tags: MessageTags::new(),
+                messages: vec![
+                    "@batch=sxtUfAeXBgNoD;msgid=1 :nick!user@host PRIVMSG #channel :hi"
+                        .parse()
+                        .unwrap()
+                ],
+                batches: Vec::new(),
+            }),
+            handle(&mut batches, ":irc.host BATCH -sxtUfAeXBgNoD")
+        );
+        assert_eq!(
+            Outcome::Buffered,
+            handle(&mut batches, ":irc.host BATCH -sxtUfAeXBgNoD")
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let mut batches = Batches::new();
+        handle(
+            &mut batches,
+            "@label=a :irc.host BATCH +outer labeled-response",
+        );
+        handle(
+            &mut batches,
+            "@batch=outer :irc.host BATCH +inner chathistory #channel",
+        );
+        handle(&mut batches, "@batch=inner :nick PRIVMSG #channel :hi");
+        assert_eq!(Outcome::Buffered, handle(&mut batches, "BATCH -inner"));
+
+        match handle(&mut batches, "BATCH -outer") {
+            Outcome::Complete(batch) => {
+                assert_eq!("labeled-response", batch.batch_type);
+                assert_eq!(Some("a"), batch.tags.get("label"));
+                assert!(batch.messages.is_empty());
+                assert_eq!(1, batch.batches.len());
+                assert_eq!("chathistory", batch.batches[0].batch_type);
+                assert_eq!(1, batch.batches[0].messages.len());
+            }
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        }
+    }
+}
@@ -0,0 +1,446 @@
+use crikey_irc_common::{CapSubcommand, Command, ReplyType, TARGET_STATE};
+use log::{info, warn};
+use std::collections::HashMap;
+
+/// The version of capability negotiation that we speak. 302 adds capability
+/// values and multi-line replies, as well as CAP NEW and CAP DEL.
+const CAP_VERSION: u16 = 302;
+
+/// AUTHENTICATE payloads longer than this are split over several commands.
+const SASL_CHUNK_SIZE: usize = 400;
+
+/// An account and password to log in with during registration, using SASL
+/// PLAIN.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Sasl {
+    pub account: String,
+    pub password: String,
+}
+
+/// Tracks IRCv3 capability negotiation. Negotiation is opt-in: unless at least
+/// one capability has been requested, nothing is sent and registration
+/// proceeds exactly as described in RFC 2812.
+///
+/// ```text
+/// client: CAP LS 302
+/// server: CAP * LS * :multi-prefix batch
+/// server: CAP * LS :draft/chathistory sasl=PLAIN
+/// client: CAP REQ :batch draft/chathistory
+/// server: CAP * ACK :batch draft/chathistory
+/// client: CAP END
+/// ```
+///
+/// Only capabilities that the server advertises are requested, and CAP END is
+/// sent as soon as the server has answered, whether or not it agreed. If we
+/// have SASL credentials and the server acknowledges "sasl", we log in before
+/// ending negotiation, whether or not that succeeds:
+///
+/// ```text
+/// client: AUTHENTICATE PLAIN
+/// server: AUTHENTICATE +
+/// client: AUTHENTICATE c3B1ZGx5AHNwdWRseQBodW50ZXIy
+/// server: 903 spudly :SASL authentication successful
+/// client: CAP END
+/// ```
+#[derive(Debug, Default)]
+pub struct Capabilities {
+    requested: Vec<String>,
+    available: HashMap<String, Option<String>>,
+    enabled: Vec<String>,
+    negotiating: bool,
+    sasl: Option<Sasl>,
+    authenticating: bool,
+}
+
+impl Capabilities {
+    pub fn new(requested: Vec<String>) -> Self {
+        Self {
+            requested,
+            ..Self::default()
+        }
+    }
+
+    /// Log in with SASL during registration, requesting the capability if it
+    /// hasn't been already.
+    pub fn set_sasl(&mut self, sasl: Option<Sasl>) {
+        if sasl.is_some() && !self.requested.iter().any(|requested| requested == "sasl") {
+            self.requested.push("sasl".to_string());
+        }
+        self.sasl = sasl;
+    }
+
+    pub fn is_enabled(&self, capability: &str) -> bool {
+        self.enabled.iter().any(|enabled| enabled == capability)
+    }
+
+    /// The commands to send before NICK and USER.
+    pub fn start(&mut self) -> Vec<Command> {
+        if self.requested.is_empty() {
+            Vec::new()
+        } else {
+            self.negotiating = true;
+            vec![Command::CapLs {
+                version: Some(CAP_VERSION),
+            }]
+        }
+    }
+
+    pub fn handle_command(&mut self, command: &Command) -> Vec<Command> {
+        if let Command::Authenticate { data } = command {
+            return self.authenticate(data);
+        }
+
+        let (subcommand, more, capabilities) = match command {
+            Command::Cap {
+                subcommand,
+                more,
+                capabilities,
+                ..
+            } => (subcommand, *more, capabilities),
+            _ => return Vec::new(),
+        };
+
+        match subcommand {
+            CapSubcommand::Ls => {
+                self.advertise(capabilities);
+                if more || !self.negotiating {
+                    Vec::new()
+                } else {
+                    self.request()
+                }
+            }
+            CapSubcommand::New => {
+                self.advertise(capabilities);
+                self.request()
+            }
+            CapSubcommand::Del => {
+                for capability in capabilities.split_whitespace() {
+                    self.available.remove(capability);
+                    self.enabled.retain(|enabled| enabled != capability);
+                }
+                Vec::new()
+            }
+            CapSubcommand::Ack => {
+                for capability in capabilities.split_whitespace() {
+                    if let Some(capability) = capability.strip_prefix('-') {
+                        self.enabled.retain(|enabled| enabled != capability);
+                    } else if !self.is_enabled(capability) {
+                        self.enabled.push(capability.to_string());
+                    }
+                }
+                info!(target: TARGET_STATE, "Enabled capabilities: {}", capabilities);
+
+                if self.negotiating && self.sasl.is_some() && self.is_enabled("sasl") {
+                    self.authenticating = true;
+                    vec![Command::Authenticate {
+                        data: "PLAIN".to_string(),
+                    }]
+                } else {
+                    self.end()
+                }
+            }
+            CapSubcommand::Nak => {
+                warn!(target: TARGET_STATE, "Refused capabilities: {}", capabilities);
+                self.end()
+            }
+            CapSubcommand::List => Vec::new(),
+        }
+    }
+
+    /// Finish negotiation once SASL has succeeded or failed.
+    pub fn handle_reply(&mut self, reply_type: &ReplyType) -> Vec<Command> {
+        match reply_type {
+            ReplyType::RplSaslSuccess if self.authenticating => {
+                info!(target: TARGET_STATE, "Logged in with SASL");
+                self.authenticating = false;
+                self.end()
+            }
+            ReplyType::ErrSaslFail
+            | ReplyType::ErrSaslTooLong
+            | ReplyType::ErrSaslAborted
+            | ReplyType::ErrSaslAlready
+                if self.authenticating =>
+            {
+                warn!(target: TARGET_STATE, "SASL login failed: {:?}", reply_type);
+                self.authenticating = false;
+                self.end()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Answer the server's go-ahead with our credentials, split into chunks.
+    /// A payload that fills its last chunk exactly is followed by "+".
+    fn authenticate(&mut self, data: &str) -> Vec<Command> {
+        let sasl = match &self.sasl {
+            Some(sasl) if self.authenticating && data == "+" => sasl,
+            _ => return Vec::new(),
+        };
+
+        let payload =
+            base64(format!("{}\0{}\0{}", sasl.account, sasl.account, sasl.password).as_bytes());
+        let mut commands: Vec<Command> = payload
+            .as_bytes()
+            .chunks(SASL_CHUNK_SIZE)
+            .map(|chunk| Command::Authenticate {
+                data: String::from_utf8_lossy(chunk).into_owned(),
+            })
+            .collect();
+        if payload.len().is_multiple_of(SASL_CHUNK_SIZE) {
+            commands.push(Command::Authenticate {
+                data: "+".to_string(),
+            });
+        }
+        commands
+    }
+
+    fn advertise(&mut self, capabilities: &str) {
+        for capability in capabilities.split_whitespace() {
+            match capability.find('=') {
+                Some(index) => self.available.insert(
+                    capability[..index].to_string(),
+                    Some(capability[index + 1..].to_string()),
+                ),
+                None => self.available.insert(capability.to_string(), None),
+            };
+        }
+    }
+
+    /// Request everything we want that the server has and we don't, or finish
+    /// negotiation if there's nothing left to ask for.
+    fn request(&mut self) -> Vec<Command> {
+        let wanted: Vec<&str> = self
+            .requested
+            .iter()
+            .filter(|capability| {
+                self.available.contains_key(&capability[..]) && !self.is_enabled(capability)
+            })
+            .map(|capability| &capability[..])
+            .collect();
+
+        if wanted.is_empty() {
+            self.end()
+        } else {
+            vec![Command::CapReq {
+                capabilities: wanted.join(" "),
+            }]
+        }
+    }
+
+    fn end(&mut self) -> Vec<Command> {
+        if self.negotiating {
+            self.negotiating = false;
+            vec![Command::CapEnd]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test_capabilities {
+    use super::*;
+
+    fn cap(subcommand: CapSubcommand, more: bool, capabilities: &str) -> Command {
+        Command::Cap {
+            target: "*".to_string(),
+            subcommand,
+            more,
+            capabilities: capabilities.to_string(),
+        }
+    }
+
+    fn capabilities(requested: &[&str]) -> Capabilities {
+        Capabilities::new(requested.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn nothing_requested() {
+        let mut capabilities = capabilities(&[]);
+        assert_eq!(Vec::<Command>::new(), capabilities.start());
+    }
+
+    #[test]
+    fn negotiate() {
+        let mut capabilities = capabilities(&["batch", "draft/chathistory", "away-notify"]);
+        assert_eq!(
+            vec![Command::CapLs { version: Some(302) }],
+            capabilities.start()
+        );
+
+        assert_eq!(
+            Vec::<Command>::new(),
+            capabilities.handle_command(&cap(CapSubcommand::Ls, true, "multi-prefix batch"))
+        );
+        assert_eq!(
+            vec![Command::CapReq {
+                capabilities: "batch draft/chathistory".to_string()
+            }],
+            capabilities.handle_command(&cap(
+                CapSubcommand::Ls,
+                false,
+                "draft/chathistory sasl=PLAIN,EXTERNAL"
+            ))
+        );
+        assert_eq!(
+            Some(&Some("PLAIN,EXTERNAL".to_string())),
+            capabilities.available.get("sasl")
+        );
+
+        assert_eq!(
+            vec![Command::CapEnd],
+            capabilities.handle_command(&cap(CapSubcommand::Ack, false, "batch draft/chathistory"))
+        );
+        assert!(capabilities.is_enabled("batch"));
+        assert!(capabilities.is_enabled("draft/chathistory"));
+        assert!(!capabilities.is_enabled("away-notify"));
+
+        // The server later starts supporting something else we asked for.
+        assert_eq!(
+            vec![Command::CapReq {
+                capabilities: "away-notify".to_string()
+            }],
+            capabilities.handle_command(&cap(CapSubcommand::New, false, "away-notify"))
+        );
+        assert_eq!(
+            Vec::<Command>::new(),
+            capabilities.handle_command(&cap(CapSubcommand::Ack, false, "away-notify"))
+        );
+        assert!(capabilities.is_enabled("away-notify"));
+
+        capabilities.handle_command(&cap(CapSubcommand::Del, false, "away-notify"));
+        assert!(!capabilities.is_enabled("away-notify"));
+    }
+
+    #[test]
+    fn rejected() {
+        let mut capabilities = capabilities(&["batch"]);
+        capabilities.start();
+        capabilities.handle_command(&cap(CapSubcommand::Ls, false, "batch"));
+        assert_eq!(
+            vec![Command::CapEnd],
+            capabilities.handle_command(&cap(CapSubcommand::Nak, false, "batch"))
+        );
+        assert!(!capabilities.is_enabled("batch"));
+    }
+
+    #[test]
+    fn nothing_available() {
+        let mut capabilities = capabilities(&["batch"]);
+        capabilities.start();
+        assert_eq!(
+            vec![Command::CapEnd],
+            capabilities.handle_command(&cap(CapSubcommand::Ls, false, "multi-prefix"))
+        );
+    }
+
+    fn authenticate(data: &str) -> Command {
+        Command::Authenticate {
+            data: data.to_string(),
+        }
+    }
+
+    fn sasl(password: &str) -> Capabilities {
+        let mut capabilities = capabilities(&["batch"]);
+        capabilities.set_sasl(Some(Sasl {
+            account: "spudly".to_string(),
+            password: password.to_string(),
+        }));
+        capabilities.start();
+        capabilities
+    }
+
+    #[test]
+    fn sasl_plain() {
+        let mut capabilities = sasl("hunter2");
+        assert_eq!(
+            vec![Command::CapReq {
+                capabilities: "batch sasl".to_string()
+            }],
+            capabilities.handle_command(&cap(CapSubcommand::Ls, false, "batch sasl=PLAIN"))
+        );
+        assert_eq!(
+            vec![authenticate("PLAIN")],
+            capabilities.handle_command(&cap(CapSubcommand::Ack, false, "batch sasl"))
+        );
+        assert_eq!(
+            vec![authenticate("c3B1ZGx5AHNwdWRseQBodW50ZXIy")],
+            capabilities.handle_command(&authenticate("+"))
+        );
+        assert!(capabilities
+            .handle_reply(&ReplyType::RplLoggedIn)
+            .is_empty());
+        assert_eq!(
+            vec![Command::CapEnd],
+            capabilities.handle_reply(&ReplyType::RplSaslSuccess)
+        );
+        assert!(capabilities.handle_command(&authenticate("+")).is_empty());
+    }
+
+    #[test]
+    fn sasl_failure() {
+        let mut capabilities = sasl("hunter2");
+        capabilities.handle_command(&cap(CapSubcommand::Ls, false, "batch sasl"));
+        capabilities.handle_command(&cap(CapSubcommand::Ack, false, "batch sasl"));
+        assert_eq!(
+            vec![Command::CapEnd],
+            capabilities.handle_reply(&ReplyType::ErrSaslFail)
+        );
+
+        // No point in trying if the server doesn't support it.
+        let mut capabilities = sasl("hunter2");
+        capabilities.handle_command(&cap(CapSubcommand::Ls, false, "batch"));
+        assert_eq!(
+            vec![Command::CapEnd],
+            capabilities.handle_command(&cap(CapSubcommand::Ack, false, "batch"))
+        );
+    }
+
+    #[test]
+    fn sasl_chunks() {
+        // "spudly\0spudly\0" and the password come to 300 bytes, which is
+        // exactly 400 once encoded.
+        let mut capabilities = sasl(&"x".repeat(286));
+        capabilities.handle_command(&cap(CapSubcommand::Ls, false, "sasl"));
+        capabilities.handle_command(&cap(CapSubcommand::Ack, false, "batch sasl"));
+        let commands = capabilities.handle_command(&authenticate("+"));
+        assert_eq!(2, commands.len());
+        assert_eq!(authenticate("+"), commands[1]);
+
+        let mut capabilities = sasl(&"x".repeat(300));
+        capabilities.handle_command(&cap(CapSubcommand::Ls, false, "sasl"));
+        capabilities.handle_command(&cap(CapSubcommand::Ack, false, "sasl"));
+        let commands = capabilities.handle_command(&authenticate("+"));
+        assert_eq!(2, commands.len());
+        assert_ne!(authenticate("+"), commands[1]);
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!("", super::base64(b""));
+        assert_eq!("Zg==", super::base64(b"f"));
+        assert_eq!("Zm8=", super::base64(b"fo"));
+        assert_eq!("Zm9v", super::base64(b"foo"));
+        assert_eq!("Zm9vYmFy", super::base64(b"foobar"));
+    }
+}
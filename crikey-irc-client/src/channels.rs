@@ -0,0 +1,685 @@
+use super::{Event, ISupport};
+use crikey_irc_common::{
+    Casemapping, Channel, ChannelKey, Command, KeywordList, MessageParams, Nickname, ReplyType,
+    Sender,
+};
+use std::time::{Duration, Instant};
+
+/// The number of bytes of channels and keys we're willing to put on a single
+/// JOIN line, leaving room for the command, prefix and CRLF.
+const CHANNELS_PER_LINE: usize = 400;
+
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Retries back off exponentially, but never wait longer than this.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30 * 60);
+
+/// How to ask to be invited to an invite-only (+i) channel before retrying.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum InviteMethod {
+    #[default]
+    None,
+    /// Send KNOCK, which many servers support.
+    Knock,
+    /// Ask ChanServ with `INVITE <channel>`.
+    ChanServ,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    /// Waiting until the given time to join, or as soon as possible.
+    Waiting(Option<Instant>),
+    Joining,
+    Joined,
+}
+
+#[derive(Debug)]
+struct Desired {
+    channel: Channel,
+    name: String,
+    key: Option<ChannelKey>,
+    protected: bool,
+    state: State,
+    failures: u32,
+}
+
+/// Keeps the channels we want to be in, and gets us into them. Channels are
+/// joined in batches once registration is complete, and a JOIN that fails
+/// because the channel is full, invite-only, banned, keyed or temporarily
+/// unavailable, or because we've joined too many channels or need to
+/// register, is retried with exponential backoff. A channel that doesn't exist
+/// or has an invalid name is given up on. Optionally, we also rejoin a
+/// channel we're kicked from, and ask for an invitation to an invite-only
+/// channel.
+///
+/// Protected channels, which only let in users identified to NickServ, aren't
+/// joined until `set_identified()` is called.
+#[derive(Debug)]
+pub struct Channels {
+    desired: Vec<Desired>,
+    started: bool,
+    identified: bool,
+    casemapping: Casemapping,
+    retry_delay: Duration,
+    rejoin_on_kick: bool,
+    invite_method: InviteMethod,
+    chanserv: Nickname,
+}
+
+impl Channels {
+    pub fn new(retry_delay: Duration) -> Self {
+        Channels {
+            desired: Vec::new(),
+            started: false,
+            identified: false,
+            casemapping: Casemapping::default(),
+            retry_delay,
+            rejoin_on_kick: false,
+            invite_method: InviteMethod::None,
+            chanserv: "ChanServ".parse().unwrap(),
+        }
+    }
+
+    pub fn set_rejoin_on_kick(&mut self, rejoin: bool) {
+        self.rejoin_on_kick = rejoin;
+    }
+
+    pub fn set_invite_method(&mut self, method: InviteMethod) {
+        self.invite_method = method;
+    }
+
+    pub fn set_chanserv(&mut self, chanserv: Nickname) {
+        self.chanserv = chanserv;
+    }
+
+    /// Called once we've identified to NickServ, so that protected channels
+    /// can be joined.
+    pub fn set_identified(&mut self, now: Instant) -> Vec<Command> {
+        self.identified = true;
+        self.poll(now)
+    }
+
+    pub fn is_joined(&self, channel: &Channel) -> bool {
+        self.find(&String::from(channel.clone()))
+            .is_some_and(|index| self.desired[index].state == State::Joined)
+    }
+
+    /// Called once registration is complete, at which point we can join
+    /// everything we've been asked to.
+    pub fn start(&mut self, isupport: &ISupport, now: Instant) -> Vec<Command> {
+        self.started = true;
+        self.casemapping = isupport.casemapping();
+        self.poll(now)
+    }
+
+    /// Add a channel to the list, replacing its key if it's already there.
+    pub fn add(
+        &mut self,
+        channel: Channel,
+        key: Option<ChannelKey>,
+        protected: bool,
+        now: Instant,
+    ) -> Vec<Command> {
+        let name = String::from(channel.clone());
+        match self.find(&name) {
+            Some(index) => {
+                self.desired[index].key = key;
+                self.desired[index].protected = protected;
+            }
+            None => self.desired.push(Desired {
+                channel,
+                name,
+                key,
+                protected,
+                state: State::Waiting(None),
+                failures: 0,
+            }),
+        }
+        self.poll(now)
+    }
+
+    /// Remove a channel from the list, leaving it if we're in it.
+    pub fn remove(&mut self, channel: &Channel, message: Option<String>) -> Vec<Command> {
+        match self.find(&String::from(channel.clone())) {
+            Some(index) => {
+                let desired = self.desired.remove(index);
+                match desired.state {
+                    State::Joining | State::Joined => vec![Command::Part {
+                        channels: KeywordList::from(vec![desired.channel]),
+                        message,
+                    }],
+                    State::Waiting(_) => Vec::new(),
+                }
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Join any channels that are due to be joined.
+    pub fn poll(&mut self, now: Instant) -> Vec<Command> {
+        if !self.started {
+            return Vec::new();
+        }
+
+        let identified = self.identified;
+        let due: Vec<(Channel, Option<ChannelKey>)> = self
+            .desired
+            .iter_mut()
+            .filter(|desired| identified || !desired.protected)
+            .filter(|desired| match desired.state {
+                State::Waiting(Some(at)) => at <= now,
+                State::Waiting(None) => true,
+                _ => false,
+            })
+            .map(|desired| {
+                desired.state = State::Joining;
+                (desired.channel.clone(), desired.key.clone())
+            })
+            .collect();
+
+        batch(due)
+    }
+
+    /// Track JOIN, PART, KICK and INVITE commands concerning us.
+    pub fn handle_command(
+        &mut self,
+        sender: Option<&Sender>,
+        command: &Command,
+        nickname: &Nickname,
+        now: Instant,
+    ) -> (Vec<Command>, Vec<Event>) {
+        let from_us = match sender {
+            Some(Sender::User { nickname: from, .. }) => self.is_us(from, nickname),
+            _ => false,
+        };
+
+        match command {
+            Command::Join { channels, .. } if from_us => {
+                let events = channels
+                    .iter()
+                    .filter_map(|channel| {
+                        let index = self.find(&String::from(channel.clone()))?;
+                        let desired = &mut self.desired[index];
+                        desired.state = State::Joined;
+                        desired.failures = 0;
+                        Some(Event::Joined(desired.channel.clone()))
+                    })
+                    .collect();
+                (Vec::new(), events)
+            }
+            Command::Part { channels, .. } if from_us => {
+                for channel in channels.iter() {
+                    if let Some(index) = self.find(&String::from(channel.clone())) {
+                        self.desired.remove(index);
+                    }
+                }
+                (Vec::new(), Vec::new())
+            }
+            Command::Kick {
+                channels,
+                nicknames,
+                comment,
+            } => {
+                let mut events = Vec::new();
+                for (index, kicked) in nicknames.iter().enumerate() {
+                    let channel = match channels.iter().nth(index).or(channels.iter().next()) {
+                        Some(channel) if self.is_us(kicked, nickname) => channel,
+                        _ => continue,
+                    };
+                    events.push(Event::Kicked {
+                        channel: channel.clone(),
+                        by: sender.cloned(),
+                        comment: comment.clone(),
+                    });
+
+                    if let Some(index) = self.find(&String::from(channel.clone())) {
+                        if self.rejoin_on_kick {
+                            self.desired[index].state = State::Waiting(None);
+                        } else {
+                            self.desired.remove(index);
+                        }
+                    }
+                }
+                (self.poll(now), events)
+            }
+            Command::Invite {
+                nickname: invited,
+                channel,
+            } if self.is_us(invited, nickname) => {
+                // Any invitation to a channel we're waiting to get into can
+                // be acted on right away.
+                if let Some(index) = self.find(&String::from(channel.clone())) {
+                    if let State::Waiting(_) = self.desired[index].state {
+                        self.desired[index].state = State::Waiting(None);
+                    }
+                }
+                (self.poll(now), Vec::new())
+            }
+            _ => (Vec::new(), Vec::new()),
+        }
+    }
+
+    /// Schedule a retry when a JOIN is refused, or give up on the channel if
+    /// there's no point.
+    pub fn handle_reply(
+        &mut self,
+        reply_type: &ReplyType,
+        params: &MessageParams,
+        now: Instant,
+    ) -> (Vec<Command>, Vec<Event>) {
+        let retry = match reply_type {
+            ReplyType::ErrChannelIsFull
+            | ReplyType::ErrInviteOnlyChan
+            | ReplyType::ErrBannedFromChan
+            | ReplyType::ErrBadChannelKey
+            | ReplyType::ErrTooManyChannels
+            | ReplyType::ErrUnavailResource
+            // ERR_NEEDREGGEDNICK on most servers.
+            | ReplyType::ErrNoChanModes => true,
+            ReplyType::ErrNoSuchChannel | ReplyType::ErrBadChanMask => false,
+            _ => return (Vec::new(), Vec::new()),
+        };
+
+        // The same numerics are sent for other commands, so only a channel
+        // we're trying to join is affected.
+        let index = match params
+            .get(1)
+            .and_then(|name| self.find(name))
+            .filter(|index| self.desired[*index].state == State::Joining)
+        {
+            Some(index) => index,
+            None => return (Vec::new(), Vec::new()),
+        };
+
+        if !retry {
+            let desired = self.desired.remove(index);
+            return (
+                Vec::new(),
+                vec![Event::JoinAbandoned {
+                    channel: desired.channel,
+                    reason: reply_type.clone(),
+                }],
+            );
+        }
+
+        let retry_delay = self.retry_delay;
+        let desired = &mut self.desired[index];
+        let delay = retry_delay
+            .checked_mul(1 << desired.failures.min(16))
+            .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY));
+        desired.failures += 1;
+        desired.state = State::Waiting(Some(now + delay));
+        let channel = desired.channel.clone();
+
+        let commands = match (reply_type, self.invite_method) {
+            (ReplyType::ErrInviteOnlyChan, InviteMethod::Knock) => vec![Command::Knock {
+                channel: channel.clone(),
+                message: None,
+            }],
+            (ReplyType::ErrInviteOnlyChan, InviteMethod::ChanServ) => vec![Command::Privmsg {
+                recipients: KeywordList::from(vec![self.chanserv.clone().into()]),
+                message: format!("INVITE {}", String::from(channel.clone())),
+            }],
+            _ => Vec::new(),
+        };
+
+        (
+            commands,
+            vec![Event::JoinFailed {
+                channel,
+                reason: reply_type.clone(),
+            }],
+        )
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.desired
+            .iter()
+            .position(|desired| self.casemapping.eq(&desired.name, name))
+    }
+
+    fn is_us(&self, a: &Nickname, b: &Nickname) -> bool {
+        self.casemapping.eq(a.as_ref(), b.as_ref())
+    }
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Self::new(DEFAULT_RETRY_DELAY)
+    }
+}
+
+/// Split a list of channels into JOIN commands that will comfortably fit on a
+/// line. Within each, channels with keys are listed first, since keys are
+/// matched to channels by position.
+fn batch(channels: Vec<(Channel, Option<ChannelKey>)>) -> Vec<Command> {
+    let mut commands = Vec::new();
+    let mut current: Vec<(Channel, Option<ChannelKey>)> = Vec::new();
+    let mut length = 0;
+
+    for (channel, key) in channels {
+        let channel_length = String::from(channel.clone()).len()
+            + key
+                .as_ref()
+                .map_or(0, |key| String::from(key.clone()).len() + 1)
+            + 1;
+        if length + channel_length > CHANNELS_PER_LINE && !current.is_empty() {
+            commands.push(join(current));
+            current = Vec::new();
+            length = 0;
+        }
+        length += channel_length;
+        current.push((channel, key));
+    }
+
+    if !current.is_empty() {
+        commands.push(join(current));
+    }
+    commands
+}
+
+fn join(channels: Vec<(Channel, Option<ChannelKey>)>) -> Command {
+    let (keyed, unkeyed): (Vec<_>, Vec<_>) =
+        channels.into_iter().partition(|(_, key)| key.is_some());
+
+    Command::Join {
+        channels: keyed
+            .iter()
+            .chain(unkeyed.iter())
+            .map(|(channel, _)| channel.clone())
+            .collect(),
+        keys: keyed.into_iter().filter_map(|(_, key)| key).collect(),
+    }
+}
+
+#[cfg(test)]
+mod test_channels {
+    use super::super::test_helpers::to_strings;
+    use super::*;
+
+    fn started(channels: &[(&str, Option<&str>)]) -> (Channels, Instant) {
+        let now = Instant::now();
+        let mut manager = Channels::default();
+        for (channel, key) in channels {
+            let key = key.map(|key| key.parse().unwrap());
+            assert!(manager
+                .add(channel.parse().unwrap(), key, false, now)
+                .is_empty());
+        }
+        (manager, now)
+    }
+
+    fn command(manager: &mut Channels, raw: &str, now: Instant) -> (Vec<String>, Vec<Event>) {
+        let message: crikey_irc_common::Message = raw.parse().unwrap();
+        let command = match message.body {
+            crikey_irc_common::MessageBody::Command(command) => command,
+            _ => panic!("Expected a command"),
+        };
+        let (commands, events) = manager.handle_command(
+            message.sender.as_ref(),
+            &command,
+            &"spudly".parse().unwrap(),
+            now,
+        );
+        (to_strings(commands), events)
+    }
+
+    fn reply(
+        manager: &mut Channels,
+        reply_type: ReplyType,
+        raw: &str,
+        now: Instant,
+    ) -> (Vec<String>, Vec<Event>) {
+        let (commands, events) = manager.handle_reply(&reply_type, &raw.parse().unwrap(), now);
+        (to_strings(commands), events)
+    }
+
+    #[test]
+    fn joins_after_registration() {
+        let (mut manager, now) = started(&[("#foo", None), ("#bar", Some("fubar"))]);
+        assert_eq!(
+            vec!["JOIN #bar,#foo fubar"],
+            to_strings(manager.start(&ISupport::new(), now))
+        );
+        assert!(manager.poll(now).is_empty());
+        assert!(!manager.is_joined(&"#foo".parse().unwrap()));
+
+        let (_, events) = command(&mut manager, ":spudly!pj@example.com JOIN #FOO", now);
+        assert_eq!(vec![Event::Joined("#foo".parse().unwrap())], events);
+        assert!(manager.is_joined(&"#foo".parse().unwrap()));
+
+        // Someone else joining doesn't count.
+        let (_, events) = command(&mut manager, ":Wiz!wiz@example.com JOIN #bar", now);
+        assert!(events.is_empty());
+
+        assert_eq!(
+            vec!["JOIN #baz"],
+            to_strings(manager.add("#baz".parse().unwrap(), None, false, now))
+        );
+        assert_eq!(
+            vec!["PART #foo :See you later"],
+            to_strings(manager.remove(&"#foo".parse().unwrap(), Some("See you later".to_string())))
+        );
+        assert!(!manager.is_joined(&"#foo".parse().unwrap()));
+    }
+
+    #[test]
+    fn waits_for_identify() {
+        let (mut manager, now) = started(&[("#foo", None)]);
+        manager.add("#secret".parse().unwrap(), None, true, now);
+        assert_eq!(
+            vec!["JOIN #foo"],
+            to_strings(manager.start(&ISupport::new(), now))
+        );
+        assert_eq!(
+            vec!["JOIN #secret"],
+            to_strings(manager.set_identified(now))
+        );
+    }
+
+    #[test]
+    fn batches_long_lists() {
+        let names: Vec<String> = (0..60).map(|i| format!("#channel{:02}", i)).collect();
+        let channels: Vec<(&str, Option<&str>)> =
+            names.iter().map(|name| (&name[..], None)).collect();
+        let (mut manager, now) = started(&channels);
+
+        let commands = to_strings(manager.start(&ISupport::new(), now));
+        assert_eq!(2, commands.len());
+        assert!(commands.iter().all(|command| command.len() < 512));
+        assert!(commands[1].ends_with("#channel59"));
+    }
+
+    #[test]
+    fn retries_with_backoff() {
+        let (mut manager, now) = started(&[("#foo", None)]);
+        manager.start(&ISupport::new(), now);
+
+        let (commands, events) = reply(
+            &mut manager,
+            ReplyType::ErrChannelIsFull,
+            "spudly #foo :Cannot join channel (+l)",
+            now,
+        );
+        assert!(commands.is_empty());
+        assert_eq!(
+            vec![Event::JoinFailed {
+                channel: "#foo".parse().unwrap(),
+                reason: ReplyType::ErrChannelIsFull,
+            }],
+            events
+        );
+
+        assert!(manager.poll(now + Duration::from_secs(29)).is_empty());
+        assert_eq!(
+            vec!["JOIN #foo"],
+            to_strings(manager.poll(now + Duration::from_secs(30)))
+        );
+
+        // The second failure waits twice as long.
+        let now = now + Duration::from_secs(30);
+        reply(
+            &mut manager,
+            ReplyType::ErrBannedFromChan,
+            "spudly #foo :Cannot join channel (+b)",
+            now,
+        );
+        assert!(manager.poll(now + Duration::from_secs(59)).is_empty());
+        assert_eq!(1, manager.poll(now + Duration::from_secs(60)).len());
+
+        // Other channels' errors are ignored.
+        let (_, events) = reply(
+            &mut manager,
+            ReplyType::ErrBadChannelKey,
+            "spudly #bar :Cannot join channel (+k)",
+            now,
+        );
+        assert!(events.is_empty());
+    }
+
+    /// Fail to join #foo with a numeric, returning the events and whether
+    /// it's retried after the backoff.
+    fn refused(reply_type: ReplyType, raw: &str) -> (Vec<Event>, bool) {
+        let (mut manager, now) = started(&[("#foo", None)]);
+        manager.start(&ISupport::new(), now);
+        let (_, events) = reply(&mut manager, reply_type, raw, now);
+        let retried = manager.poll(now + DEFAULT_RETRY_DELAY)
+            == vec![Command::join().channel("#foo").build().unwrap()];
+        (events, retried)
+    }
+
+    #[test]
+    fn retries_too_many_channels() {
+        let (events, retried) = refused(
+            ReplyType::ErrTooManyChannels,
+            "spudly #foo :You have joined too many channels",
+        );
+        assert_eq!(
+            vec![Event::JoinFailed {
+                channel: "#foo".parse().unwrap(),
+                reason: ReplyType::ErrTooManyChannels,
+            }],
+            events
+        );
+        assert!(retried);
+    }
+
+    #[test]
+    fn retries_unavailable() {
+        let (events, retried) = refused(
+            ReplyType::ErrUnavailResource,
+            "spudly #foo :Nick/channel is temporarily unavailable",
+        );
+        assert_eq!(
+            vec![Event::JoinFailed {
+                channel: "#foo".parse().unwrap(),
+                reason: ReplyType::ErrUnavailResource,
+            }],
+            events
+        );
+        assert!(retried);
+    }
+
+    #[test]
+    fn retries_registration_needed() {
+        let (events, retried) = refused(
+            ReplyType::ErrNoChanModes,
+            "spudly #foo :You need to be identified to a registered account to join this channel",
+        );
+        assert_eq!(
+            vec![Event::JoinFailed {
+                channel: "#foo".parse().unwrap(),
+                reason: ReplyType::ErrNoChanModes,
+            }],
+            events
+        );
+        assert!(retried);
+    }
+
+    #[test]
+    fn gives_up_on_missing_channels() {
+        let (events, retried) =
+            refused(ReplyType::ErrNoSuchChannel, "spudly #foo :No such channel");
+        assert_eq!(
+            vec![Event::JoinAbandoned {
+                channel: "#foo".parse().unwrap(),
+                reason: ReplyType::ErrNoSuchChannel,
+            }],
+            events
+        );
+        assert!(!retried);
+
+        // Once we're in a channel, the same numeric for another command
+        // doesn't affect it.
+        let (mut manager, now) = started(&[("#foo", None)]);
+        manager.start(&ISupport::new(), now);
+        command(&mut manager, ":spudly!pj@example.com JOIN #foo", now);
+        let (_, events) = reply(
+            &mut manager,
+            ReplyType::ErrNoSuchChannel,
+            "spudly #foo :No such channel",
+            now,
+        );
+        assert!(events.is_empty());
+        assert!(manager.is_joined(&"#foo".parse().unwrap()));
+    }
+
+    #[test]
+    fn requests_invites() {
+        let (mut manager, now) = started(&[("#foo", None)]);
+        manager.start(&ISupport::new(), now);
+        manager.set_invite_method(InviteMethod::Knock);
+
+        let (commands, _) = reply(
+            &mut manager,
+            ReplyType::ErrInviteOnlyChan,
+            "spudly #foo :Cannot join channel (+i)",
+            now,
+        );
+        assert_eq!(vec!["KNOCK #foo"], commands);
+
+        // Once we're invited, we don't wait for the backoff.
+        let (commands, _) = command(&mut manager, ":Wiz!wiz@example.com INVITE spudly #foo", now);
+        assert_eq!(vec!["JOIN #foo"], commands);
+
+        manager.set_invite_method(InviteMethod::ChanServ);
+        let (commands, _) = reply(
+            &mut manager,
+            ReplyType::ErrInviteOnlyChan,
+            "spudly #foo :Cannot join channel (+i)",
+            now,
+        );
+        assert_eq!(vec!["PRIVMSG ChanServ :INVITE #foo"], commands);
+    }
+
+    #[test]
+    fn kicks() {
+        let (mut manager, now) = started(&[("#foo", None), ("#bar", None)]);
+        manager.start(&ISupport::new(), now);
+        command(&mut manager, ":spudly!pj@example.com JOIN #foo,#bar", now);
+
+        let (commands, events) = command(
+            &mut manager,
+            ":Wiz!wiz@example.com KICK #foo spudly :Behave",
+            now,
+        );
+        assert!(commands.is_empty());
+        assert_eq!(
+            vec![Event::Kicked {
+                channel: "#foo".parse().unwrap(),
+                by: Some("Wiz!wiz@example.com".parse().unwrap()),
+                comment: Some("Behave".to_string()),
+            }],
+            events
+        );
+        assert!(!manager.is_joined(&"#foo".parse().unwrap()));
+
+        manager.set_rejoin_on_kick(true);
+        let (commands, _) = command(
+            &mut manager,
+            ":Wiz!wiz@example.com KICK #foo,#bar Angel,spudly",
+            now,
+        );
+        assert_eq!(vec!["JOIN #bar"], commands);
+    }
+}
@@ -0,0 +1,769 @@
+use super::Event;
+use crikey_irc_common::{
+    strip_formatting, Command, Message, MessageBody, Nickname, ParseError, Recipient, Sender,
+};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The format of the lines written by a `ChatLogger`.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum LogFormat {
+    /// `12:34 <nick> Hello there`, as irssi writes them.
+    #[default]
+    Irssi,
+    /// `2021-03-04 12:34:56\tnick\tHello there`, as WeeChat writes them.
+    Weechat,
+    /// One JSON object per line, with the tags and raw line of the message.
+    JsonLines,
+}
+
+impl FromStr for LogFormat {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "irssi" => Ok(LogFormat::Irssi),
+            "weechat" => Ok(LogFormat::Weechat),
+            "json" => Ok(LogFormat::JsonLines),
+            _ => Err(ParseError::new("LogFormat")),
+        }
+    }
+}
+
+impl From<LogFormat> for String {
+    fn from(format: LogFormat) -> String {
+        match format {
+            LogFormat::Irssi => "irssi",
+            LogFormat::Weechat => "weechat",
+            LogFormat::JsonLines => "json",
+        }
+        .to_string()
+    }
+}
+
+/// Where and how a `ChatLogger` writes its logs.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ChatLogConfig {
+    /// Logs are written to `<directory>/<network>/<target>/<date>.log`, or
+    /// `.jsonl` for `LogFormat::JsonLines`.
+    pub directory: PathBuf,
+    pub format: LogFormat,
+    /// Remove colours, bold and the like from the text. The raw line in
+    /// `LogFormat::JsonLines` is always left as it was received.
+    pub strip_formatting: bool,
+}
+
+impl Default for ChatLogConfig {
+    fn default() -> Self {
+        ChatLogConfig {
+            directory: PathBuf::from("logs"),
+            format: LogFormat::default(),
+            strip_formatting: true,
+        }
+    }
+}
+
+/// Writes the conversations on a network to a file per channel or private
+/// conversation per day, from the events returned by `Client::next_event()`.
+///
+/// Lines are timestamped with the `time` tag if the server supports
+/// `server-time`, or otherwise with the time they're logged. Either way,
+/// times and the dates that files are named after are in UTC.
+///
+/// A NICK or QUIT is logged to every channel the user shared with us, as
+/// given by `Event::Activity`. Messages retrieved with CHATHISTORY aren't
+/// logged, since we can't tell whether they were logged before.
+#[derive(Debug)]
+pub struct ChatLogger {
+    config: ChatLogConfig,
+    network: String,
+    date: String,
+    files: HashMap<String, File>,
+}
+
+impl ChatLogger {
+    pub fn new(network: &str, config: ChatLogConfig) -> Self {
+        ChatLogger {
+            config,
+            network: network.to_string(),
+            date: String::new(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Log an event, if it's one that belongs in a log. `nickname` is our
+    /// current nickname, from `Client::nickname()`, which is needed to tell
+    /// which file a private message belongs in.
+    pub fn log(&mut self, event: &Event, nickname: &Nickname) -> io::Result<()> {
+        self.log_at(event, nickname, SystemTime::now())
+    }
+
+    fn log_at(&mut self, event: &Event, nickname: &Nickname, now: SystemTime) -> io::Result<()> {
+        let (message, targets) = match event {
+            Event::Message {
+                message,
+                history: false,
+            } => (message, message_targets(message, nickname)),
+            Event::Activity { message, channels } => (
+                message,
+                channels.iter().cloned().map(String::from).collect(),
+            ),
+            _ => return Ok(()),
+        };
+
+        let timestamp = Timestamp::of(message, now);
+        for target in targets {
+            if let Some(line) = self.format(message, &target, &timestamp) {
+                self.write(&target, &timestamp.date, &line)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn format(&self, message: &Message, target: &str, timestamp: &Timestamp) -> Option<String> {
+        let line = match self.config.format {
+            LogFormat::Irssi => format!("{} {}", &timestamp.time[..5], irssi(message)?),
+            LogFormat::Weechat => format!(
+                "{} {}\t{}",
+                timestamp.date,
+                timestamp.time,
+                weechat(message)?
+            ),
+            LogFormat::JsonLines => return Some(self.json(message, target, timestamp)),
+        };
+
+        if self.config.strip_formatting {
+            Some(strip_formatting(&line))
+        } else {
+            Some(line)
+        }
+    }
+
+    fn json(&self, message: &Message, target: &str, timestamp: &Timestamp) -> String {
+        let mut fields = vec![
+            ("time", json_string(&timestamp.iso)),
+            ("network", json_string(&self.network)),
+            ("target", json_string(target)),
+        ];
+        if let Some(nickname) = nickname_of(message) {
+            fields.push(("nick", json_string(&nickname)));
+        }
+        if let MessageBody::Command(command) = &message.body {
+            fields.push(("command", json_string(&command_name(command))));
+            if let Some(text) = text_of(command) {
+                let text = if self.config.strip_formatting {
+                    strip_formatting(text)
+                } else {
+                    text.to_string()
+                };
+                fields.push(("text", json_string(&text)));
+            }
+        }
+        let tags = message
+            .tags
+            .iter()
+            .map(|(key, value)| format!("{}:{}", json_string(key), json_string(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+        fields.push(("tags", format!("{{{}}}", tags)));
+        fields.push(("raw", json_string(&String::from(message.clone()))));
+
+        let fields = fields
+            .iter()
+            .map(|(key, value)| format!("\"{}\":{}", key, value))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{{{}}}", fields)
+    }
+
+    fn write(&mut self, target: &str, date: &str, line: &str) -> io::Result<()> {
+        // Rotate to new files when the day changes.
+        if date != self.date {
+            self.files.clear();
+            self.date = date.to_string();
+        }
+
+        let name = file_name(target);
+        if !self.files.contains_key(&name) {
+            let directory = self
+                .config
+                .directory
+                .join(file_name(&self.network))
+                .join(&name);
+            fs::create_dir_all(&directory)?;
+            let extension = match self.config.format {
+                LogFormat::JsonLines => "jsonl",
+                _ => "log",
+            };
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(directory.join(format!("{}.{}", date, extension)))?;
+            self.files.insert(name.clone(), file);
+        }
+
+        writeln!(self.files.get_mut(&name).unwrap(), "{}", line)
+    }
+}
+
+/// The time of a message, split up into the parts the formats need.
+struct Timestamp {
+    /// `2021-03-04`
+    date: String,
+    /// `12:34:56`
+    time: String,
+    /// `2021-03-04T12:34:56.789Z`
+    iso: String,
+}
+
+impl Timestamp {
+    fn of(message: &Message, now: SystemTime) -> Self {
+        match message.tags.get("time") {
+            Some(time) if is_server_time(time) => Timestamp {
+                date: time[..10].to_string(),
+                time: time[11..19].to_string(),
+                iso: time.to_string(),
+            },
+            _ => Timestamp::from(now),
+        }
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let seconds = since_epoch.as_secs();
+        let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+        let date = format!("{:04}-{:02}-{:02}", year, month, day);
+        let time = format!(
+            "{:02}:{:02}:{:02}",
+            seconds % 86400 / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        );
+        Timestamp {
+            iso: format!("{}T{}.{:03}Z", date, time, since_epoch.subsec_millis()),
+            date,
+            time,
+        }
+    }
+}
+
+/// Whether a `time` tag looks like `YYYY-MM-DDThh:mm:ss.sssZ`, as the
+/// server-time specification requires.
+fn is_server_time(time: &str) -> bool {
+    let bytes = time.as_bytes();
+    bytes.len() >= 20
+        && bytes[..19].iter().enumerate().all(|(i, &b)| match i {
+            4 | 7 => b == b'-',
+            10 => b == b'T',
+            13 | 16 => b == b':',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+/// Convert days since 1970-01-01 into a year, month and day. See
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u32;
+    let month = if month < 10 { month + 3 } else { month - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// The channels or nicknames whose logs a PRIVMSG or NOTICE belongs in. A
+/// private message goes in the log for whoever we're talking to, whether they
+/// sent it to us or it's an echo of one we sent to them.
+fn message_targets(message: &Message, nickname: &Nickname) -> Vec<String> {
+    let recipients = match &message.body {
+        MessageBody::Command(Command::Privmsg { recipients, .. })
+        | MessageBody::Command(Command::Notice { recipients, .. }) => recipients,
+        _ => return Vec::new(),
+    };
+
+    recipients
+        .iter()
+        .filter_map(|recipient| match recipient {
+            Recipient::Channel(channel) | Recipient::StatusChannel(_, channel) => {
+                Some(String::from(channel.clone()))
+            }
+            Recipient::Nickname(recipient) | Recipient::NicknameUserHost(recipient, ..) => {
+                if recipient.as_ref().eq_ignore_ascii_case(nickname.as_ref()) {
+                    nickname_of(message)
+                } else {
+                    Some(String::from(recipient.clone()))
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn irssi(message: &Message) -> Option<String> {
+    let nickname = nickname_of(message).unwrap_or_default();
+    let mask = mask_of(message);
+    let command = match &message.body {
+        MessageBody::Command(command) => command,
+        MessageBody::Reply(..) => return None,
+    };
+
+    Some(match command {
+        Command::Privmsg { message: text, .. } => match ctcp(text) {
+            Some(("ACTION", action)) => format!(" * {} {}", nickname, action),
+            Some(_) => return None,
+            None => format!("<{}> {}", nickname, text),
+        },
+        Command::Notice { message: text, .. } if ctcp(text).is_none() => {
+            format!("-{}- {}", nickname, text)
+        }
+        Command::Join { channels, .. } => format!(
+            "-!- {} [{}] has joined {}",
+            nickname,
+            mask,
+            String::from(channels.clone())
+        ),
+        Command::Part {
+            channels,
+            message: reason,
+        } => format!(
+            "-!- {} [{}] has left {} [{}]",
+            nickname,
+            mask,
+            String::from(channels.clone()),
+            reason.as_deref().unwrap_or_default()
+        ),
+        Command::Quit { message: reason } => format!(
+            "-!- {} [{}] has quit [{}]",
+            nickname,
+            mask,
+            reason.as_deref().unwrap_or_default()
+        ),
+        Command::Nick { nickname: new } => {
+            format!(
+                "-!- {} is now known as {}",
+                nickname,
+                String::from(new.clone())
+            )
+        }
+        Command::Kick {
+            channels,
+            nicknames,
+            comment,
+        } => format!(
+            "-!- {} was kicked from {} by {} [{}]",
+            String::from(nicknames.clone()),
+            String::from(channels.clone()),
+            nickname,
+            comment.as_deref().unwrap_or_default()
+        ),
+        Command::Topic {
+            channel,
+            topic: Some(topic),
+        } => format!(
+            "-!- {} changed the topic of {} to: {}",
+            nickname,
+            String::from(channel.clone()),
+            topic
+        ),
+        Command::ChannelMode { channel, modes } => format!(
+            "-!- mode/{} [{}] by {}",
+            String::from(channel.clone()),
+            modes,
+            nickname
+        ),
+        _ => return None,
+    })
+}
+
+fn weechat(message: &Message) -> Option<String> {
+    let nickname = nickname_of(message).unwrap_or_default();
+    let mask = mask_of(message);
+    let command = match &message.body {
+        MessageBody::Command(command) => command,
+        MessageBody::Reply(..) => return None,
+    };
+
+    Some(match command {
+        Command::Privmsg { message: text, .. } => match ctcp(text) {
+            Some(("ACTION", action)) => format!(" *\t{} {}", nickname, action),
+            Some(_) => return None,
+            None => format!("{}\t{}", nickname, text),
+        },
+        Command::Notice { message: text, .. } if ctcp(text).is_none() => {
+            format!("--\tNotice({}): {}", nickname, text)
+        }
+        Command::Join { channels, .. } => format!(
+            "-->\t{} ({}) has joined {}",
+            nickname,
+            mask,
+            String::from(channels.clone())
+        ),
+        Command::Part {
+            channels,
+            message: reason,
+        } => format!(
+            "<--\t{} ({}) has left {} ({})",
+            nickname,
+            mask,
+            String::from(channels.clone()),
+            reason.as_deref().unwrap_or_default()
+        ),
+        Command::Quit { message: reason } => format!(
+            "<--\t{} ({}) has quit ({})",
+            nickname,
+            mask,
+            reason.as_deref().unwrap_or_default()
+        ),
+        Command::Nick { nickname: new } => {
+            format!(
+                "--\t{} is now known as {}",
+                nickname,
+                String::from(new.clone())
+            )
+        }
+        Command::Kick {
+            nicknames, comment, ..
+        } => format!(
+            "<--\t{} has kicked {} ({})",
+            nickname,
+            String::from(nicknames.clone()),
+            comment.as_deref().unwrap_or_default()
+        ),
+        Command::Topic {
+            channel,
+            topic: Some(topic),
+        } => format!(
+            "--\t{} has changed topic for {} to \"{}\"",
+            nickname,
+            String::from(channel.clone()),
+            topic
+        ),
+        Command::ChannelMode { channel, modes } => format!(
+            "--\tMode {} [{}] by {}",
+            String::from(channel.clone()),
+            modes,
+            nickname
+        ),
+        _ => return None,
+    })
+}
+
+/// Split a CTCP message into its type and parameters.
+fn ctcp(text: &str) -> Option<(&str, &str)> {
+    let text = text.strip_prefix('\x01')?;
+    let text = text.strip_suffix('\x01').unwrap_or(text);
+    let mut parts = text.splitn(2, ' ');
+    Some((parts.next()?, parts.next().unwrap_or_default()))
+}
+
+fn nickname_of(message: &Message) -> Option<String> {
+    match &message.sender {
+        Some(Sender::User { nickname, .. }) => Some(String::from(nickname.clone())),
+        Some(Sender::Server(servername)) => Some(String::from(servername.clone())),
+        None => None,
+    }
+}
+
+/// The `user@host` of the sender, as far as we know it.
+fn mask_of(message: &Message) -> String {
+    match &message.sender {
+        Some(Sender::User {
+            user: Some(user),
+            host: Some(host),
+            ..
+        }) => format!(
+            "{}@{}",
+            String::from(user.clone()),
+            String::from(host.clone())
+        ),
+        _ => String::new(),
+    }
+}
+
+fn command_name(command: &Command) -> String {
+    let raw = String::from(command.clone());
+    raw.split(' ').next().unwrap_or_default().to_string()
+}
+
+fn text_of(command: &Command) -> Option<&str> {
+    match command {
+        Command::Privmsg { message, .. } | Command::Notice { message, .. } => Some(message),
+        Command::Part { message, .. } | Command::Quit { message } => message.as_deref(),
+        Command::Kick { comment, .. } => comment.as_deref(),
+        Command::Topic { topic, .. } => topic.as_deref(),
+        _ => None,
+    }
+}
+
+/// Case-fold a channel or nickname into something safe to use as a file name,
+/// so `#Channel` and `#channel` share a log.
+fn file_name(target: &str) -> String {
+    target
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '\0' => '_',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod test_chat_log {
+    use super::*;
+    use std::path::Path;
+    use std::time::Duration;
+
+    /// A directory under the system temp dir that's removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("crikey-irc-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            TempDir(path)
+        }
+
+        fn read(&self, path: &str) -> String {
+            fs::read_to_string(self.0.join(path)).unwrap()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn logger(dir: &TempDir, format: LogFormat, strip_formatting: bool) -> ChatLogger {
+        ChatLogger::new(
+            "Libera",
+            ChatLogConfig {
+                directory: dir.0.clone(),
+                format,
+                strip_formatting,
+            },
+        )
+    }
+
+    fn message(raw: &str) -> Event {
+        Event::Message {
+            message: raw.parse().unwrap(),
+            history: false,
+        }
+    }
+
+    fn activity(raw: &str, channels: &[&str]) -> Event {
+        Event::Activity {
+            message: raw.parse().unwrap(),
+            channels: channels.iter().map(|c| c.parse().unwrap()).collect(),
+        }
+    }
+
+    fn log(logger: &mut ChatLogger, event: Event) {
+        // 2021-03-04 12:34:56 UTC
+        let now = UNIX_EPOCH + Duration::from_secs(1_614_861_296);
+        logger
+            .log_at(&event, &"spudly".parse().unwrap(), now)
+            .unwrap();
+    }
+
+    fn conversation(logger: &mut ChatLogger) {
+        log(
+            logger,
+            message(
+                "@time=2021-03-04T09:08:07.000Z :Wiz!wiz@example.com PRIVMSG #Foo :\x02Hi\x02 all",
+            ),
+        );
+        log(
+            logger,
+            message(":Wiz!wiz@example.com PRIVMSG #foo :\x01ACTION waves\x01"),
+        );
+        log(
+            logger,
+            message(":Wiz!wiz@example.com PRIVMSG #foo :\x01VERSION\x01"),
+        );
+        log(logger, message(":Wiz!wiz@example.com NOTICE spudly :Psst"));
+        log(logger, message(":spudly!pj@example.com PRIVMSG Wiz :Hello"));
+        log(
+            logger,
+            activity(":Angel!wings@irc.org JOIN #foo", &["#foo"]),
+        );
+        log(
+            logger,
+            activity(":Wiz!wiz@example.com NICK Wizard", &["#foo", "#bar"]),
+        );
+        log(
+            logger,
+            activity(":Angel!wings@irc.org QUIT :Bye", &["#foo", "#bar"]),
+        );
+        log(
+            logger,
+            Event::Message {
+                message: ":Wiz!wiz@example.com PRIVMSG #foo :Old news"
+                    .parse()
+                    .unwrap(),
+                history: true,
+            },
+        );
+        log(logger, Event::Joined("#foo".parse().unwrap()));
+    }
+
+    #[test]
+    fn irssi() {
+        let dir = TempDir::new("irssi");
+        let mut logger = logger(&dir, LogFormat::Irssi, true);
+        conversation(&mut logger);
+
+        assert_eq!(
+            "\
+09:08 <Wiz> Hi all
+12:34  * Wiz waves
+12:34 -!- Angel [wings@irc.org] has joined #foo
+12:34 -!- Wiz is now known as Wizard
+12:34 -!- Angel [wings@irc.org] has quit [Bye]
+",
+            dir.read("libera/#foo/2021-03-04.log")
+        );
+        assert_eq!(
+            "\
+12:34 -!- Wiz is now known as Wizard
+12:34 -!- Angel [wings@irc.org] has quit [Bye]
+",
+            dir.read("libera/#bar/2021-03-04.log")
+        );
+        assert_eq!(
+            "12:34 -Wiz- Psst\n12:34 <spudly> Hello\n",
+            dir.read("libera/wiz/2021-03-04.log")
+        );
+    }
+
+    #[test]
+    fn weechat() {
+        let dir = TempDir::new("weechat");
+        let mut logger = logger(&dir, LogFormat::Weechat, false);
+        conversation(&mut logger);
+
+        assert_eq!(
+            "\
+2021-03-04 09:08:07\tWiz\t\x02Hi\x02 all
+2021-03-04 12:34:56\t *\tWiz waves
+2021-03-04 12:34:56\t-->\tAngel (wings@irc.org) has joined #foo
+2021-03-04 12:34:56\t--\tWiz is now known as Wizard
+2021-03-04 12:34:56\t<--\tAngel (wings@irc.org) has quit (Bye)
+",
+            dir.read("libera/#foo/2021-03-04.log")
+        );
+        assert_eq!(
+            "\
+2021-03-04 12:34:56\t--\tNotice(Wiz): Psst
+2021-03-04 12:34:56\tspudly\tHello
+",
+            dir.read("libera/wiz/2021-03-04.log")
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let dir = TempDir::new("json");
+        let mut logger = logger(&dir, LogFormat::JsonLines, true);
+        log(
+            &mut logger,
+            message("@time=2021-03-04T09:08:07.000Z;msgid=abc :Wiz!wiz@example.com PRIVMSG #foo :\x02\"Hi\"\x02"),
+        );
+        log(
+            &mut logger,
+            activity(":Wiz!wiz@example.com PART #foo", &["#foo"]),
+        );
+
+        assert_eq!(
+            concat!(
+                r##"{"time":"2021-03-04T09:08:07.000Z","network":"Libera","target":"#foo","nick":"Wiz","command":"PRIVMSG","text":"\"Hi\"","tags":{"time":"2021-03-04T09:08:07.000Z","msgid":"abc"},"raw":"@time=2021-03-04T09:08:07.000Z;msgid=abc :Wiz!wiz@example.com PRIVMSG #foo \u0002\"Hi\"\u0002"}"##,
+                "\n",
+                r##"{"time":"2021-03-04T12:34:56.000Z","network":"Libera","target":"#foo","nick":"Wiz","command":"PART","tags":{},"raw":":Wiz!wiz@example.com PART #foo"}"##,
+                "\n",
+            ),
+            dir.read("libera/#foo/2021-03-04.jsonl")
+        );
+    }
+
+    #[test]
+    fn rotates_daily() {
+        let dir = TempDir::new("rotate");
+        let mut logger = logger(&dir, LogFormat::Irssi, true);
+        log(
+            &mut logger,
+            message("@time=2021-03-04T23:59:59.000Z :Wiz!wiz@example.com PRIVMSG #foo :Late"),
+        );
+        log(
+            &mut logger,
+            message("@time=2021-03-05T00:00:01.000Z :Wiz!wiz@example.com PRIVMSG #foo :Early"),
+        );
+
+        assert_eq!("23:59 <Wiz> Late\n", dir.read("libera/#foo/2021-03-04.log"));
+        assert_eq!(
+            "00:00 <Wiz> Early\n",
+            dir.read("libera/#foo/2021-03-05.log")
+        );
+        assert!(!Path::new(&dir.0.join("libera/#foo/2021-03-06.log")).exists());
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!((1970, 1, 1), civil_from_days(0));
+        assert_eq!((2000, 2, 29), civil_from_days(11_016));
+        assert_eq!((2021, 3, 4), civil_from_days(18_690));
+
+        let timestamp = Timestamp::from(UNIX_EPOCH + Duration::from_millis(1_614_861_296_789));
+        assert_eq!("2021-03-04", timestamp.date);
+        assert_eq!("12:34:56", timestamp.time);
+        assert_eq!("2021-03-04T12:34:56.789Z", timestamp.iso);
+
+        assert!(is_server_time("2021-03-04T12:34:56.789Z"));
+        assert!(!is_server_time("yesterday"));
+        assert!(!is_server_time("2021-03-04 12:34:56.789Z"));
+    }
+
+    #[test]
+    fn formats() {
+        for format in &[LogFormat::Irssi, LogFormat::Weechat, LogFormat::JsonLines] {
+            assert_eq!(Ok(*format), String::from(*format).parse());
+        }
+        assert!("html".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!("#foo", file_name("#Foo"));
+        assert_eq!("#a_b", file_name("#a/b"));
+    }
+}
@@ -0,0 +1,64 @@
+use super::AwaySummary;
+use crikey_irc_common::{Channel, Message, Nickname, Recipient, ReplyType, Sender};
+
+/// Something that happened on the network which the user of the `Client` may
+/// want to react to. Events are queued by `Client::poll()` and retrieved with
+/// `Client::next_event()`.
+#[derive(PartialEq, Debug)]
+pub enum Event {
+    /// A tracked nickname has come online.
+    Online(Nickname),
+    /// A tracked nickname has gone offline, or was offline when we first
+    /// started tracking it.
+    Offline(Nickname),
+    /// A PRIVMSG or NOTICE. `history` is set if the message was sent while we
+    /// weren't around to see it, and was only retrieved with CHATHISTORY.
+    Message { message: Message, history: bool },
+    /// A PRIVMSG or NOTICE that matched an ignore rule with
+    /// `IgnoreAction::Flag`.
+    Ignored { message: Message, history: bool },
+    /// A conversation with history available, in reply to
+    /// `CHATHISTORY TARGETS`. `timestamp` is the time of its latest message.
+    HistoryTarget {
+        target: Recipient,
+        timestamp: String,
+    },
+    /// We've identified to NickServ, or logged in to our account some other
+    /// way.
+    Identified,
+    /// We joined a channel that was added with `Client::join()`.
+    Joined(Channel),
+    /// The server refused to let us join a channel, because it's full,
+    /// invite-only, unavailable, we're banned, the key was wrong, we're in
+    /// too many channels or we need to register. We'll try again later.
+    JoinFailed { channel: Channel, reason: ReplyType },
+    /// The server refused to let us join a channel because it doesn't exist
+    /// or its name isn't valid, so we've stopped trying, as if
+    /// `Client::part()` had been called.
+    JoinAbandoned { channel: Channel, reason: ReplyType },
+    /// We were kicked from a channel.
+    Kicked {
+        channel: Channel,
+        by: Option<Sender>,
+        comment: Option<String>,
+    },
+    /// A JOIN, PART, KICK, QUIT, NICK, TOPIC or MODE concerning channels
+    /// we're in, including our own. `channels` are the channels it affects,
+    /// which for a QUIT or NICK are all those we share with the user.
+    Activity {
+        message: Message,
+        channels: Vec<Channel>,
+    },
+    /// The server has confirmed that we're marked as away.
+    Away,
+    /// The server has confirmed that we're no longer marked as away, with
+    /// what we missed in the meantime.
+    Back(AwaySummary),
+    /// The server stopped responding to our keepalive PINGs, so the
+    /// connection is probably dead. See `Client::set_keepalive()`.
+    PingTimeout,
+    /// The server closed the connection, or reading from it failed. Nothing
+    /// more will be received, so the caller should reconnect with a new
+    /// `Client`.
+    Disconnected,
+}
@@ -0,0 +1,335 @@
+use super::batch::Batch;
+use super::Event;
+use crikey_irc_common::{
+    Command, HistorySelector, Message, MessageBody, Nickname, Recipient, Sender,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The batch type used for the messages returned by every CHATHISTORY
+/// subcommand except TARGETS.
+pub const BATCH_CHATHISTORY: &str = "chathistory";
+
+/// The batch type used for replies to CHATHISTORY TARGETS.
+pub const BATCH_CHATHISTORY_TARGETS: &str = "draft/chathistory-targets";
+
+/// How many messages to ask for when the server doesn't impose a lower limit.
+const DEFAULT_LIMIT: u16 = 100;
+
+/// How many msgids to remember for deduplication. Backfill only ever overlaps
+/// with recent history, so there's no point in remembering everything.
+const SEEN_CAPACITY: usize = 1000;
+
+/// Keeps track of where each conversation left off, so that missed messages
+/// can be requested with CHATHISTORY, and of which messages we've already
+/// delivered, so that backfilled messages aren't delivered twice.
+///
+/// Positions are recorded from the `msgid` tag where available, falling back
+/// to the `time` tag. Messages without either can't be deduplicated and are
+/// always delivered.
+#[derive(Debug, Default)]
+pub struct History {
+    seen: HashSet<String>,
+    seen_order: VecDeque<String>,
+    positions: HashMap<String, HistorySelector>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last position we know of in a conversation, if any.
+    pub fn position(&self, target: &str) -> Option<&HistorySelector> {
+        self.positions.get(&target.to_ascii_lowercase())
+    }
+
+    /// Build a request for everything in a conversation since the last
+    /// message we saw, or the most recent messages if we haven't seen any.
+    /// `max_limit` is the CHATHISTORY limit advertised in ISUPPORT.
+    pub fn backfill(&self, target: Recipient, max_limit: Option<usize>) -> Command {
+        let selector = self
+            .position(&String::from(target.clone()))
+            .cloned()
+            .unwrap_or(HistorySelector::Any);
+
+        Command::ChatHistoryLatest {
+            target,
+            selector,
+            limit: limit(max_limit),
+        }
+    }
+
+    /// Record a message received in real time. Returns an event for anything
+    /// that should be delivered.
+    pub fn handle_live(&mut self, message: Message, nickname: &Nickname) -> Option<Event> {
+        let conversation = conversation(&message, nickname)?;
+        self.see(&message);
+        self.advance(&conversation, &message);
+        Some(Event::Message {
+            message,
+            history: false,
+        })
+    }
+
+    /// Unpack a completed chathistory batch into events, skipping anything
+    /// we've already delivered.
+    pub fn handle_batch(&mut self, batch: Batch, nickname: &Nickname) -> Vec<Event> {
+        match &batch.batch_type[..] {
+            BATCH_CHATHISTORY => {
+                let mut events = Vec::new();
+                for message in batch.messages {
+                    let conversation = match conversation(&message, nickname) {
+                        Some(conversation) => conversation,
+                        None => continue,
+                    };
+                    if self.see(&message) {
+                        self.advance(&conversation, &message);
+                        events.push(Event::Message {
+                            message,
+                            history: true,
+                        });
+                    }
+                }
+                events
+            }
+            BATCH_CHATHISTORY_TARGETS => batch
+                .messages
+                .into_iter()
+                .filter_map(|message| match message.body {
+                    MessageBody::Command(Command::ChatHistoryTarget { target, timestamp }) => {
+                        Some(Event::HistoryTarget { target, timestamp })
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Remember a message's msgid. Returns `false` if we've seen it before.
+    fn see(&mut self, message: &Message) -> bool {
+        let msgid = match message.tags.get("msgid") {
+            Some(msgid) => msgid.to_string(),
+            None => return true,
+        };
+
+        if self.seen.contains(&msgid) {
+            return false;
+        }
+
+        if self.seen_order.len() >= SEEN_CAPACITY {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.seen.insert(msgid.clone());
+        self.seen_order.push_back(msgid);
+        true
+    }
+
+    fn advance(&mut self, conversation: &str, message: &Message) {
+        let selector = if let Some(msgid) = message.tags.get("msgid") {
+            HistorySelector::MsgId(msgid.to_string())
+        } else if let Some(time) = message.tags.get("time") {
+            HistorySelector::Timestamp(time.to_string())
+        } else {
+            return;
+        };
+
+        self.positions
+            .insert(conversation.to_ascii_lowercase(), selector);
+    }
+}
+
+fn limit(max_limit: Option<usize>) -> u16 {
+    match max_limit {
+        Some(max_limit) if max_limit > 0 && max_limit < DEFAULT_LIMIT as usize => max_limit as u16,
+        _ => DEFAULT_LIMIT,
+    }
+}
+
+/// The conversation a PRIVMSG or NOTICE belongs to: the channel for channel
+/// messages, or the other party for private messages. Anything else isn't
+/// part of a conversation.
+fn conversation(message: &Message, nickname: &Nickname) -> Option<String> {
+    let recipients = match &message.body {
+        MessageBody::Command(Command::Privmsg { recipients, .. })
+        | MessageBody::Command(Command::Notice { recipients, .. }) => recipients,
+        _ => return None,
+    };
+
+    let recipient = String::from(recipients.clone());
+    if recipient.eq_ignore_ascii_case(nickname.as_ref()) {
+        match &message.sender {
+            Some(Sender::User { nickname, .. }) => Some(String::from(nickname.clone())),
+            _ => None,
+        }
+    } else {
+        Some(recipient)
+    }
+}
+
+#[cfg(test)]
+mod test_history {
+    use super::*;
+    use crikey_irc_common::MessageTags;
+
+    fn nickname() -> Nickname {
+        "spudly".parse().unwrap()
+    }
+
+    fn batch(batch_type: &str, messages: &[&str]) -> Batch {
+        Batch {
+            reference: "abc".to_string(),
+            batch_type: batch_type.to_string(),
+            params: Vec::new(),
+            tags: MessageTags::new(),
+            messages: messages.iter().map(|raw| raw.parse().unwrap()).collect(),
+            batches: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn backfill_from_scratch() {
+        let history = History::new();
+        assert_eq!(
+            "CHATHISTORY LATEST #channel * 100"
+                .parse::<Command>()
+                .unwrap(),
+            history.backfill("#channel".parse().unwrap(), None)
+        );
+        assert_eq!(
+            "CHATHISTORY LATEST #channel * 50"
+                .parse::<Command>()
+                .unwrap(),
+            history.backfill("#channel".parse().unwrap(), Some(50))
+        );
+        assert_eq!(
+            "CHATHISTORY LATEST #channel * 100"
+                .parse::<Command>()
+                .unwrap(),
+            history.backfill("#channel".parse().unwrap(), Some(0))
+        );
+    }
+
+    #[test]
+    fn backfill_from_position() {
+        let mut history = History::new();
+        history.handle_live(
+            "@msgid=1 :Wiz PRIVMSG #Channel :hello".parse().unwrap(),
+            &nickname(),
+        );
+        history.handle_live(
+            "@time=2019-01-04T14:33:26.123Z :Wiz PRIVMSG spudly :hi"
+                .parse()
+                .unwrap(),
+            &nickname(),
+        );
+
+        assert_eq!(
+            "CHATHISTORY LATEST #channel msgid=1 100"
+                .parse::<Command>()
+                .unwrap(),
+            history.backfill("#channel".parse().unwrap(), None)
+        );
+        assert_eq!(
+            "CHATHISTORY LATEST Wiz timestamp=2019-01-04T14:33:26.123Z 100"
+                .parse::<Command>()
+                .unwrap(),
+            history.backfill("Wiz".parse().unwrap(), None)
+        );
+    }
+
+    #[test]
+    fn live() {
+        let mut history = History::new();
+        assert_eq!(
+            Some(Event::Message {
+                message: ":Wiz PRIVMSG #channel :hello".parse().unwrap(),
+                history: false,
+            }),
+            history.handle_live(":Wiz PRIVMSG #channel :hello".parse().unwrap(), &nickname())
+        );
+        assert_eq!(
+            None,
+            history.handle_live(":Wiz JOIN #channel".parse().unwrap(), &nickname())
+        );
+    }
+
+    #[test]
+    fn deduplicate() {
+        let mut history = History::new();
+        history.handle_live(
+            "@msgid=2 :Wiz PRIVMSG #channel :two".parse().unwrap(),
+            &nickname(),
+        );
+
+        assert_eq!(
+            vec![
+                Event::Message {
+                    message: "@msgid=1 :Wiz PRIVMSG #channel :one".parse().unwrap(),
+                    history: true,
+                },
+                Event::Message {
+                    message: ":Wiz NOTICE #channel :no msgid".parse().unwrap(),
+                    history: true,
+                },
+                Event::Message {
+                    message: "@msgid=3 :Wiz PRIVMSG #channel :three".parse().unwrap(),
+                    history: true,
+                },
+            ],
+            history.handle_batch(
+                batch(
+                    BATCH_CHATHISTORY,
+                    &[
+                        "@msgid=1 :Wiz PRIVMSG #channel :one",
+                        "@msgid=2 :Wiz PRIVMSG #channel :two",
+                        ":Wiz NOTICE #channel :no msgid",
+                        "@msgid=3 :Wiz PRIVMSG #channel :three",
+                        "@msgid=4 :Wiz JOIN #channel",
+                    ]
+                ),
+                &nickname()
+            )
+        );
+        assert_eq!(
+            Some(&HistorySelector::MsgId("3".to_string())),
+            history.position("#channel")
+        );
+    }
+
+    #[test]
+    fn bounded() {
+        let mut history = History::new();
+        for i in 0..=SEEN_CAPACITY {
+            history.handle_live(
+                format!("@msgid={} :Wiz PRIVMSG #channel :hi", i)
+                    .parse()
+                    .unwrap(),
+                &nickname(),
+            );
+        }
+        assert_eq!(SEEN_CAPACITY, history.seen.len());
+        assert!(!history.seen.contains("0"));
+        assert!(history.seen.contains("1"));
+    }
+
+    #[test]
+    fn targets() {
+        let mut history = History::new();
+        assert_eq!(
+            vec![Event::HistoryTarget {
+                target: "#channel".parse().unwrap(),
+                timestamp: "2020-07-18T12:34:56.000Z".to_string(),
+            }],
+            history.handle_batch(
+                batch(
+                    BATCH_CHATHISTORY_TARGETS,
+                    &[":irc.host CHATHISTORY TARGETS #channel 2020-07-18T12:34:56.000Z"]
+                ),
+                &nickname()
+            )
+        );
+    }
+}
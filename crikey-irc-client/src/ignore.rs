@@ -0,0 +1,464 @@
+use super::Event;
+use crikey_irc_common::{
+    Casemapping, Channel, Command, Extbans, MaskSubject, Message, MessageBody, Recipient, Sender,
+    UserMask, TARGET_PARSE,
+};
+use log::warn;
+use regex::Regex;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// What to do with a message that matches an ignore rule.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum IgnoreAction {
+    /// Drop the message without emitting an event.
+    #[default]
+    Drop,
+    /// Emit `Event::Ignored` instead of `Event::Message`, so that it can
+    /// still be logged.
+    Flag,
+}
+
+/// A rule for ignoring inbound PRIVMSGs and NOTICEs. A message is ignored if
+/// it matches every criterion that's set, so a rule with none set ignores
+/// everything within its scope:
+///
+/// ```
+/// # use crikey_irc_client::{IgnoreRule, Regex};
+/// let rule = IgnoreRule {
+///     mask: Some("*!*@*.example.com".parse()?),
+///     pattern: Some(Regex::new("(?i)free crypto").unwrap()),
+///     channel: Some("#channel".parse()?),
+///     ..IgnoreRule::default()
+/// };
+/// # Ok::<(), crikey_irc_client::common::ParseError>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct IgnoreRule {
+    /// The senders to ignore.
+    pub mask: Option<UserMask>,
+    /// A regular expression matched against the text of the message.
+    pub pattern: Option<Regex>,
+    /// Only ignore CTCP requests of this type, such as "VERSION", or of any
+    /// type if "*".
+    pub ctcp: Option<String>,
+    /// Only ignore messages sent to this channel, rather than everywhere.
+    pub channel: Option<Channel>,
+    /// When to stop ignoring, if ever.
+    pub expires: Option<Instant>,
+    pub action: IgnoreAction,
+}
+
+/// Automatically ignore anyone who sends more than `messages` messages within
+/// `period`, for `ignore_for`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FloodLimit {
+    pub messages: usize,
+    pub period: Duration,
+    pub ignore_for: Duration,
+}
+
+/// Filters inbound messages through the ignore list, before they're emitted
+/// as events, and watches for senders flooding us.
+#[derive(Debug, Default)]
+pub struct Ignores {
+    rules: Vec<IgnoreRule>,
+    flood_limit: Option<FloodLimit>,
+    recent: HashMap<String, VecDeque<Instant>>,
+}
+
+impl Ignores {
+    pub fn add(&mut self, rule: IgnoreRule) {
+        self.rules.push(rule);
+    }
+
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+
+    pub fn set_flood_limit(&mut self, flood_limit: Option<FloodLimit>) {
+        self.flood_limit = flood_limit;
+        self.recent.clear();
+    }
+
+    /// Forget rules that have expired, and senders who haven't sent anything
+    /// within the flood limit's period.
+    pub fn poll(&mut self, now: Instant) {
+        self.rules
+            .retain(|rule| rule.expires.is_none_or(|expires| expires > now));
+
+        let period = match self.flood_limit {
+            Some(flood_limit) => flood_limit.period,
+            None => return,
+        };
+        self.recent.retain(|_, recent| {
+            prune(recent, period, now);
+            !recent.is_empty()
+        });
+    }
+
+    /// Pass an event through the ignore list. Messages that should be dropped
+    /// return `None`, and anything other than a message passes untouched.
+    pub fn filter(
+        &mut self,
+        event: Event,
+        now: Instant,
+        casemapping: Casemapping,
+        extbans: &Extbans,
+    ) -> Option<Event> {
+        let (message, history) = match event {
+            Event::Message { message, history } => (message, history),
+            event => return Some(event),
+        };
+
+        self.rules
+            .retain(|rule| rule.expires.is_none_or(|expires| expires > now));
+        if !history {
+            self.track(&message, now);
+        }
+
+        let action = self
+            .rules
+            .iter()
+            .find(|rule| is_match(rule, &message, casemapping, extbans))
+            .map(|rule| rule.action);
+
+        match action {
+            None => Some(Event::Message { message, history }),
+            Some(IgnoreAction::Flag) => Some(Event::Ignored { message, history }),
+            Some(IgnoreAction::Drop) => None,
+        }
+    }
+
+    /// Count a live message against the flood limit of its sender, and ignore
+    /// them for a while if they've gone over it.
+    fn track(&mut self, message: &Message, now: Instant) {
+        let flood_limit = match self.flood_limit {
+            Some(flood_limit) => flood_limit,
+            None => return,
+        };
+        let mask = match &message.sender {
+            Some(Sender::User {
+                host: Some(host), ..
+            }) => format!("*!*@{}", String::from(host.clone())),
+            Some(Sender::User { nickname, .. }) => String::from(nickname.clone()),
+            _ => return,
+        };
+
+        let recent = self.recent.entry(mask.clone()).or_default();
+        recent.push_back(now);
+        prune(recent, flood_limit.period, now);
+
+        if recent.is_empty() {
+            self.recent.remove(&mask);
+        } else if recent.len() > flood_limit.messages {
+            self.recent.remove(&mask);
+            self.ignore_flooder(&mask, now + flood_limit.ignore_for);
+        }
+    }
+
+    /// Ignore everyone matching `mask` until `expires`. A mask that doesn't
+    /// parse is skipped, since a rule without one would ignore everyone.
+    fn ignore_flooder(&mut self, mask: &str, expires: Instant) {
+        match mask.parse() {
+            Ok(mask) => self.rules.push(IgnoreRule {
+                mask: Some(mask),
+                expires: Some(expires),
+                ..IgnoreRule::default()
+            }),
+            Err(e) => warn!(
+                target: TARGET_PARSE,
+                "Not ignoring flooder with invalid mask {:?}: {}", mask, e
+            ),
+        }
+    }
+}
+
+/// Drop the times of messages sent longer than `period` ago.
+fn prune(recent: &mut VecDeque<Instant>, period: Duration, now: Instant) {
+    while recent
+        .front()
+        .is_some_and(|&sent| now.duration_since(sent) >= period)
+    {
+        recent.pop_front();
+    }
+}
+
+fn is_match(
+    rule: &IgnoreRule,
+    message: &Message,
+    casemapping: Casemapping,
+    extbans: &Extbans,
+) -> bool {
+    let (recipients, text) = match &message.body {
+        MessageBody::Command(Command::Privmsg {
+            recipients,
+            message,
+        })
+        | MessageBody::Command(Command::Notice {
+            recipients,
+            message,
+        }) => (recipients, message),
+        _ => return false,
+    };
+
+    if let Some(mask) = &rule.mask {
+        let subject = match &message.sender {
+            Some(sender) => MaskSubject::from(sender.clone()),
+            None => return false,
+        };
+        if !mask.matches(&subject, casemapping, extbans) {
+            return false;
+        }
+    }
+
+    if let Some(channel) = &rule.channel {
+        let channel = String::from(channel.clone());
+        let in_channel = recipients.iter().any(|recipient| match recipient {
+            Recipient::Channel(c) | Recipient::StatusChannel(_, c) => {
+                casemapping.eq(&String::from(c.clone()), &channel)
+            }
+            _ => false,
+        });
+        if !in_channel {
+            return false;
+        }
+    }
+
+    if let Some(ctcp) = &rule.ctcp {
+        match ctcp_type(text) {
+            Some(ctcp_type) if ctcp == "*" || ctcp.eq_ignore_ascii_case(ctcp_type) => (),
+            _ => return false,
+        }
+    }
+
+    rule.pattern
+        .as_ref()
+        .is_none_or(|pattern| pattern.is_match(text))
+}
+
+/// The type of a CTCP request, which is sent as a message wrapped in "\x01",
+/// such as "\x01VERSION\x01".
+fn ctcp_type(text: &str) -> Option<&str> {
+    let ctcp = text.strip_prefix('\x01')?;
+    let ctcp = ctcp.strip_suffix('\x01').unwrap_or(ctcp);
+    ctcp.split(' ')
+        .next()
+        .filter(|ctcp_type| !ctcp_type.is_empty())
+}
+
+#[cfg(test)]
+mod test_ignores {
+    use super::*;
+
+    fn filter(ignores: &mut Ignores, raw: &str, now: Instant) -> Option<Event> {
+        ignores.filter(
+            Event::Message {
+                message: raw.parse().unwrap(),
+                history: false,
+            },
+            now,
+            Casemapping::default(),
+            &Extbans::default(),
+        )
+    }
+
+    fn is_dropped(ignores: &mut Ignores, raw: &str) -> bool {
+        filter(ignores, raw, Instant::now()).is_none()
+    }
+
+    #[test]
+    fn hostmasks() {
+        let mut ignores = Ignores::default();
+        ignores.add(IgnoreRule {
+            mask: Some("*!*@*.example.com".parse().unwrap()),
+            ..IgnoreRule::default()
+        });
+
+        assert!(is_dropped(
+            &mut ignores,
+            ":Wiz!wiz@spam.example.com PRIVMSG #channel :hi"
+        ));
+        assert!(is_dropped(
+            &mut ignores,
+            ":Wiz!wiz@spam.example.com NOTICE spudly :hi"
+        ));
+        assert!(!is_dropped(
+            &mut ignores,
+            ":Angel!wings@irc.org PRIVMSG #channel :hi"
+        ));
+        assert!(!is_dropped(&mut ignores, "PRIVMSG #channel :no sender"));
+
+        // Other events aren't affected.
+        assert_eq!(
+            Some(Event::Online("Wiz".parse().unwrap())),
+            ignores.filter(
+                Event::Online("Wiz".parse().unwrap()),
+                Instant::now(),
+                Casemapping::default(),
+                &Extbans::default()
+            )
+        );
+
+        ignores.clear();
+        assert!(!is_dropped(
+            &mut ignores,
+            ":Wiz!wiz@spam.example.com PRIVMSG #channel :hi"
+        ));
+    }
+
+    #[test]
+    fn patterns_and_channels() {
+        let mut ignores = Ignores::default();
+        ignores.add(IgnoreRule {
+            pattern: Some(Regex::new("(?i)free crypto").unwrap()),
+            channel: Some("#Channel".parse().unwrap()),
+            action: IgnoreAction::Flag,
+            ..IgnoreRule::default()
+        });
+
+        let raw = ":Wiz!wiz@example.com PRIVMSG #channel :FREE CRYPTO here";
+        assert_eq!(
+            Some(Event::Ignored {
+                message: raw.parse().unwrap(),
+                history: false,
+            }),
+            filter(&mut ignores, raw, Instant::now())
+        );
+        assert!(matches!(
+            filter(
+                &mut ignores,
+                ":Wiz!wiz@example.com PRIVMSG #other :free crypto here",
+                Instant::now()
+            ),
+            Some(Event::Message { .. })
+        ));
+        assert!(matches!(
+            filter(
+                &mut ignores,
+                ":Wiz!wiz@example.com PRIVMSG #channel :hello",
+                Instant::now()
+            ),
+            Some(Event::Message { .. })
+        ));
+    }
+
+    #[test]
+    fn ctcp() {
+        let mut ignores = Ignores::default();
+        ignores.add(IgnoreRule {
+            ctcp: Some("version".to_string()),
+            ..IgnoreRule::default()
+        });
+
+        assert!(is_dropped(
+            &mut ignores,
+            ":Wiz!wiz@example.com PRIVMSG spudly :\x01VERSION\x01"
+        ));
+        assert!(!is_dropped(
+            &mut ignores,
+            ":Wiz!wiz@example.com PRIVMSG spudly :\x01ACTION waves\x01"
+        ));
+        assert!(!is_dropped(
+            &mut ignores,
+            ":Wiz!wiz@example.com PRIVMSG spudly :VERSION"
+        ));
+
+        ignores.add(IgnoreRule {
+            ctcp: Some("*".to_string()),
+            ..IgnoreRule::default()
+        });
+        assert!(is_dropped(
+            &mut ignores,
+            ":Wiz!wiz@example.com PRIVMSG spudly :\x01ACTION waves\x01"
+        ));
+    }
+
+    #[test]
+    fn expiry() {
+        let now = Instant::now();
+        let mut ignores = Ignores::default();
+        ignores.add(IgnoreRule {
+            mask: Some("Wiz".parse().unwrap()),
+            expires: Some(now + Duration::from_secs(60)),
+            ..IgnoreRule::default()
+        });
+
+        let raw = ":Wiz!wiz@example.com PRIVMSG #channel :hi";
+        assert!(filter(&mut ignores, raw, now + Duration::from_secs(59)).is_none());
+        assert!(filter(&mut ignores, raw, now + Duration::from_secs(60)).is_some());
+    }
+
+    #[test]
+    fn flood_limit() {
+        let now = Instant::now();
+        let mut ignores = Ignores::default();
+        ignores.set_flood_limit(Some(FloodLimit {
+            messages: 3,
+            period: Duration::from_secs(10),
+            ignore_for: Duration::from_secs(60),
+        }));
+
+        let spam = ":Wiz!wiz@example.com PRIVMSG #channel :spam";
+        for i in 0..3 {
+            assert!(filter(&mut ignores, spam, now + Duration::from_secs(i * 4)).is_some());
+        }
+        // The first message has aged out of the period.
+        assert!(filter(&mut ignores, spam, now + Duration::from_secs(12)).is_some());
+        assert!(filter(&mut ignores, spam, now + Duration::from_secs(13)).is_none());
+
+        // The whole host is ignored, but only for a while.
+        let other = ":Angel!angel@example.com PRIVMSG #channel :me too";
+        assert!(filter(&mut ignores, other, now + Duration::from_secs(14)).is_none());
+        assert!(filter(&mut ignores, other, now + Duration::from_secs(73)).is_some());
+
+        for _ in 0..10 {
+            let history = Event::Message {
+                message: spam.parse().unwrap(),
+                history: true,
+            };
+            assert!(ignores
+                .filter(
+                    history,
+                    now + Duration::from_secs(80),
+                    Casemapping::default(),
+                    &Extbans::default()
+                )
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn skips_invalid_flooder_mask() {
+        let now = Instant::now();
+        let mut ignores = Ignores::default();
+        ignores.ignore_flooder("*!*@", now + Duration::from_secs(60));
+        assert!(ignores.rules.is_empty());
+        assert!(!is_dropped(
+            &mut ignores,
+            ":Wiz!wiz@example.com PRIVMSG #channel :Hello"
+        ));
+    }
+
+    #[test]
+    fn forgets_quiet_senders() {
+        let now = Instant::now();
+        let mut ignores = Ignores::default();
+        ignores.set_flood_limit(Some(FloodLimit {
+            messages: 3,
+            period: Duration::from_secs(10),
+            ignore_for: Duration::from_secs(60),
+        }));
+
+        for i in 0..100 {
+            let raw = format!(":user{0}!user@host{0}.example.com PRIVMSG #channel :hi", i);
+            filter(&mut ignores, &raw, now);
+        }
+        assert_eq!(100, ignores.recent.len());
+
+        ignores.poll(now + Duration::from_secs(9));
+        assert_eq!(100, ignores.recent.len());
+        ignores.poll(now + Duration::from_secs(10));
+        assert!(ignores.recent.is_empty());
+    }
+}
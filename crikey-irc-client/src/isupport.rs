@@ -0,0 +1,252 @@
+use crikey_irc_common::{Casemapping, Extbans, MessageParams, ValidationProfile};
+use std::collections::HashMap;
+
+/// The features advertised by the server in RPL_ISUPPORT (005) replies. RFC
+/// 2812 defines 005 as RPL_BOUNCE, but in practice every modern server uses
+/// it to list supported features:
+///
+/// ```text
+/// isupport   =  "005" " " client 1*13( " " token ) " :are supported by this server"
+/// token      =  [ "-" ] parameter [ "=" [ value ] ]
+/// ```
+///
+/// A parameter prefixed with "-" negates an earlier advertisement. Values may
+/// contain `\xHH` escapes, which are decoded on the way in.
+#[derive(PartialEq, Debug, Default)]
+pub struct ISupport {
+    tokens: HashMap<String, Option<String>>,
+}
+
+impl ISupport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the tokens from a single 005 reply. Returns `false` if the reply
+    /// doesn't look like ISUPPORT at all (ie. it's an RFC 2812 bounce).
+    pub fn update(&mut self, params: &MessageParams) -> bool {
+        if params.len() < 3 {
+            return false;
+        }
+
+        for index in 1..params.len() - 1 {
+            let token = &params[index];
+            if let Some(parameter) = token.strip_prefix('-') {
+                self.tokens.remove(parameter);
+            } else if let Some(index) = token.find('=') {
+                let value = &token[index + 1..];
+                self.tokens.insert(
+                    token[..index].to_string(),
+                    if value.is_empty() {
+                        None
+                    } else {
+                        Some(unescape(value))
+                    },
+                );
+            } else {
+                self.tokens.insert(token.to_string(), None);
+            }
+        }
+        true
+    }
+
+    pub fn contains(&self, parameter: &str) -> bool {
+        self.tokens.contains_key(parameter)
+    }
+
+    pub fn value(&self, parameter: &str) -> Option<&str> {
+        self.tokens.get(parameter)?.as_deref()
+    }
+
+    /// The maximum number of MONITOR targets, if MONITOR is supported at all.
+    /// `Some(None)` means the server imposes no limit.
+    pub fn monitor(&self) -> Option<Option<usize>> {
+        self.limit("MONITOR")
+    }
+
+    /// The maximum number of WATCH entries, if WATCH is supported at all.
+    pub fn watch(&self) -> Option<Option<usize>> {
+        self.limit("WATCH")
+    }
+
+    /// The maximum number of messages that may be requested in a single
+    /// CHATHISTORY command, if CHATHISTORY is supported at all. A limit of 0
+    /// means there's no limit.
+    pub fn chathistory(&self) -> Option<Option<usize>> {
+        self.limit("CHATHISTORY")
+    }
+
+    /// The prefixes that may precede a channel name to address only members
+    /// with that status, such as "@" in "@#channel". The `Client` uses these
+    /// to parse the recipients of incoming messages, with
+    /// `Recipient::parse_with_statusmsg()`.
+    pub fn statusmsg(&self) -> &str {
+        self.value("STATUSMSG").unwrap_or("")
+    }
+
+    /// Whether the server only sends and accepts UTF-8, as advertised with
+    /// UTF8ONLY.
+    pub fn utf8_only(&self) -> bool {
+        self.contains("UTF8ONLY")
+    }
+
+    /// The rules for comparing nicknames and channel names. Servers that don't
+    /// say, or use casemapping we don't know, are assumed to follow RFC 1459.
+    pub fn casemapping(&self) -> Casemapping {
+        self.value("CASEMAPPING")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// The extban prefix and types the server supports, for use with
+    /// `UserMask`. Without EXTBAN, the common prefixes and types are assumed.
+    pub fn extbans(&self) -> Extbans {
+        self.value("EXTBAN")
+            .map(Extbans::from_isupport)
+            .unwrap_or_default()
+    }
+
+    /// The rules the server applies to nicknames and channel names, for
+    /// checking names before we send them. CHANTYPES defaults to "#&" when
+    /// not advertised; lengths default to unlimited.
+    pub fn profile(&self) -> ValidationProfile {
+        let nicklen = self.value("NICKLEN").and_then(|v| v.parse().ok());
+        let channellen = self.value("CHANNELLEN").and_then(|v| v.parse().ok());
+        let chantypes = if self.contains("CHANTYPES") {
+            self.value("CHANTYPES").unwrap_or("")
+        } else {
+            "#&"
+        };
+        ValidationProfile::advertised(nicklen, chantypes, channellen)
+    }
+
+    fn limit(&self, parameter: &str) -> Option<Option<usize>> {
+        if self.contains(parameter) {
+            Some(self.value(parameter).and_then(|v| v.parse().ok()))
+        } else {
+            None
+        }
+    }
+}
+
+fn unescape(raw: &str) -> String {
+    let mut result = String::with_capacity(raw.len());
+    let mut rest = raw;
+
+    while let Some(index) = rest.find("\\x") {
+        result.push_str(&rest[..index]);
+        let escaped = rest.get(index + 2..index + 4);
+        match escaped.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                result.push(char::from(byte));
+                rest = &rest[index + 4..];
+            }
+            None => {
+                result.push_str("\\x");
+                rest = &rest[index + 2..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod test_isupport {
+    use super::*;
+    use crikey_irc_common::{MaskSubject, UserMask};
+
+    fn isupport(raw: &str) -> ISupport {
+        let mut isupport = ISupport::new();
+        assert!(isupport.update(&raw.parse().unwrap()));
+        isupport
+    }
+
+    #[test]
+    fn update() {
+        let isupport = isupport(
+            "spudly RFC2812 CASEMAPPING=ascii MONITOR=100 EXCEPTS= NETWORK=Example\\x20Net :are supported on this server",
+        );
+        assert!(isupport.contains("RFC2812"));
+        assert_eq!(None, isupport.value("RFC2812"));
+        assert_eq!(Some("ascii"), isupport.value("CASEMAPPING"));
+        assert!(isupport.contains("EXCEPTS"));
+        assert_eq!(None, isupport.value("EXCEPTS"));
+        assert_eq!(Some("Example Net"), isupport.value("NETWORK"));
+        assert!(!isupport.contains("are supported on this server"));
+        assert_eq!("", isupport.statusmsg());
+        assert_eq!(Casemapping::Ascii, isupport.casemapping());
+        assert!(!isupport.utf8_only());
+        assert!(self::isupport("spudly UTF8ONLY :are supported").utf8_only());
+    }
+
+    #[test]
+    fn masks() {
+        assert_eq!(Casemapping::Rfc1459, ISupport::new().casemapping());
+
+        let isupport = isupport("spudly CASEMAPPING=rfc7613 EXTBAN=$,a :are supported");
+        assert_eq!(Casemapping::Rfc1459, isupport.casemapping());
+
+        let subject = MaskSubject {
+            account: Some("spudly".to_string()),
+            ..MaskSubject::default()
+        };
+        let extbans = isupport.extbans();
+        for (raw, expected) in &[("$a:spudly", true), ("$r:*", false)] {
+            let mask = UserMask::parse_with_extbans(raw, &extbans).unwrap();
+            assert_eq!(
+                *expected,
+                mask.matches(&subject, Casemapping::Rfc1459, &extbans)
+            );
+        }
+    }
+
+    #[test]
+    fn negation() {
+        let mut isupport = isupport("spudly MONITOR=100 STATUSMSG=@+ :are supported");
+        assert_eq!("@+", isupport.statusmsg());
+        assert!(isupport.update(&"spudly -MONITOR :are supported".parse().unwrap()));
+        assert!(!isupport.contains("MONITOR"));
+    }
+
+    #[test]
+    fn profile() {
+        assert_eq!(
+            ValidationProfile::advertised(None, "#&", None),
+            ISupport::new().profile()
+        );
+        assert_eq!(
+            ValidationProfile::advertised(Some(30), "#", Some(64)),
+            isupport("spudly NICKLEN=30 CHANTYPES=# CHANNELLEN=64 :are supported").profile()
+        );
+        // An empty CHANTYPES means the server has no channels at all.
+        assert!(!isupport("spudly CHANTYPES= :are supported")
+            .profile()
+            .is_valid_channel("#channel"));
+    }
+
+    #[test]
+    fn bounce() {
+        let mut isupport = ISupport::new();
+        assert!(!isupport.update(&":Try server irc.example.com, port 6667".parse().unwrap()));
+        assert_eq!(ISupport::new(), isupport);
+    }
+
+    #[test]
+    fn limits() {
+        let isupport = isupport("spudly MONITOR=100 WATCH CHATHISTORY=50 :are supported");
+        assert_eq!(Some(Some(100)), isupport.monitor());
+        assert_eq!(Some(None), isupport.watch());
+        assert_eq!(Some(Some(50)), isupport.chathistory());
+        assert_eq!(None, ISupport::new().monitor());
+    }
+
+    #[test]
+    fn unescape_values() {
+        assert_eq!("a b", unescape("a\\x20b"));
+        assert_eq!("a=b\\", unescape("a\\x3Db\\x5C"));
+        assert_eq!("trailing\\x", unescape("trailing\\x"));
+        assert_eq!("bad\\xZZ", unescape("bad\\xZZ"));
+    }
+}
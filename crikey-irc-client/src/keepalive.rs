@@ -0,0 +1,225 @@
+use crikey_irc_common::{Command, Sender};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(60);
+pub const DEFAULT_PING_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The number of recent round trips averaged by `average_lag()`.
+const LAG_SAMPLES: usize = 10;
+
+/// Checks that the connection is still alive. If nothing has been received
+/// from the server for `interval`, we send a PING with a token of our own,
+/// and time how long the matching PONG takes to arrive. If the server then
+/// stays silent for `timeout`, the connection is considered dead. PONGs are
+/// fed back in via `handle_pong()`.
+#[derive(Debug)]
+pub struct Keepalive {
+    interval: Duration,
+    timeout: Duration,
+    last_received: Option<Instant>,
+    pending: Option<(String, Instant)>,
+    next_token: u64,
+    samples: VecDeque<Duration>,
+    timed_out: bool,
+}
+
+impl Keepalive {
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Keepalive {
+            interval,
+            timeout,
+            last_received: None,
+            pending: None,
+            next_token: 0,
+            samples: VecDeque::new(),
+            timed_out: false,
+        }
+    }
+
+    /// Called once registration is complete, since servers won't answer a
+    /// PING before then.
+    pub fn start(&mut self, now: Instant) {
+        self.last_received = Some(now);
+    }
+
+    pub fn set_interval(&mut self, interval: Duration, timeout: Duration) {
+        self.interval = interval;
+        self.timeout = timeout;
+    }
+
+    /// Called whenever anything at all is received from the server.
+    pub fn received(&mut self, now: Instant) {
+        if self.last_received.is_some() {
+            self.last_received = Some(now);
+        }
+    }
+
+    /// Send a PING if the connection has been idle for too long, or give up
+    /// on it if an earlier one went unanswered.
+    pub fn poll(&mut self, now: Instant) -> Vec<Command> {
+        let last_received = match self.last_received {
+            Some(last_received) if !self.timed_out => last_received,
+            _ => return Vec::new(),
+        };
+
+        if let Some((_, sent)) = &self.pending {
+            if now.duration_since(last_received.max(*sent)) >= self.timeout {
+                self.timed_out = true;
+            }
+            return Vec::new();
+        }
+
+        if now.duration_since(last_received) < self.interval {
+            return Vec::new();
+        }
+
+        self.next_token += 1;
+        let token = format!("crikey{}", self.next_token);
+        let command = Command::Ping {
+            from: token.parse::<Sender>().ok(),
+            to: None,
+        };
+        self.pending = Some((token, now));
+        vec![command]
+    }
+
+    /// Record the lag if this is the reply to our PING. Servers usually echo
+    /// the token as the second parameter, but some send it alone.
+    pub fn handle_pong(&mut self, command: &Command, now: Instant) -> bool {
+        let token = match command {
+            Command::Pong { to: Some(to), .. } => String::from(to.clone()),
+            Command::Pong { from, to: None } => String::from(from.clone()),
+            _ => return false,
+        };
+
+        match self.pending.take() {
+            Some((pending, sent)) if pending == token => {
+                if self.samples.len() == LAG_SAMPLES {
+                    self.samples.pop_front();
+                }
+                self.samples.push_back(now.duration_since(sent));
+                true
+            }
+            pending => {
+                self.pending = pending;
+                false
+            }
+        }
+    }
+
+    /// The round-trip time of our most recent PING.
+    pub fn lag(&self) -> Option<Duration> {
+        self.samples.back().copied()
+    }
+
+    /// The average round-trip time of our recent PINGs.
+    pub fn average_lag(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            None
+        } else {
+            Some(self.samples.iter().sum::<Duration>() / self.samples.len() as u32)
+        }
+    }
+
+    pub fn is_timed_out(&self) -> bool {
+        self.timed_out
+    }
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Self::new(DEFAULT_PING_INTERVAL, DEFAULT_PING_TIMEOUT)
+    }
+}
+
+#[cfg(test)]
+mod test_keepalive {
+    use super::super::test_helpers::to_strings;
+    use super::*;
+
+    fn pong(raw: &str) -> Command {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn idle_before_registration() {
+        let mut keepalive = Keepalive::default();
+        let now = Instant::now();
+        keepalive.received(now);
+        assert!(keepalive.poll(now + Duration::from_secs(600)).is_empty());
+        assert!(!keepalive.is_timed_out());
+    }
+
+    #[test]
+    fn pings_when_idle() {
+        let mut keepalive = Keepalive::default();
+        let now = Instant::now();
+        keepalive.start(now);
+
+        assert!(keepalive.poll(now + Duration::from_secs(30)).is_empty());
+        keepalive.received(now + Duration::from_secs(30));
+        assert!(keepalive.poll(now + Duration::from_secs(60)).is_empty());
+        assert_eq!(
+            vec!["PING :crikey1"],
+            to_strings(keepalive.poll(now + Duration::from_secs(90)))
+        );
+
+        // Only one PING is outstanding at a time.
+        assert!(keepalive.poll(now + Duration::from_secs(150)).is_empty());
+
+        assert!(!keepalive.handle_pong(
+            &pong("PONG irc.example.com :crikey0"),
+            now + Duration::from_secs(91)
+        ));
+        assert_eq!(None, keepalive.lag());
+        assert!(keepalive.handle_pong(
+            &pong("PONG irc.example.com :crikey1"),
+            now + Duration::from_secs(92)
+        ));
+        assert_eq!(Some(Duration::from_secs(2)), keepalive.lag());
+
+        keepalive.received(now + Duration::from_secs(92));
+        assert_eq!(
+            vec!["PING :crikey2"],
+            to_strings(keepalive.poll(now + Duration::from_secs(152)))
+        );
+        assert!(keepalive.handle_pong(&pong("PONG crikey2"), now + Duration::from_secs(156)));
+        assert_eq!(Some(Duration::from_secs(4)), keepalive.lag());
+        assert_eq!(Some(Duration::from_secs(3)), keepalive.average_lag());
+    }
+
+    #[test]
+    fn times_out() {
+        let mut keepalive = Keepalive::new(Duration::from_secs(60), Duration::from_secs(30));
+        let now = Instant::now();
+        keepalive.start(now);
+        assert_eq!(1, keepalive.poll(now + Duration::from_secs(60)).len());
+
+        // Anything received from the server shows it's still there.
+        keepalive.received(now + Duration::from_secs(80));
+        assert!(keepalive.poll(now + Duration::from_secs(100)).is_empty());
+        assert!(!keepalive.is_timed_out());
+
+        assert!(keepalive.poll(now + Duration::from_secs(110)).is_empty());
+        assert!(keepalive.is_timed_out());
+        assert!(keepalive.poll(now + Duration::from_secs(600)).is_empty());
+    }
+
+    #[test]
+    fn average_lag() {
+        let mut keepalive = Keepalive::new(Duration::from_secs(0), Duration::from_secs(30));
+        let now = Instant::now();
+        keepalive.start(now);
+
+        for i in 1..=20 {
+            keepalive.poll(now);
+            keepalive.handle_pong(
+                &pong(&format!("PONG irc.example.com :crikey{}", i)),
+                now + Duration::from_secs(i),
+            );
+        }
+        assert_eq!(Some(Duration::from_secs(20)), keepalive.lag());
+        assert_eq!(Some(Duration::from_millis(15500)), keepalive.average_lag());
+    }
+}
@@ -0,0 +1,1945 @@
+//! A library for writing IRC clients, bots and services. The `Client`
+//! registers with the server, negotiates capabilities, answers PINGs and
+//! tracks what the server supports, surfacing everything else as `Event`s. A
+//! `ClientManager` runs clients for several networks from one loop.
+//! The protocol types it uses live in `crikey-irc-common`, re-exported here as
+//! `common`.
+//!
+//! The bookkeeping behind the client, such as tracking presence, sending
+//! keepalive PINGs, joining channels, identifying to services, tracking channel
+//! members and setting away, is split into subsystems that don't do any I/O.
+//! The `Client` feeds each one the messages and time it needs, and each returns
+//! the commands to send or the events that result, so they can be tested
+//! without a server.
+use self::away::Away;
+pub use self::away::{AutoAway, AwaySummary};
+pub use self::batch::Batch;
+use self::batch::{Batches, Outcome};
+use self::capabilities::Capabilities;
+pub use self::capabilities::Sasl;
+use self::channels::Channels;
+pub use self::channels::{InviteMethod, DEFAULT_RETRY_DELAY};
+pub use self::chat_log::{ChatLogConfig, ChatLogger, LogFormat};
+pub use self::event::Event;
+use self::history::{History, BATCH_CHATHISTORY, BATCH_CHATHISTORY_TARGETS};
+use self::ignore::Ignores;
+pub use self::ignore::{FloodLimit, IgnoreAction, IgnoreRule};
+pub use self::isupport::ISupport;
+use self::keepalive::Keepalive;
+pub use self::keepalive::{DEFAULT_PING_INTERVAL, DEFAULT_PING_TIMEOUT};
+pub use self::manager::{ClientManager, NetworkEvent};
+use self::members::Members;
+use self::presence::Presence;
+pub use self::responses::{LabeledResponse, ResponseHandle, LABEL_TIMEOUT};
+use self::responses::{Responses, BATCH_LABELED_RESPONSE};
+use self::services::Services;
+pub use self::services::{Reclaim, ServicesConfig};
+pub use crikey_irc_common as common;
+pub use crikey_irc_common::{
+    Casemapping, Charset, Clock, Decoding, ExtbanMatcher, Extbans, MaskSubject, Recorder,
+    Recording, Replay, SystemClock, UserMask, VirtualClock, Wildcard,
+};
+use crikey_irc_common::{
+    Channel, ChannelKey, Command, Connection, Message, MessageBody, MessageParams, Nickname,
+    Recipient, ReplyType, Sender, Username, TARGET_PARSE, TARGET_STATE,
+};
+use log::{debug, info, warn};
+pub use regex::Regex;
+use std::collections::VecDeque;
+use std::io;
+use std::net;
+use std::time::Duration;
+
+mod away;
+mod batch;
+mod capabilities;
+mod channels;
+mod chat_log;
+mod event;
+mod history;
+mod ignore;
+mod isupport;
+mod keepalive;
+mod manager;
+mod members;
+mod presence;
+mod responses;
+#[cfg(test)]
+mod scripted_server;
+mod services;
+
+/// How many variations on our nickname to try when it and all of the
+/// alternates are refused during registration.
+const MAX_FALLBACK_NICKNAMES: u32 = 10;
+
+/// The longest nickname RFC 2812 lets us assume the server accepts, used
+/// until the server advertises NICKLEN.
+const DEFAULT_NICKLEN: usize = 9;
+
+pub struct Client {
+    connection: Connection,
+    auth_token: AuthToken,
+    nickname: Nickname,
+    alternates: VecDeque<Nickname>,
+    fallback: Option<(String, u32)>,
+    registered: bool,
+    isupport: ISupport,
+    capabilities: Capabilities,
+    batches: Batches,
+    history: History,
+    presence: Presence,
+    channels: Channels,
+    members: Members,
+    away: Away,
+    services: Services,
+    ignores: Ignores,
+    keepalive: Keepalive,
+    responses: Responses,
+    surface_echoes: bool,
+    events: VecDeque<Event>,
+    clock: Box<dyn Clock>,
+}
+
+impl Client {
+    /// Connect to a server and start registering with it. Fails if the
+    /// server can't be reached.
+    pub fn connect<T: net::ToSocketAddrs>(addr: T, auth_token: AuthToken) -> io::Result<Client> {
+        let stream = net::TcpStream::connect(addr)?;
+        let connection = Connection::connect(stream)?;
+        Ok(Client::start(connection, auth_token))
+    }
+
+    /// Like `connect()`, but saving everything sent and received with a
+    /// `Recorder`, so the session can be replayed later.
+    pub fn connect_recorded<T: net::ToSocketAddrs>(
+        addr: T,
+        auth_token: AuthToken,
+        recorder: &Recorder,
+    ) -> io::Result<Client> {
+        let stream = net::TcpStream::connect(addr)?;
+        let connection = Connection::connect_recorded(stream, recorder)?;
+        Ok(Client::start(connection, auth_token))
+    }
+
+    /// Like `connect()`, but over TLS. The server's certificate must be valid
+    /// for `domain`, usually the host name in `addr`.
+    #[cfg(feature = "tls")]
+    pub fn connect_tls<T: net::ToSocketAddrs>(
+        addr: T,
+        domain: &str,
+        auth_token: AuthToken,
+    ) -> io::Result<Client> {
+        let stream = net::TcpStream::connect(addr)?;
+        let connection = Connection::connect_tls(stream, domain)?;
+        Ok(Client::start(connection, auth_token))
+    }
+
+    /// Like `connect_tls()`, but saving everything sent and received with a
+    /// `Recorder`.
+    #[cfg(feature = "tls")]
+    pub fn connect_tls_recorded<T: net::ToSocketAddrs>(
+        addr: T,
+        domain: &str,
+        auth_token: AuthToken,
+        recorder: &Recorder,
+    ) -> io::Result<Client> {
+        let stream = net::TcpStream::connect(addr)?;
+        let connection = Connection::connect_tls_recorded(stream, domain, recorder)?;
+        Ok(Client::start(connection, auth_token))
+    }
+
+    /// A client that plays back a recorded session, keeping time with the
+    /// replay's clock. Step through the replay, polling the client after
+    /// each step, and the client ends up in the state it was in when the
+    /// session was recorded:
+    ///
+    /// ```no_run
+    /// # use crikey_irc_client::{AuthToken, Client, Recording, Replay};
+    /// # fn auth_token() -> AuthToken { unimplemented!() }
+    /// let replay = Replay::new(Recording::load("session.rec")?);
+    /// let mut client = Client::replay(&replay, auth_token());
+    /// while replay.step() {
+    ///     while client.poll() {}
+    /// }
+    /// assert!(client.is_joined(&"#channel".parse().unwrap()));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn replay(replay: &Replay, auth_token: AuthToken) -> Client {
+        let mut client = Client::new(replay.connection(), auth_token);
+        client.clock = Box::new(replay.clock());
+        client.authenticate();
+        client
+    }
+
+    fn start(connection: Connection, auth_token: AuthToken) -> Client {
+        let mut client = Client::new(connection, auth_token);
+        client.authenticate();
+        client
+    }
+
+    fn new(connection: Connection, auth_token: AuthToken) -> Client {
+        let mut capabilities = Capabilities::new(auth_token.capabilities.clone());
+        capabilities.set_sasl(auth_token.sasl.clone());
+        Client {
+            connection,
+            capabilities,
+            nickname: auth_token.nickname.clone(),
+            alternates: VecDeque::new(),
+            fallback: None,
+            auth_token,
+            registered: false,
+            isupport: ISupport::new(),
+            batches: Batches::new(),
+            history: History::new(),
+            presence: Presence::default(),
+            channels: Channels::default(),
+            members: Members::default(),
+            away: Away::default(),
+            services: Services::default(),
+            ignores: Ignores::default(),
+            keepalive: Keepalive::default(),
+            responses: Responses::new(),
+            surface_echoes: false,
+            events: VecDeque::new(),
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    fn authenticate(&mut self) {
+        let commands = self.capabilities.start();
+        self.send_commands(commands)
+            .expect("Could not authenticate with server.");
+
+        if let Some(command) = self.auth_token.pass() {
+            self.connection
+                .send_command(command)
+                .expect("Could not authenticate with server.");
+        }
+
+        self.connection
+            .send_command(self.auth_token.nick())
+            .expect("Could not authenticate with server.");
+        self.connection
+            .send_command(self.auth_token.user())
+            .expect("Could not authenticate with server.");
+    }
+
+    /// Handle the next message from the server, if there is one, and do
+    /// anything that's due. Returns whether a message was received. Once the
+    /// connection is closed, this emits `Event::Disconnected` and does
+    /// nothing more.
+    pub fn poll(&mut self) -> bool {
+        if !self.is_connected() {
+            return false;
+        }
+
+        let now = self.clock.now();
+        let received = match self.connection.poll() {
+            Some(message) => {
+                self.keepalive.received(now);
+                self.handle_message(message);
+                true
+            }
+            None => false,
+        };
+        if !self.is_connected() {
+            self.handle_events(vec![Event::Disconnected]);
+            return received;
+        }
+
+        self.responses.expire(now);
+        self.ignores.poll(now);
+
+        let commands = self.presence.poll(now);
+        self.send_commands(commands).ok();
+
+        let commands = self.channels.poll(now);
+        self.send_commands(commands).ok();
+
+        let commands = self.away.poll(now);
+        self.send_commands(commands).ok();
+
+        let timed_out = self.keepalive.is_timed_out();
+        let commands = self.keepalive.poll(now);
+        self.send_commands(commands).ok();
+        if !timed_out && self.keepalive.is_timed_out() {
+            self.handle_events(vec![Event::PingTimeout]);
+        }
+
+        received
+    }
+
+    /// Take the oldest event that hasn't yet been handled, if any.
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    /// The nickname we're currently using. If the one in the `AuthToken` was
+    /// taken or refused when we registered, this is one of the alternates, or
+    /// failing that, a variation on the last nickname we tried.
+    pub fn nickname(&self) -> &Nickname {
+        &self.nickname
+    }
+
+    /// Nicknames to try in turn if ours is taken when we register.
+    pub fn set_alternate_nicknames(&mut self, alternates: Vec<Nickname>) {
+        self.alternates = alternates.into();
+    }
+
+    /// The features advertised by the server during registration.
+    pub fn isupport(&self) -> &ISupport {
+        &self.isupport
+    }
+
+    /// Whether the server agreed to enable an IRCv3 capability requested in
+    /// the `AuthToken`.
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.is_enabled(capability)
+    }
+
+    /// Start tracking whether a user is online. `Event::Online` and
+    /// `Event::Offline` will be emitted as their status changes.
+    pub fn monitor(&mut self, nickname: Nickname) -> io::Result<()> {
+        let commands = self.presence.add(nickname);
+        self.send_commands(commands)
+    }
+
+    pub fn unmonitor(&mut self, nickname: &Nickname) -> io::Result<()> {
+        let commands = self.presence.remove(nickname);
+        self.send_commands(commands)
+    }
+
+    /// Whether a monitored user is online, or `None` if we don't know yet.
+    pub fn is_online(&self, nickname: &Nickname) -> Option<bool> {
+        self.presence.is_online(nickname)
+    }
+
+    /// Join a channel, once registration is complete if it isn't already. If
+    /// the server refuses for a reason that may change, such as the channel
+    /// being full or invite-only, we'll keep trying with an increasing delay
+    /// starting at `DEFAULT_RETRY_DELAY`, emitting `Event::JoinFailed` each
+    /// time. If the channel doesn't exist, we give up with
+    /// `Event::JoinAbandoned`. Calling this again for the same channel
+    /// replaces its key.
+    pub fn join(&mut self, channel: Channel, key: Option<ChannelKey>) -> io::Result<()> {
+        let commands = self.channels.add(channel, key, false, self.clock.now());
+        self.send_commands(commands)
+    }
+
+    /// Join a channel that only lets in users identified to NickServ, once
+    /// we've identified. See `set_services()`.
+    pub fn join_protected(&mut self, channel: Channel, key: Option<ChannelKey>) -> io::Result<()> {
+        let commands = self.channels.add(channel, key, true, self.clock.now());
+        self.send_commands(commands)
+    }
+
+    /// Leave a channel, or stop trying to join it.
+    pub fn part(&mut self, channel: &Channel, message: Option<String>) -> io::Result<()> {
+        let commands = self.channels.remove(channel, message);
+        self.send_commands(commands)
+    }
+
+    /// Whether we're in a channel that was added with `join()`.
+    pub fn is_joined(&self, channel: &Channel) -> bool {
+        self.channels.is_joined(channel)
+    }
+
+    /// Whether someone is in a channel we're in, as far as we know from the
+    /// NAMES reply on joining and the activity since.
+    pub fn is_member(&self, channel: &Channel, nickname: &Nickname) -> bool {
+        self.members.contains(channel, nickname)
+    }
+
+    /// Whether to rejoin a channel after being kicked from it. If not, the
+    /// channel is forgotten, as if `part()` had been called.
+    pub fn set_rejoin_on_kick(&mut self, rejoin: bool) {
+        self.channels.set_rejoin_on_kick(rejoin);
+    }
+
+    /// How to ask for an invitation when a channel we want to join is
+    /// invite-only.
+    pub fn set_invite_method(&mut self, method: InviteMethod) {
+        self.channels.set_invite_method(method);
+    }
+
+    /// How to talk to NickServ and ChanServ. If a password is set, we identify
+    /// to NickServ once registered, unless we've already logged in with SASL,
+    /// and reclaim our nickname if we had to register under an alternate.
+    /// This should be set before connecting.
+    pub fn set_services(&mut self, config: ServicesConfig) {
+        self.connection.set_nickserv(config.nickserv.as_ref());
+        self.channels.set_chanserv(config.chanserv.clone());
+        self.services.set_config(config);
+    }
+
+    /// Whether we've identified to NickServ.
+    pub fn is_identified(&self) -> bool {
+        self.services.is_identified()
+    }
+
+    /// Ask ChanServ to op us in a channel.
+    pub fn request_op(&mut self, channel: &Channel) -> io::Result<()> {
+        let command = self.services.op(channel, &self.nickname);
+        self.send_command(command)
+    }
+
+    /// Ask ChanServ to voice us in a channel.
+    pub fn request_voice(&mut self, channel: &Channel) -> io::Result<()> {
+        let command = self.services.voice(channel, &self.nickname);
+        self.send_command(command)
+    }
+
+    /// Request the messages in a conversation that were sent since the last
+    /// one we saw, or the most recent messages if we haven't seen any. They
+    /// will be emitted as `Event::Message` with `history` set, skipping any
+    /// that have already been emitted.
+    ///
+    /// This requires the `draft/chathistory` and `batch` capabilities, and the
+    /// `message-tags` and `server-time` capabilities are strongly recommended.
+    pub fn backfill(&mut self, target: Recipient) -> io::Result<()> {
+        let max_limit = self.isupport.chathistory().flatten();
+        let command = self.history.backfill(target, max_limit);
+        self.send_command(command)
+    }
+
+    /// Ignore PRIVMSGs and NOTICEs matching a rule. They are dropped, or
+    /// emitted as `Event::Ignored`, depending on the rule's action.
+    pub fn ignore(&mut self, rule: IgnoreRule) {
+        self.ignores.add(rule);
+    }
+
+    /// Remove every ignore rule, including those added automatically by the
+    /// flood limit.
+    pub fn clear_ignores(&mut self) {
+        self.ignores.clear();
+    }
+
+    /// Temporarily ignore any host that sends messages faster than this.
+    /// There's no limit by default.
+    pub fn set_flood_limit(&mut self, flood_limit: Option<FloodLimit>) {
+        self.ignores.set_flood_limit(flood_limit);
+    }
+
+    /// Whether our own messages, echoed back by the server when
+    /// `echo-message` is enabled, should be emitted as `Event::Message`. They
+    /// are suppressed by default, since we already know what we sent.
+    pub fn surface_echoes(&mut self, surface: bool) {
+        self.surface_echoes = surface;
+    }
+
+    /// How long the connection may be idle before we PING the server, and
+    /// how long to wait for it to respond before giving up with
+    /// `Event::PingTimeout`. The defaults are `DEFAULT_PING_INTERVAL` and
+    /// `DEFAULT_PING_TIMEOUT`.
+    pub fn set_keepalive(&mut self, interval: Duration, timeout: Duration) {
+        self.keepalive.set_interval(interval, timeout);
+    }
+
+    /// The round-trip time of our most recent keepalive PING, or `None` if
+    /// the server hasn't answered one yet.
+    pub fn lag(&self) -> Option<Duration> {
+        self.keepalive.lag()
+    }
+
+    /// The average round-trip time of our recent keepalive PINGs.
+    pub fn average_lag(&self) -> Option<Duration> {
+        self.keepalive.average_lag()
+    }
+
+    /// Whether the connection to the server is still open. See
+    /// `Event::Disconnected`.
+    pub fn is_connected(&self) -> bool {
+        self.connection.error().is_none()
+    }
+
+    /// Whether the server has stopped responding, in which case the caller
+    /// should reconnect.
+    pub fn is_timed_out(&self) -> bool {
+        self.keepalive.is_timed_out()
+    }
+
+    /// Mark ourselves as away with a message. Messages sent to us directly
+    /// or mentioning our nickname are kept until we're back, and given with
+    /// `Event::Back`.
+    pub fn set_away(&mut self, message: String) -> io::Result<()> {
+        self.change_away(Some(message))
+    }
+
+    /// Mark ourselves as no longer away.
+    pub fn set_back(&mut self) -> io::Result<()> {
+        self.change_away(None)
+    }
+
+    /// Whether the server has confirmed that we're away.
+    pub fn is_away(&self) -> bool {
+        self.away.is_away()
+    }
+
+    /// Go away automatically after a period without activity, and come back
+    /// on the next. Sending commands counts as activity, as does calling
+    /// `mark_active()`, which applications should do on any user input.
+    pub fn set_auto_away(&mut self, auto_away: Option<AutoAway>) {
+        self.away.set_auto_away(auto_away);
+    }
+
+    /// Note that the user has done something, for `AutoAway`.
+    pub fn mark_active(&mut self) -> io::Result<()> {
+        let commands = self.away.active(self.clock.now());
+        self.send_commands(commands)
+    }
+
+    fn change_away(&mut self, message: Option<String>) -> io::Result<()> {
+        let commands = self.away.set_away(message, self.clock.now());
+        self.send_commands(commands)
+    }
+
+    /// How to decode lines from the server that aren't valid UTF-8. This is
+    /// ignored once the server advertises UTF8ONLY.
+    pub fn set_decoding(&mut self, decoding: Decoding) {
+        self.connection.set_decoding(decoding);
+    }
+
+    /// Send a command with a `label` tag, so that the server's response to it
+    /// can be told apart from everything else. Returns `None` if the server
+    /// hasn't enabled `labeled-response`, in which case the command is sent
+    /// without a label, or if there was nothing to send, as with an AWAY that
+    /// doesn't change anything.
+    ///
+    /// Like `send_command()`, this counts as activity, and an AWAY goes
+    /// through `set_away()` or `set_back()`. The response is also handled as
+    /// usual, so (for instance) a labeled PRIVMSG echo still updates the
+    /// CHATHISTORY position.
+    pub fn send_labeled(&mut self, command: Command) -> io::Result<Option<ResponseHandle>> {
+        let command = match command {
+            Command::Away { message } => {
+                match self.away.set_away(message, self.clock.now()).pop() {
+                    Some(command) => command,
+                    None => return Ok(None),
+                }
+            }
+            Command::Notice { .. } => command,
+            _ => {
+                self.mark_active()?;
+                command
+            }
+        };
+        if !self.capabilities.is_enabled("labeled-response") {
+            self.connection.send_command(command)?;
+            return Ok(None);
+        }
+
+        let (tags, handle) = self.responses.label(self.clock.now());
+        self.connection.send_message(Message {
+            tags,
+            sender: None,
+            body: MessageBody::Command(command),
+        })?;
+        Ok(Some(handle))
+    }
+
+    fn handle_message(&mut self, message: Message) {
+        let message = match self.apply_statusmsg(message) {
+            Some(message) => message,
+            None => return,
+        };
+        match self.batches.handle(message) {
+            Outcome::Unbatched(message) => {
+                if let Some(label) = message.tags.get("label") {
+                    let response = match message.body {
+                        MessageBody::Command(Command::Ack) => LabeledResponse::Ack,
+                        _ => LabeledResponse::Message(message.clone()),
+                    };
+                    self.responses.complete(label, response);
+                }
+                self.dispatch(message);
+            }
+            Outcome::Complete(batch) => {
+                if batch.batch_type == BATCH_LABELED_RESPONSE {
+                    if let Some(label) = batch.tags.get("label") {
+                        self.responses
+                            .complete(label, LabeledResponse::Batch(batch.clone()));
+                    }
+                }
+                self.handle_batch(batch);
+            }
+            Outcome::Buffered => (),
+        }
+    }
+
+    /// Reinterpret the recipients of a PRIVMSG, NOTICE or TAGMSG with the
+    /// STATUSMSG prefixes the server advertised, since the parser can only
+    /// guess at them. Returns `None` if none of the recipients are valid.
+    fn apply_statusmsg(&self, mut message: Message) -> Option<Message> {
+        if let MessageBody::Command(Command::Privmsg { recipients, .. })
+        | MessageBody::Command(Command::Notice { recipients, .. })
+        | MessageBody::Command(Command::TagMsg { recipients }) = &mut message.body
+        {
+            let statusmsg = self.isupport.statusmsg();
+            *recipients = std::mem::take(recipients)
+                .into_iter()
+                .filter_map(|recipient| {
+                    let raw = String::from(recipient);
+                    let recipient = Recipient::parse_with_statusmsg(&raw, statusmsg).ok();
+                    if recipient.is_none() {
+                        warn!(
+                            target: TARGET_PARSE,
+                            "Ignoring {}, which has a STATUSMSG prefix the server didn't advertise",
+                            raw
+                        );
+                    }
+                    recipient
+                })
+                .collect();
+            if recipients.is_empty() {
+                return None;
+            }
+        }
+        Some(message)
+    }
+
+    fn dispatch(&mut self, message: Message) {
+        match message.body {
+            MessageBody::Command(Command::Privmsg { .. })
+            | MessageBody::Command(Command::Notice { .. }) => {
+                if let MessageBody::Command(command @ Command::Notice { .. }) = &message.body {
+                    let (commands, events) = self.services.handle_command(
+                        message.sender.as_ref(),
+                        command,
+                        self.isupport.casemapping(),
+                    );
+                    self.send_commands(commands).ok();
+                    self.handle_events(events);
+                }
+
+                let is_echo = self.is_echo(&message);
+                let event = self.history.handle_live(message, &self.nickname);
+                if !is_echo || self.surface_echoes {
+                    self.emit_messages(event);
+                }
+            }
+            MessageBody::Command(_) => {
+                let events = self.members.handle_message(&message, &self.nickname);
+                self.handle_events(events);
+                if let MessageBody::Command(command) = message.body {
+                    self.handle_command(message.sender, command);
+                }
+            }
+            MessageBody::Reply(reply_type, reply_body) => {
+                self.members.handle_reply(&reply_type, &reply_body);
+                self.handle_reply(reply_type, reply_body)
+            }
+        }
+    }
+
+    fn is_echo(&self, message: &Message) -> bool {
+        self.capabilities.is_enabled("echo-message") && self.is_from_us(message.sender.as_ref())
+    }
+
+    fn handle_batch(&mut self, batch: Batch) {
+        match &batch.batch_type[..] {
+            BATCH_CHATHISTORY | BATCH_CHATHISTORY_TARGETS => {
+                let events = self.history.handle_batch(batch, &self.nickname);
+                self.emit_messages(events);
+            }
+            _ => {
+                for message in batch.messages {
+                    self.dispatch(message);
+                }
+                for batch in batch.batches {
+                    self.handle_batch(batch);
+                }
+            }
+        }
+    }
+
+    fn handle_command(&mut self, sender: Option<Sender>, command: Command) {
+        match command {
+            Command::Ping { .. } => self.handle_command_ping(command),
+            Command::Pong { .. } => {
+                self.keepalive.handle_pong(&command, self.clock.now());
+            }
+            Command::Cap { .. } | Command::Authenticate { .. } => {
+                let commands = self.capabilities.handle_command(&command);
+                self.send_commands(commands).ok();
+            }
+            Command::Nick { nickname } => {
+                if self.is_from_us(sender.as_ref()) {
+                    info!(target: TARGET_STATE, "Changed nickname to {}", nickname.as_ref());
+                    self.nickname = nickname.clone();
+                }
+                self.handle_services(sender, Command::Nick { nickname });
+            }
+            Command::Quit { .. } => self.handle_services(sender, command),
+            Command::Join { .. }
+            | Command::Part { .. }
+            | Command::Kick { .. }
+            | Command::Invite { .. } => {
+                let (commands, events) = self.channels.handle_command(
+                    sender.as_ref(),
+                    &command,
+                    &self.nickname,
+                    self.clock.now(),
+                );
+                self.send_commands(commands).ok();
+                self.handle_events(events);
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_services(&mut self, sender: Option<Sender>, command: Command) {
+        let (commands, events) =
+            self.services
+                .handle_command(sender.as_ref(), &command, self.isupport.casemapping());
+        self.send_commands(commands).ok();
+        self.handle_events(events);
+    }
+
+    fn is_from_us(&self, sender: Option<&Sender>) -> bool {
+        match sender {
+            Some(Sender::User { nickname, .. }) => self
+                .isupport
+                .casemapping()
+                .eq(nickname.as_ref(), self.nickname.as_ref()),
+            _ => false,
+        }
+    }
+
+    /// Queue messages that have made it past the ignore list.
+    fn emit_messages<I: IntoIterator<Item = Event>>(&mut self, events: I) {
+        let now = self.clock.now();
+        let casemapping = self.isupport.casemapping();
+        let extbans = self.isupport.extbans();
+        for event in events {
+            if let Some(event) = self.ignores.filter(event, now, casemapping, &extbans) {
+                if let Event::Message {
+                    message,
+                    history: false,
+                } = &event
+                {
+                    self.away
+                        .handle_message(message, &self.nickname, casemapping);
+                }
+                self.events.push_back(event);
+            }
+        }
+    }
+
+    /// Queue events, and react to any that concern other subsystems.
+    fn handle_events(&mut self, events: Vec<Event>) {
+        for event in events {
+            log_event(&event);
+            if event == Event::Identified {
+                let commands = self.channels.set_identified(self.clock.now());
+                self.send_commands(commands).ok();
+            }
+            self.events.push_back(event);
+        }
+    }
+
+    fn handle_command_ping(&mut self, command: Command) {
+        if let Command::Ping { from, .. } = command {
+            self.connection
+                .send_command(Command::Pong {
+                    to: from,
+                    from: self.nickname.clone().into(),
+                })
+                .ok();
+        }
+    }
+
+    fn handle_reply(&mut self, reply_type: ReplyType, reply_body: MessageParams) {
+        match reply_type {
+            ReplyType::PrvWelcome => {
+                if let Some(nickname) = reply_body.get(0).and_then(|raw| raw.parse().ok()) {
+                    self.nickname = nickname;
+                }
+            }
+            ReplyType::ErrNicknameInUse | ReplyType::ErrErroneusNickname if !self.registered => {
+                match self.next_nickname() {
+                    Some(nickname) => {
+                        self.nickname = nickname.clone();
+                        self.connection
+                            .send_command(Command::Nick { nickname })
+                            .ok();
+                    }
+                    None => warn!(target: TARGET_STATE, "Ran out of nicknames to try"),
+                }
+            }
+            ReplyType::PrvBounce => {
+                self.isupport.update(&reply_body);
+                self.connection.set_utf8_only(self.isupport.utf8_only());
+            }
+            ReplyType::RplEndOfMotd | ReplyType::ErrNoMotd if !self.registered => {
+                self.handle_registered()
+            }
+            _ => (),
+        }
+
+        let commands = self.capabilities.handle_reply(&reply_type);
+        self.send_commands(commands).ok();
+
+        let events = self.presence.handle_reply(&reply_type, &reply_body);
+        self.handle_events(events);
+
+        let events = self.services.handle_reply(&reply_type);
+        self.handle_events(events);
+
+        let events = self.away.handle_reply(&reply_type, self.clock.now());
+        self.handle_events(events);
+
+        let (commands, events) =
+            self.channels
+                .handle_reply(&reply_type, &reply_body, self.clock.now());
+        self.send_commands(commands).ok();
+        self.handle_events(events);
+    }
+
+    /// The next nickname to try when ours is refused during registration: each
+    /// of the alternates, then the last of them (or our own) with "_"
+    /// appended for as long as it fits within NICKLEN, then with a number in
+    /// place of its end. After `MAX_FALLBACK_NICKNAMES` of those, we give up.
+    fn next_nickname(&mut self) -> Option<Nickname> {
+        if let Some(alternate) = self.alternates.pop_front() {
+            return Some(alternate);
+        }
+
+        let current = String::from(self.nickname.clone());
+        let (base, attempts) = self.fallback.get_or_insert((current, 0));
+        *attempts += 1;
+        if *attempts > MAX_FALLBACK_NICKNAMES {
+            return None;
+        }
+
+        // NICKLEN usually isn't known until after registration. Lengths are
+        // counted in characters, since nicknames needn't be ASCII.
+        let base_len = base.chars().count();
+        let nicklen = self
+            .isupport
+            .value("NICKLEN")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_else(|| base_len.max(DEFAULT_NICKLEN));
+        let underscores = nicklen.saturating_sub(base_len);
+        let attempts = *attempts as usize;
+        let nickname = if attempts <= underscores {
+            format!("{}{}", base, "_".repeat(attempts))
+        } else {
+            let suffix = (attempts - underscores).to_string();
+            let length = nicklen.saturating_sub(suffix.len());
+            let truncated: String = base.chars().take(length).collect();
+            format!("{}{}", truncated, suffix)
+        };
+        nickname.parse().ok()
+    }
+
+    /// The end of the MOTD is the last thing sent during registration, so by
+    /// now we know everything the server is going to tell us about itself.
+    fn handle_registered(&mut self) {
+        info!(
+            target: TARGET_STATE,
+            "Registered as {}",
+            self.nickname.as_ref()
+        );
+        self.registered = true;
+        self.keepalive.start(self.clock.now());
+        self.members.start(&self.isupport);
+
+        let commands = self.presence.start(&self.isupport);
+        self.send_commands(commands).ok();
+
+        let commands = self.services.start(
+            &self.nickname,
+            &self.auth_token.nickname,
+            self.isupport.casemapping(),
+        );
+        self.send_commands(commands).ok();
+
+        let commands = self.channels.start(&self.isupport, self.clock.now());
+        self.send_commands(commands).ok();
+
+        let commands = self.away.start(self.clock.now());
+        self.send_commands(commands).ok();
+    }
+
+    fn send_commands(&mut self, commands: Vec<Command>) -> io::Result<()> {
+        for command in commands {
+            self.connection.send_command(command)?;
+        }
+        Ok(())
+    }
+
+    /// Send a command. This counts as activity for `AutoAway`, unless it's a
+    /// NOTICE, which is meant for automatic replies. An AWAY goes through
+    /// `set_away()` or `set_back()`.
+    pub fn send_command(&mut self, command: Command) -> io::Result<()> {
+        match command {
+            Command::Away { message } => return self.change_away(message),
+            Command::Notice { .. } => (),
+            _ => self.mark_active()?,
+        }
+        self.connection.send_command(command)
+    }
+
+    /// Send a line as it is. Like `send_command()`, this counts as activity,
+    /// and an AWAY goes through `set_away()` or `set_back()`.
+    pub fn send_command_raw(&mut self, raw_command: String) -> io::Result<()> {
+        if let Ok(Message {
+            body: MessageBody::Command(Command::Away { message }),
+            ..
+        }) = raw_command.parse()
+        {
+            return self.change_away(message);
+        }
+        self.mark_active()?;
+        self.connection.send_command_raw(raw_command)
+    }
+}
+
+/// Log the events that change our state. Messages are already logged as they
+/// arrive, by the `Connection`.
+fn log_event(event: &Event) {
+    match event {
+        Event::Online(nickname) => debug!(target: TARGET_STATE, "{} is online", nickname.as_ref()),
+        Event::Offline(nickname) => {
+            debug!(target: TARGET_STATE, "{} is offline", nickname.as_ref())
+        }
+        Event::Identified => info!(target: TARGET_STATE, "Identified to services"),
+        Event::Joined(channel) => info!(
+            target: TARGET_STATE,
+            "Joined {}",
+            String::from(channel.clone())
+        ),
+        Event::JoinFailed { channel, reason } => warn!(
+            target: TARGET_STATE,
+            "Could not join {}: {:?}",
+            String::from(channel.clone()),
+            reason
+        ),
+        Event::JoinAbandoned { channel, reason } => warn!(
+            target: TARGET_STATE,
+            "Gave up joining {}: {:?}",
+            String::from(channel.clone()),
+            reason
+        ),
+        Event::Kicked { channel, .. } => info!(
+            target: TARGET_STATE,
+            "Kicked from {}",
+            String::from(channel.clone())
+        ),
+        Event::Away => info!(target: TARGET_STATE, "Marked as away"),
+        Event::Back(summary) => info!(
+            target: TARGET_STATE,
+            "No longer marked as away, after missing {} private messages and {} highlights",
+            summary.private_messages.len(),
+            summary.highlights.len()
+        ),
+        Event::PingTimeout => warn!(target: TARGET_STATE, "The server stopped responding"),
+        Event::Disconnected => warn!(target: TARGET_STATE, "Disconnected from the server"),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test_helpers {
+    use super::Command;
+
+    /// The wire form of each command, for comparing against expected lines.
+    pub fn to_strings(commands: Vec<Command>) -> Vec<String> {
+        commands.into_iter().map(String::from).collect()
+    }
+}
+
+#[cfg(test)]
+mod test_client {
+    use super::scripted_server::ScriptedServer;
+    use super::*;
+    use pipe::pipe;
+    use std::io::prelude::*;
+    use std::thread::spawn;
+
+    fn get_token(password: Option<String>) -> AuthToken {
+        AuthToken {
+            nickname: "spudly".parse().unwrap(),
+            username: "pjohnson".parse().unwrap(),
+            mode: 0,
+            realname: "Potato Johnson".to_string(),
+            password,
+            capabilities: Vec::new(),
+            sasl: None,
+        }
+    }
+
+    fn spawn_client(
+        auth_token: AuthToken,
+        client_callback: fn(Client),
+    ) -> (pipe::PipeReader, pipe::PipeWriter) {
+        let (input_pipe_read, input_pipe_write) = pipe();
+        let (output_pipe_read, output_pipe_write) = pipe();
+
+        spawn(move || {
+            let connection =
+                Connection::new(Box::new(input_pipe_read), Box::new(output_pipe_write));
+            let client = Client::new(connection, auth_token);
+            client_callback(client);
+        });
+
+        (output_pipe_read, input_pipe_write)
+    }
+
+    #[test]
+    fn authenticate_without_password() {
+        let (mut reader, _) = spawn_client(get_token(None), |mut client| client.authenticate());
+
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        assert_eq!("NICK spudly\r\n".to_string(), buffer);
+
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        assert_eq!("USER pjohnson 0 * :Potato Johnson\r\n".to_string(), buffer);
+
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        assert_eq!("".to_string(), buffer);
+    }
+
+    #[test]
+    fn authenticate_with_password() {
+        let (mut reader, _) =
+            spawn_client(get_token(Some("secretpass".to_string())), |mut client| {
+                client.authenticate()
+            });
+
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        assert_eq!("PASS secretpass\r\n".to_string(), buffer);
+
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        assert_eq!("NICK spudly\r\n".to_string(), buffer);
+
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        assert_eq!("USER pjohnson 0 * :Potato Johnson\r\n".to_string(), buffer);
+
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        assert_eq!("".to_string(), buffer);
+    }
+
+    #[test]
+    fn responds_to_ping() {
+        let (mut reader, mut writer) = spawn_client(get_token(None), |mut client| {
+            client.poll();
+        });
+        write!(writer, "PING irc.example.com spudly\r\n").unwrap();
+
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        assert_eq!("PONG spudly irc.example.com\r\n", buffer);
+    }
+
+    #[test]
+    fn monitors_after_registration() {
+        let (mut reader, mut writer) = spawn_client(get_token(None), |mut client| {
+            client.monitor("Wiz".parse().unwrap()).unwrap();
+            client.monitor("Angel".parse().unwrap()).unwrap();
+            client.monitor("syrk".parse().unwrap()).unwrap();
+            client.poll();
+            client.poll();
+        });
+        write!(
+            writer,
+            ":irc.example.com 005 spudly MONITOR=2 :are supported by this server\r\n"
+        )
+        .unwrap();
+        write!(
+            writer,
+            ":irc.example.com 422 spudly :MOTD File is missing\r\n"
+        )
+        .unwrap();
+
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        assert_eq!("MONITOR + Wiz,Angel\r\n", buffer);
+
+        let mut buffer = String::new();
+        reader.read_line(&mut buffer).unwrap();
+        assert_eq!("ISON syrk\r\n", buffer);
+    }
+
+    fn scripted_client(server: &ScriptedServer, capabilities: &[&str]) -> Client {
+        let mut auth_token = get_token(None);
+        auth_token.capabilities = capabilities.iter().map(|s| s.to_string()).collect();
+        let mut client = Client::new(server.connection(), auth_token);
+        client.authenticate();
+        while client.poll() {}
+        client
+    }
+
+    fn without_activity(events: Vec<Event>) -> Vec<Event> {
+        events
+            .into_iter()
+            .filter(|event| !matches!(event, Event::Activity { .. }))
+            .collect()
+    }
+
+    fn events(client: &mut Client) -> Vec<Event> {
+        while client.poll() {}
+        let mut events = Vec::new();
+        while let Some(event) = client.next_event() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn negotiates_capabilities() {
+        let server = ScriptedServer::new();
+        server.on(
+            "CAP LS 302",
+            &["CAP * LS :multi-prefix batch draft/chathistory"],
+        );
+        server.on(
+            "CAP REQ :batch draft/chathistory",
+            &["CAP spudly ACK :batch draft/chathistory"],
+        );
+
+        let client = scripted_client(&server, &["batch", "draft/chathistory", "server-time"]);
+        assert_eq!(
+            vec![
+                "CAP LS 302",
+                "NICK spudly",
+                "USER pjohnson 0 * :Potato Johnson",
+                "CAP REQ :batch draft/chathistory",
+                "CAP END",
+            ],
+            server.received()
+        );
+        assert!(server.is_finished());
+        assert!(client.has_capability("batch"));
+        assert!(!client.has_capability("server-time"));
+    }
+
+    #[test]
+    fn backfills_chathistory() {
+        let server = ScriptedServer::new();
+        server.on("CAP LS 302", &["CAP * LS :batch draft/chathistory"]);
+        server.on(
+            "CAP REQ :batch draft/chathistory",
+            &[
+                "CAP spudly ACK :batch draft/chathistory",
+                ":irc.example.com 005 spudly CHATHISTORY=50 :are supported by this server",
+                ":irc.example.com 422 spudly :MOTD File is missing",
+                "@msgid=2 :Wiz!wiz@example.com PRIVMSG #channel :two",
+            ],
+        );
+        let mut client = scripted_client(&server, &["batch", "draft/chathistory"]);
+        server.received();
+
+        assert_eq!(
+            vec![Event::Message {
+                message: "@msgid=2 :Wiz!wiz@example.com PRIVMSG #channel :two"
+                    .parse()
+                    .unwrap(),
+                history: false,
+            }],
+            events(&mut client)
+        );
+
+        // We reconnect after missing a message, and the server also returns
+        // one we've already seen.
+        server.on(
+            "CHATHISTORY LATEST #channel msgid=2 50",
+            &[
+                ":irc.example.com BATCH +sxtUfAeXBgNoD chathistory #channel",
+                "@batch=sxtUfAeXBgNoD;msgid=2 :Wiz!wiz@example.com PRIVMSG #channel :two",
+                "@batch=sxtUfAeXBgNoD;msgid=3 :Wiz!wiz@example.com PRIVMSG #channel :three",
+                ":irc.example.com BATCH -sxtUfAeXBgNoD",
+            ],
+        );
+        client.backfill("#channel".parse().unwrap()).unwrap();
+
+        assert_eq!(
+            vec![Event::Message {
+                message:
+                    "@batch=sxtUfAeXBgNoD;msgid=3 :Wiz!wiz@example.com PRIVMSG #channel :three"
+                        .parse()
+                        .unwrap(),
+                history: true,
+            }],
+            events(&mut client)
+        );
+        assert_eq!(
+            vec!["CHATHISTORY LATEST #channel msgid=2 50"],
+            server.received()
+        );
+        assert!(server.is_finished());
+    }
+
+    fn labeled_client(server: &ScriptedServer) -> Client {
+        server.on(
+            "CAP LS 302",
+            &["CAP * LS :batch echo-message labeled-response"],
+        );
+        server.on(
+            "CAP REQ :batch echo-message labeled-response",
+            &["CAP spudly ACK :batch echo-message labeled-response"],
+        );
+        let client = scripted_client(server, &["batch", "echo-message", "labeled-response"]);
+        server.received();
+        client
+    }
+
+    #[test]
+    fn labeled_message() {
+        let server = ScriptedServer::new();
+        let mut client = labeled_client(&server);
+
+        server.on(
+            "@label=1 PRIVMSG #channel :hello there",
+            &["@label=1;msgid=abc :spudly!pj@example.com PRIVMSG #channel :hello there"],
+        );
+        let handle = client
+            .send_labeled("PRIVMSG #channel :hello there".parse().unwrap())
+            .unwrap()
+            .unwrap();
+        assert!(!handle.is_complete());
+
+        // Our own echo is suppressed by default.
+        assert!(events(&mut client).is_empty());
+        assert!(server.is_finished());
+        assert_eq!(Some("abc"), handle.take().unwrap().msgid());
+    }
+
+    #[test]
+    fn labeled_ack_and_batch() {
+        let server = ScriptedServer::new();
+        let mut client = labeled_client(&server);
+        server.send(":irc.example.com 376 spudly :End of /MOTD command.");
+        while client.poll() {}
+
+        server.on(
+            "@label=1 AWAY :Gone fishing",
+            &["@label=1 :irc.example.com ACK"],
+        );
+        server.on(
+            "@label=2 WHOIS Wiz",
+            &[
+                "@label=2 :irc.example.com BATCH +NMzYSq45x labeled-response",
+                "@batch=NMzYSq45x :irc.example.com 311 spudly Wiz wiz example.com * :Wiz",
+                "@batch=NMzYSq45x :irc.example.com 318 spudly Wiz :End of /WHOIS list",
+                ":irc.example.com BATCH -NMzYSq45x",
+            ],
+        );
+        let ack = client
+            .send_labeled("AWAY :Gone fishing".parse().unwrap())
+            .unwrap()
+            .unwrap();
+        let whois = client
+            .send_labeled("WHOIS Wiz".parse().unwrap())
+            .unwrap()
+            .unwrap();
+        while client.poll() {}
+
+        assert_eq!(Some(LabeledResponse::Ack), ack.take());
+        match whois.take() {
+            Some(LabeledResponse::Batch(batch)) => {
+                assert_eq!(2, batch.messages.len());
+            }
+            response => panic!("Unexpected response {:?}", response),
+        }
+    }
+
+    #[test]
+    fn decodes_legacy_charsets_until_utf8only() {
+        let server = ScriptedServer::new();
+        let mut client = scripted_client(&server, &[]);
+        let message = |text: &str| Event::Message {
+            message: format!(":Wiz!wiz@example.com PRIVMSG #channel :{}", text)
+                .parse()
+                .unwrap(),
+            history: false,
+        };
+
+        server.send_bytes(b":Wiz!wiz@example.com PRIVMSG #channel :Sk\xe5l");
+        assert_eq!(vec![message("Skål")], events(&mut client));
+
+        client.set_decoding(Decoding::Strict);
+        server.send_bytes(b":Wiz!wiz@example.com PRIVMSG #channel :Sk\xe5l");
+        assert_eq!(Vec::<Event>::new(), events(&mut client));
+
+        client.set_decoding(Decoding::default());
+        server.send(":irc.example.com 005 spudly UTF8ONLY :are supported by this server");
+        server.send_bytes(b":Wiz!wiz@example.com PRIVMSG #channel :Sk\xe5l");
+        assert_eq!(vec![message("Sk\u{fffd}l")], events(&mut client));
+    }
+
+    #[test]
+    fn joins_channels() {
+        let server = ScriptedServer::new();
+        let mut client = scripted_client(&server, &[]);
+        client
+            .join("#foo".parse().unwrap(), Some("fubar".parse().unwrap()))
+            .unwrap();
+        client.join("#bar".parse().unwrap(), None).unwrap();
+        client.set_rejoin_on_kick(true);
+        server.received();
+
+        server.on(
+            "JOIN #foo,#bar fubar",
+            &[
+                ":spudly!pj@example.com JOIN #foo",
+                ":irc.example.com 474 spudly #bar :Cannot join channel (+b)",
+            ],
+        );
+        server.send(":irc.example.com 422 spudly :MOTD File is missing");
+        assert_eq!(
+            vec![
+                Event::Joined("#foo".parse().unwrap()),
+                Event::JoinFailed {
+                    channel: "#bar".parse().unwrap(),
+                    reason: ReplyType::ErrBannedFromChan,
+                },
+            ],
+            without_activity(events(&mut client))
+        );
+        assert!(client.is_joined(&"#foo".parse().unwrap()));
+
+        server.on("JOIN #foo fubar", &[":spudly!pj@example.com JOIN #foo"]);
+        server.send(":Wiz!wiz@example.com KICK #foo spudly");
+        assert_eq!(
+            vec![
+                Event::Kicked {
+                    channel: "#foo".parse().unwrap(),
+                    by: Some("Wiz!wiz@example.com".parse().unwrap()),
+                    comment: None,
+                },
+                Event::Joined("#foo".parse().unwrap()),
+            ],
+            without_activity(events(&mut client))
+        );
+        assert!(server.is_finished());
+    }
+
+    #[test]
+    fn reports_activity() {
+        let server = ScriptedServer::new();
+        let mut client = scripted_client(&server, &[]);
+        server.send(":irc.example.com 422 spudly :MOTD File is missing");
+        server.send(":spudly!pj@example.com JOIN #foo");
+        server.send(":irc.example.com 353 spudly = #foo :spudly @Wiz");
+        server.send(":spudly!pj@example.com JOIN #bar");
+        server.send(":irc.example.com 353 spudly = #bar :spudly Wiz");
+        events(&mut client);
+
+        server.send(":Wiz!wiz@example.com QUIT :Bye");
+        assert_eq!(
+            vec![Event::Activity {
+                message: ":Wiz!wiz@example.com QUIT :Bye".parse().unwrap(),
+                channels: vec!["#foo".parse().unwrap(), "#bar".parse().unwrap()],
+            }],
+            events(&mut client)
+        );
+        assert!(client.is_member(&"#foo".parse().unwrap(), &"spudly".parse().unwrap()));
+        assert!(!client.is_member(&"#foo".parse().unwrap(), &"Wiz".parse().unwrap()));
+    }
+
+    #[test]
+    fn alternate_nicknames() {
+        let server = ScriptedServer::new();
+        server.on(
+            "USER pjohnson 0 * :Potato Johnson",
+            &[":irc.example.com 433 * spudly :Nickname is already in use"],
+        );
+        server.on(
+            "NICK potato",
+            &[":irc.example.com 433 * potato :Nickname is already in use"],
+        );
+        server.on(
+            "NICK potato_",
+            &[":irc.example.com 001 potato_ :Welcome to the Internet Relay Network"],
+        );
+
+        let mut client = Client::new(server.connection(), get_token(None));
+        client.set_alternate_nicknames(vec!["potato".parse().unwrap()]);
+        client.authenticate();
+        events(&mut client);
+
+        assert!(server.is_finished());
+        assert_eq!("potato_", client.nickname().as_ref());
+    }
+
+    #[test]
+    fn unreachable_server() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        assert!(Client::connect(addr, get_token(None)).is_err());
+    }
+
+    #[test]
+    fn fallback_nicknames() {
+        let server = ScriptedServer::new();
+        server.on(
+            "USER pjohnson 0 * :Potato Johnson",
+            &[":irc.example.com 433 * spudly :Nickname is already in use"],
+        );
+        server.on(
+            "NICK spudly_",
+            &[":irc.example.com 432 * spudly_ :Erroneous nickname"],
+        );
+        server.on(
+            "NICK spudly__",
+            &[":irc.example.com 433 * spudly__ :Nickname is already in use"],
+        );
+        // A server that truncates to NICKLEN would refuse this forever.
+        server.on(
+            "NICK spudly___",
+            &[":irc.example.com 433 * spudly__ :Nickname is already in use"],
+        );
+        server.on(
+            "NICK spudly1",
+            &[":irc.example.com 433 * spudly1 :Nickname is already in use"],
+        );
+        server.on(
+            "NICK spudly2",
+            &[":irc.example.com 001 spudly2 :Welcome to the Internet Relay Network"],
+        );
+
+        let mut client = Client::new(server.connection(), get_token(None));
+        client.authenticate();
+        events(&mut client);
+
+        assert!(server.is_finished());
+        assert_eq!("spudly2", client.nickname().as_ref());
+    }
+
+    #[test]
+    fn fallback_multibyte_nickname() {
+        let server = ScriptedServer::new();
+        let mut auth_token = get_token(None);
+        auth_token.nickname = "abcdefghö".parse().unwrap();
+        let mut client = Client::new(server.connection(), auth_token);
+        client.authenticate();
+        server.received();
+
+        server.send(":irc.example.com 433 * abcdefghö :Nickname is already in use");
+        server.send(":irc.example.com 433 * abcdefgh1 :Nickname is already in use");
+        events(&mut client);
+        assert_eq!(vec!["NICK abcdefgh1", "NICK abcdefgh2"], server.received());
+    }
+
+    #[test]
+    fn runs_out_of_nicknames() {
+        let server = ScriptedServer::new();
+        let mut client = Client::new(server.connection(), get_token(None));
+        client.authenticate();
+        server.received();
+
+        for _ in 0..20 {
+            server.send(":irc.example.com 433 * spudly :Nickname is already in use");
+        }
+        events(&mut client);
+        let received = server.received();
+        assert_eq!(MAX_FALLBACK_NICKNAMES as usize, received.len());
+        assert_eq!(Some(&"NICK spudly7".to_string()), received.last());
+    }
+
+    #[test]
+    fn logs_in_with_sasl() {
+        let server = ScriptedServer::new();
+        server.on("CAP LS 302", &[":irc.example.com CAP * LS :sasl=PLAIN"]);
+        server.on("CAP REQ sasl", &[":irc.example.com CAP * ACK :sasl"]);
+        server.on("AUTHENTICATE PLAIN", &["AUTHENTICATE +"]);
+        server.on(
+            "AUTHENTICATE c3B1ZGx5AHNwdWRseQBodW50ZXIy",
+            &[
+                ":irc.example.com 900 spudly spudly!pj@example.com spudly :You are now logged in as spudly",
+                ":irc.example.com 903 spudly :SASL authentication successful",
+            ],
+        );
+
+        let mut auth_token = get_token(None);
+        auth_token.sasl = Some(Sasl {
+            account: "spudly".to_string(),
+            password: "hunter2".to_string(),
+        });
+        let mut client = Client::new(server.connection(), auth_token);
+        client.set_services(ServicesConfig {
+            password: Some("hunter2".to_string()),
+            ..ServicesConfig::default()
+        });
+        client.authenticate();
+
+        assert_eq!(vec![Event::Identified], events(&mut client));
+        assert!(server.is_finished());
+        assert!(client.has_capability("sasl"));
+        assert_eq!(
+            vec![
+                "CAP LS 302",
+                "NICK spudly",
+                "USER pjohnson 0 * :Potato Johnson",
+                "CAP REQ sasl",
+                "AUTHENTICATE PLAIN",
+                "AUTHENTICATE c3B1ZGx5AHNwdWRseQBodW50ZXIy",
+                "CAP END",
+            ],
+            server.received()
+        );
+    }
+
+    #[test]
+    fn identifies_and_reclaims_nickname() {
+        let server = ScriptedServer::new();
+        server.on(
+            "USER pjohnson 0 * :Potato Johnson",
+            &[":irc.example.com 433 * spudly :Nickname is already in use"],
+        );
+        server.on(
+            "NICK spudly_",
+            &[
+                ":irc.example.com 001 spudly_ :Welcome to the Internet Relay Network",
+                ":irc.example.com 422 spudly_ :MOTD File is missing",
+            ],
+        );
+        server.on(
+            "PRIVMSG NickServ :GHOST spudly hunter2",
+            &[
+                ":spudly!pj@example.com QUIT :Killed",
+                ":NickServ!NickServ@services.example.com NOTICE spudly_ :You are now identified for spudly.",
+            ],
+        );
+        server.on("NICK spudly", &[":spudly_!pj@example.com NICK spudly"]);
+
+        let mut client = Client::new(server.connection(), get_token(None));
+        client.set_services(ServicesConfig {
+            password: Some("hunter2".to_string()),
+            ..ServicesConfig::default()
+        });
+        client.join("#public".parse().unwrap(), None).unwrap();
+        client
+            .join_protected("#secret".parse().unwrap(), None)
+            .unwrap();
+        client.authenticate();
+
+        assert_eq!(Event::Identified, events(&mut client)[0]);
+        assert!(server.is_finished());
+        assert!(client.is_identified());
+        assert_eq!("spudly", client.nickname().as_ref());
+        assert_eq!(
+            vec![
+                "NICK spudly",
+                "USER pjohnson 0 * :Potato Johnson",
+                "NICK spudly_",
+                "PRIVMSG NickServ :IDENTIFY spudly hunter2",
+                "PRIVMSG NickServ :GHOST spudly hunter2",
+                "JOIN #public",
+                "NICK spudly",
+                "JOIN #secret",
+            ],
+            server.received()
+        );
+
+        client.request_op(&"#secret".parse().unwrap()).unwrap();
+        assert_eq!(
+            vec!["PRIVMSG ChanServ :OP #secret spudly"],
+            server.received()
+        );
+    }
+
+    #[test]
+    fn ignores_messages() {
+        let server = ScriptedServer::new();
+        let mut client = scripted_client(&server, &[]);
+        client.ignore(IgnoreRule {
+            mask: Some("*!*@spam.example.com".parse().unwrap()),
+            ..IgnoreRule::default()
+        });
+
+        server.send(":Wiz!wiz@spam.example.com PRIVMSG #channel :buy now");
+        server.send(":Angel!wings@irc.org PRIVMSG #channel :hello");
+        assert_eq!(
+            vec![Event::Message {
+                message: ":Angel!wings@irc.org PRIVMSG #channel :hello"
+                    .parse()
+                    .unwrap(),
+                history: false,
+            }],
+            events(&mut client)
+        );
+    }
+
+    #[test]
+    fn keepalive() {
+        let server = ScriptedServer::new();
+        let mut client = scripted_client(&server, &[]);
+        server.send(":irc.example.com 422 spudly :MOTD File is missing");
+        events(&mut client);
+        server.received();
+
+        client.set_keepalive(Duration::from_secs(0), Duration::from_secs(600));
+        server.on(
+            "PING :crikey1",
+            &[":irc.example.com PONG irc.example.com :crikey1"],
+        );
+        assert_eq!(None, client.lag());
+        client.poll();
+        assert!(events(&mut client).is_empty());
+        assert!(server.is_finished());
+        assert!(client.lag().is_some());
+        assert!(!client.is_timed_out());
+
+        client.set_keepalive(Duration::from_secs(0), Duration::from_secs(0));
+        assert_eq!(vec![Event::PingTimeout], events(&mut client));
+        assert!(client.is_timed_out());
+        assert_eq!(vec!["PING :crikey1", "PING :crikey2"], server.received());
+    }
+
+    #[test]
+    fn records_and_replays() {
+        let path = std::env::temp_dir().join(format!("crikey-irc-{}.rec", std::process::id()));
+        let setup = |client: &mut Client| {
+            client.set_keepalive(Duration::from_secs(60), Duration::from_secs(600));
+            client.join("#foo".parse().unwrap(), None).unwrap();
+        };
+
+        let server = ScriptedServer::new();
+        let clock = VirtualClock::new();
+        let recorder = Recorder::new(
+            Box::new(std::fs::File::create(&path).unwrap()),
+            Box::new(clock.clone()),
+        );
+        let mut client = Client::new(server.recorded_connection(&recorder), get_token(None));
+        client.clock = Box::new(clock.clone());
+        setup(&mut client);
+        client.authenticate();
+        server.on(
+            "JOIN #foo",
+            &[
+                ":spudly!pj@example.com JOIN #foo",
+                ":irc.example.com 353 spudly = #foo :spudly @Wiz",
+            ],
+        );
+        server.on(
+            "PING :crikey1",
+            &[":irc.example.com PONG irc.example.com :crikey1"],
+        );
+        server.send(":irc.example.com 001 spudly :Welcome to the network");
+        server.send(":irc.example.com 422 spudly :MOTD File is missing");
+        events(&mut client);
+        clock.advance(Duration::from_secs(61));
+        client.poll();
+        events(&mut client);
+        assert!(server.is_finished());
+        assert_eq!(Some(Duration::from_secs(0)), client.lag());
+        drop((client, recorder));
+
+        let recording = Recording::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            Duration::from_secs(61),
+            recording.entries.last().unwrap().elapsed
+        );
+
+        let replay = Replay::new(recording.clone());
+        let mut client = Client::replay(&replay, get_token(None));
+        setup(&mut client);
+        let mut replayed = Vec::new();
+        while replay.step() {
+            replayed.extend(events(&mut client));
+        }
+
+        assert_eq!(recording.sent(), replay.sent());
+        assert!(replayed.contains(&Event::Joined("#foo".parse().unwrap())));
+        assert!(client.is_joined(&"#foo".parse().unwrap()));
+        assert!(client.is_member(&"#foo".parse().unwrap(), &"Wiz".parse().unwrap()));
+        assert_eq!(Some(Duration::from_secs(0)), client.lag());
+    }
+
+    #[test]
+    fn away() {
+        let server = ScriptedServer::new();
+        let clock = VirtualClock::new();
+        let mut client = scripted_client(&server, &[]);
+        client.clock = Box::new(clock.clone());
+        client.set_keepalive(Duration::from_secs(3600), Duration::from_secs(600));
+        client.set_auto_away(Some(AutoAway {
+            after: Duration::from_secs(600),
+            message: "Idle".to_string(),
+        }));
+        server.send(":irc.example.com 422 spudly :MOTD File is missing");
+        events(&mut client);
+        server.received();
+
+        server.on(
+            "AWAY Idle",
+            &[":irc.example.com 306 spudly :You have been marked as being away"],
+        );
+        clock.advance(Duration::from_secs(600));
+        client.poll();
+        assert_eq!(vec![Event::Away], events(&mut client));
+        assert!(client.is_away());
+
+        server.send(":Wiz!wiz@example.com PRIVMSG spudly :Are you there?");
+        server.send(":Wiz!wiz@example.com PRIVMSG #foo :spudly: ping");
+        events(&mut client);
+
+        // Anything the user sends brings us back.
+        server.on(
+            "AWAY",
+            &[":irc.example.com 305 spudly :You are no longer marked as being away"],
+        );
+        clock.advance(Duration::from_secs(60));
+        client
+            .send_command_raw("PRIVMSG Wiz :Yes".to_string())
+            .unwrap();
+        assert_eq!(
+            vec![Event::Back(AwaySummary {
+                duration: Duration::from_secs(60),
+                private_messages: vec![":Wiz!wiz@example.com PRIVMSG spudly :Are you there?"
+                    .parse()
+                    .unwrap()],
+                highlights: vec![":Wiz!wiz@example.com PRIVMSG #foo :spudly: ping"
+                    .parse()
+                    .unwrap()],
+            })],
+            events(&mut client)
+        );
+        assert!(!client.is_away());
+
+        // An AWAY sent as a command sets the away message, which stays set
+        // despite activity.
+        client
+            .send_command("AWAY :Gone fishing".parse().unwrap())
+            .unwrap();
+        client.mark_active().unwrap();
+        assert_eq!(
+            vec![
+                "AWAY Idle",
+                "AWAY",
+                "PRIVMSG Wiz :Yes",
+                "AWAY :Gone fishing"
+            ],
+            server.received()
+        );
+        assert!(server.is_finished());
+    }
+
+    #[test]
+    fn surfaces_echoes() {
+        let server = ScriptedServer::new();
+        let mut client = labeled_client(&server);
+        client.surface_echoes(true);
+
+        server.send(":spudly!pj@example.com PRIVMSG #channel :hello");
+        assert_eq!(
+            vec![Event::Message {
+                message: ":spudly!pj@example.com PRIVMSG #channel :hello"
+                    .parse()
+                    .unwrap(),
+                history: false,
+            }],
+            events(&mut client)
+        );
+    }
+
+    #[test]
+    fn applies_statusmsg() {
+        let server = ScriptedServer::new();
+        let mut client = scripted_client(&server, &[]);
+        let recipients = |events: Vec<Event>| -> Vec<Vec<Recipient>> {
+            events
+                .into_iter()
+                .map(|event| match event {
+                    Event::Message { message, .. } => match message.body {
+                        MessageBody::Command(Command::Privmsg { recipients, .. }) => {
+                            recipients.into_iter().collect()
+                        }
+                        body => panic!("Unexpected message body {:?}", body),
+                    },
+                    event => panic!("Unexpected event {:?}", event),
+                })
+                .collect()
+        };
+
+        // Without STATUSMSG, "+" is a channel type and "@" means nothing.
+        server.send(":Wiz!wiz@example.com PRIVMSG +#channel :hi");
+        server.send(":Wiz!wiz@example.com PRIVMSG @#channel :hi");
+        server.send(":Wiz!wiz@example.com PRIVMSG @#channel,spudly :hi");
+        assert_eq!(
+            vec![
+                vec![Recipient::Channel("+#channel".parse().unwrap())],
+                vec![Recipient::Nickname("spudly".parse().unwrap())],
+            ],
+            recipients(events(&mut client))
+        );
+
+        server.send(":irc.example.com 005 spudly STATUSMSG=@+ :are supported by this server");
+        server.send(":Wiz!wiz@example.com PRIVMSG +#channel :hi");
+        server.send(":Wiz!wiz@example.com PRIVMSG @#channel :hi");
+        assert_eq!(
+            vec![
+                vec![Recipient::StatusChannel('+', "#channel".parse().unwrap())],
+                vec![Recipient::StatusChannel('@', "#channel".parse().unwrap())],
+            ],
+            recipients(events(&mut client))
+        );
+    }
+
+    #[test]
+    fn labeled_away() {
+        let server = ScriptedServer::new();
+        let mut client = labeled_client(&server);
+
+        // We're not away yet, and can't be until we've registered.
+        assert!(client
+            .send_labeled("AWAY".parse().unwrap())
+            .unwrap()
+            .is_none());
+        assert!(client
+            .send_labeled("AWAY :Gone fishing".parse().unwrap())
+            .unwrap()
+            .is_none());
+        assert!(server.received().is_empty());
+
+        server.send(":irc.example.com 376 spudly :End of /MOTD command.");
+        server.on(
+            "AWAY :Gone fishing",
+            &[":irc.example.com 306 spudly :You have been marked as being away"],
+        );
+        assert_eq!(vec![Event::Away], events(&mut client));
+        assert!(client.is_away());
+    }
+
+    #[test]
+    fn unlabeled_without_capability() {
+        let server = ScriptedServer::new();
+        let mut client = scripted_client(&server, &[]);
+        server.received();
+
+        assert!(client
+            .send_labeled("WHOIS Wiz".parse().unwrap())
+            .unwrap()
+            .is_none());
+        assert_eq!(vec!["WHOIS Wiz"], server.received());
+    }
+
+    #[test]
+    fn handles_other_batches_normally() {
+        let server = ScriptedServer::new();
+        let mut client = scripted_client(&server, &[]);
+        server.received();
+
+        server.send(":irc.example.com BATCH +abc example.com/unknown");
+        server.send("@batch=abc PING irc.example.com spudly");
+        server.send("@batch=abc :Wiz PRIVMSG spudly :hi");
+        assert!(events(&mut client).is_empty());
+        assert!(server.received().is_empty());
+
+        server.send(":irc.example.com BATCH -abc");
+        assert_eq!(
+            vec![Event::Message {
+                message: "@batch=abc :Wiz PRIVMSG spudly :hi".parse().unwrap(),
+                history: false,
+            }],
+            events(&mut client)
+        );
+        assert_eq!(vec!["PONG spudly irc.example.com"], server.received());
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct AuthToken {
+    pub nickname: Nickname,
+    pub username: Username,
+    pub mode: u8,
+    pub realname: String,
+    pub password: Option<String>,
+    /// IRCv3 capabilities to request during registration. If this is empty,
+    /// capability negotiation is skipped entirely.
+    pub capabilities: Vec<String>,
+    /// Credentials to log in with SASL during registration. The "sasl"
+    /// capability is requested automatically.
+    pub sasl: Option<Sasl>,
+}
+
+impl AuthToken {
+    fn pass(&self) -> Option<Command> {
+        self.password.as_ref().map(|password| Command::Pass {
+            password: password.clone(),
+        })
+    }
+
+    fn nick(&self) -> Command {
+        Command::Nick {
+            nickname: self.nickname.clone(),
+        }
+    }
+
+    fn user(&self) -> Command {
+        Command::User {
+            username: self.username.clone(),
+            mode: self.mode,
+            realname: self.realname.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_auth_token {
+    use super::*;
+
+    fn get_token(password: Option<String>) -> AuthToken {
+        AuthToken {
+            nickname: "spudly".parse().unwrap(),
+            username: "pjohnson".parse().unwrap(),
+            mode: 0,
+            realname: "Potato Johnson".to_string(),
+            password,
+            capabilities: Vec::new(),
+            sasl: None,
+        }
+    }
+
+    #[test]
+    fn pass_none() {
+        let auth_token = get_token(None);
+        assert_eq!(None, auth_token.pass());
+    }
+
+    #[test]
+    fn pass_some() {
+        let auth_token = get_token(Some("secretpass".to_string()));
+        assert_eq!(
+            Some(Command::Pass {
+                password: "secretpass".to_string()
+            }),
+            auth_token.pass()
+        );
+    }
+
+    #[test]
+    fn nick() {
+        let auth_token = get_token(None);
+        assert_eq!(
+            Command::Nick {
+                nickname: "spudly".parse().unwrap()
+            },
+            auth_token.nick()
+        );
+    }
+
+    #[test]
+    fn user() {
+        let auth_token = get_token(None);
+        assert_eq!(
+            Command::User {
+                username: "pjohnson".parse().unwrap(),
+                mode: 0,
+                realname: "Potato Johnson".parse().unwrap()
+            },
+            auth_token.user()
+        );
+    }
+}
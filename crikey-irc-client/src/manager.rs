@@ -0,0 +1,222 @@
+use super::{Client, Event};
+use crikey_irc_common::Command;
+use std::collections::VecDeque;
+use std::io;
+
+/// An `Event` from one of the networks of a `ClientManager`.
+#[derive(PartialEq, Debug)]
+pub struct NetworkEvent {
+    pub network: String,
+    pub event: Event,
+}
+
+/// Runs a `Client` for each of several networks from a single loop. Each is
+/// added under a name of our choosing, which is used to tag the events it
+/// emits and to send commands to it. A network whose connection drops emits
+/// `Event::Disconnected` and is left alone, while the others carry on, until
+/// it's replaced with a new client by `add()`:
+///
+/// ```no_run
+/// # use crikey_irc_client::{AuthToken, Client, ClientManager, Event};
+/// # use crikey_irc_client::common::{Command, MessageBody};
+/// # fn token() -> AuthToken { unimplemented!() }
+/// let mut manager = ClientManager::new();
+/// manager.add("libera", Client::connect("irc.libera.chat:6667", token())?);
+/// manager.add("oftc", Client::connect("irc.oftc.net:6667", token())?);
+///
+/// loop {
+///     manager.poll();
+///     while let Some(network_event) = manager.next_event() {
+///         // Relay messages from one network to the other.
+///         if let Event::Message { message, .. } = network_event.event {
+///             if let MessageBody::Command(Command::Privmsg { message, .. }) = message.body {
+///                 let other = if network_event.network == "libera" { "oftc" } else { "libera" };
+///                 manager.send_command(other, Command::privmsg("#relay", &message)?)?;
+///             }
+///         }
+///     }
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Default)]
+pub struct ClientManager {
+    clients: Vec<(String, Client)>,
+    events: VecDeque<NetworkEvent>,
+}
+
+impl ClientManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a client under a network name, returning the one it replaces.
+    pub fn add(&mut self, network: &str, client: Client) -> Option<Client> {
+        let replaced = self.remove(network);
+        self.clients.push((network.to_string(), client));
+        replaced
+    }
+
+    pub fn remove(&mut self, network: &str) -> Option<Client> {
+        let index = self.find(network)?;
+        Some(self.clients.remove(index).1)
+    }
+
+    pub fn get(&self, network: &str) -> Option<&Client> {
+        self.find(network).map(|index| &self.clients[index].1)
+    }
+
+    pub fn get_mut(&mut self, network: &str) -> Option<&mut Client> {
+        let index = self.find(network)?;
+        Some(&mut self.clients[index].1)
+    }
+
+    /// The names of the networks, in the order they were added.
+    pub fn networks(&self) -> impl Iterator<Item = &str> {
+        self.clients.iter().map(|(network, _)| &network[..])
+    }
+
+    /// Poll every client once, collecting their events. Returns whether any
+    /// of them received a message, as `Client::poll()` does.
+    pub fn poll(&mut self) -> bool {
+        let mut received = false;
+        for (network, client) in self.clients.iter_mut() {
+            received |= client.poll();
+            while let Some(event) = client.next_event() {
+                self.events.push_back(NetworkEvent {
+                    network: network.clone(),
+                    event,
+                });
+            }
+        }
+        received
+    }
+
+    /// Take the oldest event from any network that hasn't yet been handled.
+    pub fn next_event(&mut self) -> Option<NetworkEvent> {
+        self.events.pop_front()
+    }
+
+    /// Send a command to one of the networks.
+    pub fn send_command(&mut self, network: &str, command: Command) -> io::Result<()> {
+        match self.get_mut(network) {
+            Some(client) => client.send_command(command),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Unknown network {:?}", network),
+            )),
+        }
+    }
+
+    fn find(&self, network: &str) -> Option<usize> {
+        self.clients.iter().position(|(name, _)| name == network)
+    }
+}
+
+#[cfg(test)]
+mod test_client_manager {
+    use super::super::scripted_server::ScriptedServer;
+    use super::super::AuthToken;
+    use super::*;
+
+    fn client(server: &ScriptedServer) -> Client {
+        let auth_token = AuthToken {
+            nickname: "spudly".parse().unwrap(),
+            username: "pjohnson".parse().unwrap(),
+            mode: 0,
+            realname: "Potato Johnson".to_string(),
+            password: None,
+            capabilities: Vec::new(),
+            sasl: None,
+        };
+        let mut client = Client::new(server.connection(), auth_token);
+        client.authenticate();
+        server.received();
+        client
+    }
+
+    #[test]
+    fn networks() {
+        let (libera, oftc) = (ScriptedServer::new(), ScriptedServer::new());
+        let mut manager = ClientManager::new();
+        assert!(manager.add("libera", client(&libera)).is_none());
+        assert!(manager.add("oftc", client(&oftc)).is_none());
+        assert!(manager.add("oftc", client(&oftc)).is_some());
+        assert_eq!(
+            vec!["libera", "oftc"],
+            manager.networks().collect::<Vec<_>>()
+        );
+        assert!(manager.get("efnet").is_none());
+
+        assert!(manager.remove("libera").is_some());
+        assert_eq!(vec!["oftc"], manager.networks().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn tags_events_and_relays() {
+        let (libera, oftc) = (ScriptedServer::new(), ScriptedServer::new());
+        let mut manager = ClientManager::new();
+        manager.add("libera", client(&libera));
+        manager.add("oftc", client(&oftc));
+
+        oftc.send(":Wiz!wiz@example.com PRIVMSG #channel :from oftc");
+        libera.send(":Angel!wings@irc.org PRIVMSG #channel :from libera");
+        while manager.poll() {}
+
+        let mut events = Vec::new();
+        while let Some(network_event) = manager.next_event() {
+            if let Event::Message { message, .. } = &network_event.event {
+                if network_event.network == "libera" {
+                    let relayed = match &message.body {
+                        crikey_irc_common::MessageBody::Command(Command::Privmsg {
+                            message,
+                            ..
+                        }) => Command::privmsg("#channel", message).unwrap(),
+                        _ => panic!("Expected a PRIVMSG"),
+                    };
+                    manager.send_command("oftc", relayed).unwrap();
+                }
+            }
+            events.push(network_event.network);
+        }
+
+        assert_eq!(vec!["libera", "oftc"], events);
+        assert_eq!(vec!["PRIVMSG #channel :from libera"], oftc.received());
+        assert!(libera.received().is_empty());
+        assert!(manager
+            .send_command("efnet", "AWAY".parse().unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn survives_disconnects() {
+        let (libera, oftc) = (ScriptedServer::new(), ScriptedServer::new());
+        let mut manager = ClientManager::new();
+        manager.add("libera", client(&libera));
+        manager.add("oftc", client(&oftc));
+
+        libera.send(":Angel!wings@irc.org PRIVMSG #channel :bye");
+        libera.disconnect();
+        while manager.poll() {}
+        oftc.send(":Wiz!wiz@example.com PRIVMSG #channel :still here");
+        while manager.poll() {}
+
+        let mut events = Vec::new();
+        while let Some(network_event) = manager.next_event() {
+            events.push((network_event.network, network_event.event));
+        }
+        assert_eq!(3, events.len());
+        assert_eq!(
+            ("libera".to_string(), Event::Disconnected),
+            events.remove(1)
+        );
+        assert_eq!(
+            vec!["libera", "oftc"],
+            events
+                .iter()
+                .map(|(network, _)| &network[..])
+                .collect::<Vec<_>>()
+        );
+        assert!(!manager.get("libera").unwrap().is_connected());
+        assert!(manager.get("oftc").unwrap().is_connected());
+    }
+}
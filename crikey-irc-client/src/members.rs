@@ -0,0 +1,303 @@
+use super::{Event, ISupport};
+use crikey_irc_common::{
+    Casemapping, Channel, Command, Message, MessageBody, MessageParams, Nickname, ReplyType, Sender,
+};
+use std::collections::HashSet;
+
+/// Keeps track of who is in each of the channels we're in, from the NAMES
+/// reply we get on joining and the JOINs, PARTs, KICKs, QUITs and NICKs that
+/// follow. A QUIT or NICK doesn't name any channels, so this is how we know
+/// which channels it affects.
+#[derive(Debug)]
+pub struct Members {
+    channels: Vec<(Channel, HashSet<String>)>,
+    casemapping: Casemapping,
+    prefixes: String,
+}
+
+impl Members {
+    /// Called once registration is complete, when we know how the server
+    /// compares nicknames and marks their status in a channel.
+    pub fn start(&mut self, isupport: &ISupport) {
+        self.casemapping = isupport.casemapping();
+        if let Some(prefix) = isupport.value("PREFIX") {
+            self.prefixes = prefix
+                .find(')')
+                .map_or(prefix, |index| &prefix[index + 1..])
+                .to_string();
+        }
+    }
+
+    /// Whether someone is in a channel we're in.
+    pub fn contains(&self, channel: &Channel, nickname: &Nickname) -> bool {
+        self.find(channel).is_some_and(|index| {
+            self.channels[index]
+                .1
+                .contains(&self.casemapping.fold(nickname.as_ref()))
+        })
+    }
+
+    /// Track a message, returning `Event::Activity` if it concerns any of
+    /// our channels.
+    pub fn handle_message(&mut self, message: &Message, nickname: &Nickname) -> Vec<Event> {
+        let from = match &message.sender {
+            Some(Sender::User { nickname, .. }) => self.casemapping.fold(nickname.as_ref()),
+            _ => String::new(),
+        };
+        let us = self.casemapping.fold(nickname.as_ref());
+
+        let channels = match &message.body {
+            MessageBody::Command(Command::Join { channels, .. }) if !from.is_empty() => channels
+                .iter()
+                .filter_map(|channel| {
+                    if from == us && self.find(channel).is_none() {
+                        self.channels.push((channel.clone(), HashSet::new()));
+                    }
+                    let index = self.find(channel)?;
+                    self.channels[index].1.insert(from.clone());
+                    Some(channel.clone())
+                })
+                .collect(),
+            MessageBody::Command(Command::Part { channels, .. }) => channels
+                .iter()
+                .filter_map(|channel| self.remove(channel, &from, &us))
+                .collect(),
+            MessageBody::Command(Command::Kick {
+                channels,
+                nicknames,
+                ..
+            }) => nicknames
+                .iter()
+                .enumerate()
+                .filter_map(|(index, kicked)| {
+                    let channel = channels.iter().nth(index).or(channels.iter().next())?;
+                    self.remove(channel, &self.casemapping.fold(kicked.as_ref()), &us)
+                })
+                .collect(),
+            MessageBody::Command(Command::Quit { .. }) => {
+                let channels = self.shared_with(&from);
+                for (_, members) in self.channels.iter_mut() {
+                    members.remove(&from);
+                }
+                channels
+            }
+            MessageBody::Command(Command::Nick { nickname: new }) => {
+                let channels = self.shared_with(&from);
+                let new = self.casemapping.fold(new.as_ref());
+                for (_, members) in self.channels.iter_mut() {
+                    if members.remove(&from) {
+                        members.insert(new.clone());
+                    }
+                }
+                channels
+            }
+            MessageBody::Command(Command::Topic {
+                channel,
+                topic: Some(_),
+            })
+            | MessageBody::Command(Command::ChannelMode { channel, .. }) => {
+                match self.find(channel) {
+                    Some(_) => vec![channel.clone()],
+                    None => Vec::new(),
+                }
+            }
+            _ => return Vec::new(),
+        };
+
+        if channels.is_empty() {
+            Vec::new()
+        } else {
+            vec![Event::Activity {
+                message: message.clone(),
+                channels,
+            }]
+        }
+    }
+
+    /// Add the nicknames in a NAMES reply to their channel.
+    pub fn handle_reply(&mut self, reply_type: &ReplyType, params: &MessageParams) {
+        if reply_type != &ReplyType::RplNamReply {
+            return;
+        }
+
+        let (channel, names) = match (params.get(2), params.get(3)) {
+            (Some(channel), Some(names)) => (channel, names),
+            _ => return,
+        };
+        let index = match channel.parse().ok().and_then(|channel| self.find(&channel)) {
+            Some(index) => index,
+            None => return,
+        };
+
+        for name in names.split(' ') {
+            // With userhost-in-names, each is a full nick!user@host.
+            let name = name.trim_start_matches(|c| self.prefixes.contains(c));
+            let name = name.split('!').next().unwrap_or_default();
+            if !name.is_empty() {
+                let name = self.casemapping.fold(name);
+                self.channels[index].1.insert(name);
+            }
+        }
+    }
+
+    /// Remove someone from a channel, or forget the channel entirely if it's
+    /// us, returning the channel if it's one of ours.
+    fn remove(&mut self, channel: &Channel, nickname: &str, us: &str) -> Option<Channel> {
+        let index = self.find(channel)?;
+        if nickname == us {
+            Some(self.channels.remove(index).0)
+        } else {
+            self.channels[index].1.remove(nickname);
+            Some(self.channels[index].0.clone())
+        }
+    }
+
+    fn shared_with(&self, nickname: &str) -> Vec<Channel> {
+        self.channels
+            .iter()
+            .filter(|(_, members)| members.contains(nickname))
+            .map(|(channel, _)| channel.clone())
+            .collect()
+    }
+
+    fn find(&self, channel: &Channel) -> Option<usize> {
+        let name = String::from(channel.clone());
+        self.channels
+            .iter()
+            .position(|(channel, _)| self.casemapping.eq(&String::from(channel.clone()), &name))
+    }
+}
+
+impl Default for Members {
+    fn default() -> Self {
+        Members {
+            channels: Vec::new(),
+            casemapping: Casemapping::default(),
+            prefixes: "@+".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_members {
+    use super::*;
+
+    fn handle(members: &mut Members, raw: &str) -> Vec<Channel> {
+        let message: Message = raw.parse().unwrap();
+        match members
+            .handle_message(&message, &"spudly".parse().unwrap())
+            .pop()
+        {
+            Some(Event::Activity {
+                message: activity,
+                channels,
+            }) => {
+                assert_eq!(message, activity);
+                channels
+            }
+            Some(event) => panic!("Unexpected event {:?}", event),
+            None => Vec::new(),
+        }
+    }
+
+    fn names(members: &mut Members, raw: &str) {
+        let message: Message = raw.parse().unwrap();
+        if let MessageBody::Reply(reply_type, params) = message.body {
+            members.handle_reply(&reply_type, &params);
+        }
+    }
+
+    fn channels(names: &[&str]) -> Vec<Channel> {
+        names.iter().map(|name| name.parse().unwrap()).collect()
+    }
+
+    fn is_member(members: &Members, channel: &str, nickname: &str) -> bool {
+        members.contains(&channel.parse().unwrap(), &nickname.parse().unwrap())
+    }
+
+    #[test]
+    fn tracks_members() {
+        let mut members = Members::default();
+        assert!(handle(&mut members, ":Wiz!wiz@example.com JOIN #foo").is_empty());
+
+        assert_eq!(
+            channels(&["#foo"]),
+            handle(&mut members, ":spudly!pj@example.com JOIN #foo")
+        );
+        assert_eq!(
+            channels(&["#bar"]),
+            handle(&mut members, ":spudly!pj@example.com JOIN #bar")
+        );
+        names(
+            &mut members,
+            ":irc.example.com 353 spudly = #foo :@Wiz +Angel spudly",
+        );
+        names(
+            &mut members,
+            ":irc.example.com 353 spudly = #bar :Wiz!wiz@example.com spudly!pj@example.com",
+        );
+        assert!(is_member(&members, "#foo", "wiz"));
+        assert!(is_member(&members, "#foo", "Angel"));
+        assert!(is_member(&members, "#bar", "Wiz"));
+        assert!(!is_member(&members, "#bar", "Angel"));
+
+        // NICK and QUIT affect every channel the user shares with us.
+        assert_eq!(
+            channels(&["#foo", "#bar"]),
+            handle(&mut members, ":Wiz!wiz@example.com NICK Wizard")
+        );
+        assert!(is_member(&members, "#bar", "Wizard"));
+        assert!(!is_member(&members, "#bar", "Wiz"));
+        assert_eq!(
+            channels(&["#foo"]),
+            handle(&mut members, ":Angel!wings@irc.org QUIT :Bye")
+        );
+        assert!(handle(&mut members, ":Angel!wings@irc.org QUIT :Bye").is_empty());
+
+        assert_eq!(
+            channels(&["#bar"]),
+            handle(&mut members, ":Wizard!wiz@example.com PART #bar")
+        );
+        assert_eq!(
+            channels(&["#foo"]),
+            handle(&mut members, ":Wiz!wiz@example.com KICK #foo Wizard")
+        );
+        assert_eq!(
+            channels(&["#foo"]),
+            handle(&mut members, ":Wiz!wiz@example.com TOPIC #foo :New topic")
+        );
+        assert_eq!(
+            channels(&["#foo"]),
+            handle(&mut members, ":Wiz!wiz@example.com MODE #foo +o spudly")
+        );
+        assert!(handle(&mut members, ":Wiz!wiz@example.com MODE #baz +o spudly").is_empty());
+
+        // Leaving a channel forgets it.
+        assert_eq!(
+            channels(&["#foo"]),
+            handle(&mut members, ":spudly!pj@example.com PART #foo")
+        );
+        assert!(!is_member(&members, "#foo", "spudly"));
+        assert!(is_member(&members, "#bar", "spudly"));
+    }
+
+    #[test]
+    fn prefixes() {
+        let mut isupport = ISupport::new();
+        isupport.update(
+            &"spudly PREFIX=(qov)~@+ CASEMAPPING=ascii :are supported"
+                .parse()
+                .unwrap(),
+        );
+        let mut members = Members::default();
+        members.start(&isupport);
+
+        handle(&mut members, ":spudly!pj@example.com JOIN #foo");
+        names(
+            &mut members,
+            ":irc.example.com 353 spudly = #foo :~Wiz @+Angel",
+        );
+        assert!(is_member(&members, "#foo", "Wiz"));
+        assert!(is_member(&members, "#foo", "Angel"));
+    }
+}
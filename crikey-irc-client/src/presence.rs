@@ -0,0 +1,543 @@
+use super::{Event, ISupport};
+use crikey_irc_common::{
+    Casemapping, Command, KeywordList, MessageParams, Nickname, ReplyType, WatchEntry,
+};
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// The number of bytes of target list we're willing to put on a single line.
+/// This leaves plenty of room for the command, prefix and CRLF within the
+/// 512-byte limit.
+const TARGETS_PER_LINE: usize = 400;
+
+pub const DEFAULT_ISON_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The mechanism used to learn about the presence of other users, in order of
+/// preference. The limit is the number of targets the server will track for
+/// us, where `None` is unlimited.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Mechanism {
+    Monitor(Option<usize>),
+    Watch(Option<usize>),
+    IsOn,
+}
+
+#[derive(Debug)]
+struct Target {
+    nickname: Nickname,
+    online: Option<bool>,
+    server_side: bool,
+}
+
+/// Tracks whether a set of nicknames is online, preferring the server-side
+/// MONITOR list and falling back to the legacy WATCH list. Targets that don't
+/// fit within the server's advertised limit, or all targets if neither is
+/// supported, are polled periodically in batches with ISON. Replies are fed
+/// back in via `handle_reply()`.
+#[derive(Debug)]
+pub struct Presence {
+    targets: Vec<Target>,
+    mechanism: Option<Mechanism>,
+    casemapping: Casemapping,
+    ison_interval: Duration,
+    last_ison: Option<Instant>,
+    pending_ison: VecDeque<Vec<Nickname>>,
+}
+
+impl Presence {
+    pub fn new(ison_interval: Duration) -> Self {
+        Presence {
+            targets: Vec::new(),
+            mechanism: None,
+            casemapping: Casemapping::default(),
+            ison_interval,
+            last_ison: None,
+            pending_ison: VecDeque::new(),
+        }
+    }
+
+    pub fn is_online(&self, nickname: &Nickname) -> Option<bool> {
+        self.find(nickname.as_ref())
+            .and_then(|index| self.targets[index].online)
+    }
+
+    /// Called once registration is complete and the ISUPPORT tokens are
+    /// known, at which point we can decide how to track our targets.
+    pub fn start(&mut self, isupport: &ISupport) -> Vec<Command> {
+        self.casemapping = isupport.casemapping();
+        self.mechanism = Some(if let Some(limit) = isupport.monitor() {
+            Mechanism::Monitor(limit)
+        } else if let Some(limit) = isupport.watch() {
+            Mechanism::Watch(limit)
+        } else {
+            Mechanism::IsOn
+        });
+        self.fill()
+    }
+
+    pub fn add(&mut self, nickname: Nickname) -> Vec<Command> {
+        if self.find(nickname.as_ref()).is_some() {
+            return Vec::new();
+        }
+
+        self.targets.push(Target {
+            nickname,
+            online: None,
+            server_side: false,
+        });
+        self.fill()
+    }
+
+    pub fn remove(&mut self, nickname: &Nickname) -> Vec<Command> {
+        let target = match self.find(nickname.as_ref()) {
+            Some(index) => self.targets.remove(index),
+            None => return Vec::new(),
+        };
+
+        let mut commands = Vec::new();
+        if target.server_side {
+            commands.push(match self.mechanism {
+                Some(Mechanism::Monitor(_)) => Command::MonitorRemove {
+                    targets: keyword_list(vec![target.nickname]),
+                },
+                _ => Command::Watch {
+                    entries: keyword_list(vec![WatchEntry::Remove(target.nickname)]),
+                },
+            });
+        }
+
+        // Some room may have opened up on the server-side list.
+        commands.append(&mut self.fill());
+        commands
+    }
+
+    /// Send ISON queries for any targets not tracked by the server, if the
+    /// polling interval has elapsed. Queries still unanswered by then are
+    /// assumed lost, and sent again.
+    pub fn poll(&mut self, now: Instant) -> Vec<Command> {
+        if self.mechanism.is_none() {
+            return Vec::new();
+        }
+
+        if let Some(last_ison) = self.last_ison {
+            if now.duration_since(last_ison) < self.ison_interval {
+                return Vec::new();
+            }
+        }
+        self.pending_ison.clear();
+
+        let batches = batch(
+            self.targets
+                .iter()
+                .filter(|target| !target.server_side)
+                .map(|target| target.nickname.clone()),
+        );
+
+        if !batches.is_empty() {
+            self.last_ison = Some(now);
+        }
+
+        batches
+            .into_iter()
+            .map(|batch| {
+                let command = Command::IsOn {
+                    nicknames: keyword_list(batch.iter().cloned()),
+                };
+                self.pending_ison.push_back(batch);
+                command
+            })
+            .collect()
+    }
+
+    pub fn handle_reply(&mut self, reply_type: &ReplyType, params: &MessageParams) -> Vec<Event> {
+        match reply_type {
+            ReplyType::RplMonOnline => last_param(params)
+                .split(',')
+                .filter_map(|target| target.split('!').next())
+                .filter_map(|nickname| self.set_online(nickname, true))
+                .collect(),
+            ReplyType::RplMonOffline => last_param(params)
+                .split(',')
+                .filter_map(|nickname| self.set_online(nickname, false))
+                .collect(),
+            ReplyType::RplLogOn | ReplyType::RplNowOn | ReplyType::RplNowIsAway => params
+                .get(1)
+                .and_then(|nickname| self.set_online(nickname, true))
+                .into_iter()
+                .collect(),
+            ReplyType::RplLogOff | ReplyType::RplNowOff => params
+                .get(1)
+                .and_then(|nickname| self.set_online(nickname, false))
+                .into_iter()
+                .collect(),
+            ReplyType::ErrMonListFull => {
+                if let Some(targets) = params.get(2) {
+                    for nickname in targets.split(',') {
+                        self.move_to_ison(nickname);
+                    }
+                }
+                Vec::new()
+            }
+            ReplyType::ErrTooManyWatch => {
+                if let Some(nickname) = params.get(1) {
+                    self.move_to_ison(nickname);
+                }
+                Vec::new()
+            }
+            ReplyType::RplIsOn => {
+                let batch = match self.pending_ison.pop_front() {
+                    Some(batch) => batch,
+                    None => return Vec::new(),
+                };
+                let online: Vec<&str> = last_param(params).split_whitespace().collect();
+
+                batch
+                    .iter()
+                    .filter_map(|nickname| {
+                        let is_online = online
+                            .iter()
+                            .any(|o| self.casemapping.eq(o, nickname.as_ref()));
+                        self.set_online(nickname.as_ref(), is_online)
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn find(&self, nickname: &str) -> Option<usize> {
+        self.targets
+            .iter()
+            .position(|target| self.casemapping.eq(target.nickname.as_ref(), nickname))
+    }
+
+    fn set_online(&mut self, nickname: &str, online: bool) -> Option<Event> {
+        let index = self.find(nickname)?;
+        let target = &mut self.targets[index];
+
+        if target.online == Some(online) {
+            None
+        } else {
+            target.online = Some(online);
+            let nickname = target.nickname.clone();
+            Some(if online {
+                Event::Online(nickname)
+            } else {
+                Event::Offline(nickname)
+            })
+        }
+    }
+
+    fn move_to_ison(&mut self, nickname: &str) {
+        if let Some(index) = self.find(nickname) {
+            self.targets[index].server_side = false;
+            self.mechanism = match self.mechanism {
+                // The server told us the list is full, so trust it over 005.
+                Some(Mechanism::Monitor(_)) => Some(Mechanism::Monitor(Some(self.server_side()))),
+                Some(Mechanism::Watch(_)) => Some(Mechanism::Watch(Some(self.server_side()))),
+                mechanism => mechanism,
+            };
+        }
+    }
+
+    fn server_side(&self) -> usize {
+        self.targets
+            .iter()
+            .filter(|target| target.server_side)
+            .count()
+    }
+
+    /// Delegate as many targets as the server will allow to its own list.
+    fn fill(&mut self) -> Vec<Command> {
+        let capacity = match self.mechanism {
+            Some(Mechanism::Monitor(None)) | Some(Mechanism::Watch(None)) => usize::MAX,
+            Some(Mechanism::Monitor(Some(limit))) | Some(Mechanism::Watch(Some(limit))) => {
+                limit.saturating_sub(self.server_side())
+            }
+            Some(Mechanism::IsOn) | None => 0,
+        };
+
+        let nicknames: Vec<Nickname> = self
+            .targets
+            .iter_mut()
+            .filter(|target| !target.server_side)
+            .take(capacity)
+            .map(|target| {
+                target.server_side = true;
+                target.nickname.clone()
+            })
+            .collect();
+
+        batch(nicknames.into_iter())
+            .into_iter()
+            .map(|batch| match self.mechanism {
+                Some(Mechanism::Monitor(_)) => Command::MonitorAdd {
+                    targets: keyword_list(batch),
+                },
+                _ => Command::Watch {
+                    entries: keyword_list(batch.into_iter().map(WatchEntry::Add)),
+                },
+            })
+            .collect()
+    }
+}
+
+impl Default for Presence {
+    fn default() -> Self {
+        Self::new(DEFAULT_ISON_INTERVAL)
+    }
+}
+
+/// Split a list of nicknames into groups that will comfortably fit on a line.
+fn batch<I: Iterator<Item = Nickname>>(nicknames: I) -> Vec<Vec<Nickname>> {
+    let mut batches = Vec::new();
+    let mut current = Vec::new();
+    let mut length = 0;
+
+    for nickname in nicknames {
+        let nickname_length = nickname.as_ref().len() + 1;
+        if length + nickname_length > TARGETS_PER_LINE && !current.is_empty() {
+            batches.push(current);
+            current = Vec::new();
+            length = 0;
+        }
+        length += nickname_length;
+        current.push(nickname);
+    }
+
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+fn keyword_list<T, I>(elements: I) -> KeywordList<T>
+where
+    T: FromStr + Into<String>,
+    I: IntoIterator<Item = T>,
+{
+    let mut keyword_list = KeywordList::new();
+    for element in elements {
+        keyword_list.push(element);
+    }
+    keyword_list
+}
+
+fn last_param(params: &MessageParams) -> &str {
+    params.get(params.len().wrapping_sub(1)).map_or("", |s| s)
+}
+
+#[cfg(test)]
+mod test_presence {
+    use super::super::test_helpers::to_strings;
+    use super::*;
+
+    fn isupport(raw: &str) -> ISupport {
+        let mut isupport = ISupport::new();
+        isupport.update(&raw.parse().unwrap());
+        isupport
+    }
+
+    fn reply(presence: &mut Presence, reply_type: ReplyType, raw: &str) -> Vec<Event> {
+        presence.handle_reply(&reply_type, &raw.parse().unwrap())
+    }
+
+    fn presence_with(nicknames: &[&str]) -> Presence {
+        let mut presence = Presence::default();
+        for nickname in nicknames {
+            assert!(presence.add(nickname.parse().unwrap()).is_empty());
+        }
+        presence
+    }
+
+    #[test]
+    fn monitor_within_limit() {
+        let mut presence = presence_with(&["Wiz", "Angel", "syrk"]);
+        assert_eq!(
+            vec!["MONITOR + Wiz,Angel"],
+            to_strings(presence.start(&isupport("spudly MONITOR=2 :are supported")))
+        );
+
+        // The overflow is polled with ISON.
+        let now = Instant::now();
+        assert_eq!(vec!["ISON syrk"], to_strings(presence.poll(now)));
+        assert!(presence.poll(now + Duration::from_secs(30)).is_empty());
+        assert_eq!(
+            vec![Event::Online("syrk".parse().unwrap())],
+            reply(&mut presence, ReplyType::RplIsOn, "spudly :SYRK")
+        );
+        assert!(presence.poll(now + Duration::from_secs(30)).is_empty());
+        assert_eq!(
+            vec!["ISON syrk"],
+            to_strings(presence.poll(now + Duration::from_secs(60)))
+        );
+
+        // Removing a server-side target makes room for the overflow.
+        assert_eq!(
+            vec!["MONITOR - Angel", "MONITOR + syrk"],
+            to_strings(presence.remove(&"angel".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    fn monitor_events() {
+        let mut presence = presence_with(&["Wiz", "Angel"]);
+        presence.start(&isupport("spudly MONITOR :are supported"));
+
+        assert_eq!(
+            vec![
+                Event::Online("Wiz".parse().unwrap()),
+                Event::Online("Angel".parse().unwrap())
+            ],
+            reply(
+                &mut presence,
+                ReplyType::RplMonOnline,
+                "spudly :wiz!jto@tolsun.oulu.fi,Angel!wings@irc.org"
+            )
+        );
+        assert_eq!(Some(true), presence.is_online(&"WIZ".parse().unwrap()));
+
+        // No change, no event.
+        assert!(reply(&mut presence, ReplyType::RplMonOnline, "spudly :Wiz").is_empty());
+
+        assert_eq!(
+            vec![Event::Offline("Wiz".parse().unwrap())],
+            reply(
+                &mut presence,
+                ReplyType::RplMonOffline,
+                "spudly :Wiz,Kilroy"
+            )
+        );
+    }
+
+    #[test]
+    fn monitor_list_full() {
+        let mut presence = presence_with(&["Wiz", "Angel", "syrk"]);
+        assert_eq!(
+            vec!["MONITOR + Wiz,Angel,syrk"],
+            to_strings(presence.start(&isupport("spudly MONITOR :are supported")))
+        );
+        assert!(reply(
+            &mut presence,
+            ReplyType::ErrMonListFull,
+            "spudly 2 syrk :Monitor list is full."
+        )
+        .is_empty());
+        assert_eq!(vec!["ISON syrk"], to_strings(presence.poll(Instant::now())));
+
+        // The server's limit sticks, so new targets go straight to ISON.
+        assert!(presence.add("Kilroy".parse().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn watch() {
+        let mut presence = presence_with(&["Wiz", "Angel"]);
+        assert_eq!(
+            vec!["WATCH +Wiz +Angel"],
+            to_strings(presence.start(&isupport("spudly WATCH=128 :are supported")))
+        );
+        assert_eq!(
+            vec![Event::Online("Angel".parse().unwrap())],
+            reply(
+                &mut presence,
+                ReplyType::RplNowOn,
+                "spudly Angel wings irc.org 1598000000 :is online"
+            )
+        );
+        assert_eq!(
+            vec![Event::Offline("Angel".parse().unwrap())],
+            reply(
+                &mut presence,
+                ReplyType::RplLogOff,
+                "spudly Angel wings irc.org 1598000000 :logged offline"
+            )
+        );
+        assert_eq!(
+            vec!["WATCH -Wiz"],
+            to_strings(presence.remove(&"Wiz".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    fn ison_fallback() {
+        let mut presence = presence_with(&["Wiz", "Angel"]);
+        assert!(presence
+            .start(&isupport("spudly CASEMAPPING=ascii :are supported"))
+            .is_empty());
+        assert!(presence.add("syrk".parse().unwrap()).is_empty());
+        assert_eq!(
+            vec!["ISON Wiz Angel syrk"],
+            to_strings(presence.poll(Instant::now()))
+        );
+        assert_eq!(
+            vec![
+                Event::Offline("Wiz".parse().unwrap()),
+                Event::Online("Angel".parse().unwrap()),
+                Event::Offline("syrk".parse().unwrap())
+            ],
+            reply(&mut presence, ReplyType::RplIsOn, "spudly :Angel")
+        );
+    }
+
+    #[test]
+    fn resends_lost_ison() {
+        let now = Instant::now();
+        let mut presence = presence_with(&["Wiz"]);
+        presence.start(&isupport("spudly :are supported"));
+        assert_eq!(vec!["ISON Wiz"], to_strings(presence.poll(now)));
+        assert!(presence.poll(now + Duration::from_secs(30)).is_empty());
+
+        // The reply never came.
+        assert_eq!(
+            vec!["ISON Wiz"],
+            to_strings(presence.poll(now + DEFAULT_ISON_INTERVAL))
+        );
+        assert_eq!(
+            vec![Event::Online("Wiz".parse().unwrap())],
+            reply(&mut presence, ReplyType::RplIsOn, "spudly :Wiz")
+        );
+        assert!(presence
+            .poll(now + DEFAULT_ISON_INTERVAL + Duration::from_secs(30))
+            .is_empty());
+    }
+
+    #[test]
+    fn uses_server_casemapping() {
+        let mut presence = presence_with(&["Wiz[m]"]);
+        presence.start(&isupport("spudly :are supported"));
+        presence.poll(Instant::now());
+        assert_eq!(
+            vec![Event::Online("Wiz[m]".parse().unwrap())],
+            reply(&mut presence, ReplyType::RplIsOn, "spudly :wiz{m}")
+        );
+
+        let mut presence = presence_with(&["Wiz[m]"]);
+        presence.start(&isupport("spudly CASEMAPPING=ascii :are supported"));
+        presence.poll(Instant::now());
+        assert_eq!(
+            vec![Event::Offline("Wiz[m]".parse().unwrap())],
+            reply(&mut presence, ReplyType::RplIsOn, "spudly :wiz{m}")
+        );
+    }
+
+    #[test]
+    fn batches_long_lists() {
+        let nicknames: Vec<String> = (0..100).map(|i| format!("nick{:04}", i)).collect();
+        let mut presence = Presence::default();
+        for nickname in nicknames.iter() {
+            presence.add(nickname.parse().unwrap());
+        }
+
+        let commands = to_strings(presence.start(&isupport("spudly MONITOR :are supported")));
+        assert_eq!(3, commands.len());
+        assert!(commands.iter().all(|command| command.len() < 512));
+    }
+
+    #[test]
+    fn nothing_before_registration() {
+        let mut presence = presence_with(&["Wiz"]);
+        assert!(presence.poll(Instant::now()).is_empty());
+        assert_eq!(None, presence.is_online(&"Wiz".parse().unwrap()));
+    }
+}
@@ -0,0 +1,189 @@
+use super::batch::Batch;
+use crikey_irc_common::{Message, MessageTags};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// The batch type used when a labeled response consists of several messages.
+pub const BATCH_LABELED_RESPONSE: &str = "labeled-response";
+
+/// How long to wait for the response to a labeled command before giving up
+/// on it.
+pub const LABEL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The server's reply to a command sent with a `label` tag, per the IRCv3
+/// labeled-response specification. The server replies with exactly one of:
+///
+/// - `ACK`, if the command produced no other response;
+/// - a single message carrying the label; or
+/// - a `labeled-response` batch containing every message it produced.
+///
+/// If none of these arrive within `LABEL_TIMEOUT`, the response is
+/// `TimedOut`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum LabeledResponse {
+    Ack,
+    Message(Message),
+    Batch(Batch),
+    TimedOut,
+}
+
+impl LabeledResponse {
+    /// The msgid of the response, if it was a single message with an ID. When
+    /// `echo-message` is enabled, this is the ID our PRIVMSG was delivered
+    /// with.
+    pub fn msgid(&self) -> Option<&str> {
+        match self {
+            LabeledResponse::Message(message) => message.tags.get("msgid"),
+            _ => None,
+        }
+    }
+}
+
+/// A handle to the eventual response to a labeled command. The response is
+/// filled in by `Client::poll()` once it arrives.
+#[derive(Clone, Debug)]
+pub struct ResponseHandle {
+    label: String,
+    response: Rc<RefCell<Option<LabeledResponse>>>,
+}
+
+impl ResponseHandle {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.response.borrow().is_some()
+    }
+
+    /// Take the response, if it has arrived. Subsequent calls return `None`.
+    pub fn take(&self) -> Option<LabeledResponse> {
+        self.response.borrow_mut().take()
+    }
+}
+
+/// Generates labels for outbound commands and routes responses back to the
+/// handle of the command that caused them.
+#[derive(Debug, Default)]
+pub struct Responses {
+    next_label: u64,
+    pending: HashMap<String, Pending>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    sent: Instant,
+    response: Rc<RefCell<Option<LabeledResponse>>>,
+}
+
+impl Responses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allocate a label, returning the tags to send it with and a handle for
+    /// the response.
+    pub fn label(&mut self, now: Instant) -> (MessageTags, ResponseHandle) {
+        self.next_label += 1;
+        let label = self.next_label.to_string();
+        let response = Rc::new(RefCell::new(None));
+        self.pending.insert(
+            label.clone(),
+            Pending {
+                sent: now,
+                response: Rc::clone(&response),
+            },
+        );
+
+        let mut tags = MessageTags::new();
+        tags.insert("label".to_string(), label.clone());
+        (tags, ResponseHandle { label, response })
+    }
+
+    /// Deliver a response to whoever is waiting on `label`. Returns `false`
+    /// if nobody was waiting.
+    pub fn complete(&mut self, label: &str, response: LabeledResponse) -> bool {
+        match self.pending.remove(label) {
+            Some(pending) => {
+                *pending.response.borrow_mut() = Some(response);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Give up on any response that's taken longer than `LABEL_TIMEOUT`.
+    pub fn expire(&mut self, now: Instant) {
+        self.pending.retain(|_, pending| {
+            if now.duration_since(pending.sent) < LABEL_TIMEOUT {
+                true
+            } else {
+                *pending.response.borrow_mut() = Some(LabeledResponse::TimedOut);
+                false
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_responses {
+    use super::*;
+
+    #[test]
+    fn label() {
+        let mut responses = Responses::new();
+        let (tags, first) = responses.label(Instant::now());
+        assert_eq!(Some(first.label()), tags.get("label"));
+
+        let (_, second) = responses.label(Instant::now());
+        assert_ne!(first.label(), second.label());
+    }
+
+    #[test]
+    fn complete() {
+        let mut responses = Responses::new();
+        let (_, handle) = responses.label(Instant::now());
+        assert!(!handle.is_complete());
+
+        assert!(!responses.complete("unknown", LabeledResponse::Ack));
+        assert!(responses.complete(handle.label(), LabeledResponse::Ack));
+        assert!(!responses.complete(handle.label(), LabeledResponse::Ack));
+
+        assert!(handle.is_complete());
+        assert_eq!(Some(LabeledResponse::Ack), handle.take());
+        assert_eq!(None, handle.take());
+    }
+
+    #[test]
+    fn expire() {
+        let now = Instant::now();
+        let mut responses = Responses::new();
+        let (_, first) = responses.label(now);
+        let (_, second) = responses.label(now + Duration::from_secs(60));
+
+        responses.expire(now + LABEL_TIMEOUT);
+        assert_eq!(Some(LabeledResponse::TimedOut), first.take());
+        assert!(!second.is_complete());
+        assert!(!responses.complete(first.label(), LabeledResponse::Ack));
+        assert_eq!(1, responses.pending.len());
+
+        assert!(responses.complete(second.label(), LabeledResponse::Ack));
+        assert!(responses.pending.is_empty());
+    }
+
+    #[test]
+    fn msgid() {
+        assert_eq!(
+            Some("abc"),
+            LabeledResponse::Message(
+                "@label=1;msgid=abc :spudly PRIVMSG #channel :hi"
+                    .parse()
+                    .unwrap()
+            )
+            .msgid()
+        );
+        assert_eq!(None, LabeledResponse::Ack.msgid());
+    }
+}
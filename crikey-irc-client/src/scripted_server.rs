@@ -0,0 +1,151 @@
+use crikey_irc_common::{Connection, Recorder};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::rc::Rc;
+
+/// A stand-in for an IRC server, for testing the `Client` without threads or
+/// sockets. Lines queued with `send()` are read by the client in order, and
+/// lines written by the client can be inspected with `received()`.
+///
+/// Responses can also be scripted in advance with `on()`: when the client
+/// sends the expected line, the canned responses are queued for it to read.
+/// Like a non-blocking socket, reading from an empty server returns
+/// `WouldBlock`, so `Client::poll()` returns `false` once the client has
+/// caught up, until `disconnect()` is called.
+#[derive(Clone, Default)]
+pub struct ScriptedServer {
+    state: Rc<RefCell<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    to_client: VecDeque<u8>,
+    from_client: Vec<u8>,
+    received: VecDeque<String>,
+    script: VecDeque<(String, Vec<String>)>,
+    disconnected: bool,
+}
+
+impl ScriptedServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A connection for the client to talk to this server over.
+    pub fn connection(&self) -> Connection {
+        Connection::new(
+            Box::new(io::BufReader::new(self.clone())),
+            Box::new(self.clone()),
+        )
+    }
+
+    /// A connection to this server that saves everything sent and received
+    /// with a `Recorder`.
+    pub fn recorded_connection(&self, recorder: &Recorder) -> Connection {
+        let (reader, writer) = recorder.wrap(
+            Box::new(io::BufReader::new(self.clone())),
+            Box::new(self.clone()),
+        );
+        Connection::new(reader, writer)
+    }
+
+    /// Queue a line for the client to read.
+    pub fn send(&self, line: &str) {
+        let mut state = self.state.borrow_mut();
+        state.to_client.extend(line.as_bytes());
+        state.to_client.extend(b"\r\n");
+    }
+
+    /// Queue a line that may not be valid UTF-8 for the client to read.
+    pub fn send_bytes(&self, line: &[u8]) {
+        let mut state = self.state.borrow_mut();
+        state.to_client.extend(line);
+        state.to_client.extend(b"\r\n");
+    }
+
+    /// When the client next sends `expected`, respond with `responses`.
+    /// Scripted exchanges must happen in the order they were added.
+    pub fn on(&self, expected: &str, responses: &[&str]) {
+        self.state.borrow_mut().script.push_back((
+            expected.to_string(),
+            responses.iter().map(|line| line.to_string()).collect(),
+        ));
+    }
+
+    /// Take every line sent by the client so far, without line endings.
+    pub fn received(&self) -> Vec<String> {
+        self.state.borrow_mut().received.drain(..).collect()
+    }
+
+    /// Hang up once the client has read everything queued so far.
+    pub fn disconnect(&self) {
+        self.state.borrow_mut().disconnected = true;
+    }
+
+    /// Whether every scripted exchange has taken place.
+    pub fn is_finished(&self) -> bool {
+        self.state.borrow().script.is_empty()
+    }
+}
+
+impl io::Read for ScriptedServer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        if state.to_client.is_empty() {
+            return if state.disconnected {
+                Ok(0)
+            } else {
+                Err(io::ErrorKind::WouldBlock.into())
+            };
+        }
+
+        let len = buf.len().min(state.to_client.len());
+        for (byte, queued) in buf.iter_mut().zip(state.to_client.drain(..len)) {
+            *byte = queued;
+        }
+        Ok(len)
+    }
+}
+
+impl io::Write for ScriptedServer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.state.borrow_mut().from_client.extend(buf);
+
+        loop {
+            let line = {
+                let mut state = self.state.borrow_mut();
+                match state.from_client.iter().position(|&byte| byte == b'\n') {
+                    Some(index) => {
+                        let line: Vec<u8> = state.from_client.drain(..=index).collect();
+                        String::from_utf8_lossy(&line)
+                            .trim_end_matches(&['\r', '\n'][..])
+                            .to_string()
+                    }
+                    None => break,
+                }
+            };
+
+            let responses = {
+                let mut state = self.state.borrow_mut();
+                state.received.push_back(line.clone());
+                match state.script.front() {
+                    Some((expected, _)) if *expected == line => state.script.pop_front(),
+                    _ => None,
+                }
+            };
+
+            if let Some((_, responses)) = responses {
+                for response in responses {
+                    self.send(&response);
+                }
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
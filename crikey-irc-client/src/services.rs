@@ -0,0 +1,376 @@
+use super::Event;
+use crikey_irc_common::{
+    Casemapping, Channel, Command, KeywordList, Nickname, Recipient, ReplyType, Sender,
+};
+
+/// How to get our primary nickname back from whoever is using it, most likely
+/// a ghost of our own previous connection.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Reclaim {
+    /// Leave it, and carry on under the alternate nickname.
+    None,
+    /// Ask NickServ to disconnect the other user, then change nickname once
+    /// they've quit. Supported by both Atheme and Anope.
+    #[default]
+    Ghost,
+    /// Ask NickServ to disconnect the other user and change our nickname in
+    /// one step. Supported by Atheme.
+    Regain,
+}
+
+/// How to talk to the services on a network. The defaults suit Atheme and
+/// Anope, which most networks run, but the nicknames of the services and the
+/// messages sent to and expected from them can be changed.
+///
+/// In the commands, `{account}`, `{password}`, `{nickname}` and `{channel}`
+/// are replaced with the relevant values.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ServicesConfig {
+    pub nickserv: Nickname,
+    pub chanserv: Nickname,
+    /// The account to identify to, if it isn't our primary nickname.
+    pub account: Option<String>,
+    /// The NickServ password. If this is `None`, we don't identify.
+    pub password: Option<String>,
+    pub reclaim: Reclaim,
+    pub identify_command: String,
+    pub ghost_command: String,
+    pub regain_command: String,
+    pub op_command: String,
+    pub voice_command: String,
+    /// If a NOTICE from NickServ contains any of these, we've identified.
+    /// Servers that send the 900 reply on identifying don't need them.
+    pub identified_patterns: Vec<String>,
+}
+
+impl Default for ServicesConfig {
+    fn default() -> Self {
+        ServicesConfig {
+            nickserv: "NickServ".parse().unwrap(),
+            chanserv: "ChanServ".parse().unwrap(),
+            account: None,
+            password: None,
+            reclaim: Reclaim::default(),
+            identify_command: "IDENTIFY {account} {password}".to_string(),
+            ghost_command: "GHOST {nickname} {password}".to_string(),
+            regain_command: "REGAIN {nickname} {password}".to_string(),
+            op_command: "OP {channel} {nickname}".to_string(),
+            voice_command: "VOICE {channel} {nickname}".to_string(),
+            identified_patterns: vec![
+                "You are now identified".to_string(),
+                "Password accepted".to_string(),
+            ],
+        }
+    }
+}
+
+/// Identifies to NickServ once registration is complete, unless we've
+/// already logged in some other way, and reclaims our primary nickname if we
+/// had to register under an alternate.
+#[derive(Debug, Default)]
+pub struct Services {
+    config: ServicesConfig,
+    identified: bool,
+    ghosted: Option<Nickname>,
+}
+
+impl Services {
+    pub fn set_config(&mut self, config: ServicesConfig) {
+        self.config = config;
+    }
+
+    pub fn is_identified(&self) -> bool {
+        self.identified
+    }
+
+    /// Called once registration is complete, with the nickname we registered
+    /// under and the one we wanted.
+    pub fn start(
+        &mut self,
+        nickname: &Nickname,
+        primary: &Nickname,
+        casemapping: Casemapping,
+    ) -> Vec<Command> {
+        let password = match &self.config.password {
+            Some(password) => password.clone(),
+            None => return Vec::new(),
+        };
+
+        let mut commands = Vec::new();
+        if !self.identified {
+            let account = match &self.config.account {
+                Some(account) => account.clone(),
+                None => String::from(primary.clone()),
+            };
+            commands.push(self.nickserv(fill(
+                &self.config.identify_command,
+                &[("{account}", &account), ("{password}", &password)],
+            )));
+        }
+
+        if !casemapping.eq(nickname.as_ref(), primary.as_ref()) {
+            let primary_name = String::from(primary.clone());
+            let vars = [("{nickname}", &primary_name[..]), ("{password}", &password)];
+            match self.config.reclaim {
+                Reclaim::None => (),
+                Reclaim::Ghost => {
+                    commands.push(self.nickserv(fill(&self.config.ghost_command, &vars)));
+                    self.ghosted = Some(primary.clone());
+                }
+                Reclaim::Regain => {
+                    commands.push(self.nickserv(fill(&self.config.regain_command, &vars)))
+                }
+            }
+        }
+        commands
+    }
+
+    /// Watch for confirmation that we've identified, and for the ghost of our
+    /// primary nickname to leave.
+    pub fn handle_command(
+        &mut self,
+        sender: Option<&Sender>,
+        command: &Command,
+        casemapping: Casemapping,
+    ) -> (Vec<Command>, Vec<Event>) {
+        let from = match sender {
+            Some(Sender::User { nickname, .. }) => nickname,
+            _ => return (Vec::new(), Vec::new()),
+        };
+
+        match command {
+            Command::Notice { message, .. }
+                if casemapping.eq(from.as_ref(), self.config.nickserv.as_ref()) =>
+            {
+                let message = message.to_lowercase();
+                let identified = self
+                    .config
+                    .identified_patterns
+                    .iter()
+                    .any(|pattern| message.contains(&pattern.to_lowercase()));
+                (Vec::new(), self.set_identified(identified))
+            }
+            Command::Quit { .. } | Command::Nick { .. } => match &self.ghosted {
+                Some(ghosted) if casemapping.eq(from.as_ref(), ghosted.as_ref()) => {
+                    let nickname = self.ghosted.take().unwrap();
+                    (vec![Command::Nick { nickname }], Vec::new())
+                }
+                _ => (Vec::new(), Vec::new()),
+            },
+            _ => (Vec::new(), Vec::new()),
+        }
+    }
+
+    pub fn handle_reply(&mut self, reply_type: &ReplyType) -> Vec<Event> {
+        match reply_type {
+            ReplyType::RplLoggedIn => self.set_identified(true),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Ask ChanServ to op someone in a channel.
+    pub fn op(&self, channel: &Channel, nickname: &Nickname) -> Command {
+        self.chanserv(&self.config.op_command, channel, nickname)
+    }
+
+    /// Ask ChanServ to voice someone in a channel.
+    pub fn voice(&self, channel: &Channel, nickname: &Nickname) -> Command {
+        self.chanserv(&self.config.voice_command, channel, nickname)
+    }
+
+    fn set_identified(&mut self, identified: bool) -> Vec<Event> {
+        if identified && !self.identified {
+            self.identified = true;
+            vec![Event::Identified]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn nickserv(&self, message: String) -> Command {
+        Command::Privmsg {
+            recipients: KeywordList::from(vec![Recipient::from(self.config.nickserv.clone())]),
+            message,
+        }
+    }
+
+    fn chanserv(&self, template: &str, channel: &Channel, nickname: &Nickname) -> Command {
+        Command::Privmsg {
+            recipients: KeywordList::from(vec![Recipient::from(self.config.chanserv.clone())]),
+            message: fill(
+                template,
+                &[
+                    ("{channel}", &String::from(channel.clone())),
+                    ("{nickname}", nickname.as_ref()),
+                ],
+            ),
+        }
+    }
+}
+
+fn fill(template: &str, vars: &[(&str, &str)]) -> String {
+    vars.iter()
+        .fold(template.to_string(), |result, (name, value)| {
+            result.replace(name, value)
+        })
+}
+
+#[cfg(test)]
+mod test_services {
+    use super::super::test_helpers::to_strings;
+    use super::*;
+
+    fn services(reclaim: Reclaim) -> Services {
+        let mut services = Services::default();
+        services.set_config(ServicesConfig {
+            password: Some("hunter2".to_string()),
+            reclaim,
+            ..ServicesConfig::default()
+        });
+        services
+    }
+
+    fn command(services: &mut Services, raw: &str) -> (Vec<String>, Vec<Event>) {
+        let message: crikey_irc_common::Message = raw.parse().unwrap();
+        let command = match message.body {
+            crikey_irc_common::MessageBody::Command(command) => command,
+            _ => panic!("Expected a command"),
+        };
+        let (commands, events) =
+            services.handle_command(message.sender.as_ref(), &command, Casemapping::Rfc1459);
+        (to_strings(commands), events)
+    }
+
+    #[test]
+    fn identifies() {
+        let mut services = services(Reclaim::Ghost);
+        let spudly = "spudly".parse().unwrap();
+        assert_eq!(
+            vec!["PRIVMSG NickServ :IDENTIFY spudly hunter2"],
+            to_strings(services.start(&spudly, &spudly, Casemapping::Rfc1459))
+        );
+        assert!(!services.is_identified());
+
+        // Only NickServ can tell us we've identified.
+        let (_, events) = command(
+            &mut services,
+            ":Wiz!wiz@example.com NOTICE spudly :You are now identified",
+        );
+        assert!(events.is_empty());
+        let (_, events) = command(
+            &mut services,
+            ":NickServ!NickServ@services.example.com NOTICE spudly :You are now identified for spudly.",
+        );
+        assert_eq!(vec![Event::Identified], events);
+        assert!(services.is_identified());
+    }
+
+    #[test]
+    fn skips_identify_when_logged_in() {
+        let mut services = services(Reclaim::Ghost);
+        assert_eq!(
+            vec![Event::Identified],
+            services.handle_reply(&ReplyType::RplLoggedIn)
+        );
+        let spudly = "spudly".parse().unwrap();
+        assert!(services
+            .start(&spudly, &spudly, Casemapping::Rfc1459)
+            .is_empty());
+
+        let mut services = Services::default();
+        assert!(services
+            .start(&spudly, &spudly, Casemapping::Rfc1459)
+            .is_empty());
+    }
+
+    #[test]
+    fn ghosts() {
+        let mut services = services(Reclaim::Ghost);
+        assert_eq!(
+            vec![
+                "PRIVMSG NickServ :IDENTIFY spudly hunter2",
+                "PRIVMSG NickServ :GHOST spudly hunter2",
+            ],
+            to_strings(services.start(
+                &"spudly_".parse().unwrap(),
+                &"spudly".parse().unwrap(),
+                Casemapping::Rfc1459
+            ))
+        );
+
+        let (commands, _) = command(&mut services, ":Wiz!wiz@example.com QUIT :Bye");
+        assert!(commands.is_empty());
+        let (commands, _) = command(&mut services, ":spudly!pj@example.com QUIT :Killed");
+        assert_eq!(vec!["NICK spudly"], commands);
+        let (commands, _) = command(&mut services, ":spudly!pj@example.com QUIT :Killed");
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn uses_casemapping() {
+        let primary = "spud[m]".parse().unwrap();
+        let nickname = "spud{m}".parse().unwrap();
+        let mut rfc1459 = services(Reclaim::Ghost);
+        assert_eq!(
+            vec!["PRIVMSG NickServ :IDENTIFY spud[m] hunter2"],
+            to_strings(rfc1459.start(&nickname, &primary, Casemapping::Rfc1459))
+        );
+
+        let mut ascii = services(Reclaim::Ghost);
+        assert_eq!(
+            vec![
+                "PRIVMSG NickServ :IDENTIFY spud[m] hunter2",
+                "PRIVMSG NickServ :GHOST spud[m] hunter2",
+            ],
+            to_strings(ascii.start(&nickname, &primary, Casemapping::Ascii))
+        );
+        let (commands, _) = ascii.handle_command(
+            Some(&"spud[m]!pj@example.com".parse().unwrap()),
+            &"QUIT :Killed".parse().unwrap(),
+            Casemapping::Ascii,
+        );
+        assert_eq!(vec!["NICK spud[m]"], to_strings(commands));
+    }
+
+    #[test]
+    fn regains() {
+        let mut services = Services::default();
+        services.set_config(ServicesConfig {
+            account: Some("potato".to_string()),
+            password: Some("hunter2".to_string()),
+            reclaim: Reclaim::Regain,
+            ..ServicesConfig::default()
+        });
+        assert_eq!(
+            vec![
+                "PRIVMSG NickServ :IDENTIFY potato hunter2",
+                "PRIVMSG NickServ :REGAIN spudly hunter2",
+            ],
+            to_strings(services.start(
+                &"spudly_".parse().unwrap(),
+                &"spudly".parse().unwrap(),
+                Casemapping::Rfc1459
+            ))
+        );
+    }
+
+    #[test]
+    fn chanserv() {
+        let mut services = Services::default();
+        services.set_config(ServicesConfig {
+            chanserv: "Q".parse().unwrap(),
+            op_command: "OP {channel}".to_string(),
+            ..ServicesConfig::default()
+        });
+        let channel = "#channel".parse().unwrap();
+        let nickname = "spudly".parse().unwrap();
+        assert_eq!(
+            "PRIVMSG Q :OP #channel",
+            String::from(services.op(&channel, &nickname))
+        );
+        assert_eq!(
+            "PRIVMSG ChanServ :VOICE #channel spudly",
+            String::from(Services::default().voice(&channel, &nickname))
+        );
+    }
+}
@@ -0,0 +1,15 @@
+//! Feed arbitrary bytes to the parsers for the parts of a message, which are
+//! also used on their own and must never panic.
+#![no_main]
+use crikey_irc_common::{Channel, Recipient, Sender, TargetMask, UserMask};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(raw) = std::str::from_utf8(data) {
+        let _ = raw.parse::<Channel>();
+        let _ = raw.parse::<Recipient>();
+        let _ = raw.parse::<Sender>();
+        let _ = raw.parse::<TargetMask>();
+        let _ = raw.parse::<UserMask>();
+    }
+});
@@ -0,0 +1,14 @@
+//! Feed arbitrary bytes to the message parser, which must never panic, and
+//! check that anything it accepts survives a round trip.
+#![no_main]
+use crikey_irc_common::Message;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(raw) = std::str::from_utf8(data) {
+        if let Ok(message) = raw.parse::<Message>() {
+            let serialized = String::from(message.clone());
+            assert_eq!(Ok(message), serialized.parse::<Message>());
+        }
+    }
+});
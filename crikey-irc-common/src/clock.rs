@@ -0,0 +1,80 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Where the time comes from. Everything that keeps time, such as the
+/// keepalive PINGs and the delay before rejoining a channel, asks a clock
+/// rather than calling `Instant::now()`, so that a replay can control it.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The real time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when it's told to. Clones share the same time, so
+/// one can be handed to a `Client` while another is used to advance it.
+#[derive(Clone, Debug)]
+pub struct VirtualClock {
+    now: Rc<Cell<Instant>>,
+}
+
+impl VirtualClock {
+    /// A clock stopped at the current time.
+    pub fn new() -> Self {
+        VirtualClock {
+            now: Rc::new(Cell::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+
+    /// Move the clock forward to `time`. A time in the past is ignored, so
+    /// the clock never runs backwards.
+    pub fn advance_to(&self, time: Instant) {
+        if time > self.now.get() {
+            self.now.set(time);
+        }
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
+
+#[cfg(test)]
+mod test_clock {
+    use super::*;
+
+    #[test]
+    fn virtual_clock() {
+        let clock = VirtualClock::new();
+        let shared = clock.clone();
+        let start = clock.now();
+
+        shared.advance(Duration::from_secs(5));
+        assert_eq!(start + Duration::from_secs(5), clock.now());
+
+        clock.advance_to(start + Duration::from_secs(3));
+        assert_eq!(start + Duration::from_secs(5), shared.now());
+        clock.advance_to(start + Duration::from_secs(8));
+        assert_eq!(start + Duration::from_secs(8), shared.now());
+    }
+}
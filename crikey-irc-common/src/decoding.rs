@@ -0,0 +1,175 @@
+use super::ParseError;
+use std::result::Result;
+use std::str::FromStr;
+
+/// How to turn the bytes of an incoming line into text. IRC predates Unicode,
+/// and while almost everyone now sends UTF-8, a user with a legacy client can
+/// still send a line in Latin-1 or CP1252:
+///
+/// - `Strict`: drop any line that isn't valid UTF-8.
+/// - `Lossy`: replace invalid sequences with U+FFFD.
+/// - `Fallback(charset)`: decode lines that aren't valid UTF-8 using the
+///   legacy charset instead. This is the default, with CP1252, since it's
+///   what mIRC and most Windows clients of that era sent.
+///
+/// If the server advertises the UTF8ONLY ISUPPORT token, it promises to send
+/// nothing but UTF-8, so a line that isn't is damaged rather than legacy, and
+/// `Fallback` is treated as `Lossy`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Decoding {
+    Strict,
+    Lossy,
+    Fallback(Charset),
+}
+
+impl Decoding {
+    /// Decode a line, or return `None` if it should be dropped.
+    pub fn decode(self, bytes: &[u8], utf8_only: bool) -> Option<String> {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Some(text.to_string()),
+            Err(_) => match self {
+                Decoding::Strict => None,
+                Decoding::Fallback(charset) if !utf8_only => Some(charset.decode(bytes)),
+                Decoding::Lossy | Decoding::Fallback(_) => {
+                    Some(String::from_utf8_lossy(bytes).into_owned())
+                }
+            },
+        }
+    }
+}
+
+impl Default for Decoding {
+    fn default() -> Self {
+        Decoding::Fallback(Charset::Cp1252)
+    }
+}
+
+impl FromStr for Decoding {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "strict" => Ok(Decoding::Strict),
+            "lossy" => Ok(Decoding::Lossy),
+            charset => charset
+                .parse()
+                .map(Decoding::Fallback)
+                .map_err(|_| ParseError::new("Decoding")),
+        }
+    }
+}
+
+impl From<Decoding> for String {
+    fn from(decoding: Decoding) -> String {
+        match decoding {
+            Decoding::Strict => "strict".to_string(),
+            Decoding::Lossy => "lossy".to_string(),
+            Decoding::Fallback(charset) => String::from(charset),
+        }
+    }
+}
+
+/// A legacy single-byte character set.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Charset {
+    Latin1, // latin1, aka. ISO-8859-1
+    Cp1252, // cp1252, aka. Windows-1252
+}
+
+/// The characters CP1252 puts in the range 0x80-0x9F, where Latin-1 has C1
+/// control codes. The five bytes CP1252 leaves undefined map to the control
+/// codes, as they do in browsers.
+const CP1252_C1: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+impl Charset {
+    pub fn decode(self, bytes: &[u8]) -> String {
+        bytes
+            .iter()
+            .map(|&byte| match (self, byte) {
+                (Charset::Cp1252, 0x80..=0x9f) => CP1252_C1[(byte - 0x80) as usize],
+                _ => char::from(byte),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Charset {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match &raw.to_ascii_lowercase()[..] {
+            "latin1" | "iso-8859-1" => Ok(Charset::Latin1),
+            "cp1252" | "windows-1252" => Ok(Charset::Cp1252),
+            _ => Err(ParseError::new("Charset")),
+        }
+    }
+}
+
+impl From<Charset> for String {
+    fn from(charset: Charset) -> String {
+        match charset {
+            Charset::Latin1 => "latin1",
+            Charset::Cp1252 => "cp1252",
+        }
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod test_decoding {
+    use super::*;
+
+    #[test]
+    fn utf8() {
+        for decoding in &[Decoding::Strict, Decoding::Lossy, Decoding::default()] {
+            assert_eq!(
+                Some("PRIVMSG #channel :Skål!".to_string()),
+                decoding.decode("PRIVMSG #channel :Skål!".as_bytes(), false)
+            );
+        }
+    }
+
+    #[test]
+    fn legacy() {
+        let latin1 = b"PRIVMSG #channel :Sk\xe5l!";
+        assert_eq!(None, Decoding::Strict.decode(latin1, false));
+        assert_eq!(
+            Some("PRIVMSG #channel :Sk\u{fffd}l!".to_string()),
+            Decoding::Lossy.decode(latin1, false)
+        );
+        assert_eq!(
+            Some("PRIVMSG #channel :Skål!".to_string()),
+            Decoding::Fallback(Charset::Latin1).decode(latin1, false)
+        );
+        assert_eq!(
+            Some("PRIVMSG #channel :Sk\u{fffd}l!".to_string()),
+            Decoding::Fallback(Charset::Latin1).decode(latin1, true)
+        );
+    }
+
+    #[test]
+    fn charsets() {
+        assert_eq!("“€5”", Charset::Cp1252.decode(b"\x93\x805\x94"));
+        assert_eq!(
+            "\u{93}\u{80}5\u{94}",
+            Charset::Latin1.decode(b"\x93\x805\x94")
+        );
+        assert_eq!("\u{81}ÿ", Charset::Cp1252.decode(b"\x81\xff"));
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(Ok(Decoding::Strict), "strict".parse());
+        assert_eq!(Ok(Decoding::Lossy), "lossy".parse());
+        assert_eq!(
+            Ok(Decoding::Fallback(Charset::Latin1)),
+            "ISO-8859-1".parse()
+        );
+        assert_eq!(Ok(Decoding::Fallback(Charset::Cp1252)), "cp1252".parse());
+        assert!("utf-16".parse::<Decoding>().is_err());
+        assert_eq!("cp1252".to_string(), String::from(Decoding::default()));
+    }
+}
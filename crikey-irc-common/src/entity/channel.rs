@@ -1,5 +1,5 @@
 use super::super::syntax::ServerMask;
-use super::ParseError;
+use super::{ParseError, ValidationProfile};
 use std::result::Result;
 use std::str::FromStr;
 
@@ -22,13 +22,28 @@ const PUBLIC_PREFIX: char = '#';
 ///
 /// The RFC does a poor job of explaining, but the ':' character precedes a
 /// channel mask, that being a server mask to which the channel is restricted.
-#[derive(PartialEq, Debug)]
+///
+/// The RFC also limits channel names to 50 characters, but servers advertise
+/// their own limit in the CHANNELLEN ISUPPORT token, so no limit is enforced
+/// when parsing. Use `parse_with_profile()` to apply one.
+#[derive(Clone, PartialEq, Debug)]
 pub struct Channel {
     channel_type: ChannelType,
     channel_name: ChannelName,
     server_mask: Option<ServerMask>,
 }
 
+impl Channel {
+    /// Parse a channel, rejecting anything that `profile` doesn't allow.
+    pub fn parse_with_profile(raw: &str, profile: &ValidationProfile) -> Result<Self, ParseError> {
+        if profile.is_valid_channel(raw) {
+            raw.parse()
+        } else {
+            Err(ParseError::new("Channel"))
+        }
+    }
+}
+
 impl FromStr for Channel {
     type Err = ParseError;
 
@@ -39,7 +54,7 @@ impl FromStr for Channel {
             (raw, None)
         };
 
-        if raw.len() < 2 || raw.len() > 50 {
+        if raw.len() < 2 {
             Err(ParseError::new("Channel"))
         } else {
             if raw.starts_with(SAFE_PREFIX) && raw.len() > 6 && raw.is_char_boundary(6) {
@@ -98,9 +113,24 @@ mod test_channel {
         assert!("& space".parse::<Channel>().is_err());
         assert!("+comma,".parse::<Channel>().is_err());
         assert!("!12345:colon".parse::<Channel>().is_err());
-        assert!("#01234567890123456789012345678901234567890123456789"
-            .parse::<Channel>()
-            .is_err());
+        assert!(Channel::parse_with_profile(
+            "#01234567890123456789012345678901234567890123456789",
+            &ValidationProfile::rfc2812()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn profile() {
+        let long = "#01234567890123456789012345678901234567890123456789";
+        assert_eq!(long.parse::<Channel>(), {
+            Channel::parse_with_profile(long, &ValidationProfile::rfc1459())
+        });
+        assert!(Channel::parse_with_profile("+channel", &ValidationProfile::rfc1459()).is_err());
+        assert!(Channel::parse_with_profile("&channel", &ValidationProfile::rfc1459()).is_ok());
+        assert!(
+            Channel::parse_with_profile("#bad,channel", &ValidationProfile::permissive()).is_err()
+        );
     }
 
     #[test]
@@ -201,7 +231,7 @@ mod test_channel {
 /// - `+` is public but does not support modes such as +o and +v
 /// - `!` is public but "safe" and is prefixed with a server-generated channel
 ///   ID to mitigate name collisions between servers
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum ChannelType {
     Local,           // Prefix: &
     Safe(ChannelID), // Prefix: ![A-Z0-9]{5}
@@ -300,7 +330,7 @@ mod test_channel_type {
 /// ```
 ///
 /// (Yes, this is backwards from normal base-x encoding.)
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct ChannelID(String);
 
 impl FromStr for ChannelID {
@@ -308,7 +338,6 @@ impl FromStr for ChannelID {
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
         if raw.len() != 5 {
-            println!("{:?}", raw);
             Err(ParseError::new("ChannelID"))
         } else {
             if raw.contains(|c: char| !c.is_ascii_uppercase() && !c.is_ascii_digit()) {
@@ -369,14 +398,14 @@ mod test_channel_id {
 /// chanstring =/ %x2D-39 / %x3B-FF
 ///                 ; any octet except NUL, BELL, CR, LF, " ", "," and ":"
 /// ```
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct ChannelName(String);
 
 impl FromStr for ChannelName {
     type Err = ParseError;
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
-        if raw.len() < 1 || raw.contains(&['\x00', '\x07', '\r', '\n', ' ', ',', ':'][..]) {
+        if raw.is_empty() || raw.contains(&['\x00', '\x07', '\r', '\n', ' ', ',', ':'][..]) {
             Err(ParseError::new("ChannelName"))
         } else {
             Ok(Self(raw.to_string()))
@@ -443,14 +472,14 @@ mod test_channel_name {
 /// Note that the formal notation excludes the ACK character (\x06) rather than
 /// FF (\x0c) as the comment indicates. This implementation treats the formal
 /// notation as authoritative.
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct ChannelKey(String);
 
 impl FromStr for ChannelKey {
     type Err = ParseError;
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
-        if raw.len() < 1
+        if raw.is_empty()
             || raw.len() > 23
             || !raw.is_ascii()
             || raw.contains(&['\x00', '\x06', '\x09', '\x0a', '\x0b', '\x0d', '\x20'][..])
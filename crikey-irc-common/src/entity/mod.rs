@@ -1,6 +1,6 @@
 pub use self::channel::{Channel, ChannelKey};
-use self::server::Host;
-pub use self::server::Servername;
+pub use self::profile::ValidationProfile;
+pub use self::server::{Cloak, Host, Servername};
 pub use self::user::{Nickname, Username};
 use super::syntax::TargetMask;
 use super::ParseError;
@@ -8,6 +8,7 @@ use std::result::Result;
 use std::str::FromStr;
 
 mod channel;
+mod profile;
 mod server;
 mod user;
 
@@ -58,9 +59,21 @@ mod user;
 /// - "user%host@example.com" => is the username "user" or "user%host"? "user"
 /// - "user%host" => is the username "user%host" or "user"? "user"
 /// - "user%host%host" => what is even happening here? invalid, reject
-#[derive(PartialEq, Debug)]
+///
+/// Many servers also accept a channel preceded by a STATUSMSG prefix, such as
+/// "@#channel", which addresses only the members of the channel with that
+/// status or higher. The prefixes are advertised in the STATUSMSG ISUPPORT
+/// token, so `FromStr` can only guess, and recognises the ones that can't be
+/// mistaken for anything else. Once ISUPPORT is known, use
+/// `parse_with_statusmsg()` instead:
+///
+/// - "@#channel" => status channel, prefix "@"
+/// - "+#channel" => status channel or the modeless channel "+#channel"?
+///   channel, unless parsed with `parse_with_statusmsg()` and "+" is listed
+#[derive(Clone, PartialEq, Debug)]
 pub enum Recipient {
     Channel(Channel),
+    StatusChannel(char, Channel), // @#channel
     Nickname(Nickname),
     NicknameUserHost(Nickname, Username, Host), // nickname!user@host
     TargetMask(TargetMask),
@@ -69,10 +82,41 @@ pub enum Recipient {
     UserServername(Username, Servername),           // user@servername
 }
 
+/// STATUSMSG prefixes that are neither channel types nor valid at the start of
+/// a nickname, and so are unambiguous without ISUPPORT.
+const UNAMBIGUOUS_STATUSMSG: &str = "~@%";
+
+impl Recipient {
+    /// Parse a recipient, treating a leading character listed in `statusmsg`
+    /// (the value of the STATUSMSG ISUPPORT token) as a status prefix if the
+    /// remainder is a valid channel. This takes precedence over treating the
+    /// prefix as a channel type. Unlike `FromStr`, a prefix that isn't listed
+    /// is never a status prefix, so "@#channel" is an error unless "@" is.
+    pub fn parse_with_statusmsg(raw: &str, statusmsg: &str) -> Result<Self, ParseError> {
+        if let Some(recipient) = status_channel(raw, statusmsg) {
+            Ok(recipient)
+        } else if status_channel(raw, UNAMBIGUOUS_STATUSMSG).is_some() {
+            Err(ParseError::new("Recipient"))
+        } else {
+            raw.parse()
+        }
+    }
+}
+
+fn status_channel(raw: &str, statusmsg: &str) -> Option<Recipient> {
+    let prefix = raw.chars().next().filter(|c| statusmsg.contains(*c))?;
+    let channel = raw[prefix.len_utf8()..].parse().ok()?;
+    Some(Recipient::StatusChannel(prefix, channel))
+}
+
 impl FromStr for Recipient {
     type Err = ParseError;
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        if let Some(recipient) = status_channel(raw, UNAMBIGUOUS_STATUSMSG) {
+            return Ok(recipient);
+        }
+
         if raw.starts_with('#') && raw.contains(&['*', '?'][..]) {
             if let Ok(target_mask) = raw.parse() {
                 return Ok(Recipient::TargetMask(target_mask));
@@ -142,6 +186,11 @@ impl From<Recipient> for String {
     fn from(msg_to: Recipient) -> String {
         match msg_to {
             Recipient::Channel(channel) => String::from(channel),
+            Recipient::StatusChannel(prefix, channel) => {
+                let mut result = prefix.to_string();
+                result.push_str(&String::from(channel));
+                result
+            }
             Recipient::Nickname(nickname) => String::from(nickname),
             Recipient::NicknameUserHost(nickname, user, host) => [
                 &String::from(nickname),
@@ -211,6 +260,49 @@ mod test_recipient {
         assert!("".parse::<Recipient>().is_err());
         assert!("user%host%host".parse::<Recipient>().is_err());
         assert!("🥔️".parse::<Recipient>().is_err());
+        assert!("@".parse::<Recipient>().is_err());
+        assert!("@nickname".parse::<Recipient>().is_err());
+    }
+
+    #[test]
+    fn status_channel() {
+        assert_eq!(
+            Ok(Recipient::StatusChannel('@', "#channel".parse().unwrap())),
+            "@#channel".parse::<Recipient>()
+        );
+        assert_eq!(
+            Ok(Recipient::StatusChannel('%', "&local".parse().unwrap())),
+            "%&local".parse::<Recipient>()
+        );
+
+        // "+" is ambiguous, so it's only a status prefix if the server says so.
+        assert_eq!(
+            Ok(Recipient::Channel("+#channel".parse().unwrap())),
+            "+#channel".parse::<Recipient>()
+        );
+        assert_eq!(
+            Ok(Recipient::StatusChannel('+', "#channel".parse().unwrap())),
+            Recipient::parse_with_statusmsg("+#channel", "@+")
+        );
+        assert_eq!(
+            Ok(Recipient::Channel("+channel".parse().unwrap())),
+            Recipient::parse_with_statusmsg("+channel", "@+")
+        );
+        assert_eq!(
+            Ok(Recipient::Nickname("nickname".parse().unwrap())),
+            Recipient::parse_with_statusmsg("nickname", "@+")
+        );
+        assert_eq!(
+            Ok(Recipient::StatusChannel('@', "#channel".parse().unwrap())),
+            Recipient::parse_with_statusmsg("@#channel", "@+")
+        );
+        assert!(Recipient::parse_with_statusmsg("%#channel", "@+").is_err());
+        assert!(Recipient::parse_with_statusmsg("@#channel", "").is_err());
+
+        assert_eq!(
+            "@#channel".to_string(),
+            String::from(Recipient::StatusChannel('@', "#channel".parse().unwrap()))
+        );
     }
 
     #[test]
@@ -265,6 +357,22 @@ mod test_recipient {
             )),
             "user%host".parse::<Recipient>()
         );
+        assert_eq!(
+            Ok(Recipient::NicknameUserHost(
+                "mynick".parse().unwrap(),
+                "user".parse().unwrap(),
+                "user/mynick".parse().unwrap()
+            )),
+            "mynick!user@user/mynick".parse::<Recipient>()
+        );
+        assert_eq!(
+            Ok(Recipient::NicknameUserHost(
+                "mynick".parse().unwrap(),
+                "user".parse().unwrap(),
+                "2001:db8::1".parse().unwrap()
+            )),
+            "mynick!user@2001:db8::1".parse::<Recipient>()
+        );
     }
 
     #[test]
@@ -340,7 +448,24 @@ mod test_recipient {
     }
 }
 
-#[derive(PartialEq, Debug)]
+/// The source of a message, from the prefix. According to RFC 2812:
+///
+/// ```text
+/// prefix     =  servername / ( nickname [ [ "!" user ] "@" host ] )
+/// ```
+///
+/// A bare name could be either a server or a nickname, so the following
+/// precedence is used by the parser:
+///
+/// - "irc.example.com" => server or nickname? server, since nicknames can't
+///   contain "."
+/// - "localhost" => server or nickname? nickname; a server name without a
+///   "." is indistinguishable from a nickname
+/// - "1.2.3.4" => server, by the first rule; servers never use IPv6 literals
+///   as names
+/// - "nick!user@2001:db8::1", "nick@user/spudly" => user; see `Host` for how
+///   the host is parsed
+#[derive(Clone, PartialEq, Debug)]
 pub enum Sender {
     User {
         nickname: Nickname,
@@ -468,6 +593,45 @@ mod test_sender {
         );
     }
 
+    #[test]
+    fn hosts() {
+        for raw in &[
+            "nickname!user@2001:db8::1",
+            "nickname!user@0::1",
+            "nickname!~user@user/spudly/bot",
+            "nickname!uid123@gateway/web/irccloud.com/x-abcdef",
+            "nickname!user@ABC123:DEF456:IP",
+            "nickname@user/spudly",
+        ] {
+            let sender = raw.parse::<Sender>();
+            assert!(
+                matches!(sender, Ok(Sender::User { host: Some(_), .. })),
+                "{} should be a user with a host",
+                raw
+            );
+            assert_eq!(raw.to_string(), String::from(sender.unwrap()));
+        }
+    }
+
+    #[test]
+    fn ambiguity() {
+        assert_eq!(
+            Ok(Sender::Server("irc.example.com".parse().unwrap())),
+            "irc.example.com".parse::<Sender>()
+        );
+        assert_eq!(
+            Ok(Sender::Server("1.2.3.4".parse().unwrap())),
+            "1.2.3.4".parse::<Sender>()
+        );
+        assert_eq!(
+            Ok(Sender::from("localhost".parse::<Nickname>().unwrap())),
+            "localhost".parse::<Sender>()
+        );
+        assert!("2001:db8::1".parse::<Sender>().is_err());
+        assert!("irc.example.com!user@host".parse::<Sender>().is_err());
+        assert!("nickname!user@host@host".parse::<Sender>().is_err());
+    }
+
     #[test]
     fn from_types() {
         assert_eq!(
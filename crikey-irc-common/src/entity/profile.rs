@@ -0,0 +1,185 @@
+use super::Channel;
+
+/// The rules that nicknames and channel names must follow. The `FromStr`
+/// implementations of `Nickname` and `Channel` accept anything a server might
+/// reasonably send, so that we never drop a message because a network is
+/// more permissive than the RFC. Before sending a name of our own choosing,
+/// check it against a stricter profile with `Nickname::parse_with_profile()`
+/// or `Channel::parse_with_profile()`.
+///
+/// - `rfc2812()`: ASCII nicknames of up to 9 characters, and channels of up to
+///   50 characters starting with "#", "&", "+" or "!".
+/// - `rfc1459()`: as above, but nicknames must start with a letter and may not
+///   contain "_" or "|", and channels of up to 200 characters start with "#"
+///   or "&".
+/// - `permissive()`: Unicode letters and digits in nicknames, and no length
+///   limits. This is what `FromStr` accepts.
+/// - `advertised()`: the limits the server advertises in the NICKLEN,
+///   CHANTYPES and CHANNELLEN ISUPPORT tokens. See `ISupport::profile()`.
+///
+/// Lengths are measured in bytes, as servers measure them.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ValidationProfile {
+    pub(super) nicknames: NicknameRules,
+    pub(super) nicklen: Option<usize>,
+    pub(super) chantypes: String,
+    pub(super) channellen: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(super) enum NicknameRules {
+    Rfc1459,
+    Rfc2812,
+    Unicode,
+}
+
+impl ValidationProfile {
+    pub fn rfc2812() -> Self {
+        ValidationProfile {
+            nicknames: NicknameRules::Rfc2812,
+            nicklen: Some(9),
+            chantypes: "#&+!".to_string(),
+            channellen: Some(50),
+        }
+    }
+
+    pub fn rfc1459() -> Self {
+        ValidationProfile {
+            nicknames: NicknameRules::Rfc1459,
+            nicklen: Some(9),
+            chantypes: "#&".to_string(),
+            channellen: Some(200),
+        }
+    }
+
+    pub fn permissive() -> Self {
+        ValidationProfile {
+            nicknames: NicknameRules::Unicode,
+            nicklen: None,
+            chantypes: "#&+!".to_string(),
+            channellen: None,
+        }
+    }
+
+    /// The limits advertised by a server. Any that weren't advertised are
+    /// unlimited.
+    pub fn advertised(nicklen: Option<usize>, chantypes: &str, channellen: Option<usize>) -> Self {
+        ValidationProfile {
+            nicknames: NicknameRules::Unicode,
+            nicklen,
+            chantypes: chantypes.to_string(),
+            channellen,
+        }
+    }
+
+    pub fn is_valid_nickname(&self, raw: &str) -> bool {
+        is_valid_nickname(self.nicknames, raw)
+            && self.nicklen.is_none_or(|nicklen| raw.len() <= nicklen)
+    }
+
+    pub fn is_valid_channel(&self, raw: &str) -> bool {
+        raw.starts_with(|c| self.chantypes.contains(c))
+            && raw.parse::<Channel>().is_ok()
+            && self
+                .channellen
+                .is_none_or(|channellen| raw.len() <= channellen)
+    }
+}
+
+impl Default for ValidationProfile {
+    fn default() -> Self {
+        Self::permissive()
+    }
+}
+
+/// According to RFC 2812:
+///
+/// ```text
+/// nickname   =  ( letter / special ) *8( letter / digit / special / "-" )
+/// special    =  %x5B-60 / %x7B-7D
+///                  ; "[", "]", "\", "`", "_", "^", "{", "|", "}"
+/// ```
+///
+/// RFC 1459 is stricter:
+///
+/// ```text
+/// <nick>       ::= <letter> { <letter> | <number> | <special> }
+/// <special>    ::= '-' | '[' | ']' | '\' | '`' | '^' | '{' | '}'
+/// ```
+///
+/// Servers that allow UTF-8 nicknames accept any letter or digit where the
+/// RFC accepts an ASCII one.
+pub(super) fn is_valid_nickname(rules: NicknameRules, raw: &str) -> bool {
+    let mut chars = raw.chars();
+    let first = match chars.next() {
+        Some(first) => first,
+        None => return false,
+    };
+
+    match rules {
+        NicknameRules::Rfc1459 => {
+            first.is_ascii_alphabetic()
+                && chars.all(|c| c.is_ascii_alphanumeric() || "-[]\\`^{}".contains(c))
+        }
+        NicknameRules::Rfc2812 => {
+            (first.is_ascii_alphabetic() || is_special(first))
+                && chars.all(|c| c.is_ascii_alphanumeric() || is_special(c) || c == '-')
+        }
+        NicknameRules::Unicode => {
+            (first.is_alphabetic() || is_special(first))
+                && chars.all(|c| c.is_alphanumeric() || is_special(c) || c == '-')
+        }
+    }
+}
+
+fn is_special(c: char) -> bool {
+    matches!(c, '\x5b'..='\x60' | '\x7b'..='\x7d')
+}
+
+#[cfg(test)]
+mod test_validation_profile {
+    use super::*;
+
+    #[test]
+    fn nicknames() {
+        let rfc2812 = ValidationProfile::rfc2812();
+        assert!(rfc2812.is_valid_nickname("[spud]"));
+        assert!(rfc2812.is_valid_nickname("spud_ly|9"));
+        assert!(!rfc2812.is_valid_nickname("spudly1234"));
+        assert!(!rfc2812.is_valid_nickname("Ærøskøbing"));
+
+        let rfc1459 = ValidationProfile::rfc1459();
+        assert!(rfc1459.is_valid_nickname("spud-[ly]"));
+        assert!(!rfc1459.is_valid_nickname("[spud]"));
+        assert!(!rfc1459.is_valid_nickname("spud_ly"));
+
+        let permissive = ValidationProfile::permissive();
+        assert!(permissive.is_valid_nickname("Ærøskøbing"));
+        assert!(permissive.is_valid_nickname("spudly_is_a_very_long_nickname"));
+        assert!(!permissive.is_valid_nickname("potat🥔️"));
+        assert!(!permissive.is_valid_nickname("2hot4u"));
+        assert!(!permissive.is_valid_nickname(""));
+
+        let advertised = ValidationProfile::advertised(Some(5), "#", None);
+        assert!(advertised.is_valid_nickname("Æsop"));
+        assert!(!advertised.is_valid_nickname("Ærøskøbing"));
+    }
+
+    #[test]
+    fn channels() {
+        let rfc2812 = ValidationProfile::rfc2812();
+        assert!(rfc2812.is_valid_channel("+channel"));
+        assert!(!rfc2812.is_valid_channel(&format!("#{}", "x".repeat(50))));
+
+        let rfc1459 = ValidationProfile::rfc1459();
+        assert!(!rfc1459.is_valid_channel("+channel"));
+        assert!(rfc1459.is_valid_channel(&format!("#{}", "x".repeat(199))));
+
+        let advertised = ValidationProfile::advertised(None, "#", Some(64));
+        assert!(advertised.is_valid_channel(&format!("#{}", "x".repeat(63))));
+        assert!(!advertised.is_valid_channel(&format!("#{}", "x".repeat(64))));
+        assert!(!advertised.is_valid_channel("&local"));
+        assert!(!advertised.is_valid_channel(""));
+        assert!(!advertised.is_valid_channel("#spaced out"));
+    }
+}
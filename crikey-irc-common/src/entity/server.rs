@@ -3,11 +3,36 @@ use std::net::IpAddr;
 use std::result::Result;
 use std::str::FromStr;
 
-/// A hostname or IP address.
-#[derive(PartialEq, Debug)]
+/// A hostname, IP address, or cloak. According to RFC 2812:
+///
+/// ```text
+/// host       =  hostname / hostaddr
+/// hostaddr   =  ip4addr / ip6addr
+/// ip4addr    =  1*3digit "." 1*3digit "." 1*3digit "." 1*3digit
+/// ip6addr    =  1*hexdigit 7( ":" 1*hexdigit )
+/// ip6addr    =/ "0:0:0:0:0:" ( "0" / "FFFF" ) ":" ip4addr
+/// ```
+///
+/// In practice, servers send compressed IPv6 addresses such as "2001:db8::1",
+/// and replace the real host with a cloak such as "user/spudly" or
+/// "gateway/web/irccloud.com/x-abcdef" on request. The parser tries each in
+/// turn:
+///
+/// - "1.2.3.4", "2001:db8::1" => IP address
+/// - "0::1" => IP address "::1"; servers add the leading "0" because a
+///   parameter starting with ":" would be mistaken for a trailing parameter
+/// - "irc.example.com" => hostname
+/// - "user/spudly", "ABC123:DEF456:IP" => cloak, since it's not an address or
+///   hostname but contains "/" or ":"
+/// - "abc..ghi" => neither, reject
+///
+/// IP addresses are stored parsed, so they're written out in their canonical
+/// form, with a leading "0" added if they would otherwise start with ":".
+#[derive(Clone, PartialEq, Debug)]
 pub enum Host {
     Hostaddr(IpAddr),
     Hostname(Hostname),
+    Cloak(Cloak),
 }
 
 impl FromStr for Host {
@@ -18,6 +43,8 @@ impl FromStr for Host {
             Ok(Host::Hostaddr(ipaddr))
         } else if let Ok(hostname) = raw.parse() {
             Ok(Host::Hostname(hostname))
+        } else if let Ok(cloak) = raw.parse() {
+            Ok(Host::Cloak(cloak))
         } else {
             Err(ParseError::new("Host"))
         }
@@ -36,11 +63,25 @@ impl From<Hostname> for Host {
     }
 }
 
+impl From<Cloak> for Host {
+    fn from(cloak: Cloak) -> Host {
+        Host::Cloak(cloak)
+    }
+}
+
 impl From<Host> for String {
     fn from(host: Host) -> String {
         match host {
-            Host::Hostaddr(ip_addr) => ip_addr.to_string(),
+            Host::Hostaddr(ip_addr) => {
+                let result = ip_addr.to_string();
+                if result.starts_with(':') {
+                    format!("0{}", result)
+                } else {
+                    result
+                }
+            }
             Host::Hostname(hostname) => String::from(hostname),
+            Host::Cloak(cloak) => String::from(cloak),
         }
     }
 }
@@ -60,6 +101,9 @@ mod test_host {
         assert!("abc..ghi".parse::<Host>().is_err());
         assert!(".a".parse::<Host>().is_err());
         assert!("abc.def.ghi.".parse::<Host>().is_err());
+        assert!(":1".parse::<Host>().is_err());
+        assert!("user/spud ly".parse::<Host>().is_err());
+        assert!("user@spudly/cloak".parse::<Host>().is_err());
     }
 
     #[test]
@@ -80,6 +124,54 @@ mod test_host {
             Ok(Host::Hostaddr("::1".parse().unwrap())),
             "::1".parse::<Host>()
         );
+        assert_eq!(
+            Ok(Host::Hostaddr("::1".parse().unwrap())),
+            "0::1".parse::<Host>()
+        );
+        assert_eq!(
+            Ok(Host::Hostaddr("2001:db8::ff00:42:8329".parse().unwrap())),
+            "2001:db8::ff00:42:8329".parse::<Host>()
+        );
+        assert_eq!(
+            Ok(Host::Cloak(Cloak("user/spudly/bot".to_string()))),
+            "user/spudly/bot".parse::<Host>()
+        );
+        assert_eq!(
+            Ok(Host::Cloak(Cloak(
+                "gateway/web/irccloud.com/x-abcdef".to_string()
+            ))),
+            "gateway/web/irccloud.com/x-abcdef".parse::<Host>()
+        );
+        assert_eq!(
+            Ok(Host::Cloak(Cloak("ABC123:DEF456:IP".to_string()))),
+            "ABC123:DEF456:IP".parse::<Host>()
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        for raw in &[
+            "irc.example.com",
+            "1.2.3.4",
+            "2001:db8::1",
+            "0::1",
+            "0::ffff:1.2.3.4",
+            "user/spudly",
+            "gateway/web/irccloud.com/x-abcdef",
+            "ABC123:DEF456:IP",
+        ] {
+            assert_eq!(raw.to_string(), String::from(raw.parse::<Host>().unwrap()));
+        }
+
+        // Non-canonical addresses are normalized.
+        assert_eq!(
+            "0::1".to_string(),
+            String::from("::1".parse::<Host>().unwrap())
+        );
+        assert_eq!(
+            "2001:db8::1".to_string(),
+            String::from("2001:0db8:0:0::1".parse::<Host>().unwrap())
+        );
     }
 
     #[test]
@@ -104,10 +196,43 @@ mod test_host {
             "abc.def.ghi".to_string(),
             String::from(Host::Hostname(Hostname("abc.def.ghi".to_string())))
         );
+        assert_eq!(
+            "user/spudly".to_string(),
+            String::from(Host::Cloak(Cloak("user/spudly".to_string())))
+        );
+    }
+}
+
+/// A host that has been replaced by the server to hide the user's real
+/// address, such as "user/spudly". There is no standard format, but cloaks
+/// that aren't valid hostnames always contain "/" or ":". They may not start
+/// with ":", or contain spaces, "!" or "@", since these would break the
+/// message prefix.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cloak(String);
+
+impl FromStr for Cloak {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        if raw.starts_with(':')
+            || !raw.contains(&['/', ':'][..])
+            || raw.contains(&['\0', '\r', '\n', ' ', '!', '@'][..])
+        {
+            Err(ParseError::new("Cloak"))
+        } else {
+            Ok(Self(raw.to_string()))
+        }
+    }
+}
+
+impl From<Cloak> for String {
+    fn from(cloak: Cloak) -> String {
+        cloak.0
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Servername(String);
 
 impl FromStr for Servername {
@@ -115,7 +240,7 @@ impl FromStr for Servername {
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
         name_from_string(raw)
-            .map(|s| Self(s))
+            .map(Self)
             .ok_or(ParseError::new("Servername"))
     }
 }
@@ -126,7 +251,7 @@ impl From<Servername> for String {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Hostname(String);
 
 impl FromStr for Hostname {
@@ -134,7 +259,7 @@ impl FromStr for Hostname {
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
         name_from_string(raw)
-            .map(|s| Self(s))
+            .map(Self)
             .ok_or(ParseError::new("Hostname"))
     }
 }
@@ -147,7 +272,7 @@ impl From<Hostname> for String {
 
 fn name_from_string(raw: &str) -> Option<String> {
     for raw_part in raw.split('.') {
-        if raw_part.len() < 1
+        if raw_part.is_empty()
             || !raw_part.starts_with(|c: char| c.is_ascii_alphanumeric())
             || !raw_part.ends_with(|c: char| c.is_ascii_alphanumeric())
             || raw_part.contains(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_')
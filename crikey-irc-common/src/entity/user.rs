@@ -1,4 +1,5 @@
-use super::ParseError;
+use super::profile::{is_valid_nickname, NicknameRules};
+use super::{ParseError, ValidationProfile};
 use std::result::Result;
 use std::str::FromStr;
 
@@ -15,7 +16,7 @@ impl FromStr for Username {
     type Err = ParseError;
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
-        if raw.len() < 1 || raw.contains(&['\0', '\r', '\n', ' ', '@'][..]) {
+        if raw.is_empty() || raw.contains(&['\0', '\r', '\n', ' ', '@'][..]) {
             Err(ParseError::new("Username"))
         } else {
             Ok(Username(raw.to_string()))
@@ -70,28 +71,29 @@ mod test_username {
 ///
 /// Note that this notation limits nicknames to 9 characters, but the RFC
 /// elsewhere recommends supporting longer nicknames for forwards compatibility.
-/// We currently enforce no upper bound.
+/// Many servers also accept non-ASCII letters and digits. Since we need to
+/// understand whatever the server sends us, we accept both; use
+/// `parse_with_profile()` to apply stricter rules. See `ValidationProfile`.
 #[derive(Clone, PartialEq, Debug)]
 pub struct Nickname(String);
 
+impl Nickname {
+    /// Parse a nickname, rejecting anything that `profile` doesn't allow.
+    pub fn parse_with_profile(raw: &str, profile: &ValidationProfile) -> Result<Self, ParseError> {
+        if profile.is_valid_nickname(raw) {
+            Ok(Self(raw.to_string()))
+        } else {
+            Err(ParseError::new("Nickname"))
+        }
+    }
+}
+
 impl FromStr for Nickname {
     type Err = ParseError;
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
-        if raw.len() < 1 || !raw.is_ascii() {
-            Err(ParseError::new("Nickname"))
-        } else if let '\x41'..='\x7d' = raw.chars().nth(0).unwrap() {
-            if raw[1..].contains(|c: char| {
-                if let '\x2d' | '\x30'..='\x39' | '\x41'..='\x7d' = c {
-                    false
-                } else {
-                    true
-                }
-            }) {
-                Err(ParseError::new("Nickname"))
-            } else {
-                Ok(Self(raw.to_string()))
-            }
+        if is_valid_nickname(NicknameRules::Unicode, raw) {
+            Ok(Self(raw.to_string()))
         } else {
             Err(ParseError::new("Nickname"))
         }
@@ -104,9 +106,15 @@ impl From<Nickname> for String {
     }
 }
 
+impl AsRef<str> for Nickname {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
 #[cfg(test)]
 mod test_nickname {
-    use super::Nickname;
+    use super::{Nickname, ValidationProfile};
 
     #[test]
     fn invalid() {
@@ -166,6 +174,28 @@ mod test_nickname {
             Ok(Nickname("abcdefghijklmnopqrstuvwxyz".to_string())),
             "abcdefghijklmnopqrstuvwxyz".parse::<Nickname>()
         );
+        assert_eq!(
+            Ok(Nickname("Ærøskøbing".to_string())),
+            "Ærøskøbing".parse::<Nickname>()
+        );
+    }
+
+    #[test]
+    fn profile() {
+        assert_eq!(
+            Ok(Nickname("spudly".to_string())),
+            Nickname::parse_with_profile("spudly", &ValidationProfile::rfc2812())
+        );
+        assert!(Nickname::parse_with_profile("Ærøskøbing", &ValidationProfile::rfc2812()).is_err());
+        assert!(Nickname::parse_with_profile("spudlyspud", &ValidationProfile::rfc2812()).is_err());
+        assert!(Nickname::parse_with_profile("spud_ly", &ValidationProfile::rfc1459()).is_err());
+        assert_eq!(
+            Ok(Nickname("spudlyspud".to_string())),
+            Nickname::parse_with_profile(
+                "spudlyspud",
+                &ValidationProfile::advertised(Some(30), "#", None)
+            )
+        );
     }
 
     #[test]
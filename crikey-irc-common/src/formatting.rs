@@ -0,0 +1,84 @@
+/// Remove the control codes that clients use to format text: bold, italics,
+/// underline, strikethrough, monospace, reverse, reset, and colours along with
+/// their numbers. See <https://modern.ircdocs.horse/formatting.html>.
+///
+/// ```
+/// # use crikey_irc_common::strip_formatting;
+/// assert_eq!("Hello there", strip_formatting("\x02Hello\x02 \x0304,12there\x0F"));
+/// ```
+pub fn strip_formatting(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x02' | '\x0F' | '\x11' | '\x16' | '\x1D' | '\x1E' | '\x1F' => (),
+            // A colour is up to two digits for the foreground, optionally
+            // followed by a comma and up to two more for the background.
+            '\x03' => skip_color(&mut chars, 2, |c| c.is_ascii_digit()),
+            // The same, but with six hex digits for each.
+            '\x04' => skip_color(&mut chars, 6, |c| c.is_ascii_hexdigit()),
+            c => stripped.push(c),
+        }
+    }
+
+    stripped
+}
+
+fn skip_color<I, F>(chars: &mut std::iter::Peekable<I>, digits: usize, is_digit: F)
+where
+    I: Iterator<Item = char> + Clone,
+    F: Fn(char) -> bool,
+{
+    if skip_digits(chars, digits, &is_digit) == 0 {
+        return;
+    }
+
+    // Only take the comma if there's a background colour after it, since
+    // "\x034,hello" is a red comma.
+    let mut lookahead = chars.clone();
+    if lookahead.next() == Some(',') && lookahead.peek().is_some_and(|&c| is_digit(c)) {
+        chars.next();
+        skip_digits(chars, digits, &is_digit);
+    }
+}
+
+fn skip_digits<I, F>(chars: &mut std::iter::Peekable<I>, digits: usize, is_digit: &F) -> usize
+where
+    I: Iterator<Item = char>,
+    F: Fn(char) -> bool,
+{
+    let mut skipped = 0;
+    while skipped < digits && chars.peek().is_some_and(|&c| is_digit(c)) {
+        chars.next();
+        skipped += 1;
+    }
+    skipped
+}
+
+#[cfg(test)]
+mod test_formatting {
+    use super::*;
+
+    #[test]
+    fn strips_formatting() {
+        assert_eq!("plain text", strip_formatting("plain text"));
+        assert_eq!(
+            "bold italic underline strike mono reverse reset",
+            strip_formatting(
+                "\x02bold\x02 \x1Ditalic\x1D \x1Funderline\x1F \x1Estrike\x1E \x11mono\x11 \x16reverse\x16 \x0Freset"
+            )
+        );
+    }
+
+    #[test]
+    fn strips_colors() {
+        assert_eq!("red", strip_formatting("\x034red"));
+        assert_eq!("red on blue", strip_formatting("\x0304,12red on blue"));
+        assert_eq!("123", strip_formatting("\x0304123"));
+        assert_eq!(",comma", strip_formatting("\x034,comma"));
+        assert_eq!("reset", strip_formatting("\x03reset"));
+        assert_eq!("hex", strip_formatting("\x04FF0000,00ff00hex"));
+        assert_eq!("trailing", strip_formatting("trailing\x03"));
+    }
+}
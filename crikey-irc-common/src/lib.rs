@@ -0,0 +1,543 @@
+//! Parsing and serialization of the IRC protocol, shared by the crikey-irc
+//! client and server.
+//!
+//! Every message, command and entity implements `FromStr`, and converts back
+//! into a `String` ready to be sent:
+//!
+//! ```
+//! use crikey_irc_common::{Command, Message, MessageBody};
+//!
+//! let message: Message = ":Wiz!jto@tolsun.oulu.fi PRIVMSG #channel :Hello there".parse()?;
+//! match &message.body {
+//!     MessageBody::Command(Command::Privmsg { recipients, message }) => {
+//!         assert_eq!("#channel", String::from(recipients[0].clone()));
+//!         assert_eq!("Hello there", message);
+//!     }
+//!     body => panic!("Unexpected message body {:?}", body),
+//! }
+//! assert_eq!(
+//!     ":Wiz!jto@tolsun.oulu.fi PRIVMSG #channel :Hello there",
+//!     String::from(message),
+//! );
+//! # Ok::<(), crikey_irc_common::ParseError>(())
+//! ```
+//!
+//! A `Connection` sends and receives messages over any reader and writer,
+//! usually both halves of a `TcpStream`:
+//!
+//! ```
+//! use crikey_irc_common::{Command, Connection};
+//! use std::io::{self, BufRead};
+//!
+//! let (reader, writer) = io::pipe()?;
+//! let mut connection = Connection::new(Box::new(io::empty()), Box::new(writer));
+//! connection.send_command(Command::join().key("#bar", "fubar").channel("#foo").build().unwrap())?;
+//!
+//! let mut line = String::new();
+//! io::BufReader::new(reader).read_line(&mut line)?;
+//! assert_eq!("JOIN #bar,#foo fubar\r\n", line);
+//! # Ok::<(), io::Error>(())
+//! ```
+pub use self::clock::{Clock, SystemClock, VirtualClock};
+pub use self::decoding::{Charset, Decoding};
+pub use self::entity::{
+    Channel, ChannelKey, Cloak, Host, Nickname, Recipient, Sender, Servername, Username,
+    ValidationProfile,
+};
+pub use self::formatting::strip_formatting;
+pub use self::logging::{redact, TARGET_PARSE, TARGET_STATE, TARGET_WIRE};
+pub use self::message::{
+    Command, JoinBuilder, Message, MessageBody, MessageParams, MessageTags, Reply, ReplyType,
+    MAX_PARAMS,
+};
+pub use self::recording::{Direction, Entry, Recorder, Recording, Replay};
+pub use self::syntax::{
+    CapSubcommand, Casemapping, ExtbanMatcher, Extbans, HistorySelector, KeywordList, MaskSubject,
+    ServerMask, StatsQuery, Target, TargetMask, UserMask, WatchEntry, Wildcard,
+};
+use log::{debug, warn};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::net;
+
+mod clock;
+mod decoding;
+mod entity;
+mod formatting;
+mod logging;
+mod message;
+mod recording;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(test)]
+mod strategies;
+mod syntax;
+#[cfg(feature = "tls")]
+mod tls;
+
+/// The longest line a server may send, including the CRLF but not including
+/// any tags. RFC 2812 section 2.3 says:
+///
+/// ```text
+/// IRC messages are always lines of characters terminated with a CR-LF
+/// (Carriage Return - Line Feed) pair, and these messages SHALL NOT
+/// exceed 512 characters in length, counting all characters including
+/// the trailing CR-LF.
+/// ```
+pub const MAX_LINE_LEN: usize = 512;
+
+/// The longest the tags on a line may be, including the leading "@" and the
+/// trailing space, according to the IRCv3 message-tags specification.
+pub const MAX_TAGS_LEN: usize = 8191;
+
+pub struct Connection {
+    reader: Box<dyn io::BufRead>,
+    writer: Box<dyn io::Write>,
+    buffer: Vec<u8>,
+    overlong: bool,
+    decoding: Decoding,
+    utf8_only: bool,
+    error: Option<io::Error>,
+    nickserv: String,
+}
+
+impl Connection {
+    pub fn connect(stream: net::TcpStream) -> io::Result<Self> {
+        let (reader, writer) = halves(stream)?;
+        Ok(Self::new(reader, writer))
+    }
+
+    /// Like `connect()`, but saving everything sent and received with a
+    /// `Recorder`.
+    pub fn connect_recorded(stream: net::TcpStream, recorder: &Recorder) -> io::Result<Self> {
+        let (reader, writer) = halves(stream)?;
+        let (reader, writer) = recorder.wrap(reader, writer);
+        Ok(Self::new(reader, writer))
+    }
+
+    /// Like `connect()`, but over TLS, checking that the server's certificate
+    /// is valid for `domain`. Fails if the handshake does.
+    #[cfg(feature = "tls")]
+    pub fn connect_tls(stream: net::TcpStream, domain: &str) -> io::Result<Self> {
+        let (reader, writer) = tls::halves(stream, domain)?;
+        Ok(Self::new(reader, writer))
+    }
+
+    /// Like `connect_tls()`, but saving everything sent and received with a
+    /// `Recorder`. The lines are recorded as plain text.
+    #[cfg(feature = "tls")]
+    pub fn connect_tls_recorded(
+        stream: net::TcpStream,
+        domain: &str,
+        recorder: &Recorder,
+    ) -> io::Result<Self> {
+        let (reader, writer) = tls::halves(stream, domain)?;
+        let (reader, writer) = recorder.wrap(reader, writer);
+        Ok(Self::new(reader, writer))
+    }
+
+    pub fn new(reader: Box<dyn io::BufRead>, writer: Box<dyn io::Write>) -> Self {
+        Connection {
+            reader,
+            writer,
+            buffer: Vec::new(),
+            overlong: false,
+            decoding: Decoding::default(),
+            utf8_only: false,
+            error: None,
+            nickserv: "NickServ".to_string(),
+        }
+    }
+
+    /// How to decode lines that aren't valid UTF-8.
+    pub fn set_decoding(&mut self, decoding: Decoding) {
+        self.decoding = decoding;
+    }
+
+    /// Whether the server has advertised UTF8ONLY, in which case nothing it
+    /// sends is decoded with a legacy charset.
+    pub fn set_utf8_only(&mut self, utf8_only: bool) {
+        self.utf8_only = utf8_only;
+    }
+
+    /// Who we identify to, so that the passwords we send them can be left out
+    /// of the log. Defaults to NickServ.
+    pub fn set_nickserv(&mut self, nickserv: &str) {
+        self.nickserv = nickserv.to_string();
+    }
+
+    /// Why the connection was closed, if it has been: the error reading from
+    /// it, or `UnexpectedEof` if the server hung up. Once it's closed,
+    /// `poll()` always returns `None`.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn poll(&mut self) -> Option<Message> {
+        let line = self.read_line()?;
+
+        let raw = match self.decoding.decode(&line, self.utf8_only) {
+            Some(raw) => raw,
+            None => {
+                warn!(
+                    target: TARGET_PARSE,
+                    "Discarded invalid UTF-8: {:?}",
+                    String::from_utf8_lossy(&line)
+                );
+                return None;
+            }
+        };
+
+        let line = raw.trim_end_matches(['\r', '\n']);
+        debug!(target: TARGET_WIRE, "<< {}", redact(line, &self.nickserv));
+        match raw.parse::<Message>() {
+            Ok(message) => Some(message),
+            Err(e) => {
+                warn!(target: TARGET_PARSE, "Discarded {:?}: {}", line, e);
+                None
+            }
+        }
+    }
+
+    /// Read the next complete line, if there is one. A partial line is kept
+    /// until the rest arrives, but never beyond the maximum length, so a
+    /// server that never sends a line break can't exhaust our memory. Lines
+    /// that are too long are discarded.
+    fn read_line(&mut self) -> Option<Vec<u8>> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        loop {
+            let available = match self.reader.fill_buf() {
+                Ok([]) => {
+                    warn!(target: TARGET_WIRE, "The server closed the connection");
+                    self.error = Some(io::ErrorKind::UnexpectedEof.into());
+                    return None;
+                }
+                Ok(available) => available,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return None,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    warn!(target: TARGET_WIRE, "Closing the connection: {}", e);
+                    self.error = Some(e);
+                    return None;
+                }
+            };
+
+            let (chunk, complete) = match available.iter().position(|&byte| byte == b'\n') {
+                Some(index) => (&available[..=index], true),
+                None => (available, false),
+            };
+            let consumed = chunk.len();
+
+            if !self.overlong {
+                self.buffer.extend_from_slice(chunk);
+                self.overlong = self.buffer.len() > MAX_TAGS_LEN + MAX_LINE_LEN;
+            }
+            self.reader.consume(consumed);
+
+            if complete {
+                let line = std::mem::take(&mut self.buffer);
+                let overlong = std::mem::replace(&mut self.overlong, false);
+
+                if overlong || !is_within_limits(&line) {
+                    warn!(
+                        target: TARGET_PARSE,
+                        "Discarded a line of {} bytes, which is too long",
+                        line.len()
+                    );
+                    return None;
+                }
+                return Some(line);
+            } else if self.overlong {
+                self.buffer.clear();
+            }
+        }
+    }
+
+    pub fn send_command(&mut self, command: Command) -> std::io::Result<()> {
+        let raw_command = String::from(command);
+        self.send_command_raw(raw_command)
+    }
+
+    /// Send a command along with tags, such as `label`.
+    pub fn send_message(&mut self, message: Message) -> std::io::Result<()> {
+        let raw_message = String::from(message);
+        self.send_command_raw(raw_message)
+    }
+
+    pub fn send_command_raw(&mut self, mut raw_command: String) -> std::io::Result<()> {
+        debug!(target: TARGET_WIRE, ">> {}", redact(&raw_command, &self.nickserv));
+        raw_command.push_str("\r\n");
+        self.writer.write_all(raw_command.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn halves(stream: net::TcpStream) -> io::Result<(Box<dyn io::BufRead>, Box<dyn io::Write>)> {
+    stream.set_nonblocking(true)?;
+    let reader = io::BufReader::new(stream.try_clone()?);
+    Ok((Box::new(reader), Box::new(stream)))
+}
+
+#[derive(PartialEq, Debug)]
+pub struct ParseError(&'static str);
+
+impl ParseError {
+    pub fn new(struct_name: &'static str) -> Self {
+        ParseError(struct_name)
+    }
+}
+
+/// Whether a line, including its line break, fits in `MAX_TAGS_LEN` bytes of
+/// tags and `MAX_LINE_LEN` bytes of everything else.
+fn is_within_limits(line: &[u8]) -> bool {
+    match (line.first(), line.iter().position(|&byte| byte == b' ')) {
+        (Some(b'@'), Some(index)) => index < MAX_TAGS_LEN && line.len() - index - 1 <= MAX_LINE_LEN,
+        _ => line.len() <= MAX_LINE_LEN,
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unable to parse component: {}", self.0)
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test_connection {
+    use super::*;
+    use pipe::pipe;
+    use std::io;
+    use std::thread::spawn;
+
+    #[test]
+    fn poll_disconnected() {
+        let null_read = io::empty();
+        let null_write = io::sink();
+        let buf_read = io::BufReader::new(null_read);
+        let mut connection = Connection::new(Box::new(buf_read), Box::new(null_write));
+
+        assert!(connection.error().is_none());
+        assert_eq!(None, connection.poll());
+        assert_eq!(
+            Some(io::ErrorKind::UnexpectedEof),
+            connection.error().map(io::Error::kind)
+        );
+        assert_eq!(None, connection.poll());
+    }
+
+    #[test]
+    fn poll_command() {
+        let (pipe_read, mut pipe_write) = pipe();
+        let null_write = io::sink();
+        let buf_read = io::BufReader::new(pipe_read);
+        let mut connection = Connection::new(Box::new(buf_read), Box::new(null_write));
+
+        spawn(move || write!(pipe_write, ":irc.example.com PING somebody\r\n"));
+
+        assert_eq!(
+            Some(Message {
+                tags: MessageTags::new(),
+                sender: Some("irc.example.com".parse().unwrap()),
+                body: MessageBody::Command(Command::Ping {
+                    to: Some("somebody".parse().unwrap()),
+                    from: None
+                })
+            }),
+            connection.poll()
+        );
+    }
+
+    #[test]
+    fn poll_unrecognized() {
+        let (pipe_read, mut pipe_write) = pipe();
+        let null_write = io::sink();
+        let buf_read = io::BufReader::new(pipe_read);
+        let mut connection = Connection::new(Box::new(buf_read), Box::new(null_write));
+
+        spawn(move || write!(pipe_write, "potato\r\n"));
+
+        assert_eq!(None, connection.poll());
+    }
+
+    #[test]
+    fn send_command() {
+        let (mut pipe_read, pipe_write) = pipe();
+
+        spawn(move || {
+            let buf_read = io::BufReader::new(io::empty());
+            let mut connection = Connection::new(Box::new(buf_read), Box::new(pipe_write));
+            connection
+                .send_command(Command::Pong {
+                    from: "somebody".parse().unwrap(),
+                    to: None,
+                })
+                .unwrap();
+        });
+
+        let mut buffer = String::new();
+        pipe_read.read_line(&mut buffer).unwrap();
+
+        assert_eq!("PONG somebody\r\n".to_string(), buffer);
+    }
+
+    #[test]
+    fn send_message() {
+        let (mut pipe_read, pipe_write) = pipe();
+
+        spawn(move || {
+            let buf_read = io::BufReader::new(io::empty());
+            let mut connection = Connection::new(Box::new(buf_read), Box::new(pipe_write));
+            connection
+                .send_message("@label=1 PONG somebody".parse().unwrap())
+                .unwrap();
+        });
+
+        let mut buffer = String::new();
+        pipe_read.read_line(&mut buffer).unwrap();
+
+        assert_eq!("@label=1 PONG somebody\r\n".to_string(), buffer);
+    }
+
+    #[test]
+    fn send_command_raw() {
+        let (mut pipe_read, pipe_write) = pipe();
+
+        spawn(move || {
+            let buf_read = io::BufReader::new(io::empty());
+            let mut connection = Connection::new(Box::new(buf_read), Box::new(pipe_write));
+            connection
+                .send_command_raw("hello dolly".to_string())
+                .unwrap();
+        });
+
+        let mut buffer = String::new();
+        pipe_read.read_line(&mut buffer).unwrap();
+
+        assert_eq!("hello dolly\r\n".to_string(), buffer);
+    }
+
+    /// A reader that hands out one chunk of bytes at a time, as they might
+    /// arrive from the network, and then reports that it would block.
+    struct Chunks(Vec<Vec<u8>>);
+
+    impl io::Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.fill_buf()?.len().min(buf.len());
+            buf[..len].copy_from_slice(&self.0[0][..len]);
+            self.consume(len);
+            Ok(len)
+        }
+    }
+
+    impl io::BufRead for Chunks {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            match self.0.first() {
+                Some(chunk) => Ok(chunk),
+                None => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.0[0].drain(..amt);
+            if self.0[0].is_empty() {
+                self.0.remove(0);
+            }
+        }
+    }
+
+    fn chunked(chunks: Vec<&[u8]>) -> Connection {
+        let chunks = Chunks(chunks.into_iter().map(|chunk| chunk.to_vec()).collect());
+        Connection::new(Box::new(chunks), Box::new(io::sink()))
+    }
+
+    fn privmsg(raw: &str) -> Option<Message> {
+        Some(raw.parse().unwrap())
+    }
+
+    #[test]
+    fn poll_partial_lines() {
+        let mut connection = chunked(vec![b"PRIVMSG #channel :Hel", b"lo\r\nPRIVMSG #ch"]);
+
+        assert_eq!(privmsg("PRIVMSG #channel :Hello"), connection.poll());
+        assert_eq!(None, connection.poll());
+    }
+
+    #[test]
+    fn poll_legacy_charsets() {
+        let line: &[u8] = b"PRIVMSG #channel :Sk\xe5l \x93cheers\x94\r\n";
+
+        let mut connection = chunked(vec![line]);
+        assert_eq!(
+            privmsg("PRIVMSG #channel :Skål “cheers”"),
+            connection.poll()
+        );
+
+        let mut connection = chunked(vec![line]);
+        connection.set_decoding(Decoding::Fallback(Charset::Latin1));
+        assert_eq!(
+            privmsg("PRIVMSG #channel :Skål \u{93}cheers\u{94}"),
+            connection.poll()
+        );
+
+        let mut connection = chunked(vec![line]);
+        connection.set_utf8_only(true);
+        assert_eq!(
+            privmsg("PRIVMSG #channel :Sk\u{fffd}l \u{fffd}cheers\u{fffd}"),
+            connection.poll()
+        );
+
+        let mut connection = chunked(vec![line, "PRIVMSG #channel :Skål\r\n".as_bytes()]);
+        connection.set_decoding(Decoding::Strict);
+        assert_eq!(None, connection.poll());
+        assert_eq!(privmsg("PRIVMSG #channel :Skål"), connection.poll());
+    }
+
+    #[test]
+    fn poll_line_limits() {
+        let longest = format!("PRIVMSG #channel :{}\r\n", "x".repeat(MAX_LINE_LEN - 20));
+        let too_long = format!("PRIVMSG #channel :{}\r\n", "x".repeat(MAX_LINE_LEN - 19));
+        let tags = format!("@+example={} ", "x".repeat(MAX_TAGS_LEN - 11));
+        let mut connection = chunked(vec![
+            longest.as_bytes(),
+            too_long.as_bytes(),
+            tags.as_bytes(),
+            longest.as_bytes(),
+            b"x",
+            tags.as_bytes(),
+            longest.as_bytes(),
+            b"PING\r\n",
+        ]);
+
+        assert_eq!(privmsg(longest.trim_end()), connection.poll());
+        assert_eq!(None, connection.poll());
+        assert_eq!(
+            privmsg(&format!("{}{}", tags, longest.trim_end())),
+            connection.poll()
+        );
+        assert_eq!(None, connection.poll());
+        assert_eq!(privmsg("PING"), connection.poll());
+    }
+
+    #[test]
+    fn poll_endless_line() {
+        let chunk = vec![b'x'; 4096];
+        let mut chunks: Vec<&[u8]> = vec![&chunk; 100];
+        chunks.push(b"\r\nPING\r\n");
+        let mut connection = chunked(chunks);
+
+        assert_eq!(None, connection.poll());
+        assert!(connection.buffer.len() <= MAX_TAGS_LEN + MAX_LINE_LEN);
+        assert_eq!(privmsg("PING"), connection.poll());
+    }
+}
@@ -0,0 +1,136 @@
+//! Everything the libraries log goes through the `log` facade, so nothing is
+//! output unless the application installs a logger. Records use one of these
+//! targets, so that each kind can be filtered separately:
+//!
+//! - `TARGET_WIRE`: every line sent or received, at `Debug`, with passwords
+//!   redacted.
+//! - `TARGET_PARSE`: lines that were discarded because they couldn't be
+//!   decoded or parsed, at `Warn`.
+//! - `TARGET_STATE`: changes to the state of the client, such as registering
+//!   or joining a channel, at `Info`, or `Warn` for failures.
+use std::borrow::Cow;
+
+pub const TARGET_WIRE: &str = "crikey_irc::wire";
+pub const TARGET_PARSE: &str = "crikey_irc::parse";
+pub const TARGET_STATE: &str = "crikey_irc::state";
+
+const REDACTED: &str = "<redacted>";
+
+/// Hide the secrets in a raw line before it's logged: the parameters of PASS
+/// and AUTHENTICATE, the password of OPER, and the text of anything sent to
+/// `nickserv`, which usually includes a password. A target of the form
+/// `nickserv@server` counts too.
+pub fn redact<'a>(raw: &'a str, nickserv: &str) -> Cow<'a, str> {
+    // Skip past the tags and the prefix, if there are any.
+    let mut start = 0;
+    for marker in ['@', ':'].iter() {
+        if raw[start..].starts_with(*marker) {
+            match raw[start..].find(' ') {
+                Some(index) => start += index + 1,
+                None => return Cow::Borrowed(raw),
+            }
+        }
+    }
+
+    let body = &raw[start..];
+    let mut words = body.splitn(3, ' ');
+    let command = words.next().unwrap_or_default();
+    let first = words.next();
+    let rest = words.next();
+
+    let keep = match (&command.to_ascii_uppercase()[..], first) {
+        ("PASS", Some(_)) | ("AUTHENTICATE", Some(_)) => command.len(),
+        ("OPER", Some(user)) if rest.is_some() => command.len() + 1 + user.len(),
+        ("PRIVMSG", Some(target)) | ("NOTICE", Some(target)) if is_nickserv(target, nickserv) => {
+            command.len() + 1 + target.len()
+        }
+        _ => return Cow::Borrowed(raw),
+    };
+
+    let line_break = &body[body.trim_end_matches(['\r', '\n']).len()..];
+    Cow::Owned(format!(
+        "{}{} {}{}",
+        &raw[..start],
+        &body[..keep],
+        REDACTED,
+        line_break
+    ))
+}
+
+fn is_nickserv(target: &str, nickserv: &str) -> bool {
+    let nickname = target.split('@').next().unwrap_or_default();
+    nickname.eq_ignore_ascii_case(nickserv) || target.eq_ignore_ascii_case(nickserv)
+}
+
+#[cfg(test)]
+mod test_logging {
+    use super::*;
+
+    #[test]
+    fn redacts_secrets() {
+        assert_eq!("PASS <redacted>", redact("PASS secretpass", "NickServ"));
+        assert_eq!(
+            "pass <redacted>\r\n",
+            redact("pass :secret pass\r\n", "NickServ")
+        );
+        assert_eq!("OPER foo <redacted>", redact("OPER foo bar", "NickServ"));
+        assert_eq!(
+            "AUTHENTICATE <redacted>",
+            redact("AUTHENTICATE c3B1ZGx5", "NickServ")
+        );
+        assert_eq!(
+            "PRIVMSG NickServ <redacted>",
+            redact("PRIVMSG NickServ :IDENTIFY spudly hunter2", "NickServ")
+        );
+        assert_eq!(
+            "@label=1 :spudly PASS <redacted>",
+            redact("@label=1 :spudly PASS secretpass", "NickServ")
+        );
+    }
+
+    #[test]
+    fn redacts_custom_nickserv() {
+        assert_eq!(
+            "PRIVMSG AuthServ <redacted>",
+            redact("PRIVMSG AuthServ :IDENTIFY spudly hunter2", "AuthServ")
+        );
+        assert_eq!(
+            "PRIVMSG NickServ@services.libera.chat <redacted>",
+            redact(
+                "PRIVMSG NickServ@services.libera.chat :GHOST spudly hunter2",
+                "NickServ"
+            )
+        );
+        assert_eq!(
+            "PRIVMSG NickServ@services.libera.chat <redacted>",
+            redact(
+                "PRIVMSG NickServ@services.libera.chat :REGAIN spudly hunter2",
+                "NickServ@services.libera.chat"
+            )
+        );
+        assert_eq!(
+            "PRIVMSG NickServ :HELP",
+            redact("PRIVMSG NickServ :HELP", "AuthServ")
+        );
+    }
+
+    #[test]
+    fn leaves_everything_else() {
+        for raw in &[
+            "PRIVMSG #channel :PASS secretpass",
+            "NICK spudly",
+            "PASS",
+            "OPER foo",
+            "@label=1",
+            ":irc.example.com",
+            "",
+        ] {
+            assert!(
+                matches!(redact(raw, "NickServ"), Cow::Borrowed(_)),
+                "{}",
+                raw
+            );
+            assert_eq!(*raw, redact(raw, "NickServ"));
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use super::super::entity::{Channel, ChannelKey};
+use super::{Command, ParseError};
+use std::result::Result;
+
+/// Builds a JOIN command one channel at a time, as returned by
+/// `Command::join()`:
+///
+/// ```
+/// # use crikey_irc_common::Command;
+/// let command = Command::join().channel("#foo").key("#bar", "fubar").build()?;
+/// assert_eq!("JOIN #bar,#foo fubar", String::from(command));
+/// # Ok::<(), crikey_irc_common::ParseError>(())
+/// ```
+///
+/// Each name is validated as it is added, but the first error is held until
+/// `build()` so that calls can be chained. RFC 2812 pairs keys with channels
+/// by position, so channels with keys are always listed before those without.
+#[derive(Debug)]
+pub struct JoinBuilder {
+    channels: Vec<(Channel, Option<ChannelKey>)>,
+    error: Option<ParseError>,
+}
+
+impl JoinBuilder {
+    pub(super) fn new() -> Self {
+        JoinBuilder {
+            channels: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn channel(self, channel: &str) -> Self {
+        self.add(channel, None)
+    }
+
+    pub fn key(self, channel: &str, key: &str) -> Self {
+        self.add(channel, Some(key))
+    }
+
+    pub fn build(self) -> Result<Command, ParseError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        } else if self.channels.is_empty() {
+            return Err(ParseError::new("JoinBuilder"));
+        }
+
+        let (keyed, unkeyed): (Vec<_>, Vec<_>) = self
+            .channels
+            .into_iter()
+            .partition(|(_, key)| key.is_some());
+
+        Ok(Command::Join {
+            channels: keyed
+                .iter()
+                .chain(unkeyed.iter())
+                .map(|(channel, _)| channel.clone())
+                .collect(),
+            keys: keyed.into_iter().filter_map(|(_, key)| key).collect(),
+        })
+    }
+
+    fn add(mut self, channel: &str, key: Option<&str>) -> Self {
+        if self.error.is_none() {
+            match (channel.parse(), key.map(str::parse).transpose()) {
+                (Ok(channel), Ok(key)) => self.channels.push((channel, key)),
+                (Err(e), _) | (_, Err(e)) => self.error = Some(e),
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod test_join_builder {
+    use super::*;
+
+    #[test]
+    fn build() {
+        assert_eq!(
+            Ok("JOIN #foo,&bar".to_string()),
+            Command::join()
+                .channel("#foo")
+                .channel("&bar")
+                .build()
+                .map(String::from)
+        );
+        assert_eq!(
+            Ok("JOIN #bar,#baz,#foo fubar,secret".to_string()),
+            Command::join()
+                .channel("#foo")
+                .key("#bar", "fubar")
+                .key("#baz", "secret")
+                .build()
+                .map(String::from)
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Command::join().build().is_err());
+        assert!(Command::join().channel("foo").build().is_err());
+        assert!(Command::join()
+            .channel("#foo")
+            .key("#bar", "spaced out")
+            .build()
+            .is_err());
+    }
+}
@@ -1,12 +1,19 @@
 use super::super::entity::{
     Channel, ChannelKey, Nickname, Recipient, Sender, Servername, Username,
 };
-use super::super::syntax::{KeywordList, ServerMask, StatsQuery};
-use super::{MessageParams, ParseError};
+use super::super::syntax::{
+    CapSubcommand, HistorySelector, KeywordList, ServerMask, StatsQuery, Target, WatchEntry,
+};
+use super::{JoinBuilder, MessageParams, ParseError};
 use std::result::Result;
 use std::str::FromStr;
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "command")
+)]
 pub enum Command {
     // Connection registration
     Pass {
@@ -40,6 +47,9 @@ pub enum Command {
         server: Servername,
         comment: String,
     },
+    SetName {
+        realname: String,
+    },
 
     // Channel operations
     Join {
@@ -70,11 +80,17 @@ pub enum Command {
         nickname: Nickname,
         channel: Channel,
     },
+    /// Either one channel and any number of nicknames, or the same number of
+    /// each, in which case each nickname is kicked from the matching channel.
     Kick {
         channels: KeywordList<Channel>,
-        users: KeywordList<Username>,
+        nicknames: KeywordList<Nickname>,
         comment: Option<String>,
     },
+    Knock {
+        channel: Channel,
+        message: Option<String>,
+    },
 
     // Sending messages
     Privmsg {
@@ -85,6 +101,23 @@ pub enum Command {
         recipients: KeywordList<Recipient>,
         message: String,
     },
+    TagMsg {
+        recipients: KeywordList<Recipient>,
+    },
+    CPrivmsg {
+        nickname: Nickname,
+        channel: Channel,
+        message: String,
+    },
+    CNotice {
+        nickname: Nickname,
+        channel: Channel,
+        message: String,
+    },
+    WallChops {
+        channel: Channel,
+        message: String,
+    },
 
     // Server queries and commands
     Motd {
@@ -114,14 +147,17 @@ pub enum Command {
         remote: Option<Servername>,
     },
     Trace {
-        target: Option<String>,
-    }, // TODO: add nickname
+        target: Option<Target>,
+    },
     Admin {
-        target: Option<String>,
-    }, // TODO: add nickname
+        target: Option<Target>,
+    },
     Info {
-        target: Option<String>,
-    }, // TODO: add nickname
+        target: Option<Target>,
+    },
+    Help {
+        subject: Option<String>,
+    },
 
     // Service query and commands
     ServList {
@@ -189,6 +225,151 @@ pub enum Command {
     IsOn {
         nicknames: KeywordList<Nickname>,
     },
+
+    // Presence tracking
+    MonitorAdd {
+        targets: KeywordList<Nickname>,
+    },
+    MonitorRemove {
+        targets: KeywordList<Nickname>,
+    },
+    MonitorClear,
+    MonitorList,
+    MonitorStatus,
+    Watch {
+        entries: KeywordList<WatchEntry>,
+    },
+
+    // Capability negotiation
+    CapLs {
+        version: Option<u16>,
+    },
+    CapList,
+    CapReq {
+        capabilities: String,
+    },
+    CapEnd,
+    Cap {
+        target: String,
+        subcommand: CapSubcommand,
+        more: bool,
+        capabilities: String,
+    },
+
+    // SASL authentication
+    Authenticate {
+        data: String,
+    },
+
+    // Batches
+    BatchStart {
+        reference: String,
+        batch_type: String,
+        params: Vec<String>,
+    },
+    BatchEnd {
+        reference: String,
+    },
+
+    // Labeled responses
+    Ack,
+
+    // Chat history
+    ChatHistoryLatest {
+        target: Recipient,
+        selector: HistorySelector,
+        limit: u16,
+    },
+    ChatHistoryBefore {
+        target: Recipient,
+        selector: HistorySelector,
+        limit: u16,
+    },
+    ChatHistoryAfter {
+        target: Recipient,
+        selector: HistorySelector,
+        limit: u16,
+    },
+    ChatHistoryAround {
+        target: Recipient,
+        selector: HistorySelector,
+        limit: u16,
+    },
+    ChatHistoryBetween {
+        target: Recipient,
+        start: HistorySelector,
+        end: HistorySelector,
+        limit: u16,
+    },
+    ChatHistoryTargets {
+        start: HistorySelector,
+        end: HistorySelector,
+        limit: u16,
+    },
+    ChatHistoryTarget {
+        target: Recipient,
+        timestamp: String,
+    },
+}
+
+/// Constructors for the commands a client sends most often. Each validates its
+/// arguments the same way the parser would, so a command that builds
+/// successfully will always survive a round trip to the wire.
+impl Command {
+    pub fn privmsg(recipients: &str, message: &str) -> Result<Command, ParseError> {
+        Ok(Command::Privmsg {
+            recipients: list(recipients)?,
+            message: text(message)?,
+        })
+    }
+
+    pub fn notice(recipients: &str, message: &str) -> Result<Command, ParseError> {
+        Ok(Command::Notice {
+            recipients: list(recipients)?,
+            message: text(message)?,
+        })
+    }
+
+    pub fn join() -> JoinBuilder {
+        JoinBuilder::new()
+    }
+
+    pub fn part(channels: &str, message: Option<&str>) -> Result<Command, ParseError> {
+        Ok(Command::Part {
+            channels: list(channels)?,
+            message: message.map(text).transpose()?,
+        })
+    }
+
+    pub fn nick(nickname: &str) -> Result<Command, ParseError> {
+        Ok(Command::Nick {
+            nickname: nickname.parse()?,
+        })
+    }
+
+    pub fn quit(message: Option<&str>) -> Result<Command, ParseError> {
+        Ok(Command::Quit {
+            message: message.map(text).transpose()?,
+        })
+    }
+}
+
+/// A list of recipients or channels, of which there must be at least one.
+fn list<T: FromStr + Into<String>>(raw: &str) -> Result<KeywordList<T>, ParseError> {
+    match raw.parse::<KeywordList<T>>()? {
+        list if list.is_empty() => Err(ParseError::new("Command")),
+        list => Ok(list),
+    }
+}
+
+/// Free text, such as a message or comment, may be anything that fits on one
+/// line.
+fn text(raw: &str) -> Result<String, ParseError> {
+    if raw.contains(&['\0', '\r', '\n'][..]) {
+        Err(ParseError::new("Command"))
+    } else {
+        Ok(raw.to_string())
+    }
 }
 
 impl FromStr for Command {
@@ -250,6 +431,9 @@ impl FromStr for Command {
                 server: args[0].parse()?,
                 comment: args[1].to_string(),
             }),
+            ("SETNAME", 1) => Ok(Command::SetName {
+                realname: args[0].to_string(),
+            }),
             ("JOIN", 1) => {
                 if args[0] == "0" {
                     Ok(Command::Join {
@@ -311,15 +495,32 @@ impl FromStr for Command {
                 nickname: args[0].parse()?,
                 channel: args[1].parse()?,
             }),
-            ("KICK", 2) => Ok(Command::Kick {
-                channels: args[0].parse()?,
-                users: args[1].parse()?,
-                comment: None,
+            ("KICK", 2) | ("KICK", 3) => {
+                let channels: KeywordList<Channel> = args[0].parse()?;
+                let nicknames: KeywordList<Nickname> = args[1].parse()?;
+
+                // There MUST be either one channel parameter and multiple
+                // user parameter, or as many channel parameters as there are
+                // user parameters.
+                if nicknames.is_empty()
+                    || (channels.len() != 1 && channels.len() != nicknames.len())
+                {
+                    return Err(ParseError::new("Command"));
+                }
+
+                Ok(Command::Kick {
+                    channels,
+                    nicknames,
+                    comment: args.get(2).map(|comment| comment.to_string()),
+                })
+            }
+            ("KNOCK", 1) => Ok(Command::Knock {
+                channel: args[0].parse()?,
+                message: None,
             }),
-            ("KICK", 3) => Ok(Command::Kick {
-                channels: args[0].parse()?,
-                users: args[1].parse()?,
-                comment: Some(args[2].to_string()),
+            ("KNOCK", 2) => Ok(Command::Knock {
+                channel: args[0].parse()?,
+                message: Some(args[1].to_string()),
             }),
             ("PRIVMSG", 2) => Ok(Command::Privmsg {
                 recipients: args[0].parse()?,
@@ -329,6 +530,23 @@ impl FromStr for Command {
                 recipients: args[0].parse()?,
                 message: args[1].to_string(),
             }),
+            ("TAGMSG", 1) => Ok(Command::TagMsg {
+                recipients: args[0].parse()?,
+            }),
+            ("CPRIVMSG", 3) => Ok(Command::CPrivmsg {
+                nickname: args[0].parse()?,
+                channel: args[1].parse()?,
+                message: args[2].to_string(),
+            }),
+            ("CNOTICE", 3) => Ok(Command::CNotice {
+                nickname: args[0].parse()?,
+                channel: args[1].parse()?,
+                message: args[2].to_string(),
+            }),
+            ("WALLCHOPS", 2) => Ok(Command::WallChops {
+                channel: args[0].parse()?,
+                message: args[1].to_string(),
+            }),
             ("MOTD", 0) => Ok(Command::Motd { target: None }),
             ("MOTD", 1) => Ok(Command::Motd {
                 target: Some(args[0].parse()?),
@@ -389,15 +607,19 @@ impl FromStr for Command {
             }),
             ("TRACE", 0) => Ok(Command::Trace { target: None }),
             ("TRACE", 1) => Ok(Command::Trace {
-                target: Some(args[0].to_string()),
+                target: Some(args[0].parse()?),
             }),
             ("ADMIN", 0) => Ok(Command::Admin { target: None }),
             ("ADMIN", 1) => Ok(Command::Admin {
-                target: Some(args[0].to_string()),
+                target: Some(args[0].parse()?),
             }),
             ("INFO", 0) => Ok(Command::Info { target: None }),
             ("INFO", 1) => Ok(Command::Info {
-                target: Some(args[0].to_string()),
+                target: Some(args[0].parse()?),
+            }),
+            ("HELP", 0) => Ok(Command::Help { subject: None }),
+            ("HELP", 1) => Ok(Command::Help {
+                subject: Some(args[0].to_string()),
             }),
             ("SERVLIST", 0) => Ok(Command::ServList {
                 mask: None,
@@ -529,6 +751,117 @@ impl FromStr for Command {
                     .join(",")
                     .parse()?,
             }),
+            ("MONITOR", 1) => match &args[0][..] {
+                "C" => Ok(Command::MonitorClear),
+                "L" => Ok(Command::MonitorList),
+                "S" => Ok(Command::MonitorStatus),
+                _ => Err(ParseError::new("Command")),
+            },
+            ("MONITOR", 2) => match &args[0][..] {
+                "+" => Ok(Command::MonitorAdd {
+                    targets: args[1].parse()?,
+                }),
+                "-" => Ok(Command::MonitorRemove {
+                    targets: args[1].parse()?,
+                }),
+                _ => Err(ParseError::new("Command")),
+            },
+            ("WATCH", 0) => Ok(Command::Watch {
+                entries: KeywordList::new(),
+            }),
+            ("WATCH", 1..=15) => Ok(Command::Watch {
+                entries: args
+                    .into_iter()
+                    .collect::<Vec<String>>()
+                    .join(",")
+                    .parse()?,
+            }),
+            ("CAP", 1) => match &args[0][..] {
+                "LS" => Ok(Command::CapLs { version: None }),
+                "LIST" => Ok(Command::CapList),
+                "END" => Ok(Command::CapEnd),
+                _ => Err(ParseError::new("Command")),
+            },
+            ("CAP", 2) => match &args[0][..] {
+                "LS" => Ok(Command::CapLs {
+                    version: Some(args[1].parse().map_err(|_| ParseError::new("Command"))?),
+                }),
+                "REQ" => Ok(Command::CapReq {
+                    capabilities: args[1].to_owned(),
+                }),
+                _ => Err(ParseError::new("Command")),
+            },
+            ("CAP", 3) => Ok(Command::Cap {
+                target: args[0].to_owned(),
+                subcommand: args[1].parse()?,
+                more: false,
+                capabilities: args[2].to_owned(),
+            }),
+            ("CAP", 4) if args[2] == "*" => Ok(Command::Cap {
+                target: args[0].to_owned(),
+                subcommand: args[1].parse()?,
+                more: true,
+                capabilities: args[3].to_owned(),
+            }),
+            ("AUTHENTICATE", 1) => Ok(Command::Authenticate {
+                data: args[0].to_owned(),
+            }),
+            ("BATCH", 1) => match args[0].strip_prefix('-') {
+                Some(reference) if !reference.is_empty() => Ok(Command::BatchEnd {
+                    reference: reference.to_string(),
+                }),
+                _ => Err(ParseError::new("Command")),
+            },
+            ("BATCH", 2..=15) => match args[0].strip_prefix('+') {
+                Some(reference) if !reference.is_empty() => Ok(Command::BatchStart {
+                    reference: reference.to_string(),
+                    batch_type: args[1].to_owned(),
+                    params: args.into_iter().skip(2).collect(),
+                }),
+                _ => Err(ParseError::new("Command")),
+            },
+            ("ACK", 0) => Ok(Command::Ack),
+            ("CHATHISTORY", 3) if args[0] == "TARGETS" => Ok(Command::ChatHistoryTarget {
+                target: args[1].parse()?,
+                timestamp: args[2].to_owned(),
+            }),
+            ("CHATHISTORY", 4) => {
+                let limit = args[3].parse().map_err(|_| ParseError::new("Command"))?;
+                match &args[0][..] {
+                    "LATEST" => Ok(Command::ChatHistoryLatest {
+                        target: args[1].parse()?,
+                        selector: args[2].parse()?,
+                        limit,
+                    }),
+                    "BEFORE" => Ok(Command::ChatHistoryBefore {
+                        target: args[1].parse()?,
+                        selector: args[2].parse()?,
+                        limit,
+                    }),
+                    "AFTER" => Ok(Command::ChatHistoryAfter {
+                        target: args[1].parse()?,
+                        selector: args[2].parse()?,
+                        limit,
+                    }),
+                    "AROUND" => Ok(Command::ChatHistoryAround {
+                        target: args[1].parse()?,
+                        selector: args[2].parse()?,
+                        limit,
+                    }),
+                    "TARGETS" => Ok(Command::ChatHistoryTargets {
+                        start: args[1].parse()?,
+                        end: args[2].parse()?,
+                        limit,
+                    }),
+                    _ => Err(ParseError::new("Command")),
+                }
+            }
+            ("CHATHISTORY", 5) if args[0] == "BETWEEN" => Ok(Command::ChatHistoryBetween {
+                target: args[1].parse()?,
+                start: args[2].parse()?,
+                end: args[3].parse()?,
+                limit: args[4].parse().map_err(|_| ParseError::new("Command"))?,
+            }),
             _ => Err(ParseError::new("Command")),
         }
     }
@@ -568,7 +901,7 @@ impl From<Command> for String {
                 info,
             } => MessageParams::from(vec![
                 String::from(nickname),
-                "".to_string(),
+                "*".to_string(),
                 String::from(distribution),
                 "0".to_string(),
                 "0".to_string(),
@@ -578,15 +911,18 @@ impl From<Command> for String {
             Command::Quit { message: None } => "QUIT".to_string(),
             Command::Quit {
                 message: Some(message),
-            } => MessageParams::from(vec![String::from(message)]).to_string_with_prefix("QUIT"),
+            } => MessageParams::from(vec![message]).to_string_with_prefix("QUIT"),
             Command::SQuit { server, comment } => {
                 MessageParams::from(vec![String::from(server), comment])
                     .to_string_with_prefix("SQUIT")
             }
+            Command::SetName { realname } => {
+                MessageParams::from(vec![realname]).to_string_with_prefix("SETNAME")
+            }
 
             // Channel operations
-            Command::Join { channels, .. } if channels.len() == 0 => "JOIN 0".to_string(),
-            Command::Join { channels, keys } if keys.len() == 0 => {
+            Command::Join { channels, .. } if channels.is_empty() => "JOIN 0".to_string(),
+            Command::Join { channels, keys } if keys.is_empty() => {
                 MessageParams::from(vec![String::from(channels)]).to_string_with_prefix("JOIN")
             }
             Command::Join { channels, keys } => {
@@ -617,7 +953,7 @@ impl From<Command> for String {
             Command::Names {
                 channels,
                 target: None,
-            } if channels.len() == 0 => "NAMES".to_string(),
+            } if channels.is_empty() => "NAMES".to_string(),
             Command::Names {
                 channels,
                 target: None,
@@ -630,7 +966,7 @@ impl From<Command> for String {
             Command::List {
                 channels,
                 target: None,
-            } if channels.len() == 0 => "LIST".to_string(),
+            } if channels.is_empty() => "LIST".to_string(),
             Command::List {
                 channels,
                 target: None,
@@ -646,16 +982,29 @@ impl From<Command> for String {
             }
             Command::Kick {
                 channels,
-                users,
+                nicknames,
                 comment: None,
-            } => MessageParams::from(vec![String::from(channels), String::from(users)])
+            } => MessageParams::from(vec![String::from(channels), String::from(nicknames)])
                 .to_string_with_prefix("KICK"),
             Command::Kick {
                 channels,
-                users,
+                nicknames,
                 comment: Some(comment),
-            } => MessageParams::from(vec![String::from(channels), String::from(users), comment])
-                .to_string_with_prefix("KICK"),
+            } => MessageParams::from(vec![
+                String::from(channels),
+                String::from(nicknames),
+                comment,
+            ])
+            .to_string_with_prefix("KICK"),
+            Command::Knock {
+                channel,
+                message: None,
+            } => MessageParams::from(vec![String::from(channel)]).to_string_with_prefix("KNOCK"),
+            Command::Knock {
+                channel,
+                message: Some(message),
+            } => MessageParams::from(vec![String::from(channel), message])
+                .to_string_with_prefix("KNOCK"),
 
             // Sending messages
             Command::Privmsg {
@@ -668,6 +1017,25 @@ impl From<Command> for String {
                 message,
             } => MessageParams::from(vec![String::from(recipients), message])
                 .to_string_with_prefix("NOTICE"),
+            Command::TagMsg { recipients } => {
+                MessageParams::from(vec![String::from(recipients)]).to_string_with_prefix("TAGMSG")
+            }
+            Command::CPrivmsg {
+                nickname,
+                channel,
+                message,
+            } => MessageParams::from(vec![String::from(nickname), String::from(channel), message])
+                .to_string_with_prefix("CPRIVMSG"),
+            Command::CNotice {
+                nickname,
+                channel,
+                message,
+            } => MessageParams::from(vec![String::from(nickname), String::from(channel), message])
+                .to_string_with_prefix("CNOTICE"),
+            Command::WallChops { channel, message } => {
+                MessageParams::from(vec![String::from(channel), message])
+                    .to_string_with_prefix("WALLCHOPS")
+            }
 
             // Server queries and commands
             Command::Motd { target: None } => "MOTD".to_string(),
@@ -755,6 +1123,10 @@ impl From<Command> for String {
             Command::Info {
                 target: Some(target),
             } => MessageParams::from(vec![String::from(target)]).to_string_with_prefix("INFO"),
+            Command::Help { subject: None } => "HELP".to_string(),
+            Command::Help {
+                subject: Some(subject),
+            } => MessageParams::from(vec![subject]).to_string_with_prefix("HELP"),
 
             // Service query and commands
             Command::ServList {
@@ -764,17 +1136,15 @@ impl From<Command> for String {
             Command::ServList {
                 mask: Some(mask),
                 service_type: None,
-            } => MessageParams::from(vec![String::from(mask)]).to_string_with_prefix("SERVLIST"),
+            } => MessageParams::from(vec![mask]).to_string_with_prefix("SERVLIST"),
             Command::ServList {
                 mask: None,
                 service_type: Some(service_type),
-            } => MessageParams::from(vec![String::from(service_type)])
-                .to_string_with_prefix("SERVLIST"),
+            } => MessageParams::from(vec![service_type]).to_string_with_prefix("SERVLIST"),
             Command::ServList {
                 mask: Some(mask),
                 service_type: Some(service_type),
-            } => MessageParams::from(vec![String::from(mask), service_type])
-                .to_string_with_prefix("SERVLIST"),
+            } => MessageParams::from(vec![mask, service_type]).to_string_with_prefix("SERVLIST"),
             Command::SQuery { recipient, message } => {
                 MessageParams::from(vec![String::from(recipient), message])
                     .to_string_with_prefix("SQUERY")
@@ -785,20 +1155,20 @@ impl From<Command> for String {
             Command::Who {
                 mask: Some(mask),
                 op_only: false,
-            } => MessageParams::from(vec![String::from(mask)]).to_string_with_prefix("WHO"),
+            } => MessageParams::from(vec![mask]).to_string_with_prefix("WHO"),
             Command::Who {
                 mask: Some(mask),
                 op_only: true,
-            } => MessageParams::from(vec![String::from(mask), "o".to_string()])
-                .to_string_with_prefix("WHO"),
+            } => MessageParams::from(vec![mask, "o".to_string()]).to_string_with_prefix("WHO"),
             Command::WhoIs { mask, target: None } => {
-                MessageParams::from(vec![String::from(mask)]).to_string_with_prefix("WHOIS")
+                MessageParams::from(vec![mask]).to_string_with_prefix("WHOIS")
             }
             Command::WhoIs {
                 mask,
                 target: Some(target),
-            } => MessageParams::from(vec![String::from(target), String::from(mask)])
-                .to_string_with_prefix("WHOIS"),
+            } => {
+                MessageParams::from(vec![String::from(target), mask]).to_string_with_prefix("WHOIS")
+            }
             Command::WhoWas {
                 nicknames,
                 count: None,
@@ -857,14 +1227,14 @@ impl From<Command> for String {
                     .to_string_with_prefix("PONG")
             }
             Command::Error { message } => {
-                MessageParams::from(vec![String::from(message)]).to_string_with_prefix("ERROR")
+                MessageParams::from(vec![message]).to_string_with_prefix("ERROR")
             }
 
             // Optional features
             Command::Away { message: None } => "AWAY".to_string(),
             Command::Away {
                 message: Some(message),
-            } => MessageParams::from(vec![String::from(message)]).to_string_with_prefix("AWAY"),
+            } => MessageParams::from(vec![message]).to_string_with_prefix("AWAY"),
             Command::Rehash => "REHASH".to_string(),
             Command::Die => "DIE".to_string(),
             Command::Restart => "RESTART".to_string(),
@@ -900,7 +1270,7 @@ impl From<Command> for String {
                 target: Some(target),
             } => MessageParams::from(vec![String::from(target)]).to_string_with_prefix("USERS"),
             Command::WallOps { message } => {
-                MessageParams::from(vec![String::from(message)]).to_string_with_prefix("WALLOPS")
+                MessageParams::from(vec![message]).to_string_with_prefix("WALLOPS")
             }
             Command::UserHost { nicknames } => MessageParams::from(vec![String::from(nicknames)])
                 .to_string_with_prefix("USERHOST")
@@ -908,6 +1278,147 @@ impl From<Command> for String {
             Command::IsOn { nicknames } => MessageParams::from(vec![String::from(nicknames)])
                 .to_string_with_prefix("ISON")
                 .replace(',', " "),
+
+            // Presence tracking
+            Command::MonitorAdd { targets } => {
+                MessageParams::from(vec!["+".to_string(), String::from(targets)])
+                    .to_string_with_prefix("MONITOR")
+            }
+            Command::MonitorRemove { targets } => {
+                MessageParams::from(vec!["-".to_string(), String::from(targets)])
+                    .to_string_with_prefix("MONITOR")
+            }
+            Command::MonitorClear => "MONITOR C".to_string(),
+            Command::MonitorList => "MONITOR L".to_string(),
+            Command::MonitorStatus => "MONITOR S".to_string(),
+            Command::Watch { entries } if entries.is_empty() => "WATCH".to_string(),
+            Command::Watch { entries } => MessageParams::from(vec![String::from(entries)])
+                .to_string_with_prefix("WATCH")
+                .replace(',', " "),
+
+            // Capability negotiation
+            Command::CapLs { version: None } => "CAP LS".to_string(),
+            Command::CapLs {
+                version: Some(version),
+            } => MessageParams::from(vec!["LS".to_string(), version.to_string()])
+                .to_string_with_prefix("CAP"),
+            Command::CapList => "CAP LIST".to_string(),
+            Command::CapReq { capabilities } => {
+                MessageParams::from(vec!["REQ".to_string(), capabilities])
+                    .to_string_with_prefix("CAP")
+            }
+            Command::CapEnd => "CAP END".to_string(),
+            Command::Cap {
+                target,
+                subcommand,
+                more: false,
+                capabilities,
+            } => MessageParams::from(vec![target, String::from(subcommand), capabilities])
+                .to_string_with_prefix("CAP"),
+            Command::Cap {
+                target,
+                subcommand,
+                more: true,
+                capabilities,
+            } => MessageParams::from(vec![
+                target,
+                String::from(subcommand),
+                "*".to_string(),
+                capabilities,
+            ])
+            .to_string_with_prefix("CAP"),
+
+            // SASL authentication
+            Command::Authenticate { data } => {
+                MessageParams::from(vec![data]).to_string_with_prefix("AUTHENTICATE")
+            }
+
+            // Batches
+            Command::BatchStart {
+                reference,
+                batch_type,
+                params,
+            } => {
+                let mut args = vec![format!("+{}", reference), batch_type];
+                args.extend(params);
+                MessageParams::from(args).to_string_with_prefix("BATCH")
+            }
+            Command::BatchEnd { reference } => {
+                MessageParams::from(vec![format!("-{}", reference)]).to_string_with_prefix("BATCH")
+            }
+
+            // Labeled responses
+            Command::Ack => "ACK".to_string(),
+
+            // Chat history
+            Command::ChatHistoryLatest {
+                target,
+                selector,
+                limit,
+            } => MessageParams::from(vec![
+                "LATEST".to_string(),
+                String::from(target),
+                String::from(selector),
+                limit.to_string(),
+            ])
+            .to_string_with_prefix("CHATHISTORY"),
+            Command::ChatHistoryBefore {
+                target,
+                selector,
+                limit,
+            } => MessageParams::from(vec![
+                "BEFORE".to_string(),
+                String::from(target),
+                String::from(selector),
+                limit.to_string(),
+            ])
+            .to_string_with_prefix("CHATHISTORY"),
+            Command::ChatHistoryAfter {
+                target,
+                selector,
+                limit,
+            } => MessageParams::from(vec![
+                "AFTER".to_string(),
+                String::from(target),
+                String::from(selector),
+                limit.to_string(),
+            ])
+            .to_string_with_prefix("CHATHISTORY"),
+            Command::ChatHistoryAround {
+                target,
+                selector,
+                limit,
+            } => MessageParams::from(vec![
+                "AROUND".to_string(),
+                String::from(target),
+                String::from(selector),
+                limit.to_string(),
+            ])
+            .to_string_with_prefix("CHATHISTORY"),
+            Command::ChatHistoryBetween {
+                target,
+                start,
+                end,
+                limit,
+            } => MessageParams::from(vec![
+                "BETWEEN".to_string(),
+                String::from(target),
+                String::from(start),
+                String::from(end),
+                limit.to_string(),
+            ])
+            .to_string_with_prefix("CHATHISTORY"),
+            Command::ChatHistoryTargets { start, end, limit } => MessageParams::from(vec![
+                "TARGETS".to_string(),
+                String::from(start),
+                String::from(end),
+                limit.to_string(),
+            ])
+            .to_string_with_prefix("CHATHISTORY"),
+            Command::ChatHistoryTarget { target, timestamp } => {
+                MessageParams::from(vec!["TARGETS".to_string(), String::from(target), timestamp])
+                    .to_string_with_prefix("CHATHISTORY")
+            }
         }
     }
 }
@@ -915,19 +1426,28 @@ impl From<Command> for String {
 /// The tests in this section, and their annotations, are copied verbatim from the examples in RFC 2812.
 #[cfg(test)]
 mod tests {
-    use super::super::{Message, MessageBody};
+    use super::super::{Message, MessageBody, MessageTags};
     use super::*;
 
     fn assert_roundtrip(raw: &str, sender: Option<Sender>, command: Command) {
         let parsed_message = raw.parse::<Message>();
         assert_eq!(
             Ok(Message {
+                tags: MessageTags::new(),
                 sender,
                 body: MessageBody::Command(command)
             }),
             parsed_message
         );
-        assert_eq!(raw.to_string(), String::from(parsed_message.unwrap()),);
+        let message = parsed_message.unwrap();
+
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            message,
+            serde_json::from_str(&serde_json::to_string(&message).unwrap()).unwrap()
+        );
+
+        assert_eq!(raw.to_string(), String::from(message));
     }
 
     #[test]
@@ -1436,7 +1956,7 @@ mod tests {
             None,
             Command::Kick {
                 channels: "&Melbourne".parse().unwrap(),
-                users: "Matthew".parse().unwrap(),
+                nicknames: "Matthew".parse().unwrap(),
                 comment: None,
             },
         );
@@ -1446,7 +1966,7 @@ mod tests {
             None,
             Command::Kick {
                 channels: "#Finnish".parse().unwrap(),
-                users: "John".parse().unwrap(),
+                nicknames: "John".parse().unwrap(),
                 comment: Some("Speaking English".to_string()),
             },
         );
@@ -1456,10 +1976,37 @@ mod tests {
             Some("WiZ!jto@tolsun.oulu.fi".parse().unwrap()),
             Command::Kick {
                 channels: "#Finnish".parse().unwrap(),
-                users: "John".parse().unwrap(),
+                nicknames: "John".parse().unwrap(),
+                comment: None,
+            },
+        );
+        // Kick John and Jane from #Finnish.
+        assert_roundtrip(
+            "KICK #Finnish John,Jane",
+            None,
+            Command::Kick {
+                channels: "#Finnish".parse().unwrap(),
+                nicknames: "John,Jane".parse().unwrap(),
                 comment: None,
             },
         );
+        // Kick John from #Finnish and Jane from &Melbourne.
+        assert_roundtrip(
+            "KICK #Finnish,&Melbourne John,Jane :Speaking English",
+            None,
+            Command::Kick {
+                channels: "#Finnish,&Melbourne".parse().unwrap(),
+                nicknames: "John,Jane".parse().unwrap(),
+                comment: Some("Speaking English".to_string()),
+            },
+        );
+    }
+
+    #[test]
+    fn channel_operations_kick_pairing() {
+        assert!("KICK #Finnish,&Melbourne John".parse::<Command>().is_err());
+        assert!("KICK #a,#b,#c John,Jane".parse::<Command>().is_err());
+        assert!("KICK #Finnish John!jto@host".parse::<Command>().is_err());
     }
 
     #[test]
@@ -1699,7 +2246,15 @@ mod tests {
             "TRACE *.oulu.fi",
             None,
             Command::Trace {
-                target: Some("*.oulu.fi".to_string()),
+                target: Some("*.oulu.fi".parse().unwrap()),
+            },
+        );
+        // TRACE to the server that Wiz is connected to
+        assert_roundtrip(
+            "TRACE Wiz",
+            None,
+            Command::Trace {
+                target: Some(Target::Nickname("Wiz".parse().unwrap())),
             },
         );
     }
@@ -1712,7 +2267,7 @@ mod tests {
             "ADMIN tolsun.oulu.fi",
             None,
             Command::Admin {
-                target: Some("tolsun.oulu.fi".to_string()),
+                target: Some(Target::ServerMask("tolsun.oulu.fi".parse().unwrap())),
             },
         );
         // ADMIN request for the server to which the user syrk is connected
@@ -1720,7 +2275,7 @@ mod tests {
             "ADMIN syrk",
             None,
             Command::Admin {
-                target: Some("syrk".to_string()),
+                target: Some(Target::Nickname("syrk".parse().unwrap())),
             },
         );
     }
@@ -1733,7 +2288,7 @@ mod tests {
             "INFO csd.bu.edu",
             None,
             Command::Info {
-                target: Some("csd.bu.edu".to_string()),
+                target: Some(Target::ServerMask("csd.bu.edu".parse().unwrap())),
             },
         );
         // request info from the server that Angel is connected to.
@@ -1741,14 +2296,14 @@ mod tests {
             "INFO Angel",
             None,
             Command::Info {
-                target: Some("Angel".to_string()),
+                target: Some(Target::Nickname("Angel".parse().unwrap())),
             },
         );
         assert_roundtrip(
             "INFO *.example.com",
             None,
             Command::Info {
-                target: Some("*.example.com".to_string()),
+                target: Some(Target::ServerMask("*.example.com".parse().unwrap())),
             },
         );
     }
@@ -2089,3 +2644,432 @@ mod tests {
         );
     }
 }
+
+/// The tests in this section cover commands that postdate RFC 2812. Where a
+/// specification provides examples, they are used verbatim.
+#[cfg(test)]
+mod test_extensions {
+    use super::super::{Message, MessageBody, MessageTags};
+    use super::*;
+
+    fn assert_roundtrip(raw: &str, sender: Option<Sender>, command: Command) {
+        let parsed_message = raw.parse::<Message>();
+        assert_eq!(
+            Ok(Message {
+                tags: MessageTags::new(),
+                sender,
+                body: MessageBody::Command(command)
+            }),
+            parsed_message
+        );
+        let message = parsed_message.unwrap();
+
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            message,
+            serde_json::from_str(&serde_json::to_string(&message).unwrap()).unwrap()
+        );
+
+        assert_eq!(raw.to_string(), String::from(message));
+    }
+
+    #[test]
+    fn presence_tracking_monitor() {
+        // Add "jilles" and "kaniini" to the monitor list.
+        assert_roundtrip(
+            "MONITOR + jilles,kaniini",
+            None,
+            Command::MonitorAdd {
+                targets: "jilles,kaniini".parse().unwrap(),
+            },
+        );
+        // Remove "kaniini" from the monitor list.
+        assert_roundtrip(
+            "MONITOR - kaniini",
+            None,
+            Command::MonitorRemove {
+                targets: "kaniini".parse().unwrap(),
+            },
+        );
+        assert_roundtrip("MONITOR C", None, Command::MonitorClear);
+        assert_roundtrip("MONITOR L", None, Command::MonitorList);
+        assert_roundtrip("MONITOR S", None, Command::MonitorStatus);
+
+        assert!("MONITOR".parse::<Command>().is_err());
+        assert!("MONITOR X".parse::<Command>().is_err());
+        assert!("MONITOR * jilles".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn presence_tracking_watch() {
+        assert_roundtrip(
+            "WATCH",
+            None,
+            Command::Watch {
+                entries: KeywordList::new(),
+            },
+        );
+        assert_roundtrip(
+            "WATCH +Wiz +Angel -syrk",
+            None,
+            Command::Watch {
+                entries: "+Wiz,+Angel,-syrk".parse().unwrap(),
+            },
+        );
+        assert_roundtrip(
+            "WATCH C +Wiz l",
+            None,
+            Command::Watch {
+                entries: "C,+Wiz,l".parse().unwrap(),
+            },
+        );
+
+        assert!("WATCH Wiz".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn capability_negotiation() {
+        // Client requests the list of capabilities with the 302 extensions.
+        assert_roundtrip("CAP LS 302", None, Command::CapLs { version: Some(302) });
+        assert_roundtrip("CAP LS", None, Command::CapLs { version: None });
+        assert_roundtrip("CAP LIST", None, Command::CapList);
+
+        // Server lists its capabilities across more than one line.
+        assert_roundtrip(
+            "CAP * LS * :multi-prefix extended-join account-notify batch invite-notify",
+            None,
+            Command::Cap {
+                target: "*".to_string(),
+                subcommand: CapSubcommand::Ls,
+                more: true,
+                capabilities: "multi-prefix extended-join account-notify batch invite-notify"
+                    .to_string(),
+            },
+        );
+        assert_roundtrip(
+            "CAP * LS :draft/chathistory sasl=PLAIN,EXTERNAL",
+            None,
+            Command::Cap {
+                target: "*".to_string(),
+                subcommand: CapSubcommand::Ls,
+                more: false,
+                capabilities: "draft/chathistory sasl=PLAIN,EXTERNAL".to_string(),
+            },
+        );
+
+        // Client requests two capabilities, and the server acknowledges them.
+        assert_roundtrip(
+            "CAP REQ :multi-prefix sasl",
+            None,
+            Command::CapReq {
+                capabilities: "multi-prefix sasl".to_string(),
+            },
+        );
+        assert_roundtrip(
+            "CAP spudly ACK :multi-prefix sasl",
+            None,
+            Command::Cap {
+                target: "spudly".to_string(),
+                subcommand: CapSubcommand::Ack,
+                more: false,
+                capabilities: "multi-prefix sasl".to_string(),
+            },
+        );
+        assert_roundtrip("CAP END", None, Command::CapEnd);
+
+        assert_eq!(
+            Ok(Command::CapReq {
+                capabilities: "batch".to_string(),
+            }),
+            "CAP REQ :batch".parse::<Command>()
+        );
+        assert!("CAP".parse::<Command>().is_err());
+        assert!("CAP REQ".parse::<Command>().is_err());
+        assert!("CAP LS three".parse::<Command>().is_err());
+        assert!("CAP * FOO :batch".parse::<Command>().is_err());
+        assert!("CAP * LS - :batch".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn sasl() {
+        assert_roundtrip(
+            "AUTHENTICATE PLAIN",
+            None,
+            Command::Authenticate {
+                data: "PLAIN".to_string(),
+            },
+        );
+        assert_roundtrip(
+            "AUTHENTICATE +",
+            None,
+            Command::Authenticate {
+                data: "+".to_string(),
+            },
+        );
+        assert_roundtrip(
+            "AUTHENTICATE c3B1ZGx5AHNwdWRseQBodW50ZXIy",
+            None,
+            Command::Authenticate {
+                data: "c3B1ZGx5AHNwdWRseQBodW50ZXIy".to_string(),
+            },
+        );
+
+        assert!("AUTHENTICATE".parse::<Command>().is_err());
+        assert!("AUTHENTICATE PLAIN extra".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn batches() {
+        // A netsplit between two servers.
+        assert_roundtrip(
+            ":irc.host BATCH +yXNAbvnRHTRBv netsplit irc.hub.other.host irc.host",
+            Some("irc.host".parse().unwrap()),
+            Command::BatchStart {
+                reference: "yXNAbvnRHTRBv".to_string(),
+                batch_type: "netsplit".to_string(),
+                params: vec!["irc.hub.other.host".to_string(), "irc.host".to_string()],
+            },
+        );
+        assert_roundtrip(
+            ":irc.host BATCH -yXNAbvnRHTRBv",
+            Some("irc.host".parse().unwrap()),
+            Command::BatchEnd {
+                reference: "yXNAbvnRHTRBv".to_string(),
+            },
+        );
+
+        assert!("BATCH".parse::<Command>().is_err());
+        assert!("BATCH +".parse::<Command>().is_err());
+        assert!("BATCH -".parse::<Command>().is_err());
+        assert!("BATCH abc".parse::<Command>().is_err());
+        assert!("BATCH -abc netsplit".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn labeled_responses() {
+        assert_roundtrip(
+            ":irc.example.com ACK",
+            Some("irc.example.com".parse().unwrap()),
+            Command::Ack,
+        );
+        assert!("ACK ACK".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn chat_history() {
+        assert_roundtrip(
+            "CHATHISTORY LATEST #channel * 50",
+            None,
+            Command::ChatHistoryLatest {
+                target: "#channel".parse().unwrap(),
+                selector: HistorySelector::Any,
+                limit: 50,
+            },
+        );
+        assert_roundtrip(
+            "CHATHISTORY BEFORE #channel timestamp=2019-01-04T14:33:26.123Z 50",
+            None,
+            Command::ChatHistoryBefore {
+                target: "#channel".parse().unwrap(),
+                selector: "timestamp=2019-01-04T14:33:26.123Z".parse().unwrap(),
+                limit: 50,
+            },
+        );
+        assert_roundtrip(
+            "CHATHISTORY AFTER nick msgid=1234 50",
+            None,
+            Command::ChatHistoryAfter {
+                target: "nick".parse().unwrap(),
+                selector: HistorySelector::MsgId("1234".to_string()),
+                limit: 50,
+            },
+        );
+        assert_roundtrip(
+            "CHATHISTORY AROUND #channel msgid=1234 10",
+            None,
+            Command::ChatHistoryAround {
+                target: "#channel".parse().unwrap(),
+                selector: HistorySelector::MsgId("1234".to_string()),
+                limit: 10,
+            },
+        );
+        assert_roundtrip(
+            "CHATHISTORY BETWEEN #channel msgid=1234 timestamp=2019-01-04T14:33:26.123Z 100",
+            None,
+            Command::ChatHistoryBetween {
+                target: "#channel".parse().unwrap(),
+                start: HistorySelector::MsgId("1234".to_string()),
+                end: "timestamp=2019-01-04T14:33:26.123Z".parse().unwrap(),
+                limit: 100,
+            },
+        );
+        assert_roundtrip(
+            "CHATHISTORY TARGETS timestamp=2020-07-18T00:00:00.000Z timestamp=2020-07-19T00:00:00.000Z 50",
+            None,
+            Command::ChatHistoryTargets {
+                start: "timestamp=2020-07-18T00:00:00.000Z".parse().unwrap(),
+                end: "timestamp=2020-07-19T00:00:00.000Z".parse().unwrap(),
+                limit: 50,
+            },
+        );
+        assert_roundtrip(
+            ":irc.host CHATHISTORY TARGETS #channel 2020-07-18T12:34:56.000Z",
+            Some("irc.host".parse().unwrap()),
+            Command::ChatHistoryTarget {
+                target: "#channel".parse().unwrap(),
+                timestamp: "2020-07-18T12:34:56.000Z".to_string(),
+            },
+        );
+
+        assert!("CHATHISTORY LATEST #channel * lots"
+            .parse::<Command>()
+            .is_err());
+        assert!("CHATHISTORY LATEST #channel 1234 50"
+            .parse::<Command>()
+            .is_err());
+        assert!("CHATHISTORY SOMETIME #channel * 50"
+            .parse::<Command>()
+            .is_err());
+        assert!("CHATHISTORY BETWEEN #channel * 50"
+            .parse::<Command>()
+            .is_err());
+    }
+
+    #[test]
+    fn modern_commands_knock() {
+        assert_roundtrip(
+            "KNOCK #channel",
+            None,
+            Command::Knock {
+                channel: "#channel".parse().unwrap(),
+                message: None,
+            },
+        );
+        assert_roundtrip(
+            "KNOCK #channel :let me in please",
+            None,
+            Command::Knock {
+                channel: "#channel".parse().unwrap(),
+                message: Some("let me in please".to_string()),
+            },
+        );
+        assert!("KNOCK nickname".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn modern_commands_messages() {
+        // A typing indicator, which is sent entirely in tags.
+        let raw = "@+typing=active TAGMSG #channel";
+        let message = raw.parse::<Message>().unwrap();
+        assert_eq!(Some("active"), message.tags.get("+typing"));
+        assert_eq!(
+            MessageBody::Command(Command::TagMsg {
+                recipients: "#channel".parse().unwrap(),
+            }),
+            message.body
+        );
+        assert_eq!(raw.to_string(), String::from(message));
+
+        assert_roundtrip(
+            "CPRIVMSG Wiz #channel :Hello there",
+            None,
+            Command::CPrivmsg {
+                nickname: "Wiz".parse().unwrap(),
+                channel: "#channel".parse().unwrap(),
+                message: "Hello there".to_string(),
+            },
+        );
+        assert_roundtrip(
+            "CNOTICE Wiz #channel :Hello there",
+            None,
+            Command::CNotice {
+                nickname: "Wiz".parse().unwrap(),
+                channel: "#channel".parse().unwrap(),
+                message: "Hello there".to_string(),
+            },
+        );
+        assert_roundtrip(
+            "WALLCHOPS #channel :ops only",
+            None,
+            Command::WallChops {
+                channel: "#channel".parse().unwrap(),
+                message: "ops only".to_string(),
+            },
+        );
+        assert!("CPRIVMSG #channel Wiz :backwards"
+            .parse::<Command>()
+            .is_err());
+    }
+
+    #[test]
+    fn modern_commands_statusmsg() {
+        assert_roundtrip(
+            "PRIVMSG @#channel :ops only",
+            None,
+            Command::Privmsg {
+                recipients: "@#channel".parse().unwrap(),
+                message: "ops only".to_string(),
+            },
+        );
+        assert_roundtrip(
+            "NOTICE %#channel,Wiz :halfops and Wiz",
+            None,
+            Command::Notice {
+                recipients: "%#channel,Wiz".parse().unwrap(),
+                message: "halfops and Wiz".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn modern_commands_setname_help() {
+        assert_roundtrip(
+            "SETNAME :Potato Johnson III",
+            None,
+            Command::SetName {
+                realname: "Potato Johnson III".to_string(),
+            },
+        );
+        assert_roundtrip("HELP", None, Command::Help { subject: None });
+        assert_roundtrip(
+            "HELP PRIVMSG",
+            None,
+            Command::Help {
+                subject: Some("PRIVMSG".to_string()),
+            },
+        );
+    }
+
+    #[test]
+    fn constructors() {
+        assert_eq!(
+            Ok(Command::Privmsg {
+                recipients: "#channel,Wiz".parse().unwrap(),
+                message: "Hello there".to_string(),
+            }),
+            Command::privmsg("#channel,Wiz", "Hello there")
+        );
+        assert_eq!(
+            Ok("NOTICE Wiz :Hello there".to_string()),
+            Command::notice("Wiz", "Hello there").map(String::from)
+        );
+        assert_eq!(
+            Ok("PART #channel :Gone to have lunch".to_string()),
+            Command::part("#channel", Some("Gone to have lunch")).map(String::from)
+        );
+        assert_eq!(
+            Ok("NICK Wiz".to_string()),
+            Command::nick("Wiz").map(String::from)
+        );
+        assert_eq!(
+            Ok("QUIT".to_string()),
+            Command::quit(None).map(String::from)
+        );
+
+        assert!(Command::privmsg("", "Hello there").is_err());
+        assert!(Command::privmsg("#channel", "two\r\nlines").is_err());
+        assert!(Command::part("channel", None).is_err());
+        assert!(Command::nick("2hot4u").is_err());
+        assert!(Command::quit(Some("QUIT\r\nJOIN #evil")).is_err());
+    }
+}
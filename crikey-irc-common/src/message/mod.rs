@@ -0,0 +1,679 @@
+pub use self::builder::JoinBuilder;
+pub use self::command::Command;
+pub use self::reply::{Reply, ReplyType};
+pub use self::tags::MessageTags;
+use super::{ParseError, Sender};
+use std::iter::IntoIterator;
+use std::ops::Index;
+use std::result::Result;
+use std::str::FromStr;
+use std::vec::IntoIter;
+
+mod builder;
+mod command;
+mod reply;
+mod tags;
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Message {
+    pub tags: MessageTags,
+    pub sender: Option<Sender>,
+    pub body: MessageBody,
+}
+
+impl FromStr for Message {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Self::parse(raw, false)
+    }
+}
+
+impl Message {
+    /// Parse a message without RFC 1459's limit of 15 parameters, as modern
+    /// servers may send. See `MessageParams::parse_unlimited()`.
+    pub fn parse_unlimited(raw: &str) -> Result<Self, ParseError> {
+        Self::parse(raw, true)
+    }
+
+    fn parse(raw: &str, unlimited: bool) -> Result<Self, ParseError> {
+        let raw = raw.trim_end_matches(&['\r', '\n'][..]);
+        if raw.contains(&['\0', '\r', '\n'][..]) {
+            return Err(ParseError::new("Message"));
+        }
+
+        let (tags, raw) = if raw.starts_with('@') && raw.contains(' ') {
+            let index = raw.find(' ').unwrap();
+            (
+                raw[1..index].parse()?,
+                raw[index + 1..].trim_start_matches(' '),
+            )
+        } else {
+            (MessageTags::new(), raw)
+        };
+        let (sender, raw_body) = if raw.starts_with(':') && raw.contains(' ') {
+            let index = raw.find(' ').unwrap();
+            (Some(raw[1..index].parse()?), &raw[index + 1..])
+        } else {
+            (None, raw)
+        };
+
+        Ok(Message {
+            tags,
+            sender,
+            body: MessageBody::parse(raw_body, unlimited)?,
+        })
+    }
+}
+
+/// Messages to send can be built up from a command:
+///
+/// ```
+/// # use crikey_irc_common::{Command, Message};
+/// let message = Message::from(Command::privmsg("#channel", "Hello!")?).with_tag("label", "abc")?;
+/// assert_eq!("@label=abc PRIVMSG #channel Hello!", String::from(message));
+/// # Ok::<(), crikey_irc_common::ParseError>(())
+/// ```
+impl Message {
+    pub fn with_tag(mut self, key: &str, value: &str) -> Result<Self, ParseError> {
+        if tags::is_valid_key(key) {
+            self.tags.insert(key.to_string(), value.to_string());
+            Ok(self)
+        } else {
+            Err(ParseError::new("MessageTags"))
+        }
+    }
+
+    pub fn with_sender(mut self, sender: &str) -> Result<Self, ParseError> {
+        self.sender = Some(sender.parse()?);
+        Ok(self)
+    }
+}
+
+impl From<Command> for Message {
+    fn from(command: Command) -> Message {
+        Message {
+            tags: MessageTags::new(),
+            sender: None,
+            body: MessageBody::Command(command),
+        }
+    }
+}
+
+impl From<Message> for String {
+    fn from(message: Message) -> String {
+        let mut result = String::new();
+        if !message.tags.is_empty() {
+            result.push('@');
+            result.push_str(&String::from(message.tags));
+            result.push(' ');
+        }
+        if let Some(sender) = message.sender {
+            result.push(':');
+            result.push_str(&String::from(sender));
+            result.push(' ');
+        }
+        result.push_str(&String::from(message.body));
+        result
+    }
+}
+
+#[cfg(test)]
+mod test_message {
+    use super::*;
+
+    #[test]
+    fn invalid() {
+        assert!("".parse::<Message>().is_err());
+        assert!("🥔️".parse::<Message>().is_err());
+        assert!(":abc".parse::<Message>().is_err());
+        assert!(":abc ".parse::<Message>().is_err());
+        assert!("@abc".parse::<Message>().is_err());
+        assert!("@a_b NICK me".parse::<Message>().is_err());
+    }
+
+    #[test]
+    fn fuzz_regressions() {
+        // Line breaks inside a message would be sent as two lines.
+        assert!("333 \n\n\n ".parse::<Message>().is_err());
+        assert!("PRIVMSG #channel :one\rtwo".parse::<Message>().is_err());
+        assert!("PRIVMSG #channel :nul\0".parse::<Message>().is_err());
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(
+            Ok(Message {
+                tags: MessageTags::new(),
+                sender: Some("me".parse().unwrap()),
+                body: MessageBody::Reply(ReplyType::PrvWelcome, ":Hi there".parse().unwrap())
+            }),
+            ":me 001 :Hi there\r\n".parse::<Message>()
+        );
+        assert_eq!(
+            Ok(Message {
+                tags: MessageTags::new(),
+                sender: None,
+                body: MessageBody::Command(Command::Nick {
+                    nickname: "me".parse().unwrap(),
+                })
+            }),
+            "NICK me\n".parse::<Message>()
+        );
+        assert_eq!(
+            Ok(Message {
+                tags: "msgid=abc;time=2021-01-01T00:00:00.000Z".parse().unwrap(),
+                sender: Some("me".parse().unwrap()),
+                body: MessageBody::Command(Command::Nick {
+                    nickname: "you".parse().unwrap(),
+                })
+            }),
+            "@msgid=abc;time=2021-01-01T00:00:00.000Z :me NICK you\r\n".parse::<Message>()
+        );
+    }
+
+    #[test]
+    fn string() {
+        assert_eq!(
+            ":me 001 :Hi there".to_string(),
+            String::from(Message {
+                tags: MessageTags::new(),
+                sender: Some("me".parse().unwrap()),
+                body: MessageBody::Reply(ReplyType::PrvWelcome, ":Hi there".parse().unwrap())
+            })
+        );
+        assert_eq!(
+            "NICK me".to_string(),
+            String::from(Message {
+                tags: MessageTags::new(),
+                sender: None,
+                body: MessageBody::Command(Command::Nick {
+                    nickname: "me".parse().unwrap(),
+                })
+            })
+        );
+        assert_eq!(
+            "@msgid=abc :me NICK you".to_string(),
+            String::from(Message {
+                tags: "msgid=abc".parse().unwrap(),
+                sender: Some("me".parse().unwrap()),
+                body: MessageBody::Command(Command::Nick {
+                    nickname: "you".parse().unwrap(),
+                })
+            })
+        );
+    }
+
+    #[test]
+    fn builder() {
+        assert_eq!(
+            Ok("@label=abc;+draft/reply=x\\sy :me PRIVMSG #channel :Hi there".to_string()),
+            Command::privmsg("#channel", "Hi there")
+                .map(Message::from)
+                .and_then(|message| message.with_tag("label", "abc"))
+                .and_then(|message| message.with_tag("+draft/reply", "x y"))
+                .and_then(|message| message.with_sender("me"))
+                .map(String::from)
+        );
+        assert!(Message::from(Command::CapEnd)
+            .with_tag("bad key", "x")
+            .is_err());
+        assert!(Message::from(Command::CapEnd)
+            .with_sender("spaced out")
+            .is_err());
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MessageBody {
+    Command(Command),
+    Reply(ReplyType, MessageParams),
+}
+
+impl FromStr for MessageBody {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Self::parse(raw, false)
+    }
+}
+
+impl MessageBody {
+    fn parse(raw: &str, unlimited: bool) -> Result<Self, ParseError> {
+        match raw.chars().nth(0) {
+            Some(c) if c.is_ascii_uppercase() => Ok(MessageBody::Command(raw.parse()?)),
+            Some(c) if c.is_ascii_digit() => {
+                if let Some(index) = raw.find(' ') {
+                    Ok(MessageBody::Reply(
+                        raw[..index].parse()?,
+                        MessageParams::parse(&raw[index + 1..], unlimited)?,
+                    ))
+                } else {
+                    Ok(MessageBody::Reply(raw.parse()?, MessageParams::new()))
+                }
+            }
+            _ => Err(ParseError::new("MessageBody")),
+        }
+    }
+}
+
+impl From<MessageBody> for String {
+    fn from(message_body: MessageBody) -> String {
+        match message_body {
+            MessageBody::Command(command) => String::from(command),
+            MessageBody::Reply(reply_type, reply_body) => {
+                let mut result = String::from(reply_type);
+                if !reply_body.is_empty() {
+                    result.push(' ');
+                    result.push_str(&String::from(reply_body));
+                }
+                result
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_message_body {
+    use super::*;
+
+    #[test]
+    fn invalid() {
+        assert!("".parse::<MessageBody>().is_err());
+        assert!("🥔️".parse::<MessageBody>().is_err());
+        assert!("00A ".parse::<MessageBody>().is_err());
+        assert!("00A def".parse::<MessageBody>().is_err());
+        assert!(":me 001 Hi there".parse::<MessageBody>().is_err());
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(
+            Ok(MessageBody::Reply(
+                ReplyType::PrvWelcome,
+                "".parse().unwrap()
+            )),
+            "001".parse::<MessageBody>()
+        );
+        assert_eq!(
+            Ok(MessageBody::Reply(
+                ReplyType::PrvWelcome,
+                "".parse().unwrap()
+            )),
+            "001 ".parse::<MessageBody>()
+        );
+        assert_eq!(
+            Ok(MessageBody::Reply(
+                ReplyType::PrvWelcome,
+                ":Hi there".parse().unwrap()
+            )),
+            "001 :Hi there".parse::<MessageBody>()
+        );
+        assert_eq!(
+            Ok(MessageBody::Command(Command::Nick {
+                nickname: "me".parse().unwrap(),
+            })),
+            "NICK me".parse::<MessageBody>()
+        );
+    }
+
+    #[test]
+    fn string() {
+        assert_eq!(
+            "001 :Hi there".to_string(),
+            String::from(MessageBody::Reply(
+                ReplyType::PrvWelcome,
+                ":Hi there".parse().unwrap()
+            ))
+        );
+        assert_eq!(
+            "NICK me".to_string(),
+            String::from(MessageBody::Command(Command::Nick {
+                nickname: "me".parse().unwrap(),
+            }))
+        );
+    }
+}
+
+/// The most parameters a message may have, according to RFC 1459.
+pub const MAX_PARAMS: usize = 15;
+
+/// The parameters of a message. All but the last are single words, and the
+/// last, the trailing parameter, can be anything at all if it's written after
+/// a ":". We keep track of whether it was, so that a parsed line can be
+/// written out again exactly as it was received.
+///
+/// A message may have no more than 15 parameters, the last of which takes the
+/// rest of the line whether or not it starts with ":". Modern servers may
+/// relax this limit, so `unlimited()` and `parse_unlimited()` don't apply it.
+///
+/// Two lists of parameters are equal if their arguments are, however the last
+/// was written.
+#[derive(Clone, Debug, Default)]
+pub struct MessageParams {
+    args: Vec<String>,
+    trailing: bool,
+    unlimited: bool,
+}
+
+impl MessageParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn unlimited() -> Self {
+        MessageParams {
+            unlimited: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn parse_unlimited(raw: &str) -> Result<Self, ParseError> {
+        Self::parse(raw, true)
+    }
+
+    /// Add an argument. Once one has been added that can only be written as
+    /// the trailing parameter, because it's empty, contains a space or starts
+    /// with ":", no more can follow it.
+    pub fn push(&mut self, value: String) -> Result<(), ParseError> {
+        if self.trailing || (!self.unlimited && self.args.len() >= MAX_PARAMS) {
+            return Err(ParseError::new("MessageParams"));
+        }
+
+        self.trailing = value.is_empty() || value.contains(' ') || value.starts_with(':');
+        self.args.push(value);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&String> {
+        self.args.get(index)
+    }
+
+    /// Whether the last argument is written as the trailing parameter, after
+    /// a ":".
+    pub fn has_trailing(&self) -> bool {
+        self.trailing
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_string_with_prefix(self, prefix: &str) -> String {
+        let mut result = String::from(prefix);
+        if !self.is_empty() {
+            result.push(' ');
+            result.push_str(&String::from(self));
+        }
+        result
+    }
+
+    fn parse(raw: &str, unlimited: bool) -> Result<Self, ParseError> {
+        let mut params = MessageParams {
+            unlimited,
+            ..Self::default()
+        };
+        let mut rest = raw;
+
+        loop {
+            rest = rest.trim_start_matches(' ');
+            if rest.is_empty() {
+                break;
+            } else if let Some(trailing) = rest.strip_prefix(':') {
+                params.args.push(trailing.to_string());
+                params.trailing = true;
+                break;
+            } else if !unlimited && params.args.len() == MAX_PARAMS - 1 {
+                params.args.push(rest.to_string());
+                break;
+            } else if let Some(index) = rest.find(' ') {
+                params.args.push(rest[..index].to_string());
+                rest = &rest[index + 1..];
+            } else {
+                params.args.push(rest.to_string());
+                break;
+            }
+        }
+
+        Ok(params)
+    }
+}
+
+impl PartialEq for MessageParams {
+    fn eq(&self, other: &Self) -> bool {
+        self.args == other.args
+    }
+}
+
+impl Index<usize> for MessageParams {
+    type Output = String;
+
+    fn index(&self, index: usize) -> &String {
+        &self.args[index]
+    }
+}
+
+impl IntoIterator for MessageParams {
+    type Item = String;
+    type IntoIter = IntoIter<String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.args.into_iter()
+    }
+}
+
+impl FromStr for MessageParams {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Self::parse(raw, false)
+    }
+}
+
+impl From<MessageParams> for String {
+    fn from(message_params: MessageParams) -> String {
+        let mut result = String::new();
+        let last = message_params.args.len().saturating_sub(1);
+
+        for (index, arg) in message_params.args.into_iter().enumerate() {
+            if index > 0 {
+                result.push(' ');
+            }
+            if index == last && message_params.trailing {
+                result.push(':');
+            }
+            result.push_str(&arg);
+        }
+        result
+    }
+}
+
+impl From<Vec<String>> for MessageParams {
+    fn from(original: Vec<String>) -> Self {
+        let mut message_params = Self::new();
+        for arg in original.into_iter() {
+            message_params.push(arg).unwrap();
+        }
+        message_params
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_fun_call)]
+mod test_command_args {
+    use super::*;
+
+    fn params(args: &[&str], trailing: bool) -> MessageParams {
+        MessageParams {
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            trailing,
+            unlimited: false,
+        }
+    }
+
+    fn assert_parsed(expected: MessageParams, raw: &str) {
+        let parsed = raw.parse::<MessageParams>().unwrap();
+        assert_eq!(expected, parsed);
+        assert_eq!(expected.trailing, parsed.trailing, "{:?}", raw);
+    }
+
+    #[test]
+    fn valid() {
+        assert_parsed(params(&[], false), "");
+        assert_parsed(params(&[""], true), ":");
+        assert_parsed(params(&["abc", "def"], false), "abc def");
+        assert_parsed(params(&["a", "b", "c  d"], true), " a  b  :c  d");
+        assert_parsed(params(&["a:b", "cd"], true), "a:b :cd");
+        assert_parsed(params(&["abc", ""], true), "abc :");
+        assert_parsed(params(&[" a b c"], true), ": a b c");
+        assert_parsed(params(&["abc", ":-)"], true), "abc ::-)");
+        assert_parsed(
+            params(
+                &[
+                    "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14",
+                    "15 16",
+                ],
+                false,
+            ),
+            "1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16",
+        );
+        assert_parsed(
+            params(
+                &[
+                    "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14",
+                    "15 16",
+                ],
+                true,
+            ),
+            "1 2 3 4 5 6 7 8 9 10 11 12 13 14 :15 16",
+        );
+        assert_parsed(params(&["🥔️", "🥔️ 🥔️"], true), "🥔️ :🥔️ 🥔️");
+    }
+
+    #[test]
+    fn unlimited() {
+        let raw = "1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 :17 18";
+        let parsed = MessageParams::parse_unlimited(raw).unwrap();
+        assert_eq!(17, parsed.len());
+        assert_eq!("16", &parsed[15]);
+        assert_eq!("17 18", &parsed[16]);
+        assert_eq!(raw.to_string(), String::from(parsed));
+
+        let mut command_args = MessageParams::unlimited();
+        for i in 1..=20 {
+            command_args.push(i.to_string()).unwrap();
+        }
+        assert_eq!(20, command_args.len());
+
+        let message = Message::parse_unlimited(
+            ":irc.example.com 005 me A B C D E F G H I J K L M N O :are supported",
+        )
+        .unwrap();
+        match message.body {
+            MessageBody::Reply(_, params) => assert_eq!(17, params.len()),
+            _ => panic!("Expected a reply"),
+        }
+    }
+
+    #[test]
+    fn to_string() {
+        let mut command_args = MessageParams::new();
+        command_args.push("abc".to_string()).unwrap();
+        command_args.push("def".to_string()).unwrap();
+        assert_eq!("abc def".to_string(), String::from(command_args));
+
+        let mut command_args = MessageParams::new();
+        command_args.push("abc".to_string()).unwrap();
+        command_args.push("def ghi".to_string()).unwrap();
+        assert_eq!("abc :def ghi".to_string(), String::from(command_args));
+
+        let mut command_args = MessageParams::new();
+        command_args.push("abc".to_string()).unwrap();
+        command_args.push("".to_string()).unwrap();
+        assert_eq!("abc :".to_string(), String::from(command_args));
+
+        // Found by proptest: a leading colon must be escaped with another.
+        let mut command_args = MessageParams::new();
+        command_args.push(":-)".to_string()).unwrap();
+        assert_eq!("::-)".to_string(), String::from(command_args.clone()));
+        assert_eq!(Ok(command_args), "::-)".parse());
+    }
+
+    #[test]
+    fn round_trip() {
+        for raw in &[
+            "",
+            ":",
+            "me :Welcome",
+            "me :Welcome to the Internet Relay Network me!me@localhost",
+            "me = #channel :@op +voice plain",
+            "me #channel +nt",
+            "me #channel :",
+            "me #channel ::)",
+            "me * :Nickname is already in use",
+            "me CHANTYPES=# PREFIX=(ov)@+ :are supported by this server",
+            "1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16",
+            "1 2 3 4 5 6 7 8 9 10 11 12 13 14 :15",
+        ] {
+            assert_eq!(
+                raw.to_string(),
+                String::from(raw.parse::<MessageParams>().unwrap())
+            );
+        }
+
+        assert_eq!(
+            ":irc.example.com 001 me :Welcome".to_string(),
+            String::from(
+                ":irc.example.com 001 me :Welcome"
+                    .parse::<Message>()
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            "375 me".to_string(),
+            String::from("375 me".parse::<Message>().unwrap())
+        );
+    }
+
+    #[test]
+    fn push_validates() {
+        let mut command_args = MessageParams::new();
+        for i in 1..=15 {
+            command_args
+                .push(i.to_string())
+                .expect(&format!("No error expected on iteration {}", i));
+        }
+        assert!(command_args.push("16".to_string()).is_err());
+        assert_eq!(15, command_args.len());
+
+        let mut command_args = MessageParams::new();
+        command_args.push("nospaces".to_string()).unwrap();
+        command_args.push("some spaces".to_string()).unwrap();
+        assert!(command_args.push("more spaces".to_string()).is_err());
+        assert!(command_args.push("nomorespaces".to_string()).is_err());
+        assert_eq!(2, command_args.len());
+
+        let mut command_args = MessageParams::new();
+        command_args.push("".to_string()).unwrap();
+        assert!(command_args.push("nomore".to_string()).is_err());
+
+        let mut command_args = ":trailing".parse::<MessageParams>().unwrap();
+        assert!(command_args.has_trailing());
+        assert!(command_args.push("nomore".to_string()).is_err());
+    }
+
+    #[test]
+    fn equality() {
+        assert_eq!(
+            "me :Welcome".parse::<MessageParams>().unwrap(),
+            MessageParams::from(vec!["me".to_string(), "Welcome".to_string()])
+        );
+    }
+}
@@ -2,7 +2,7 @@ use super::{MessageParams, ParseError};
 use std::result::Result;
 use std::str::FromStr;
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Reply {
     pub reply_type: ReplyType,
     pub params: MessageParams,
@@ -36,7 +36,7 @@ impl From<Reply> for String {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum ReplyType {
     PrvWelcome,           // 001 - "Welcome to the Internet Relay Network
     PrvYourHost,          // 002 - "Your host is <servername>, running version <ver>"
@@ -123,7 +123,49 @@ pub enum ReplyType {
     RplAdminLoc2,         // 258 - ":<admin info>"
     RplAdminEmail,        // 259 - ":<admin info>"
     RplTryAgain,          // 263 - "<command> :Please wait a while and try again."
-    RplUnknown(u16),      // [23]xx
+    RplLogOn,             // 600 - "<nick> <user> <host> <ts> :logged online"
+    RplLogOff,            // 601 - "<nick> <user> <host> <ts> :logged offline"
+    RplWatchOff,          // 602 - "<nick> <user> <host> <ts> :stopped watching"
+    RplWatchStat,         // 603 - ":You have <n> and are on <n> WATCH entries"
+    RplNowOn,             // 604 - "<nick> <user> <host> <ts> :is online"
+    RplNowOff,            // 605 - "<nick> <user> <host> <ts> :is offline"
+    RplWatchList,         // 606 - ":<nick> *( " " <nick> )"
+    RplEndOfWatchList,    // 607 - ":End of WATCH <l/L/S>"
+    RplClearWatch,        // 608 - ":Your WATCH list is now empty"
+    RplNowIsAway,         // 609 - "<nick> <user> <host> <ts> :is away"
+    RplMonOnline,         // 730 - ":<target>[!<user>@<host>] *( "," <target>[!<user>@<host>] )"
+    RplMonOffline,        // 731 - ":<target> *( "," <target> )"
+    RplMonList,           // 732 - ":<target> *( "," <target> )"
+    RplEndOfMonList,      // 733 - ":End of MONITOR list"
+    RplStatsConn,         // 250 - ":Highest connection count: <n> (<n> connections received)"
+    RplLocalUsers,        // 265 - "[<u> <m>] :Current local users <u>, max <m>"
+    RplGlobalUsers,       // 266 - "[<u> <m>] :Current global users <u>, max <m>"
+    RplWhoIsCertFp,       // 276 - "<nick> :has client certificate fingerprint <fingerprint>"
+    RplWhoIsRegNick,      // 307 - "<nick> :has identified for this nick"
+    RplWhoIsSpecial,      // 320 - "<nick> :<special status>"
+    RplCreationTime,      // 329 - "<channel> <creationtime>"
+    RplWhoIsAccount,      // 330 - "<nick> <account> :is logged in as"
+    RplTopicWhoTime,      // 333 - "<channel> <nick> <setat>"
+    RplWhoIsBot,          // 335 - "<nick> :is a bot"
+    RplWhoIsActually,     // 338 - "<nick> [<user>@<host>] [<ip>] :Is actually using host"
+    RplWhoSpcRpl,         // 354 - "<fields requested with WHOX>"
+    RplWhoIsHost,         // 378 - "<nick> :is connecting from *@<host> <ip>"
+    RplWhoIsModes,        // 379 - "<nick> :is using modes <modes>"
+    RplVisibleHost,       // 396 - "<host> :is now your displayed host"
+    RplStartTls,          // 670 - ":STARTTLS successful, proceed with TLS handshake"
+    RplWhoIsSecure,       // 671 - "<nick> :is using a secure connection"
+    RplHelpStart,         // 704 - "<subject> :<first line of help section>"
+    RplHelpTxt,           // 705 - "<subject> :<line of help text>"
+    RplEndOfHelp,         // 706 - "<subject> :<last line of help text>"
+    RplKnock,             // 710 - "<channel> <nick>!<user>@<host> :has asked for an invite"
+    RplKnockDlvr,         // 711 - "<channel> :Your KNOCK has been delivered"
+    RplQuietList,         // 728 - "<channel> <mode> <mask> [<who> <set-ts>]"
+    RplEndOfQuietList,    // 729 - "<channel> <mode> :End of channel quiet list"
+    RplLoggedIn,          // 900 - "<nick>!<user>@<host> <account> :You are now logged in"
+    RplLoggedOut,         // 901 - "<nick>!<user>@<host> :You are now logged out"
+    RplSaslSuccess,       // 903 - ":SASL authentication successful"
+    RplSaslMechs,         // 908 - "<mechanisms> :are available SASL mechanisms"
+    RplUnknown(u16),      // [236789]xx
     ErrNoSuchNick,        // 401 - "<nickname> :No such nick/channel"
     ErrNoSuchServer,      // 402 - "<server name> :No such server"
     ErrNoSuchChannel,     // 403 - "<channel name> :No such channel"
@@ -177,10 +219,27 @@ pub enum ReplyType {
     ErrNoOperHost,        // 491 - ":No O-lines for your host"
     ErrUModeUnknownFlag,  // 501 - ":Unknown MODE flag"
     ErrUsersDontMatch,    // 502 - ":Cannot change mode for other users"
+    ErrTooManyWatch,      // 512 - "<nick> :Maximum size for WATCH-list is <limit> entries"
+    ErrMonListFull,       // 734 - "<limit> <targets> :Monitor list is full."
+    ErrInputTooLong,      // 417 - ":Input line was too long"
+    ErrHelpNotFound,      // 524 - "<subject> :No help available on this topic"
+    ErrInvalidKey,        // 525 - "<channel> :Key is not well-formed"
+    ErrStartTls,          // 691 - ":STARTTLS failed"
+    ErrInvalidModeParam,  // 696 - "<target> <mode char> <parameter> :<description>"
+    ErrTooManyKnock,      // 712 - "<channel> :Too many KNOCKs (channel)"
+    ErrChanOpen,          // 713 - "<channel> :Channel is open"
+    ErrKnockOnChan,       // 714 - "<channel> :You are already on that channel"
+    ErrNoPrivs,           // 723 - "<priv> :Insufficient oper privileges."
+    ErrMLockRestricted,   // 742 - "<channel> <modechar> <mlock> :MODE cannot be set"
+    ErrNickLocked,        // 902 - ":You must use a nick assigned to you"
+    ErrSaslFail,          // 904 - ":SASL authentication failed"
+    ErrSaslTooLong,       // 905 - ":SASL message too long"
+    ErrSaslAborted,       // 906 - ":SASL authentication aborted"
+    ErrSaslAlready,       // 907 - ":You have already authenticated using SASL"
     ErrUnknown(u16),      // [45]xx
 }
 
-#[allow(overlapping_patterns)]
+#[allow(clippy::match_overlapping_arm, clippy::zero_prefixed_literal)]
 impl FromStr for ReplyType {
     type Err = ParseError;
 
@@ -327,15 +386,76 @@ impl FromStr for ReplyType {
                 491 => ReplyType::ErrNoOperHost,
                 501 => ReplyType::ErrUModeUnknownFlag,
                 502 => ReplyType::ErrUsersDontMatch,
+                512 => ReplyType::ErrTooManyWatch,
+                600 => ReplyType::RplLogOn,
+                601 => ReplyType::RplLogOff,
+                602 => ReplyType::RplWatchOff,
+                603 => ReplyType::RplWatchStat,
+                604 => ReplyType::RplNowOn,
+                605 => ReplyType::RplNowOff,
+                606 => ReplyType::RplWatchList,
+                607 => ReplyType::RplEndOfWatchList,
+                608 => ReplyType::RplClearWatch,
+                609 => ReplyType::RplNowIsAway,
+                730 => ReplyType::RplMonOnline,
+                731 => ReplyType::RplMonOffline,
+                732 => ReplyType::RplMonList,
+                733 => ReplyType::RplEndOfMonList,
+                734 => ReplyType::ErrMonListFull,
+                250 => ReplyType::RplStatsConn,
+                265 => ReplyType::RplLocalUsers,
+                266 => ReplyType::RplGlobalUsers,
+                276 => ReplyType::RplWhoIsCertFp,
+                307 => ReplyType::RplWhoIsRegNick,
+                320 => ReplyType::RplWhoIsSpecial,
+                329 => ReplyType::RplCreationTime,
+                330 => ReplyType::RplWhoIsAccount,
+                333 => ReplyType::RplTopicWhoTime,
+                335 => ReplyType::RplWhoIsBot,
+                338 => ReplyType::RplWhoIsActually,
+                354 => ReplyType::RplWhoSpcRpl,
+                378 => ReplyType::RplWhoIsHost,
+                379 => ReplyType::RplWhoIsModes,
+                396 => ReplyType::RplVisibleHost,
+                670 => ReplyType::RplStartTls,
+                671 => ReplyType::RplWhoIsSecure,
+                704 => ReplyType::RplHelpStart,
+                705 => ReplyType::RplHelpTxt,
+                706 => ReplyType::RplEndOfHelp,
+                710 => ReplyType::RplKnock,
+                711 => ReplyType::RplKnockDlvr,
+                728 => ReplyType::RplQuietList,
+                729 => ReplyType::RplEndOfQuietList,
+                900 => ReplyType::RplLoggedIn,
+                901 => ReplyType::RplLoggedOut,
+                903 => ReplyType::RplSaslSuccess,
+                908 => ReplyType::RplSaslMechs,
+                417 => ReplyType::ErrInputTooLong,
+                524 => ReplyType::ErrHelpNotFound,
+                525 => ReplyType::ErrInvalidKey,
+                691 => ReplyType::ErrStartTls,
+                696 => ReplyType::ErrInvalidModeParam,
+                712 => ReplyType::ErrTooManyKnock,
+                713 => ReplyType::ErrChanOpen,
+                714 => ReplyType::ErrKnockOnChan,
+                723 => ReplyType::ErrNoPrivs,
+                742 => ReplyType::ErrMLockRestricted,
+                902 => ReplyType::ErrNickLocked,
+                904 => ReplyType::ErrSaslFail,
+                905 => ReplyType::ErrSaslTooLong,
+                906 => ReplyType::ErrSaslAborted,
+                907 => ReplyType::ErrSaslAlready,
                 0..=99 => ReplyType::PrvUnknown(raw_int),
                 200..=399 => ReplyType::RplUnknown(raw_int),
                 400..=599 => ReplyType::ErrUnknown(raw_int),
+                600..=999 => ReplyType::RplUnknown(raw_int),
                 _ => return Err(ParseError::new("ReplyType")),
             })
         }
     }
 }
 
+#[allow(clippy::zero_prefixed_literal)]
 impl From<ReplyType> for String {
     fn from(reply_type: ReplyType) -> String {
         format!(
@@ -426,6 +546,48 @@ impl From<ReplyType> for String {
                 ReplyType::RplAdminLoc2 => 258,
                 ReplyType::RplAdminEmail => 259,
                 ReplyType::RplTryAgain => 263,
+                ReplyType::RplLogOn => 600,
+                ReplyType::RplLogOff => 601,
+                ReplyType::RplWatchOff => 602,
+                ReplyType::RplWatchStat => 603,
+                ReplyType::RplNowOn => 604,
+                ReplyType::RplNowOff => 605,
+                ReplyType::RplWatchList => 606,
+                ReplyType::RplEndOfWatchList => 607,
+                ReplyType::RplClearWatch => 608,
+                ReplyType::RplNowIsAway => 609,
+                ReplyType::RplMonOnline => 730,
+                ReplyType::RplMonOffline => 731,
+                ReplyType::RplMonList => 732,
+                ReplyType::RplEndOfMonList => 733,
+                ReplyType::RplStatsConn => 250,
+                ReplyType::RplLocalUsers => 265,
+                ReplyType::RplGlobalUsers => 266,
+                ReplyType::RplWhoIsCertFp => 276,
+                ReplyType::RplWhoIsRegNick => 307,
+                ReplyType::RplWhoIsSpecial => 320,
+                ReplyType::RplCreationTime => 329,
+                ReplyType::RplWhoIsAccount => 330,
+                ReplyType::RplTopicWhoTime => 333,
+                ReplyType::RplWhoIsBot => 335,
+                ReplyType::RplWhoIsActually => 338,
+                ReplyType::RplWhoSpcRpl => 354,
+                ReplyType::RplWhoIsHost => 378,
+                ReplyType::RplWhoIsModes => 379,
+                ReplyType::RplVisibleHost => 396,
+                ReplyType::RplStartTls => 670,
+                ReplyType::RplWhoIsSecure => 671,
+                ReplyType::RplHelpStart => 704,
+                ReplyType::RplHelpTxt => 705,
+                ReplyType::RplEndOfHelp => 706,
+                ReplyType::RplKnock => 710,
+                ReplyType::RplKnockDlvr => 711,
+                ReplyType::RplQuietList => 728,
+                ReplyType::RplEndOfQuietList => 729,
+                ReplyType::RplLoggedIn => 900,
+                ReplyType::RplLoggedOut => 901,
+                ReplyType::RplSaslSuccess => 903,
+                ReplyType::RplSaslMechs => 908,
                 ReplyType::RplUnknown(code) => code,
                 ReplyType::ErrNoSuchNick => 401,
                 ReplyType::ErrNoSuchServer => 402,
@@ -480,6 +642,23 @@ impl From<ReplyType> for String {
                 ReplyType::ErrNoOperHost => 491,
                 ReplyType::ErrUModeUnknownFlag => 501,
                 ReplyType::ErrUsersDontMatch => 502,
+                ReplyType::ErrTooManyWatch => 512,
+                ReplyType::ErrMonListFull => 734,
+                ReplyType::ErrInputTooLong => 417,
+                ReplyType::ErrHelpNotFound => 524,
+                ReplyType::ErrInvalidKey => 525,
+                ReplyType::ErrStartTls => 691,
+                ReplyType::ErrInvalidModeParam => 696,
+                ReplyType::ErrTooManyKnock => 712,
+                ReplyType::ErrChanOpen => 713,
+                ReplyType::ErrKnockOnChan => 714,
+                ReplyType::ErrNoPrivs => 723,
+                ReplyType::ErrMLockRestricted => 742,
+                ReplyType::ErrNickLocked => 902,
+                ReplyType::ErrSaslFail => 904,
+                ReplyType::ErrSaslTooLong => 905,
+                ReplyType::ErrSaslAborted => 906,
+                ReplyType::ErrSaslAlready => 907,
                 ReplyType::ErrUnknown(code) => code,
             }
         )
@@ -492,7 +671,7 @@ mod tests {
 
     #[test]
     fn all_values_in_range() {
-        for number in (1..=99).chain(200..=599) {
+        for number in (1..=99).chain(200..=999) {
             let number_formatted = format!("{:0>3}", number);
             assert_eq!(
                 number_formatted,
@@ -504,4 +683,118 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn presence_tracking() {
+        assert_eq!(Ok(ReplyType::RplMonOnline), "730".parse::<ReplyType>());
+        assert_eq!(Ok(ReplyType::ErrMonListFull), "734".parse::<ReplyType>());
+        assert_eq!(Ok(ReplyType::RplLogOn), "600".parse::<ReplyType>());
+        assert_eq!(Ok(ReplyType::RplNowIsAway), "609".parse::<ReplyType>());
+        assert_eq!(Ok(ReplyType::RplUnknown(999)), "999".parse::<ReplyType>());
+        assert_eq!("730".to_string(), String::from(ReplyType::RplMonOnline));
+        assert_eq!("512".to_string(), String::from(ReplyType::ErrTooManyWatch));
+    }
+    fn assert_roundtrip(raw: &str, reply_type: ReplyType) {
+        let message = raw.parse::<super::super::Message>().expect(raw);
+        match &message.body {
+            super::super::MessageBody::Reply(parsed_type, _) => {
+                assert_eq!(&reply_type, parsed_type)
+            }
+            body => panic!("Unexpected message body {:?}", body),
+        }
+
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            message,
+            serde_json::from_str(&serde_json::to_string(&message).unwrap()).unwrap()
+        );
+
+        assert_eq!(raw.to_string(), String::from(message));
+    }
+
+    /// Examples are taken from the handshake in `tests/common` and from the
+    /// Modern IRC Client Protocol documentation.
+    #[test]
+    fn modern_numerics() {
+        assert_roundtrip(
+            ":irc.example.net 250 spudly :Highest connection count: 1 (4 connections received)",
+            ReplyType::RplStatsConn,
+        );
+        assert_roundtrip(
+            ":irc.example.net 265 spudly 1 1 :Current local users: 1, Max: 1",
+            ReplyType::RplLocalUsers,
+        );
+        assert_roundtrip(
+            ":irc.example.net 266 spudly 1 1 :Current global users: 1, Max: 1",
+            ReplyType::RplGlobalUsers,
+        );
+        assert_roundtrip(
+            ":irc.example.net 276 spudly Wiz :has client certificate fingerprint 0123abcd",
+            ReplyType::RplWhoIsCertFp,
+        );
+        assert_roundtrip(
+            ":irc.example.net 330 spudly Wiz wizard :is logged in as",
+            ReplyType::RplWhoIsAccount,
+        );
+        assert_roundtrip(
+            ":irc.example.net 333 spudly #channel Wiz 1547691506",
+            ReplyType::RplTopicWhoTime,
+        );
+        assert_roundtrip(
+            ":irc.example.net 338 spudly Wiz wiz@example.com 192.0.2.1 :Is actually using host",
+            ReplyType::RplWhoIsActually,
+        );
+        assert_roundtrip(
+            ":irc.example.net 378 spudly Wiz :is connecting from *@localhost 127.0.0.1",
+            ReplyType::RplWhoIsHost,
+        );
+        assert_roundtrip(
+            ":irc.example.net 396 spudly example.com/wiz :is now your displayed host",
+            ReplyType::RplVisibleHost,
+        );
+        assert_roundtrip(
+            ":irc.example.net 671 spudly Wiz :is using a secure connection",
+            ReplyType::RplWhoIsSecure,
+        );
+        assert_roundtrip(
+            ":irc.example.net 704 spudly index :Help topics available to users:",
+            ReplyType::RplHelpStart,
+        );
+        assert_roundtrip(
+            ":irc.example.net 705 spudly index :ACCEPT  ADMIN  AWAY  CHALLENGE",
+            ReplyType::RplHelpTxt,
+        );
+        assert_roundtrip(
+            ":irc.example.net 706 spudly index :End of /HELP.",
+            ReplyType::RplEndOfHelp,
+        );
+        assert_roundtrip(
+            ":irc.example.net 723 spudly kill:global :Insufficient oper privileges.",
+            ReplyType::ErrNoPrivs,
+        );
+        assert_roundtrip(
+            ":irc.example.net 728 spudly #channel q *!*@example.com Wiz 1547691506",
+            ReplyType::RplQuietList,
+        );
+        assert_roundtrip(
+            ":irc.example.net 729 spudly #channel q :End of channel quiet list",
+            ReplyType::RplEndOfQuietList,
+        );
+        assert_roundtrip(
+            ":irc.example.net 742 spudly #channel k nt :MODE cannot be set due to channel having an active MLOCK restriction policy",
+            ReplyType::ErrMLockRestricted,
+        );
+        assert_roundtrip(
+            ":irc.example.net 900 spudly spudly!pj@example.com spudly :You are now logged in as spudly",
+            ReplyType::RplLoggedIn,
+        );
+        assert_roundtrip(
+            ":irc.example.net 904 spudly :SASL authentication failed",
+            ReplyType::ErrSaslFail,
+        );
+        assert_roundtrip(
+            ":irc.example.net 908 spudly PLAIN,EXTERNAL :are available SASL mechanisms",
+            ReplyType::RplSaslMechs,
+        );
+    }
 }
@@ -0,0 +1,249 @@
+use super::ParseError;
+use std::result::Result;
+use std::str::FromStr;
+
+/// The IRCv3 tags attached to a message, such as `time`, `msgid` or `batch`.
+/// According to the message-tags specification:
+///
+/// ```text
+/// <message>       ::= ['@' <tags> <SPACE>] [':' <prefix> <SPACE> ] <command> <params> <crlf>
+/// <tags>          ::= <tag> [';' <tag>]*
+/// <tag>           ::= <key> ['=' <escaped_value>]
+/// <key>           ::= [ <client_prefix> ] [ <vendor> '/' ] <key_name>
+/// <client_prefix> ::= '+'
+/// <key_name>      ::= <non-empty sequence of ascii letters, digits, hyphens ('-')>
+/// <escaped_value> ::= <sequence of zero or more utf8 characters except NUL, CR, LF, semicolon (`;`) and SPACE>
+/// <vendor>        ::= <host>
+/// ```
+///
+/// A tag with an empty value is equivalent to a tag with no value at all, so
+/// both are represented as an empty string. Where a key is repeated, the last
+/// value wins. The order of the remaining tags is preserved.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MessageTags(Vec<(String, String)>);
+
+impl MessageTags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| &value[..])
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: String, value: String) {
+        if let Some(existing) = self.0.iter_mut().find(|(k, _)| *k == key) {
+            existing.1 = value;
+        } else {
+            self.0.push((key, value));
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.0.iter().position(|(k, _)| k == key)?;
+        Some(self.0.remove(index).1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (&k[..], &v[..]))
+    }
+}
+
+impl FromStr for MessageTags {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let mut tags = MessageTags::new();
+
+        for raw_tag in raw.split(';').filter(|raw_tag| !raw_tag.is_empty()) {
+            let (key, value) = match raw_tag.find('=') {
+                Some(index) => (&raw_tag[..index], unescape(&raw_tag[index + 1..])),
+                None => (raw_tag, String::new()),
+            };
+
+            if !is_valid_key(key) {
+                return Err(ParseError::new("MessageTags"));
+            }
+            tags.insert(key.to_string(), value);
+        }
+
+        Ok(tags)
+    }
+}
+
+impl From<MessageTags> for String {
+    fn from(tags: MessageTags) -> String {
+        tags.0
+            .into_iter()
+            .map(|(key, value)| {
+                if value.is_empty() {
+                    key
+                } else {
+                    format!("{}={}", key, escape(&value))
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(";")
+    }
+}
+
+pub(super) fn is_valid_key(key: &str) -> bool {
+    let key = key.strip_prefix('+').unwrap_or(key);
+    let key_name = match key.rfind('/') {
+        Some(index) if index > 0 => &key[index + 1..],
+        Some(_) => return false,
+        None => key,
+    };
+
+    !key_name.is_empty()
+        && key_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !key.contains(|c: char| c.is_whitespace() || c == '\0' || c == ';')
+}
+
+fn escape(raw: &str) -> String {
+    let mut result = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        match c {
+            ';' => result.push_str("\\:"),
+            ' ' => result.push_str("\\s"),
+            '\\' => result.push_str("\\\\"),
+            '\r' => result.push_str("\\r"),
+            '\n' => result.push_str("\\n"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn unescape(raw: &str) -> String {
+    let mut result = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(':') => result.push(';'),
+                Some('s') => result.push(' '),
+                Some('r') => result.push('\r'),
+                Some('n') => result.push('\n'),
+                // Invalid escapes drop the backslash, and a trailing
+                // backslash is dropped entirely.
+                Some(c) => result.push(c),
+                None => (),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test_message_tags {
+    use super::*;
+
+    fn tags(pairs: &[(&str, &str)]) -> MessageTags {
+        MessageTags(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn invalid() {
+        assert!("=value".parse::<MessageTags>().is_err());
+        assert!("key with space".parse::<MessageTags>().is_err());
+        assert!("example.com/".parse::<MessageTags>().is_err());
+        assert!("/key".parse::<MessageTags>().is_err());
+        assert!("k_y=1".parse::<MessageTags>().is_err());
+        assert!("🥔️=1".parse::<MessageTags>().is_err());
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(Ok(MessageTags::new()), "".parse::<MessageTags>());
+        assert_eq!(
+            Ok(tags(&[("id", "123AB"), ("rose", "")])),
+            "id=123AB;rose".parse::<MessageTags>()
+        );
+        assert_eq!(
+            Ok(tags(&[
+                ("url", ""),
+                ("netsplit", "tur,ty"),
+                ("+example.com/foo", "bar")
+            ])),
+            "url=;netsplit=tur,ty;+example.com/foo=bar".parse::<MessageTags>()
+        );
+        assert_eq!(
+            Ok(tags(&[("time", "2011-10-19T16:40:51.620Z")])),
+            "time=2011-10-19T16:40:51.620Z".parse::<MessageTags>()
+        );
+        // The last of any repeated keys wins.
+        assert_eq!(Ok(tags(&[("a", "2")])), "a=1;a=2".parse::<MessageTags>());
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            Ok(tags(&[("a", "semi;colon space\\back\r\n")])),
+            "a=semi\\:colon\\sspace\\\\back\\r\\n".parse::<MessageTags>()
+        );
+        assert_eq!(
+            Ok(tags(&[("a", "bx")])),
+            "a=\\b\\x\\".parse::<MessageTags>()
+        );
+        assert_eq!(
+            "a=semi\\:colon\\sspace\\\\back\\r\\n".to_string(),
+            String::from(tags(&[("a", "semi;colon space\\back\r\n")]))
+        );
+    }
+
+    #[test]
+    fn into_string() {
+        assert_eq!(
+            "id=123AB;rose;+example.com/foo=bar".to_string(),
+            String::from(tags(&[
+                ("id", "123AB"),
+                ("rose", ""),
+                ("+example.com/foo", "bar")
+            ]))
+        );
+    }
+
+    #[test]
+    fn accessors() {
+        let mut tags = tags(&[("msgid", "abc"), ("rose", "")]);
+        assert_eq!(Some("abc"), tags.get("msgid"));
+        assert_eq!(Some(""), tags.get("rose"));
+        assert!(tags.contains("rose"));
+        assert_eq!(None, tags.get("time"));
+
+        tags.insert("msgid".to_string(), "def".to_string());
+        tags.insert("time".to_string(), "now".to_string());
+        assert_eq!(3, tags.len());
+        assert_eq!(Some("def"), tags.get("msgid"));
+        assert_eq!(Some("".to_string()), tags.remove("rose"));
+        assert_eq!(
+            vec![("msgid", "def"), ("time", "now")],
+            tags.iter().collect::<Vec<(&str, &str)>>()
+        );
+    }
+}
@@ -0,0 +1,484 @@
+//! Recording a session with a server, and replaying it later to reproduce
+//! whatever happened.
+//!
+//! A recording has a line for every line sent or received, with the time
+//! since the recording started, and `<<` for lines received or `>>` for lines
+//! sent:
+//!
+//! ```text
+//! 0.000 >> NICK spudly
+//! 0.000 >> USER pjohnson 0 * :Potato Johnson
+//! 0.153 << :irc.example.com 001 spudly :Welcome to the network
+//! ```
+//!
+//! Lines are saved exactly as they were sent or received, without the line
+//! break, so a recording may not be valid UTF-8. Passwords aren't redacted,
+//! so recordings must be kept private.
+use super::{Clock, Connection, ParseError, SystemClock, VirtualClock};
+use log::warn;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufRead, LineWriter, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    /// A line received from the server, recorded as `<<`.
+    Received,
+    /// A line sent to the server, recorded as `>>`.
+    Sent,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entry {
+    /// The time since the recording started.
+    pub elapsed: Duration,
+    pub direction: Direction,
+    pub line: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Recording {
+    pub entries: Vec<Entry>,
+}
+
+impl Recording {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    pub fn parse(raw: &[u8]) -> Result<Self, ParseError> {
+        let entries = raw
+            .split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(parse_entry)
+            .collect::<Result<_, _>>()?;
+        Ok(Recording { entries })
+    }
+
+    /// The lines that were sent, to compare with what's sent during a replay.
+    pub fn sent(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.direction == Direction::Sent)
+            .map(|entry| String::from_utf8_lossy(&entry.line).into_owned())
+            .collect()
+    }
+}
+
+fn parse_entry(raw: &[u8]) -> Result<Entry, ParseError> {
+    let mut parts = raw.splitn(3, |&byte| byte == b' ');
+    let (elapsed, direction, line) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(elapsed), Some(direction), Some(line)) => (elapsed, direction, line),
+        _ => return Err(ParseError::new("Recording")),
+    };
+
+    let elapsed = std::str::from_utf8(elapsed)
+        .ok()
+        .and_then(parse_elapsed)
+        .ok_or_else(|| ParseError::new("Recording"))?;
+    let direction = match direction {
+        b"<<" => Direction::Received,
+        b">>" => Direction::Sent,
+        _ => return Err(ParseError::new("Recording")),
+    };
+
+    Ok(Entry {
+        elapsed,
+        direction,
+        line: line.to_vec(),
+    })
+}
+
+/// Seconds and milliseconds, such as `12.345`.
+fn parse_elapsed(raw: &str) -> Option<Duration> {
+    let (seconds, millis) = raw.split_once('.')?;
+    if millis.len() != 3
+        || !raw
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || byte == b'.')
+    {
+        return None;
+    }
+    Some(Duration::from_secs(seconds.parse().ok()?) + Duration::from_millis(millis.parse().ok()?))
+}
+
+/// Saves everything sent and received over a `Connection` to a recording.
+/// Clones share the same recording.
+#[derive(Clone)]
+pub struct Recorder {
+    log: Rc<RefCell<Log>>,
+}
+
+struct Log {
+    file: Box<dyn Write>,
+    clock: Box<dyn Clock>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Record to any writer, with times from `clock`.
+    pub fn new(file: Box<dyn Write>, clock: Box<dyn Clock>) -> Self {
+        let start = clock.now();
+        Recorder {
+            log: Rc::new(RefCell::new(Log { file, clock, start })),
+        }
+    }
+
+    /// Record to a new file, replacing any that's already there. Each line
+    /// is written as soon as it's recorded, so nothing is lost if we crash.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = LineWriter::new(File::create(path)?);
+        Ok(Self::new(Box::new(file), Box::new(SystemClock)))
+    }
+
+    /// Wrap the reader and writer of a `Connection`, recording everything
+    /// that passes through them.
+    pub fn wrap(
+        &self,
+        reader: Box<dyn BufRead>,
+        writer: Box<dyn Write>,
+    ) -> (Box<dyn BufRead>, Box<dyn Write>) {
+        (
+            Box::new(RecordingReader {
+                inner: reader,
+                recorder: self.clone(),
+                partial: Vec::new(),
+            }),
+            Box::new(RecordingWriter {
+                inner: writer,
+                recorder: self.clone(),
+                partial: Vec::new(),
+            }),
+        )
+    }
+
+    fn record(&self, direction: Direction, partial: &mut Vec<u8>, bytes: &[u8]) {
+        partial.extend_from_slice(bytes);
+        while let Some(index) = partial.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = partial.drain(..=index).collect();
+            let line = line.strip_suffix(b"\n").unwrap_or(&line);
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+            let mut log = self.log.borrow_mut();
+            let elapsed = log.clock.now() - log.start;
+            let marker = match direction {
+                Direction::Received => "<<",
+                Direction::Sent => ">>",
+            };
+            let result = write!(
+                log.file,
+                "{}.{:03} {} ",
+                elapsed.as_secs(),
+                elapsed.subsec_millis(),
+                marker
+            )
+            .and_then(|_| log.file.write_all(line))
+            .and_then(|_| log.file.write_all(b"\n"));
+            if let Err(e) = result {
+                warn!("Could not write to the recording: {}", e);
+            }
+        }
+    }
+}
+
+struct RecordingReader {
+    inner: Box<dyn BufRead>,
+    recorder: Recorder,
+    partial: Vec<u8>,
+}
+
+impl Read for RecordingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.recorder
+            .record(Direction::Received, &mut self.partial, &buf[..len]);
+        Ok(len)
+    }
+}
+
+impl BufRead for RecordingReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // The bytes being consumed are still in the inner buffer, so this
+        // doesn't read anything new.
+        if let Ok(available) = self.inner.fill_buf() {
+            let consumed = &available[..amt.min(available.len())];
+            self.recorder
+                .record(Direction::Received, &mut self.partial, consumed);
+        }
+        self.inner.consume(amt);
+    }
+}
+
+struct RecordingWriter {
+    inner: Box<dyn Write>,
+    recorder: Recorder,
+    partial: Vec<u8>,
+}
+
+impl Write for RecordingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.recorder
+            .record(Direction::Sent, &mut self.partial, &buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Plays a recording back to a `Connection`, as if the server were sending
+/// the same lines at the same times. Time is kept by a `VirtualClock`, which
+/// only moves when the replay is stepped, so a replay runs as fast as it can
+/// but everything that depends on time happens exactly as it did:
+///
+/// ```
+/// use crikey_irc_common::{Recording, Replay};
+///
+/// let recording = Recording::parse(b"0.000 >> NICK spudly\n0.153 << PING irc.example.com\n")?;
+/// let replay = Replay::new(recording);
+/// let mut connection = replay.connection();
+///
+/// while replay.step() {
+///     while let Some(message) = connection.poll() {
+///         assert_eq!("PING irc.example.com", String::from(message));
+///     }
+/// }
+/// # Ok::<(), crikey_irc_common::ParseError>(())
+/// ```
+///
+/// Clones share the same replay.
+#[derive(Clone)]
+pub struct Replay {
+    state: Rc<RefCell<ReplayState>>,
+    clock: VirtualClock,
+}
+
+struct ReplayState {
+    start: Instant,
+    entries: VecDeque<Entry>,
+    to_client: VecDeque<u8>,
+    from_client: Vec<u8>,
+    sent: Vec<String>,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        let clock = VirtualClock::new();
+        Replay {
+            state: Rc::new(RefCell::new(ReplayState {
+                start: clock.now(),
+                entries: recording.entries.into(),
+                to_client: VecDeque::new(),
+                from_client: Vec::new(),
+                sent: Vec::new(),
+            })),
+            clock,
+        }
+    }
+
+    /// The clock that the replay keeps time with, for whatever reads the
+    /// connection.
+    pub fn clock(&self) -> VirtualClock {
+        self.clock.clone()
+    }
+
+    /// A connection that reads the lines received in the recording, and
+    /// keeps whatever is written to it for `sent()`. Like a non-blocking
+    /// socket, reading returns `WouldBlock` until the replay is stepped to
+    /// the next line received.
+    pub fn connection(&self) -> Connection {
+        Connection::new(
+            Box::new(io::BufReader::new(self.clone())),
+            Box::new(self.clone()),
+        )
+    }
+
+    /// Move the clock to the time of the next line in the recording, and if
+    /// it's one that was received, make it available to read. Lines that
+    /// were sent only move the clock, so that anything sent on a timer is
+    /// sent at the time it was recorded. Returns `false` once the recording
+    /// is over.
+    pub fn step(&self) -> bool {
+        let mut state = self.state.borrow_mut();
+        let entry = match state.entries.pop_front() {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        self.clock.advance_to(state.start + entry.elapsed);
+        if entry.direction == Direction::Received {
+            state.to_client.extend(entry.line);
+            state.to_client.extend(b"\r\n");
+        }
+        true
+    }
+
+    /// Whether every line in the recording has been played.
+    pub fn is_finished(&self) -> bool {
+        self.state.borrow().entries.is_empty()
+    }
+
+    /// Take every line written to the connection so far, without line
+    /// endings.
+    pub fn sent(&self) -> Vec<String> {
+        self.state.borrow_mut().sent.drain(..).collect()
+    }
+}
+
+impl Read for Replay {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        if state.to_client.is_empty() {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        let len = buf.len().min(state.to_client.len());
+        for (byte, queued) in buf.iter_mut().zip(state.to_client.drain(..len)) {
+            *byte = queued;
+        }
+        Ok(len)
+    }
+}
+
+impl Write for Replay {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        state.from_client.extend_from_slice(buf);
+        while let Some(index) = state.from_client.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = state.from_client.drain(..=index).collect();
+            let line = String::from_utf8_lossy(&line)
+                .trim_end_matches(['\r', '\n'])
+                .to_string();
+            state.sent.push(line);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_recording {
+    use super::*;
+    use crate::Command;
+
+    /// A writer whose contents can still be read after it's been boxed.
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn records() {
+        let file = Shared::default();
+        let clock = VirtualClock::new();
+        let recorder = Recorder::new(Box::new(file.clone()), Box::new(clock.clone()));
+
+        let received: &[u8] = b"PING one\r\nPRIVMSG #channel :Sk\xe5l\r\nPING tw";
+        let (reader, writer) = recorder.wrap(Box::new(received), Box::new(io::sink()));
+        let mut connection = Connection::new(reader, writer);
+
+        connection
+            .send_command(Command::Nick {
+                nickname: "spudly".parse().unwrap(),
+            })
+            .unwrap();
+        clock.advance(Duration::from_millis(1_500));
+        assert!(connection.poll().is_some());
+        clock.advance(Duration::from_millis(20));
+        assert!(connection.poll().is_some());
+        connection.send_command_raw("PONG one".to_string()).unwrap();
+
+        assert_eq!(
+            b"0.000 >> NICK spudly\n1.500 << PING one\n1.520 << PRIVMSG #channel :Sk\xe5l\n1.520 >> PONG one\n"
+                .to_vec(),
+            *file.0.borrow()
+        );
+    }
+
+    #[test]
+    fn parses() {
+        let recording = Recording::parse(b"0.000 >> NICK spudly\n12.345 << PING :a b\n\n").unwrap();
+        assert_eq!(
+            vec![
+                Entry {
+                    elapsed: Duration::from_secs(0),
+                    direction: Direction::Sent,
+                    line: b"NICK spudly".to_vec(),
+                },
+                Entry {
+                    elapsed: Duration::from_millis(12_345),
+                    direction: Direction::Received,
+                    line: b"PING :a b".to_vec(),
+                },
+            ],
+            recording.entries
+        );
+        assert_eq!(vec!["NICK spudly"], recording.sent());
+
+        for raw in &[
+            &b"NICK spudly\n"[..],
+            b"x >> NICK spudly\n",
+            b"1.0 >> NICK\n",
+            b"1.000 <> NICK\n",
+        ] {
+            assert_eq!(
+                Err(ParseError::new("Recording")),
+                Recording::parse(raw),
+                "{:?}",
+                String::from_utf8_lossy(raw)
+            );
+        }
+    }
+
+    #[test]
+    fn replays() {
+        let recording = Recording::parse(
+            b"0.000 >> NICK spudly\n1.000 << PING one\n61.000 >> PING two\n61.500 << PONG two\n",
+        )
+        .unwrap();
+        let replay = Replay::new(recording);
+        let clock = replay.clock();
+        let start = clock.now();
+        let mut connection = replay.connection();
+
+        assert_eq!(None, connection.poll());
+        assert!(replay.step());
+        assert_eq!(None, connection.poll());
+        assert!(replay.step());
+        assert_eq!(start + Duration::from_secs(1), clock.now());
+        assert_eq!(Some("PING one".parse().unwrap()), connection.poll());
+        connection.send_command_raw("PONG one".to_string()).unwrap();
+
+        assert!(replay.step());
+        assert_eq!(start + Duration::from_secs(61), clock.now());
+        assert!(replay.step());
+        assert_eq!(Some("PONG two".parse().unwrap()), connection.poll());
+        assert!(replay.is_finished());
+        assert!(!replay.step());
+
+        assert_eq!(vec!["PONG one"], replay.sent());
+    }
+}
@@ -0,0 +1,237 @@
+//! Serde support, enabled with the `serde` feature.
+//!
+//! Entities and other syntax elements are represented by the same strings
+//! they have on the wire, so a `Nickname` is `"spudly"` and a `Recipient` is
+//! `"#channel"` or `"nick!user@host"`, and are validated on the way back in.
+//! Commands are objects tagged with the variant name:
+//!
+//! ```json
+//! {"command": "Privmsg", "recipients": "#channel", "message": "hi"}
+//! ```
+//!
+//! Reply parameters are arrays of strings, and message tags are objects.
+use super::entity::{Channel, ChannelKey, Nickname, Recipient, Sender, Servername, Username};
+use super::message::{MessageParams, MessageTags, ReplyType};
+use super::syntax::{
+    CapSubcommand, Casemapping, HistorySelector, KeywordList, ServerMask, StatsQuery, Target,
+    TargetMask, UserMask, WatchEntry, Wildcard,
+};
+use super::MAX_PARAMS;
+use serde::de::{Deserialize, Deserializer, Error, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+macro_rules! wire_string {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl Serialize for $t {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_str(&String::from(self.clone()))
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $t {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let raw = String::deserialize(deserializer)?;
+                    raw.parse().map_err(|_| {
+                        D::Error::custom(format!("invalid {}: {:?}", stringify!($t), raw))
+                    })
+                }
+            }
+        )*
+    };
+}
+
+wire_string!(
+    CapSubcommand,
+    Casemapping,
+    Channel,
+    ChannelKey,
+    HistorySelector,
+    Nickname,
+    Recipient,
+    ReplyType,
+    Sender,
+    ServerMask,
+    Servername,
+    StatsQuery,
+    Target,
+    TargetMask,
+    UserMask,
+    Username,
+    WatchEntry,
+    Wildcard,
+);
+
+impl<T> Serialize for KeywordList<T>
+where
+    T: FromStr + Into<String> + Clone,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&String::from(self.clone()))
+    }
+}
+
+impl<'de, T> Deserialize<'de> for KeywordList<T>
+where
+    T: FromStr + Into<String>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse()
+            .map_err(|_| D::Error::custom(format!("invalid KeywordList: {:?}", raw)))
+    }
+}
+
+impl Serialize for MessageParams {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq((0..self.len()).map(|index| &self[index]))
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageParams {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let args = Vec::<String>::deserialize(deserializer)?;
+        let mut params = if args.len() > MAX_PARAMS {
+            MessageParams::unlimited()
+        } else {
+            MessageParams::new()
+        };
+        for param in args {
+            params
+                .push(param)
+                .map_err(|_| D::Error::custom("invalid MessageParams"))?;
+        }
+        Ok(params)
+    }
+}
+
+impl Serialize for MessageTags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self.iter() {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageTags {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MessageTagsVisitor)
+    }
+}
+
+struct MessageTagsVisitor;
+
+impl<'de> Visitor<'de> for MessageTagsVisitor {
+    type Value = MessageTags;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of message tags")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut tags = MessageTags::new();
+        while let Some((key, value)) = access.next_entry::<String, String>()? {
+            tags.insert(key, value);
+        }
+        Ok(tags)
+    }
+}
+
+#[cfg(test)]
+mod test_serialization {
+    use super::super::{Command, Message};
+    use super::*;
+    use serde_json::json;
+
+    fn assert_json<T>(value: T, expected: serde_json::Value)
+    where
+        T: Serialize + for<'de> Deserialize<'de> + PartialEq + fmt::Debug,
+    {
+        // Compare via a string, since `serde_json::Value` doesn't keep the
+        // order of message tags.
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(
+            expected,
+            serde_json::from_str::<serde_json::Value>(&json).unwrap()
+        );
+        assert_eq!(value, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn entities() {
+        assert_json("spudly".parse::<Nickname>().unwrap(), json!("spudly"));
+        assert_json("#channel".parse::<Channel>().unwrap(), json!("#channel"));
+        assert_json(
+            "nick!user@user/spudly".parse::<Recipient>().unwrap(),
+            json!("nick!user@user/spudly"),
+        );
+        assert_json(
+            "nick!user@host".parse::<Sender>().unwrap(),
+            json!("nick!user@host"),
+        );
+        assert_json(
+            "#a,#b".parse::<KeywordList<Channel>>().unwrap(),
+            json!("#a,#b"),
+        );
+        assert_json("433".parse::<ReplyType>().unwrap(), json!("433"));
+        assert_json("$a:spudly".parse::<UserMask>().unwrap(), json!("$a:spudly"));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(serde_json::from_value::<Nickname>(json!("2hot4u")).is_err());
+        assert!(serde_json::from_value::<Channel>(json!("channel")).is_err());
+        assert!(serde_json::from_value::<Nickname>(json!(42)).is_err());
+        assert!(serde_json::from_value::<Command>(json!({"command": "Bogus"})).is_err());
+    }
+
+    #[test]
+    fn commands() {
+        assert_json(
+            "PRIVMSG #channel,spudly :hello there"
+                .parse::<Command>()
+                .unwrap(),
+            json!({
+                "command": "Privmsg",
+                "recipients": "#channel,spudly",
+                "message": "hello there",
+            }),
+        );
+        assert_json(
+            "KICK #Finnish John".parse::<Command>().unwrap(),
+            json!({
+                "command": "Kick",
+                "channels": "#Finnish",
+                "nicknames": "John",
+                "comment": null,
+            }),
+        );
+        assert_json(Command::CapEnd, json!({"command": "CapEnd"}));
+    }
+
+    #[test]
+    fn messages() {
+        assert_json(
+            "@time=2020-01-01T00:00:00.000Z;+example=a\\sb :irc.example.com 001 spudly :Welcome to IRC"
+                .parse::<Message>()
+                .unwrap(),
+            json!({
+                "tags": {"time": "2020-01-01T00:00:00.000Z", "+example": "a b"},
+                "sender": "irc.example.com",
+                "body": {"Reply": ["001", ["spudly", "Welcome to IRC"]]},
+            }),
+        );
+        assert_json(
+            ":spudly!user@host NICK potato".parse::<Message>().unwrap(),
+            json!({
+                "tags": {},
+                "sender": "spudly!user@host",
+                "body": {"Command": {"command": "Nick", "nickname": "potato"}},
+            }),
+        );
+    }
+}
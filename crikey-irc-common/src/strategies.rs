@@ -0,0 +1,300 @@
+//! Proptest strategies for generating valid protocol values, and the
+//! properties that every one of them must survive a round trip through its
+//! wire format unchanged.
+//!
+//! Only the simplest entities are generated as strings and parsed. Everything
+//! else is assembled from its parts, so that a value the parser would never
+//! produce, but which we might construct and send, is still caught if it
+//! doesn't survive the trip.
+use super::entity::{Channel, Host, Nickname, Recipient, Sender, Servername, Username};
+use super::message::{Command, Message, MessageBody, MessageParams, MessageTags, ReplyType};
+use super::syntax::{KeywordList, ServerMask, TargetMask};
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Parse a string generated from a regular expression that only matches valid
+/// values.
+fn parsed<T>(pattern: &'static str) -> impl Strategy<Value = T>
+where
+    T: FromStr + std::fmt::Debug,
+    T::Err: std::fmt::Debug,
+{
+    pattern.prop_map(|raw| raw.parse().unwrap())
+}
+
+pub fn nickname() -> impl Strategy<Value = Nickname> {
+    parsed("[a-zA-Z\\[\\]\\\\`_^{|}][a-zA-Z0-9\\[\\]\\\\`_^{|}-]{0,15}")
+}
+
+pub fn username() -> impl Strategy<Value = Username> {
+    parsed("~?[a-zA-Z0-9_.-]{1,10}")
+}
+
+pub fn servername() -> impl Strategy<Value = Servername> {
+    parsed("[a-z][a-z0-9-]{0,8}[a-z0-9](\\.[a-z][a-z0-9]{0,8}){1,3}")
+}
+
+pub fn host() -> impl Strategy<Value = Host> {
+    prop_oneof![
+        any::<Ipv4Addr>().prop_map(|ip| ip.to_string().parse().unwrap()),
+        any::<Ipv6Addr>().prop_map(|ip| ip.to_string().parse().unwrap()),
+        servername().prop_map(|servername| String::from(servername).parse().unwrap()),
+        parsed("[a-z]{1,8}/[a-z0-9./-]{1,16}"),
+    ]
+}
+
+pub fn channel() -> impl Strategy<Value = Channel> {
+    prop_oneof![
+        parsed("[#&+][a-zA-Z0-9_.\\-\\[\\]|^!@éø🥔]{1,20}"),
+        parsed("![A-Z0-9]{5}[a-z0-9_]{1,10}"),
+        parsed("#[a-z]{1,10}:\\*\\.[a-z]{1,8}\\.[a-z]{2,3}"),
+    ]
+}
+
+pub fn server_mask() -> impl Strategy<Value = ServerMask> {
+    parsed("[a-z*?]{1,8}(\\.[a-z*?]{1,8})*\\.[a-z]{1,8}")
+}
+
+pub fn target_mask() -> impl Strategy<Value = TargetMask> {
+    prop_oneof![
+        parsed("\\$[a-z*?]{1,8}(\\.[a-z*?]{1,8})*\\.[a-z]{1,8}"),
+        parsed("#[a-z*?]{1,8}(\\.[a-z*?]{1,8})*\\.[a-z]{1,8}"),
+    ]
+}
+
+pub fn sender() -> impl Strategy<Value = Sender> {
+    prop_oneof![
+        servername().prop_map(Sender::Server),
+        // RFC 2812 only allows a user alongside a host.
+        (nickname(), option::of((option::of(username()), host()))).prop_map(
+            |(nickname, user_host)| match user_host {
+                Some((user, host)) => Sender::User {
+                    nickname,
+                    user,
+                    host: Some(host),
+                },
+                None => Sender::User {
+                    nickname,
+                    user: None,
+                    host: None,
+                },
+            }
+        ),
+    ]
+}
+
+pub fn recipient() -> impl Strategy<Value = Recipient> {
+    prop_oneof![
+        channel().prop_map(Recipient::Channel),
+        ("[~@%]", channel()).prop_map(|(prefix, channel)| {
+            Recipient::StatusChannel(prefix.chars().next().unwrap(), channel)
+        }),
+        nickname().prop_map(Recipient::Nickname),
+        (nickname(), username(), host()).prop_map(|(nickname, user, host)| {
+            Recipient::NicknameUserHost(nickname, user, host)
+        }),
+        parsed::<TargetMask>("\\$[a-z*?]{1,8}\\.[a-z]{1,8}").prop_map(Recipient::TargetMask),
+        (username(), host()).prop_map(|(user, host)| Recipient::UserHost(user, host)),
+        (username(), host(), servername()).prop_map(|(user, host, servername)| {
+            Recipient::UserHostServername(user, host, servername)
+        }),
+        (username(), servername())
+            .prop_map(|(user, servername)| Recipient::UserServername(user, servername)),
+    ]
+}
+
+fn keyword_list<T, S>(element: S, min: usize) -> impl Strategy<Value = KeywordList<T>>
+where
+    T: FromStr + Into<String> + std::fmt::Debug,
+    S: Strategy<Value = T>,
+{
+    vec(element, min..5).prop_map(KeywordList::from)
+}
+
+/// A single word that can appear anywhere in a message's parameters.
+fn middle() -> impl Strategy<Value = String> {
+    "[^\0\r\n :][^\0\r\n ]{0,15}"
+}
+
+/// Free text, which can only be the trailing parameter.
+fn text() -> impl Strategy<Value = String> {
+    "[^\0\r\n]{0,40}"
+}
+
+pub fn message_params() -> impl Strategy<Value = MessageParams> {
+    (vec(middle(), 0..14), option::of(text())).prop_map(|(mut args, trailing)| {
+        args.extend(trailing);
+        MessageParams::from(args)
+    })
+}
+
+/// A line of parameters as a server might send it, with the trailing
+/// parameter written after a ":" whether or not it needs one.
+pub fn raw_params() -> impl Strategy<Value = String> {
+    (vec(middle(), 0..14), option::of(text())).prop_map(|(mut args, trailing)| {
+        if let Some(trailing) = trailing {
+            args.push(format!(":{}", trailing));
+        }
+        args.join(" ")
+    })
+}
+
+pub fn command() -> impl Strategy<Value = Command> {
+    prop_oneof![
+        middle().prop_map(|password| Command::Pass { password }),
+        nickname().prop_map(|nickname| Command::Nick { nickname }),
+        (username(), 0..16u8, text()).prop_map(|(username, mode, realname)| Command::User {
+            username,
+            mode,
+            realname,
+        }),
+        option::of(text()).prop_map(|message| Command::Quit { message }),
+        (
+            keyword_list(channel(), 1),
+            keyword_list(parsed("[a-z0-9]{1,10}"), 0)
+        )
+            .prop_filter("more keys than channels", |(channels, keys)| {
+                keys.len() <= channels.len()
+            })
+            .prop_map(|(channels, keys)| Command::Join { channels, keys }),
+        (keyword_list(channel(), 1), option::of(text()))
+            .prop_map(|(channels, message)| Command::Part { channels, message }),
+        (channel(), option::of(text()))
+            .prop_map(|(channel, topic)| Command::Topic { channel, topic }),
+        (nickname(), channel())
+            .prop_map(|(nickname, channel)| Command::Invite { nickname, channel }),
+        (channel(), keyword_list(nickname(), 1), option::of(text())).prop_map(
+            |(channel, nicknames, comment)| Command::Kick {
+                channels: KeywordList::from(vec![channel]),
+                nicknames,
+                comment,
+            }
+        ),
+        (keyword_list(recipient(), 1), text()).prop_map(|(recipients, message)| Command::Privmsg {
+            recipients,
+            message
+        }),
+        (keyword_list(recipient(), 1), text()).prop_map(|(recipients, message)| Command::Notice {
+            recipients,
+            message
+        }),
+        option::of(server_mask()).prop_map(|target| Command::Motd { target }),
+        option::of(text()).prop_map(|message| Command::Away { message }),
+        (nickname(), text()).prop_map(|(nickname, comment)| Command::Kill { nickname, comment }),
+    ]
+}
+
+pub fn message() -> impl Strategy<Value = Message> {
+    (
+        vec(("[a-z][a-z0-9-]{0,8}", "[^\0\r\n]{0,10}"), 0..3),
+        option::of(sender()),
+        prop_oneof![
+            command().prop_map(MessageBody::Command),
+            message_params().prop_map(|params| MessageBody::Reply(ReplyType::PrvWelcome, params)),
+        ],
+    )
+        .prop_map(|(raw_tags, sender, body)| {
+            let mut tags = MessageTags::new();
+            for (key, value) in raw_tags {
+                tags.insert(key, value);
+            }
+            Message { tags, sender, body }
+        })
+}
+
+#[cfg(test)]
+mod test_roundtrip {
+    use super::*;
+
+    fn roundtrip<T>(value: T) -> Result<(), TestCaseError>
+    where
+        T: FromStr + Into<String> + Clone + PartialEq + std::fmt::Debug,
+        T::Err: std::fmt::Debug,
+    {
+        let raw: String = value.clone().into();
+        prop_assert_eq!(Ok(value), raw.parse::<T>().map_err(|e| format!("{:?}", e)));
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn nicknames(nickname in nickname()) {
+            roundtrip(nickname)?;
+        }
+
+        #[test]
+        fn channels(channel in channel()) {
+            roundtrip(channel)?;
+        }
+
+        #[test]
+        fn senders(sender in sender()) {
+            roundtrip(sender)?;
+        }
+
+        #[test]
+        fn recipients(recipient in recipient()) {
+            roundtrip(recipient)?;
+        }
+
+        #[test]
+        fn target_masks(target_mask in target_mask()) {
+            roundtrip(target_mask)?;
+        }
+
+        #[test]
+        fn params(params in message_params()) {
+            roundtrip(params)?;
+        }
+
+        #[test]
+        fn raw_params_exactly(raw in raw_params()) {
+            let params = raw.parse::<MessageParams>().map_err(|e| format!("{:?}", e));
+            prop_assert_eq!(Ok(raw), params.map(String::from));
+        }
+
+        #[test]
+        fn commands(command in command()) {
+            roundtrip(command)?;
+        }
+
+        #[test]
+        fn messages(message in message()) {
+            roundtrip(message)?;
+        }
+
+        #[test]
+        fn never_panics(raw in "\\PC*") {
+            let _ = raw.parse::<Message>();
+        }
+
+        /// Every command, with parameters likely to reach the deeper parts of
+        /// its parser.
+        #[test]
+        fn never_panics_on_commands(
+            raw in "(@[a-z+/=;é]{1,10} )?(:[a-z!@.é🥔]{1,10} )?\
+                (ACK|ADMIN|AWAY|BATCH|CAP|CHATHISTORY|CNOTICE|CONNECT|CPRIVMSG|DIE|ERROR|HELP\
+                |INFO|INVITE|ISON|JOIN|KICK|KILL|KNOCK|LINKS|LIST|LUSERS|MODE|MONITOR|MOTD|NAMES\
+                |NICK|NOTICE|OPER|PART|PASS|PING|PONG|PRIVMSG|QUIT|REHASH|RESTART|SERVICE\
+                |SERVLIST|SETNAME|SQUERY|SQUIT|STATS|SUMMON|TAGMSG|TIME|TOPIC|TRACE|USER|USERHOST\
+                |USERS|VERSION|WALLCHOPS|WALLOPS|WATCH|WHO|WHOIS|WHOWAS|[0-9]{3})\
+                ( [-+*=:!#&$%@~,.?/a-zA-Z0-9é🥔]{0,12}){0,6}"
+        ) {
+            let _ = raw.parse::<Message>();
+        }
+
+        /// Text made of the characters the parser splits on, mixed with
+        /// multi-byte characters that can't be sliced through.
+        #[test]
+        fn never_panics_on_syntax(raw in "[@:!#&+%$~=;,. *?/\\\\a-zA-Z0-9é🥔]{0,40}") {
+            let _ = raw.parse::<Message>();
+            let _ = raw.parse::<Recipient>();
+            let _ = raw.parse::<Channel>();
+            let _ = raw.parse::<Sender>();
+            let _ = raw.parse::<Command>();
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use super::ParseError;
+use std::result::Result;
+use std::str::FromStr;
+
+const CAP_LS: &str = "LS";
+const CAP_LIST: &str = "LIST";
+const CAP_ACK: &str = "ACK";
+const CAP_NAK: &str = "NAK";
+const CAP_NEW: &str = "NEW";
+const CAP_DEL: &str = "DEL";
+
+/// The subcommands that a server may send as part of IRCv3 capability
+/// negotiation:
+///
+/// ```text
+/// cap-reply  =  "CAP" " " target " " subcommand [ " " "*" ] " " ":" caps
+/// subcommand =  "LS" / "LIST" / "ACK" / "NAK" / "NEW" / "DEL"
+/// ```
+///
+/// Subcommands sent by the client (`REQ` and `END`) have their own `Command`
+/// variants.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CapSubcommand {
+    Ls,
+    List,
+    Ack,
+    Nak,
+    New,
+    Del,
+}
+
+impl FromStr for CapSubcommand {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            CAP_LS => Ok(CapSubcommand::Ls),
+            CAP_LIST => Ok(CapSubcommand::List),
+            CAP_ACK => Ok(CapSubcommand::Ack),
+            CAP_NAK => Ok(CapSubcommand::Nak),
+            CAP_NEW => Ok(CapSubcommand::New),
+            CAP_DEL => Ok(CapSubcommand::Del),
+            _ => Err(ParseError::new("CapSubcommand")),
+        }
+    }
+}
+
+impl From<CapSubcommand> for String {
+    fn from(cap_subcommand: CapSubcommand) -> String {
+        match cap_subcommand {
+            CapSubcommand::Ls => CAP_LS,
+            CapSubcommand::List => CAP_LIST,
+            CapSubcommand::Ack => CAP_ACK,
+            CapSubcommand::Nak => CAP_NAK,
+            CapSubcommand::New => CAP_NEW,
+            CapSubcommand::Del => CAP_DEL,
+        }
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod test_cap_subcommand {
+    use super::*;
+
+    #[test]
+    fn invalid() {
+        assert!("".parse::<CapSubcommand>().is_err());
+        assert!("ls".parse::<CapSubcommand>().is_err());
+        assert!("REQ".parse::<CapSubcommand>().is_err());
+        assert!("🥔️".parse::<CapSubcommand>().is_err());
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(Ok(CapSubcommand::Ls), "LS".parse::<CapSubcommand>());
+        assert_eq!(Ok(CapSubcommand::List), "LIST".parse::<CapSubcommand>());
+        assert_eq!(Ok(CapSubcommand::Ack), "ACK".parse::<CapSubcommand>());
+        assert_eq!(Ok(CapSubcommand::Nak), "NAK".parse::<CapSubcommand>());
+        assert_eq!(Ok(CapSubcommand::New), "NEW".parse::<CapSubcommand>());
+        assert_eq!(Ok(CapSubcommand::Del), "DEL".parse::<CapSubcommand>());
+    }
+
+    #[test]
+    fn into_string() {
+        assert_eq!("LS".to_string(), String::from(CapSubcommand::Ls));
+        assert_eq!("ACK".to_string(), String::from(CapSubcommand::Ack));
+        assert_eq!("DEL".to_string(), String::from(CapSubcommand::Del));
+    }
+}
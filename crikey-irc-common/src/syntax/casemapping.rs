@@ -0,0 +1,108 @@
+use super::ParseError;
+use std::result::Result;
+use std::str::FromStr;
+
+/// The rules a server uses to decide whether two nicknames or channel names
+/// are the same, as advertised in the CASEMAPPING ISUPPORT token. RFC 2812
+/// section 2.2 says:
+///
+/// ```text
+/// Because of IRC's Scandinavian origin, the characters {}|^ are
+/// considered to be the lower case equivalents of the characters []\~,
+/// respectively. This is a critical issue when determining the
+/// equivalence of two nicknames or channel names.
+/// ```
+///
+/// Servers that don't advertise CASEMAPPING are assumed to follow the RFC.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Casemapping {
+    Ascii, // ascii
+    #[default]
+    Rfc1459, // rfc1459
+    StrictRfc1459, // strict-rfc1459, which leaves "~" and "^" alone
+}
+
+impl Casemapping {
+    /// The lower case equivalent of a single character.
+    pub fn fold_char(self, c: char) -> char {
+        match (self, c) {
+            (_, 'A'..='Z') => c.to_ascii_lowercase(),
+            (Casemapping::Rfc1459, '[') | (Casemapping::StrictRfc1459, '[') => '{',
+            (Casemapping::Rfc1459, ']') | (Casemapping::StrictRfc1459, ']') => '}',
+            (Casemapping::Rfc1459, '\\') | (Casemapping::StrictRfc1459, '\\') => '|',
+            (Casemapping::Rfc1459, '~') => '^',
+            _ => c,
+        }
+    }
+
+    /// The lower case equivalent of a string, suitable for use as a map key.
+    pub fn fold(self, raw: &str) -> String {
+        raw.chars().map(|c| self.fold_char(c)).collect()
+    }
+
+    pub fn eq(self, a: &str, b: &str) -> bool {
+        a.chars()
+            .map(|c| self.fold_char(c))
+            .eq(b.chars().map(|c| self.fold_char(c)))
+    }
+}
+
+impl FromStr for Casemapping {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "ascii" => Ok(Casemapping::Ascii),
+            "rfc1459" => Ok(Casemapping::Rfc1459),
+            "strict-rfc1459" => Ok(Casemapping::StrictRfc1459),
+            _ => Err(ParseError::new("Casemapping")),
+        }
+    }
+}
+
+impl From<Casemapping> for String {
+    fn from(casemapping: Casemapping) -> String {
+        match casemapping {
+            Casemapping::Ascii => "ascii",
+            Casemapping::Rfc1459 => "rfc1459",
+            Casemapping::StrictRfc1459 => "strict-rfc1459",
+        }
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod test_casemapping {
+    use super::*;
+
+    #[test]
+    fn fold() {
+        assert_eq!("abc{}|^", Casemapping::Rfc1459.fold("ABC[]\\~"));
+        assert_eq!("abc{}|~", Casemapping::StrictRfc1459.fold("ABC[]\\~"));
+        assert_eq!("abc[]\\~", Casemapping::Ascii.fold("ABC[]\\~"));
+        assert_eq!("ünïcödé", Casemapping::Rfc1459.fold("ünïcödé"));
+    }
+
+    #[test]
+    fn eq() {
+        assert!(Casemapping::Rfc1459.eq("Spud[x]", "spud{X}"));
+        assert!(!Casemapping::Ascii.eq("Spud[x]", "spud{X}"));
+        assert!(!Casemapping::Rfc1459.eq("spud", "spuds"));
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(Ok(Casemapping::Ascii), "ascii".parse());
+        assert_eq!(Ok(Casemapping::Rfc1459), "rfc1459".parse());
+        assert_eq!(Ok(Casemapping::StrictRfc1459), "strict-rfc1459".parse());
+        assert!("rfc7613".parse::<Casemapping>().is_err());
+    }
+
+    #[test]
+    fn into_string() {
+        assert_eq!(
+            "strict-rfc1459".to_string(),
+            String::from(Casemapping::StrictRfc1459)
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use super::ParseError;
+use std::result::Result;
+use std::str::FromStr;
+
+const SELECTOR_ANY: &str = "*";
+const SELECTOR_TIMESTAMP: &str = "timestamp=";
+const SELECTOR_MSGID: &str = "msgid=";
+
+/// A reference to a point in a conversation's history, as used by the draft
+/// CHATHISTORY specification:
+///
+/// ```text
+/// selector   =  "timestamp=" timestamp / "msgid=" msgid / "*"
+/// timestamp  =  YYYY-MM-DDThh:mm:ss.sssZ
+///                 ; the same format as the "time" message tag
+/// ```
+///
+/// `*` means "no bound" and is only meaningful to `CHATHISTORY LATEST`. The
+/// timestamp format is not validated, since servers only ever send back
+/// values that they generated themselves.
+#[derive(Clone, PartialEq, Debug)]
+pub enum HistorySelector {
+    Any,
+    Timestamp(String),
+    MsgId(String),
+}
+
+impl FromStr for HistorySelector {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        if raw == SELECTOR_ANY {
+            Ok(HistorySelector::Any)
+        } else if let Some(timestamp) = raw.strip_prefix(SELECTOR_TIMESTAMP) {
+            if timestamp.is_empty() || timestamp.contains(' ') {
+                Err(ParseError::new("HistorySelector"))
+            } else {
+                Ok(HistorySelector::Timestamp(timestamp.to_string()))
+            }
+        } else if let Some(msgid) = raw.strip_prefix(SELECTOR_MSGID) {
+            if msgid.is_empty() || msgid.contains(' ') {
+                Err(ParseError::new("HistorySelector"))
+            } else {
+                Ok(HistorySelector::MsgId(msgid.to_string()))
+            }
+        } else {
+            Err(ParseError::new("HistorySelector"))
+        }
+    }
+}
+
+impl From<HistorySelector> for String {
+    fn from(history_selector: HistorySelector) -> String {
+        match history_selector {
+            HistorySelector::Any => SELECTOR_ANY.to_string(),
+            HistorySelector::Timestamp(timestamp) => {
+                let mut result = SELECTOR_TIMESTAMP.to_string();
+                result.push_str(&timestamp);
+                result
+            }
+            HistorySelector::MsgId(msgid) => {
+                let mut result = SELECTOR_MSGID.to_string();
+                result.push_str(&msgid);
+                result
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_history_selector {
+    use super::*;
+
+    #[test]
+    fn invalid() {
+        assert!("".parse::<HistorySelector>().is_err());
+        assert!("**".parse::<HistorySelector>().is_err());
+        assert!("timestamp=".parse::<HistorySelector>().is_err());
+        assert!("msgid=".parse::<HistorySelector>().is_err());
+        assert!("id=abc".parse::<HistorySelector>().is_err());
+        assert!("🥔️".parse::<HistorySelector>().is_err());
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(Ok(HistorySelector::Any), "*".parse::<HistorySelector>());
+        assert_eq!(
+            Ok(HistorySelector::Timestamp(
+                "2019-01-04T14:33:26.123Z".to_string()
+            )),
+            "timestamp=2019-01-04T14:33:26.123Z".parse::<HistorySelector>()
+        );
+        assert_eq!(
+            Ok(HistorySelector::MsgId("1234".to_string())),
+            "msgid=1234".parse::<HistorySelector>()
+        );
+    }
+
+    #[test]
+    fn into_string() {
+        assert_eq!("*".to_string(), String::from(HistorySelector::Any));
+        assert_eq!(
+            "timestamp=2019-01-04T14:33:26.123Z".to_string(),
+            String::from(HistorySelector::Timestamp(
+                "2019-01-04T14:33:26.123Z".to_string()
+            ))
+        );
+        assert_eq!(
+            "msgid=1234".to_string(),
+            String::from(HistorySelector::MsgId("1234".to_string()))
+        );
+    }
+}
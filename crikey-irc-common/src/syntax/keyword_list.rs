@@ -1,7 +1,17 @@
 use super::ParseError;
+use std::iter::FromIterator;
+use std::ops::Index;
+use std::slice;
 use std::str::FromStr;
+use std::vec;
 
-#[derive(PartialEq, Debug)]
+/// A comma-separated list, such as the channels in a JOIN command. According
+/// to RFC 2812:
+///
+/// ```text
+/// <channel> *( "," <channel> )
+/// ```
+#[derive(Clone, PartialEq, Debug)]
 pub struct KeywordList<T: FromStr + Into<String>>(Vec<T>);
 
 impl<T: FromStr + Into<String>> KeywordList<T> {
@@ -16,6 +26,68 @@ impl<T: FromStr + Into<String>> KeywordList<T> {
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.0.get(index)
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.0.iter()
+    }
+}
+
+impl<T: FromStr + Into<String>> Default for KeywordList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: FromStr + Into<String>> From<Vec<T>> for KeywordList<T> {
+    fn from(elements: Vec<T>) -> Self {
+        Self(elements)
+    }
+}
+
+impl<T: FromStr + Into<String>> FromIterator<T> for KeywordList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<T: FromStr + Into<String>> Extend<T> for KeywordList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+impl<T: FromStr + Into<String>> IntoIterator for KeywordList<T> {
+    type Item = T;
+    type IntoIter = vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, T: FromStr + Into<String>> IntoIterator for &'a KeywordList<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<T: FromStr + Into<String>> Index<usize> for KeywordList<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.0[index]
+    }
 }
 
 impl<T: FromStr + Into<String>> FromStr for KeywordList<T> {
@@ -23,7 +95,7 @@ impl<T: FromStr + Into<String>> FromStr for KeywordList<T> {
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
         let mut elements = Vec::<T>::new();
-        if raw.len() > 0 {
+        if !raw.is_empty() {
             for element in raw.split(',') {
                 elements.push(
                     element
@@ -51,7 +123,7 @@ impl<T: FromStr + Into<String>> From<KeywordList<T>> for String {
 mod test_keyword_list {
     use super::*;
 
-    #[derive(PartialEq, Debug)]
+    #[derive(Clone, PartialEq, Debug)]
     struct TestStruct(char);
 
     impl FromStr for TestStruct {
@@ -110,4 +182,31 @@ mod test_keyword_list {
     fn invalid() {
         assert!("a,,c".parse::<KeywordList<TestStruct>>().is_err());
     }
+
+    #[test]
+    fn collections() {
+        let list: KeywordList<TestStruct> =
+            vec!['a', 'b', 'c'].into_iter().map(TestStruct).collect();
+        assert_eq!(3, list.len());
+        assert_eq!(TestStruct('b'), list[1]);
+        assert_eq!(None, list.get(3));
+        assert_eq!(
+            vec!['a', 'b', 'c'],
+            list.iter().map(|element| element.0).collect::<Vec<char>>()
+        );
+
+        let mut extended = KeywordList::from(vec![TestStruct('z')]);
+        extended.extend(list.clone());
+        assert_eq!("z,a,b,c".to_string(), String::from(extended));
+
+        let mut chars = Vec::new();
+        for element in &list {
+            chars.push(element.0);
+        }
+        for element in list {
+            chars.push(element.0);
+        }
+        assert_eq!(vec!['a', 'b', 'c', 'a', 'b', 'c'], chars);
+        assert!(KeywordList::<TestStruct>::default().is_empty());
+    }
 }
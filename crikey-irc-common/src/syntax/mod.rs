@@ -0,0 +1,22 @@
+pub use self::cap_subcommand::CapSubcommand;
+pub use self::casemapping::Casemapping;
+pub use self::history_selector::HistorySelector;
+pub use self::keyword_list::KeywordList;
+pub use self::stats_query::StatsQuery;
+pub use self::target::Target;
+pub use self::target_mask::{ServerMask, TargetMask};
+pub use self::user_mask::{ExtbanMatcher, Extbans, MaskSubject, UserMask};
+pub use self::watch_entry::WatchEntry;
+pub use self::wildcard::Wildcard;
+use super::ParseError;
+
+mod cap_subcommand;
+mod casemapping;
+mod history_selector;
+mod keyword_list;
+mod stats_query;
+mod target;
+mod target_mask;
+mod user_mask;
+mod watch_entry;
+mod wildcard;
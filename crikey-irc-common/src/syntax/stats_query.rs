@@ -7,7 +7,7 @@ const QUERY_USAGE_COUNT: char = 'm';
 const QUERY_OPS: char = 'o';
 const QUERY_UPTIME: char = 'u';
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum StatsQuery {
     List,
     UsageCount,
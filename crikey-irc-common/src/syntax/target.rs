@@ -0,0 +1,112 @@
+use super::super::entity::Nickname;
+use super::{ParseError, ServerMask};
+use std::result::Result;
+use std::str::FromStr;
+
+/// The server to direct a query such as TRACE, ADMIN or INFO to. According to
+/// RFC 2812:
+///
+/// ```text
+/// Command: ADMIN
+/// Parameters: [ <target> ]
+///
+/// Wildcards are allowed in the <target> parameter.
+/// ```
+///
+/// The target is either a mask matching a server, or the nickname of a user,
+/// in which case the query goes to the server that user is connected to.
+/// Since nicknames can't contain ".", and server masks must, the two are never
+/// ambiguous.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Target {
+    ServerMask(ServerMask),
+    Nickname(Nickname),
+}
+
+impl FromStr for Target {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        if let Ok(server_mask) = raw.parse() {
+            Ok(Target::ServerMask(server_mask))
+        } else if let Ok(nickname) = raw.parse() {
+            Ok(Target::Nickname(nickname))
+        } else {
+            Err(ParseError::new("Target"))
+        }
+    }
+}
+
+impl From<ServerMask> for Target {
+    fn from(server_mask: ServerMask) -> Target {
+        Target::ServerMask(server_mask)
+    }
+}
+
+impl From<Nickname> for Target {
+    fn from(nickname: Nickname) -> Target {
+        Target::Nickname(nickname)
+    }
+}
+
+impl From<Target> for String {
+    fn from(target: Target) -> String {
+        match target {
+            Target::ServerMask(server_mask) => String::from(server_mask),
+            Target::Nickname(nickname) => String::from(nickname),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_target {
+    use super::*;
+
+    #[test]
+    fn invalid() {
+        assert!("".parse::<Target>().is_err());
+        assert!("*".parse::<Target>().is_err());
+        assert!("2hot4u".parse::<Target>().is_err());
+        assert!("spaced out".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(
+            Ok(Target::ServerMask("tolsun.oulu.fi".parse().unwrap())),
+            "tolsun.oulu.fi".parse::<Target>()
+        );
+        assert_eq!(
+            Ok(Target::ServerMask("*.oulu.fi".parse().unwrap())),
+            "*.oulu.fi".parse::<Target>()
+        );
+        assert_eq!(
+            Ok(Target::Nickname("syrk".parse().unwrap())),
+            "syrk".parse::<Target>()
+        );
+    }
+
+    #[test]
+    fn from_types() {
+        assert_eq!(
+            Target::Nickname("Angel".parse().unwrap()),
+            Target::from("Angel".parse::<Nickname>().unwrap())
+        );
+        assert_eq!(
+            Target::ServerMask("csd.bu.edu".parse().unwrap()),
+            Target::from("csd.bu.edu".parse::<ServerMask>().unwrap())
+        );
+    }
+
+    #[test]
+    fn into_string() {
+        assert_eq!(
+            "syrk".to_string(),
+            String::from(Target::Nickname("syrk".parse().unwrap()))
+        );
+        assert_eq!(
+            "*.oulu.fi".to_string(),
+            String::from(Target::ServerMask("*.oulu.fi".parse().unwrap()))
+        );
+    }
+}
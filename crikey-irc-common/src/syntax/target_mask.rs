@@ -1,4 +1,5 @@
-use super::ParseError;
+use super::wildcard::matches;
+use super::{Casemapping, ParseError};
 use std::result::Result;
 use std::str::FromStr;
 
@@ -30,14 +31,33 @@ const SERVER_PREFIX: char = '$';
 /// only available to operators.
 /// ```
 ///
-/// It's worth noting that the syntax listed implicitly covers IPv4 addresses but
-/// not IPv6. This is a faithful implementation of the standard.
-#[derive(PartialEq, Debug)]
+/// The syntax listed implicitly covers IPv4 addresses but not IPv6, or the
+/// cloaks that modern servers show in place of real hosts. Host masks may
+/// therefore also be:
+///
+/// - an IPv6 mask such as "#2001:db8::*", with at least one ":" and no
+///   wildcards in the first group, which is the most significant; or
+/// - a cloak mask such as "#user/*", with at least one "/" and no wildcards
+///   in the first segment.
+///
+/// Server masks must still follow the RFC, since server names are hostnames.
+#[derive(Clone, PartialEq, Debug)]
 pub enum TargetMask {
     Host(HostMask),     // #xyz
     Server(ServerMask), // $xyz
 }
 
+impl TargetMask {
+    /// Whether a user on `servername` with the host `host` is covered by the
+    /// mask. Host names are compared case-insensitively.
+    pub fn matches(&self, host: &str, servername: &str) -> bool {
+        match self {
+            TargetMask::Host(host_mask) => host_mask.matches(host),
+            TargetMask::Server(server_mask) => server_mask.matches(servername),
+        }
+    }
+}
+
 impl FromStr for TargetMask {
     type Err = ParseError;
 
@@ -67,15 +87,27 @@ impl From<TargetMask> for String {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct HostMask(String);
 
+impl HostMask {
+    pub fn matches(&self, host: &str) -> bool {
+        matches(&self.0, host, Casemapping::Ascii)
+    }
+}
+
 impl FromStr for HostMask {
     type Err = ParseError;
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
         mask_from_string(raw)
-            .map(|s| Self(s))
+            .or_else(|| separated_mask(raw, ':', |c| c.is_ascii_hexdigit() || c == '.'))
+            .or_else(|| {
+                separated_mask(raw, '/', |c| {
+                    c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_'
+                })
+            })
+            .map(Self)
             .ok_or(ParseError::new("HostMask"))
     }
 }
@@ -86,15 +118,21 @@ impl From<HostMask> for String {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct ServerMask(String);
 
+impl ServerMask {
+    pub fn matches(&self, servername: &str) -> bool {
+        matches(&self.0, servername, Casemapping::Ascii)
+    }
+}
+
 impl FromStr for ServerMask {
     type Err = ParseError;
 
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
         mask_from_string(raw)
-            .map(|s| Self(s))
+            .map(Self)
             .ok_or(ParseError::new("ServerMask"))
     }
 }
@@ -109,10 +147,10 @@ fn mask_from_string(raw: &str) -> Option<String> {
     if raw.len() > 2
         && raw.is_ascii()
         && raw.contains('.')
-        && !raw.split('.').last()?.contains(&['*', '?'][..])
+        && !raw.split('.').next_back()?.contains(&['*', '?'][..])
     {
         for raw_part in raw.split('.') {
-            if raw_part.len() < 1
+            if raw_part.is_empty()
                 || !raw_part
                     .starts_with(|c: char| c.is_ascii_alphanumeric() || c == '*' || c == '?')
                 || !raw_part.ends_with(|c: char| c.is_ascii_alphanumeric() || c == '*' || c == '?')
@@ -130,6 +168,27 @@ fn mask_from_string(raw: &str) -> Option<String> {
     }
 }
 
+/// A mask made up of groups separated by `separator`, such as an IPv6 address
+/// or a cloak. The first group may not contain wildcards, for the same reason
+/// that the last label of a dotted mask may not. Empty groups are allowed
+/// after the first, to cover the "::" of a compressed IPv6 address.
+fn separated_mask(raw: &str, separator: char, is_valid: impl Fn(char) -> bool) -> Option<String> {
+    let mut groups = raw.split(separator);
+    let first = groups.next()?;
+
+    if raw.contains(separator)
+        && !first.is_empty()
+        && first.chars().all(&is_valid)
+        && raw
+            .chars()
+            .all(|c| c == separator || c == '*' || c == '?' || is_valid(c))
+    {
+        Some(raw.to_string())
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -206,14 +265,51 @@ mod tests {
 
     #[test]
     fn ipv6() {
-        // Sadly, IPv6 isn't supported according to the RFC.
+        // IPv6 isn't supported according to the RFC, but hosts can have IPv6
+        // addresses, so host masks accept them anyway.
+        assert_eq!(
+            TargetMask::Host(HostMask("2001:db8::ff00:42:8329".to_string())),
+            "#2001:db8::ff00:42:8329"
+                .parse::<TargetMask>()
+                .expect("Expect host mask to accept an IPv6 address.")
+        );
+        assert_eq!(
+            TargetMask::Host(HostMask("2001:db8::*".to_string())),
+            "#2001:db8::*"
+                .parse::<TargetMask>()
+                .expect("Expect host mask to accept an IPv6 mask.")
+        );
+        assert!("#::1".parse::<TargetMask>().is_err());
+        assert!("#*::1".parse::<TargetMask>().is_err());
+        assert!("#2001:db8::g".parse::<TargetMask>().is_err());
+
+        // Servers are still named by hostname.
         assert!("$::1".parse::<TargetMask>().is_err());
-        assert!("#2001:db8::ff00:42:8329".parse::<TargetMask>().is_err());
         assert!("$2001:0db8:0000:0000:0000:ff00:0042:8329"
             .parse::<TargetMask>()
             .is_err());
     }
 
+    #[test]
+    fn cloak() {
+        assert_eq!(
+            TargetMask::Host(HostMask("user/*".to_string())),
+            "#user/*"
+                .parse::<TargetMask>()
+                .expect("Expect host mask to accept a cloak mask.")
+        );
+        assert_eq!(
+            TargetMask::Host(HostMask("gateway/web/irccloud.com/*".to_string())),
+            "#gateway/web/irccloud.com/*"
+                .parse::<TargetMask>()
+                .expect("Expect host mask to accept a cloak mask with dots.")
+        );
+        assert!("#*/spudly".parse::<TargetMask>().is_err());
+        assert!("#/spudly".parse::<TargetMask>().is_err());
+        assert!("#user/spud ly".parse::<TargetMask>().is_err());
+        assert!("$user/*".parse::<TargetMask>().is_err());
+    }
+
     #[test]
     fn invalid_server_and_host_mask() {
         assert!("#abc.def.ghi".parse::<HostMask>().is_err());
@@ -236,6 +332,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn matches() {
+        let host_mask = "#*.example.com".parse::<TargetMask>().unwrap();
+        assert!(host_mask.matches("spud.EXAMPLE.com", "irc.example.org"));
+        assert!(!host_mask.matches("spud.example.org", "irc.example.com"));
+
+        let cloak_mask = "#user/*".parse::<TargetMask>().unwrap();
+        assert!(cloak_mask.matches("user/spudly", "irc.example.com"));
+        assert!(!cloak_mask.matches("gateway/web/spudly", "irc.example.com"));
+
+        let server_mask = "$irc?.example.com".parse::<TargetMask>().unwrap();
+        assert!(server_mask.matches("spud.example.org", "irc2.example.com"));
+        assert!(!server_mask.matches("irc2.example.com", "irc.example.com"));
+    }
+
     #[test]
     fn into_string() {
         assert_eq!(
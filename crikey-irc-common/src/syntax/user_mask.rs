@@ -0,0 +1,495 @@
+use super::super::entity::{Recipient, Sender};
+use super::wildcard::matches;
+use super::{Casemapping, ParseError, Wildcard};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::result::Result;
+use std::str::FromStr;
+
+/// A mask matching users, as used in channel bans, ban exceptions and invite
+/// exceptions, and for ignoring people. The basic form is a wildcard mask for
+/// each part of `nickname!user@host`:
+///
+/// ```text
+/// usermask   =  mask "!" mask "@" mask
+///                 ; see Wildcard for the mask syntax
+/// ```
+///
+/// Like servers, we fill in any missing parts with "*", so "spudly" becomes
+/// "spudly!*@*" and "*@*.example.com" becomes "*!*@*.example.com". The host
+/// may also be given in CIDR notation, such as "*!*@192.0.2.0/24".
+///
+/// Most servers also support extended bans, which match on something other
+/// than the hostmask. These start with a prefix character advertised in the
+/// EXTBAN ISUPPORT token, followed by the type and an optional argument:
+///
+/// ```text
+/// extban     =  prefix [ "~" / "!" ] type [ ":" argument ]
+///                 ; "~" (Solanum) or "!" (InspIRCd) negates the match
+/// ```
+///
+/// For example, "$a:spudly" matches users logged into the account "spudly",
+/// and "~q:*!*@*.example.com" quiets users from example.com. See `Extbans`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum UserMask {
+    Hostmask {
+        nickname: Wildcard,
+        user: Wildcard,
+        host: Wildcard,
+    },
+    Extban {
+        prefix: char,
+        negated: bool,
+        extban_type: String,
+        argument: Option<String>,
+    },
+}
+
+impl UserMask {
+    /// Parse a mask, recognising extbans by the prefixes known to `extbans`
+    /// rather than the defaults.
+    pub fn parse_with_extbans(raw: &str, extbans: &Extbans) -> Result<Self, ParseError> {
+        match raw.chars().next() {
+            Some(prefix) if extbans.prefixes.contains(prefix) => {
+                extban(prefix, &raw[prefix.len_utf8()..])
+            }
+            _ => hostmask(raw),
+        }
+    }
+
+    /// Whether the mask matches a user. Nicknames are compared according to
+    /// `casemapping`, the other parts ASCII case-insensitively. Extbans of a
+    /// type `extbans` doesn't know never match.
+    pub fn matches(
+        &self,
+        subject: &MaskSubject,
+        casemapping: Casemapping,
+        extbans: &Extbans,
+    ) -> bool {
+        match self {
+            UserMask::Hostmask {
+                nickname,
+                user,
+                host,
+            } => {
+                nickname.matches(&subject.nickname, casemapping)
+                    && user.matches(subject.user.as_deref().unwrap_or(""), Casemapping::Ascii)
+                    && host.matches_host(subject.host.as_deref().unwrap_or(""))
+            }
+            UserMask::Extban {
+                negated,
+                extban_type,
+                argument,
+                ..
+            } => match extbans.matchers.get(extban_type) {
+                Some(matcher) => {
+                    matcher(argument.as_deref(), subject, casemapping, extbans) != *negated
+                }
+                None => false,
+            },
+        }
+    }
+}
+
+fn hostmask(raw: &str) -> Result<UserMask, ParseError> {
+    let (nickname, rest) = match raw.find('!') {
+        Some(index) => (&raw[..index], Some(&raw[index + 1..])),
+        None => (raw, None),
+    };
+    let (nickname, user, host) = match (rest, nickname.find('@')) {
+        (Some(rest), _) => match rest.rfind('@') {
+            Some(index) => (nickname, &rest[..index], &rest[index + 1..]),
+            None => (nickname, rest, "*"),
+        },
+        (None, Some(index)) => ("*", &raw[..index], &raw[index + 1..]),
+        (None, None) => (raw, "*", "*"),
+    };
+
+    Ok(UserMask::Hostmask {
+        nickname: nickname.parse()?,
+        user: user.parse()?,
+        host: host.parse()?,
+    })
+}
+
+fn extban(prefix: char, raw: &str) -> Result<UserMask, ParseError> {
+    let (negated, raw) = match raw.strip_prefix(&['~', '!'][..]) {
+        Some(raw) => (true, raw),
+        None => (false, raw),
+    };
+    let (extban_type, argument) = match raw.find(':') {
+        Some(index) => (&raw[..index], Some(&raw[index + 1..])),
+        None => (raw, None),
+    };
+
+    if extban_type.is_empty()
+        || !extban_type.chars().all(|c| c.is_ascii_alphanumeric())
+        || argument.is_some_and(|argument| argument.contains(&['\0', '\r', '\n', ' '][..]))
+    {
+        Err(ParseError::new("UserMask"))
+    } else {
+        Ok(UserMask::Extban {
+            prefix,
+            negated,
+            extban_type: extban_type.to_string(),
+            argument: argument.map(|argument| argument.to_string()),
+        })
+    }
+}
+
+impl FromStr for UserMask {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Self::parse_with_extbans(raw, &Extbans::default())
+    }
+}
+
+impl From<UserMask> for String {
+    fn from(user_mask: UserMask) -> String {
+        match user_mask {
+            UserMask::Hostmask {
+                nickname,
+                user,
+                host,
+            } => [
+                &String::from(nickname),
+                "!",
+                &String::from(user),
+                "@",
+                &String::from(host),
+            ]
+            .join(""),
+            UserMask::Extban {
+                prefix,
+                negated,
+                extban_type,
+                argument,
+            } => {
+                let mut result = prefix.to_string();
+                if negated {
+                    result.push(if prefix == '~' { '!' } else { '~' });
+                }
+                result.push_str(&extban_type);
+                if let Some(argument) = argument {
+                    result.push(':');
+                    result.push_str(&argument);
+                }
+                result
+            }
+        }
+    }
+}
+
+/// Everything a `UserMask` can be matched against. The nickname, user and host
+/// come from the message prefix; the account and realname have to be learned
+/// elsewhere, such as from WHOX replies or the `account-tag` capability.
+///
+/// Missing parts are matched as empty strings, so they only match masks like
+/// "*".
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MaskSubject {
+    pub nickname: String,
+    pub user: Option<String>,
+    pub host: Option<String>,
+    pub account: Option<String>,
+    pub realname: Option<String>,
+}
+
+impl From<Sender> for MaskSubject {
+    /// Servers are matched by host, with an empty nickname.
+    fn from(sender: Sender) -> MaskSubject {
+        match sender {
+            Sender::User {
+                nickname,
+                user,
+                host,
+            } => MaskSubject {
+                nickname: String::from(nickname),
+                user: user.map(String::from),
+                host: host.map(String::from),
+                ..MaskSubject::default()
+            },
+            Sender::Server(servername) => MaskSubject {
+                host: Some(String::from(servername)),
+                ..MaskSubject::default()
+            },
+        }
+    }
+}
+
+impl TryFrom<Recipient> for MaskSubject {
+    type Error = ParseError;
+
+    /// Only recipients identifying a single user by nickname can be matched.
+    fn try_from(recipient: Recipient) -> Result<Self, Self::Error> {
+        match recipient {
+            Recipient::Nickname(nickname) => Ok(MaskSubject {
+                nickname: String::from(nickname),
+                ..MaskSubject::default()
+            }),
+            Recipient::NicknameUserHost(nickname, user, host) => Ok(MaskSubject {
+                nickname: String::from(nickname),
+                user: Some(String::from(user)),
+                host: Some(String::from(host)),
+                ..MaskSubject::default()
+            }),
+            _ => Err(ParseError::new("MaskSubject")),
+        }
+    }
+}
+
+/// Decides whether an extban matches a user, given the extban's argument.
+pub type ExtbanMatcher = fn(Option<&str>, &MaskSubject, Casemapping, &Extbans) -> bool;
+
+/// The extban prefixes a server supports, and how to match each type of
+/// extban. Types are registered by name, so both the single-letter "a" used
+/// by most servers and UnrealIRCd's "account" can be supported:
+///
+/// - "a" / "account": logged in to an account matching the argument, or to
+///   any account if there's no argument
+/// - "r" / "realname": realname matching the argument
+/// - "q" / "quiet": the argument is itself a mask to match; servers use this
+///   to mute users rather than ban them
+///
+/// Other types can be added with `register()`.
+#[derive(Clone, Debug)]
+pub struct Extbans {
+    prefixes: String,
+    matchers: HashMap<String, ExtbanMatcher>,
+}
+
+impl Extbans {
+    /// A registry recognising `prefixes`, with no types registered.
+    pub fn new(prefixes: &str) -> Self {
+        Extbans {
+            prefixes: prefixes.to_string(),
+            matchers: HashMap::new(),
+        }
+    }
+
+    /// A registry for the value of the EXTBAN ISUPPORT token, such as
+    /// "$,ajrxz": the prefix, followed by the supported types. Only the
+    /// advertised types that we know how to match are registered.
+    pub fn from_isupport(value: &str) -> Self {
+        let (prefix, types) = match value.find(',') {
+            Some(index) => (&value[..index], &value[index + 1..]),
+            None => (value, ""),
+        };
+
+        let defaults = Self::default();
+        let mut extbans = Self::new(prefix);
+        for extban_type in types.chars() {
+            let extban_type = extban_type.to_string();
+            if let Some(matcher) = defaults.matchers.get(&extban_type) {
+                extbans.register(&extban_type, *matcher);
+            }
+        }
+        extbans
+    }
+
+    pub fn register(&mut self, extban_type: &str, matcher: ExtbanMatcher) {
+        self.matchers.insert(extban_type.to_string(), matcher);
+    }
+}
+
+impl Default for Extbans {
+    fn default() -> Self {
+        let mut extbans = Self::new("$~");
+        for extban_type in &["a", "account"] {
+            extbans.register(extban_type, match_account);
+        }
+        for extban_type in &["r", "realname"] {
+            extbans.register(extban_type, match_realname);
+        }
+        for extban_type in &["q", "quiet"] {
+            extbans.register(extban_type, match_mask);
+        }
+        extbans
+    }
+}
+
+fn match_account(
+    argument: Option<&str>,
+    subject: &MaskSubject,
+    casemapping: Casemapping,
+    _: &Extbans,
+) -> bool {
+    match (argument, &subject.account) {
+        (Some(pattern), Some(account)) => matches(pattern, account, casemapping),
+        (None, Some(_)) => true,
+        (_, None) => false,
+    }
+}
+
+fn match_realname(
+    argument: Option<&str>,
+    subject: &MaskSubject,
+    _: Casemapping,
+    _: &Extbans,
+) -> bool {
+    match (argument, &subject.realname) {
+        (Some(pattern), Some(realname)) => matches(pattern, realname, Casemapping::Ascii),
+        _ => false,
+    }
+}
+
+fn match_mask(
+    argument: Option<&str>,
+    subject: &MaskSubject,
+    casemapping: Casemapping,
+    extbans: &Extbans,
+) -> bool {
+    argument
+        .and_then(|argument| UserMask::parse_with_extbans(argument, extbans).ok())
+        .is_some_and(|mask| mask.matches(subject, casemapping, extbans))
+}
+
+#[cfg(test)]
+mod test_user_mask {
+    use super::*;
+
+    fn mask(raw: &str) -> UserMask {
+        raw.parse().unwrap()
+    }
+
+    fn subject(raw: &str) -> MaskSubject {
+        MaskSubject::from(raw.parse::<Sender>().unwrap())
+    }
+
+    fn matches(mask: &str, subject: &MaskSubject) -> bool {
+        self::mask(mask).matches(subject, Casemapping::Rfc1459, &Extbans::default())
+    }
+
+    #[test]
+    fn invalid() {
+        assert!("".parse::<UserMask>().is_err());
+        assert!("nick!@host".parse::<UserMask>().is_err());
+        assert!("nick!user@".parse::<UserMask>().is_err());
+        assert!("spaced out".parse::<UserMask>().is_err());
+        assert!("$".parse::<UserMask>().is_err());
+        assert!("$~".parse::<UserMask>().is_err());
+        assert!("$a:spaced out".parse::<UserMask>().is_err());
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!("spudly!*@*", String::from(mask("spudly")));
+        assert_eq!("spudly!user@*", String::from(mask("spudly!user")));
+        assert_eq!("*!*@*.example.com", String::from(mask("*@*.example.com")));
+        assert_eq!("*!*@*", String::from(mask("*!*@*")));
+        assert_eq!("$a:spudly", String::from(mask("$a:spudly")));
+        assert_eq!("$~a", String::from(mask("$~a")));
+        assert_eq!("~!q:*!*@*", String::from(mask("~!q:*!*@*")));
+    }
+
+    #[test]
+    fn hostmask() {
+        let spudly = subject("Spudly!~spud@irc.example.com");
+        assert!(matches("*!*@*", &spudly));
+        assert!(matches("spudly", &spudly));
+        assert!(matches("*!~spud@*", &spudly));
+        assert!(matches("*!*@*.example.com", &spudly));
+        assert!(matches("*@*.EXAMPLE.com", &spudly));
+        assert!(!matches("*!*@*.example.org", &spudly));
+        assert!(!matches("spud", &spudly));
+        assert!(!matches("*!spud@*", &spudly));
+
+        // Missing parts only match "*".
+        let bare = subject("spudly");
+        assert!(matches("spudly!*@*", &bare));
+        assert!(!matches("spudly!*@*.example.com", &bare));
+    }
+
+    #[test]
+    fn casemapping() {
+        let subject = subject("Spud[ly]!user@host");
+        assert!(matches("spud{ly}", &subject));
+        assert!(!mask("spud{ly}").matches(&subject, Casemapping::Ascii, &Extbans::default()));
+    }
+
+    #[test]
+    fn cidr() {
+        assert!(matches("*!*@192.0.2.0/24", &subject("nick!user@192.0.2.8")));
+        assert!(!matches(
+            "*!*@192.0.2.0/24",
+            &subject("nick!user@192.0.3.8")
+        ));
+        assert!(!matches("*!*@192.0.2.0/24", &subject("nick!user@host")));
+    }
+
+    #[test]
+    fn extbans() {
+        let mut subject = subject("spudly!user@host");
+        assert!(!matches("$a", &subject));
+        assert!(matches("$~a", &subject));
+        assert!(!matches("$r:*Spud*", &subject));
+
+        subject.account = Some("SpudAccount".to_string());
+        subject.realname = Some("Mr Spud".to_string());
+        assert!(matches("$a", &subject));
+        assert!(matches("$a:spud*", &subject));
+        assert!(!matches("$~a:spud*", &subject));
+        assert!(matches("~account:spudaccount", &subject));
+        assert!(!matches("$a:potato", &subject));
+        assert!(matches("$r:*spud", &subject));
+        assert!(matches("~q:*!*@host", &subject));
+        assert!(matches("~q:$a:spud*", &subject));
+        assert!(!matches("~q:*!*@elsewhere", &subject));
+        assert!(!matches("~q", &subject));
+
+        // Unknown types parse, but never match.
+        assert!(!matches("$x:spudly!*@*#*", &subject));
+    }
+
+    #[test]
+    fn registry() {
+        let extbans = Extbans::from_isupport("$,ar");
+        let mut subject = subject("spudly!user@host");
+        subject.account = Some("spudly".to_string());
+
+        assert_eq!(
+            Ok(UserMask::Extban {
+                prefix: '$',
+                negated: false,
+                extban_type: "a".to_string(),
+                argument: Some("spudly".to_string()),
+            }),
+            UserMask::parse_with_extbans("$a:spudly", &extbans)
+        );
+        // "~" isn't a prefix on this server, so it's just a strange nickname.
+        assert_eq!(
+            "~q!*@*".to_string(),
+            String::from(UserMask::parse_with_extbans("~q", &extbans).unwrap())
+        );
+        assert!(mask("$a:spudly").matches(&subject, Casemapping::Rfc1459, &extbans));
+        assert!(!mask("$q:*!*@*").matches(&subject, Casemapping::Rfc1459, &extbans));
+
+        let mut extbans = Extbans::new("$");
+        extbans.register("u", |argument, subject, _, _| {
+            argument == subject.user.as_deref()
+        });
+        assert!(mask("$u:user").matches(&subject, Casemapping::Rfc1459, &extbans));
+        assert!(!mask("$u:other").matches(&subject, Casemapping::Rfc1459, &extbans));
+    }
+
+    #[test]
+    fn from_recipient() {
+        assert_eq!(
+            Ok(subject("nick!user@host")),
+            MaskSubject::try_from("nick!user@host".parse::<Recipient>().unwrap())
+        );
+        assert_eq!(
+            Ok(subject("nick")),
+            MaskSubject::try_from("nick".parse::<Recipient>().unwrap())
+        );
+        assert!(MaskSubject::try_from("#channel".parse::<Recipient>().unwrap()).is_err());
+    }
+
+    #[test]
+    fn from_server() {
+        let server = subject("irc.example.com");
+        assert!(matches("*!*@*.example.com", &server));
+        assert!(!matches("*!*@*.example.org", &server));
+    }
+}
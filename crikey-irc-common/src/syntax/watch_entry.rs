@@ -0,0 +1,120 @@
+use super::super::entity::Nickname;
+use super::ParseError;
+use std::result::Result;
+use std::str::FromStr;
+
+const WATCH_ADD: char = '+';
+const WATCH_REMOVE: char = '-';
+const WATCH_CLEAR: char = 'C';
+const WATCH_LIST: char = 'L';
+const WATCH_LIST_ONLINE: char = 'l';
+const WATCH_STATUS: char = 'S';
+
+/// A single argument to the legacy WATCH command, as implemented by Bahamut,
+/// UnrealIRCd and friends. There is no RFC for WATCH, but the syntax is:
+///
+/// ```text
+/// watch      =  "WATCH" *( " " entry )
+/// entry      =  ( "+" / "-" ) nickname / "C" / "c" / "L" / "l" / "S" / "s"
+/// ```
+///
+/// `L` lists the online and offline status of every watched nickname, while
+/// `l` only lists those that are online. `C` and `S` are case-insensitive.
+#[derive(Clone, PartialEq, Debug)]
+pub enum WatchEntry {
+    Add(Nickname),
+    Remove(Nickname),
+    Clear,
+    List,
+    ListOnline,
+    Status,
+}
+
+impl FromStr for WatchEntry {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.chars().next() {
+            Some(WATCH_ADD) => Ok(WatchEntry::Add(raw[1..].parse()?)),
+            Some(WATCH_REMOVE) => Ok(WatchEntry::Remove(raw[1..].parse()?)),
+            Some(c) if raw.len() == 1 => match c {
+                'C' | 'c' => Ok(WatchEntry::Clear),
+                WATCH_LIST => Ok(WatchEntry::List),
+                WATCH_LIST_ONLINE => Ok(WatchEntry::ListOnline),
+                'S' | 's' => Ok(WatchEntry::Status),
+                _ => Err(ParseError::new("WatchEntry")),
+            },
+            _ => Err(ParseError::new("WatchEntry")),
+        }
+    }
+}
+
+impl From<WatchEntry> for String {
+    fn from(watch_entry: WatchEntry) -> String {
+        match watch_entry {
+            WatchEntry::Add(nickname) => {
+                let mut result = WATCH_ADD.to_string();
+                result.push_str(&String::from(nickname));
+                result
+            }
+            WatchEntry::Remove(nickname) => {
+                let mut result = WATCH_REMOVE.to_string();
+                result.push_str(&String::from(nickname));
+                result
+            }
+            WatchEntry::Clear => WATCH_CLEAR.to_string(),
+            WatchEntry::List => WATCH_LIST.to_string(),
+            WatchEntry::ListOnline => WATCH_LIST_ONLINE.to_string(),
+            WatchEntry::Status => WATCH_STATUS.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_watch_entry {
+    use super::*;
+
+    #[test]
+    fn invalid() {
+        assert!("".parse::<WatchEntry>().is_err());
+        assert!("+".parse::<WatchEntry>().is_err());
+        assert!("-2hot4u".parse::<WatchEntry>().is_err());
+        assert!("nick".parse::<WatchEntry>().is_err());
+        assert!("X".parse::<WatchEntry>().is_err());
+        assert!("🥔️".parse::<WatchEntry>().is_err());
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(
+            Ok(WatchEntry::Add("Wiz".parse().unwrap())),
+            "+Wiz".parse::<WatchEntry>()
+        );
+        assert_eq!(
+            Ok(WatchEntry::Remove("Wiz".parse().unwrap())),
+            "-Wiz".parse::<WatchEntry>()
+        );
+        assert_eq!(Ok(WatchEntry::Clear), "C".parse::<WatchEntry>());
+        assert_eq!(Ok(WatchEntry::Clear), "c".parse::<WatchEntry>());
+        assert_eq!(Ok(WatchEntry::List), "L".parse::<WatchEntry>());
+        assert_eq!(Ok(WatchEntry::ListOnline), "l".parse::<WatchEntry>());
+        assert_eq!(Ok(WatchEntry::Status), "S".parse::<WatchEntry>());
+        assert_eq!(Ok(WatchEntry::Status), "s".parse::<WatchEntry>());
+    }
+
+    #[test]
+    fn into_string() {
+        assert_eq!(
+            "+Wiz".to_string(),
+            String::from(WatchEntry::Add("Wiz".parse().unwrap()))
+        );
+        assert_eq!(
+            "-Wiz".to_string(),
+            String::from(WatchEntry::Remove("Wiz".parse().unwrap()))
+        );
+        assert_eq!("C".to_string(), String::from(WatchEntry::Clear));
+        assert_eq!("L".to_string(), String::from(WatchEntry::List));
+        assert_eq!("l".to_string(), String::from(WatchEntry::ListOnline));
+        assert_eq!("S".to_string(), String::from(WatchEntry::Status));
+    }
+}
@@ -0,0 +1,237 @@
+use super::{Casemapping, ParseError};
+use std::net::IpAddr;
+use std::result::Result;
+use std::str::FromStr;
+
+const WILDONE: char = '?';
+const WILDMANY: char = '*';
+const ESCAPE: char = '\\';
+
+/// A pattern that may contain wildcards. According to RFC 2812:
+///
+/// ```text
+/// mask       =  *( nowild / noesc wildone / noesc wildmany )
+/// wildone    =  %x3F
+/// wildmany   =  %x2A
+/// nowild     =  %x01-29 / %x2B-3E / %x40-FF
+///                 ; any octet except NUL, "*", "?"
+/// noesc      =  %x01-5B / %x5D-FF
+///                 ; any octet except NUL and "\"
+/// matchone   =  %x01-FF
+///                 ; matches wildone
+/// matchmany  =  *matchone
+///                 ; matches wildmany
+/// ```
+///
+/// That is, "?" matches any one character, "*" matches any number of
+/// characters (including none), and "\" makes the character after it literal.
+/// A trailing "\" is itself literal.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Wildcard(String);
+
+impl Wildcard {
+    pub fn matches(&self, subject: &str, casemapping: Casemapping) -> bool {
+        matches(&self.0, subject, casemapping)
+    }
+
+    /// Whether the pattern matches a host, either by wildcard or, if the
+    /// pattern is in CIDR notation such as "192.0.2.0/24", by address.
+    pub fn matches_host(&self, host: &str) -> bool {
+        match (cidr(&self.0), host.parse::<IpAddr>()) {
+            (Some((network, prefix_len)), Ok(address)) => in_network(address, network, prefix_len),
+            _ => matches(&self.0, host, Casemapping::Ascii),
+        }
+    }
+}
+
+impl FromStr for Wildcard {
+    type Err = ParseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        if raw.is_empty() || raw.contains(&['\0', '\r', '\n', ' '][..]) {
+            Err(ParseError::new("Wildcard"))
+        } else {
+            Ok(Self(raw.to_string()))
+        }
+    }
+}
+
+impl From<Wildcard> for String {
+    fn from(wildcard: Wildcard) -> String {
+        wildcard.0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Token {
+    Literal(char),
+    One,
+    Many,
+}
+
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let mut tokens = Vec::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            WILDONE => Token::One,
+            // Consecutive stars are equivalent to one, and cheaper to match.
+            WILDMANY if tokens.last() == Some(&Token::Many) => continue,
+            WILDMANY => Token::Many,
+            ESCAPE => Token::Literal(chars.next().unwrap_or(ESCAPE)),
+            c => Token::Literal(c),
+        });
+    }
+    tokens
+}
+
+/// Match `subject` against a wildcard `pattern`. When a star fails to lead to
+/// a match, we backtrack to it and let it consume one more character. Only
+/// the most recent star needs to be remembered, so this never takes more than
+/// O(pattern * subject) steps.
+pub(super) fn matches(pattern: &str, subject: &str, casemapping: Casemapping) -> bool {
+    let tokens = tokenize(pattern);
+    let subject: Vec<char> = subject.chars().map(|c| casemapping.fold_char(c)).collect();
+
+    let (mut t, mut s) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while s < subject.len() {
+        match tokens.get(t) {
+            Some(Token::Many) => {
+                backtrack = Some((t, s));
+                t += 1;
+                continue;
+            }
+            Some(Token::One) => {
+                t += 1;
+                s += 1;
+                continue;
+            }
+            Some(Token::Literal(c)) if casemapping.fold_char(*c) == subject[s] => {
+                t += 1;
+                s += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        match backtrack {
+            Some((star, consumed)) => {
+                t = star + 1;
+                s = consumed + 1;
+                backtrack = Some((star, s));
+            }
+            None => return false,
+        }
+    }
+
+    tokens[t..].iter().all(|token| *token == Token::Many)
+}
+
+/// Parse a pattern in CIDR notation, such as "2001:db8::/32".
+fn cidr(pattern: &str) -> Option<(IpAddr, u8)> {
+    let index = pattern.find('/')?;
+    let network: IpAddr = pattern[..index].parse().ok()?;
+    let prefix_len: u8 = pattern[index + 1..].parse().ok()?;
+    let max_len = if network.is_ipv4() { 32 } else { 128 };
+
+    if prefix_len <= max_len {
+        Some((network, prefix_len))
+    } else {
+        None
+    }
+}
+
+fn in_network(address: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+    match (address, network) {
+        (IpAddr::V4(address), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            u32::from(address) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(address), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            u128::from(address) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test_wildcard {
+    use super::*;
+
+    fn wildcard(raw: &str) -> Wildcard {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn invalid() {
+        assert!("".parse::<Wildcard>().is_err());
+        assert!("spaced out".parse::<Wildcard>().is_err());
+        assert!("new\nline".parse::<Wildcard>().is_err());
+    }
+
+    #[test]
+    fn literal() {
+        assert!(wildcard("spudly").matches("spudly", Casemapping::Ascii));
+        assert!(!wildcard("spudly").matches("spud", Casemapping::Ascii));
+        assert!(!wildcard("spud").matches("spudly", Casemapping::Ascii));
+    }
+
+    #[test]
+    fn wildone() {
+        assert!(wildcard("sp?d").matches("spud", Casemapping::Ascii));
+        assert!(!wildcard("sp?d").matches("spd", Casemapping::Ascii));
+        assert!(!wildcard("?").matches("", Casemapping::Ascii));
+    }
+
+    #[test]
+    fn wildmany() {
+        assert!(wildcard("*").matches("", Casemapping::Ascii));
+        assert!(wildcard("*").matches("anything", Casemapping::Ascii));
+        assert!(wildcard("*.example.com").matches("irc.example.com", Casemapping::Ascii));
+        assert!(!wildcard("*.example.com").matches("example.com", Casemapping::Ascii));
+        assert!(wildcard("a*b*c").matches("aXbYbZc", Casemapping::Ascii));
+        assert!(!wildcard("a*b*c").matches("aXbYbZ", Casemapping::Ascii));
+        assert!(wildcard("**?**").matches("x", Casemapping::Ascii));
+        assert!(wildcard("*aab").matches("aaaab", Casemapping::Ascii));
+    }
+
+    #[test]
+    fn escape() {
+        assert!(wildcard("what\\?").matches("what?", Casemapping::Ascii));
+        assert!(!wildcard("what\\?").matches("whatx", Casemapping::Ascii));
+        assert!(wildcard("\\*").matches("*", Casemapping::Ascii));
+        assert!(!wildcard("\\*").matches("x", Casemapping::Ascii));
+        assert!(wildcard("back\\\\slash").matches("back\\slash", Casemapping::Ascii));
+        assert!(wildcard("trailing\\").matches("trailing\\", Casemapping::Ascii));
+    }
+
+    #[test]
+    fn casemapping() {
+        assert!(wildcard("SPUD[*]").matches("spud{ly}", Casemapping::Rfc1459));
+        assert!(!wildcard("SPUD[*]").matches("spud{ly}", Casemapping::Ascii));
+        assert!(wildcard("SPUD[*]").matches("spud[ly]", Casemapping::Ascii));
+        assert!(wildcard("a~").matches("A^", Casemapping::Rfc1459));
+        assert!(!wildcard("a~").matches("A^", Casemapping::StrictRfc1459));
+    }
+
+    #[test]
+    fn cidr() {
+        assert!(wildcard("192.0.2.0/24").matches_host("192.0.2.55"));
+        assert!(!wildcard("192.0.2.0/24").matches_host("192.0.3.1"));
+        assert!(wildcard("0.0.0.0/0").matches_host("203.0.113.9"));
+        assert!(wildcard("2001:db8::/32").matches_host("2001:db8:1::42"));
+        assert!(!wildcard("2001:db8::/32").matches_host("2001:db9::42"));
+        assert!(!wildcard("2001:db8::/32").matches_host("192.0.2.1"));
+        assert!(wildcard("192.0.2.1/32").matches_host("192.0.2.1"));
+
+        // CIDR masks only match addresses, and invalid ones are plain patterns.
+        assert!(!wildcard("192.0.2.0/24").matches_host("example.com"));
+        assert!(!wildcard("192.0.2.0/33").matches_host("192.0.2.1"));
+        assert!(wildcard("user/*").matches_host("user/spudly"));
+        assert!(wildcard("*.EXAMPLE.com").matches_host("irc.example.COM"));
+    }
+}
@@ -0,0 +1,93 @@
+//! TLS sessions with servers, using rustls. The server's certificate is
+//! checked against the Mozilla root certificates bundled by `webpki-roots`.
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::io;
+use std::net;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// A TLS session, shared between the reading and writing halves of a
+/// `Connection`.
+#[derive(Clone)]
+struct TlsStream(Rc<RefCell<StreamOwned<ClientConnection, net::TcpStream>>>);
+
+impl io::Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+impl io::Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+/// Complete a TLS handshake over `stream` with a server whose certificate is
+/// valid for `domain`, and split the session into the halves of a
+/// `Connection`.
+pub fn halves(
+    mut stream: net::TcpStream,
+    domain: &str,
+) -> io::Result<(Box<dyn io::BufRead>, Box<dyn io::Write>)> {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server_name = ServerName::try_from(domain.to_string())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut session =
+        ClientConnection::new(Arc::new(config), server_name).map_err(io::Error::other)?;
+
+    // The handshake happens before the stream becomes non-blocking, so it
+    // doesn't have to be picked up again partway through.
+    while session.is_handshaking() {
+        session.complete_io(&mut stream)?;
+    }
+    stream.set_nonblocking(true)?;
+
+    let tls = TlsStream(Rc::new(RefCell::new(StreamOwned::new(session, stream))));
+    Ok((Box::new(io::BufReader::new(tls.clone())), Box::new(tls)))
+}
+
+#[cfg(test)]
+mod test_tls {
+    use super::*;
+    use std::io::prelude::*;
+    use std::thread;
+
+    #[test]
+    fn rejects_plaintext_server() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .write_all(b":irc.example.com NOTICE * :Looking up your hostname\r\n")
+                .unwrap();
+        });
+
+        let stream = net::TcpStream::connect(addr).unwrap();
+        assert!(halves(stream, "irc.example.com").is_err());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_domain() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        assert_eq!(
+            io::ErrorKind::InvalidInput,
+            halves(stream, "not a domain").err().unwrap().kind(),
+        );
+    }
+}
@@ -0,0 +1,29 @@
+//! The server side of the IRC protocol. There's nothing here yet beyond the
+//! protocol types shared with the client, which are re-exported so that a
+//! server can already parse what clients send and reply to them:
+//!
+//! ```
+//! use crikey_irc_server::common::{Command, Message, MessageBody, ReplyType};
+//!
+//! let message: Message = "NICK Wiz".parse()?;
+//! if let MessageBody::Command(Command::Nick { nickname }) = message.body {
+//!     let reply = Message {
+//!         tags: Default::default(),
+//!         sender: Some("irc.example.com".parse()?),
+//!         body: MessageBody::Reply(
+//!             ReplyType::PrvWelcome,
+//!             vec![
+//!                 String::from(nickname),
+//!                 "Welcome to the Internet Relay Network".to_string(),
+//!             ]
+//!             .into(),
+//!         ),
+//!     };
+//!     assert_eq!(
+//!         ":irc.example.com 001 Wiz :Welcome to the Internet Relay Network",
+//!         String::from(reply),
+//!     );
+//! }
+//! # Ok::<(), crikey_irc_server::common::ParseError>(())
+//! ```
+pub use crikey_irc_common as common;
@@ -0,0 +1,285 @@
+use super::config::{Config, NetworkConfig};
+use log::LevelFilter;
+use std::collections::BTreeMap;
+
+pub const USAGE: &str = "\
+Usage: crikey-irc [OPTIONS] SERVER NICK [USERNAME [REALNAME]]
+       crikey-irc [OPTIONS] --config FILE
+
+Connects to SERVER as NICK, or to every network in a config file, and sends
+whatever is typed as raw IRC commands. USERNAME and REALNAME default to NICK. With several networks,
+type \"/network NAME\" to choose which one commands go to.
+
+Options:
+  -c, --config FILE      Read the networks to connect to from a TOML file
+  -l, --log-level LEVEL  How much to log: off, error, warn, info, debug (the
+                         default, which includes every line sent and
+                         received) or trace
+  -r, --record DIR       Save everything sent to and received from each
+                         network to a file in DIR, to be replayed later
+  -h, --help             Show this message
+  -V, --version          Show the version";
+
+/// What the command line asks us to do.
+#[derive(PartialEq, Debug)]
+pub enum Args {
+    Run {
+        source: Source,
+        log_level: LevelFilter,
+        /// The directory to save recordings of each session in.
+        record: Option<String>,
+    },
+    Help,
+    Version,
+}
+
+/// Where the networks to connect to come from.
+#[derive(PartialEq, Debug)]
+pub enum Source {
+    ConfigFile(String),
+    /// A single server, given as positional arguments. The username and
+    /// realname default to the nickname.
+    Server {
+        address: String,
+        nickname: String,
+        username: Option<String>,
+        realname: Option<String>,
+    },
+}
+
+impl Args {
+    /// Parse the arguments, not including the name of the program.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut config = None;
+        let mut log_level = LevelFilter::Debug;
+        let mut record = None;
+        let mut positional = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.find('=') {
+                Some(index) if arg.starts_with("--") => {
+                    (arg[..index].to_string(), Some(arg[index + 1..].to_string()))
+                }
+                _ => (arg, None),
+            };
+
+            match &name[..] {
+                "-h" | "--help" => return Ok(Args::Help),
+                "-V" | "--version" => return Ok(Args::Version),
+                "-c" | "--config" => match value.or_else(|| args.next()) {
+                    Some(path) => config = Some(path),
+                    None => return Err(format!("{} needs a file", name)),
+                },
+                "-l" | "--log-level" => match value.or_else(|| args.next()) {
+                    Some(level) => {
+                        log_level = level
+                            .parse()
+                            .map_err(|_| format!("Unknown log level {:?}", level))?
+                    }
+                    None => return Err(format!("{} needs a level", name)),
+                },
+                "-r" | "--record" => match value.or_else(|| args.next()) {
+                    Some(directory) => record = Some(directory),
+                    None => return Err(format!("{} needs a directory", name)),
+                },
+                "--" => positional.extend(args.by_ref()),
+                _ if name.starts_with('-') && name.len() > 1 => {
+                    return Err(format!("Unknown option {}", name))
+                }
+                _ => positional.push(name),
+            }
+        }
+
+        let source = match config {
+            Some(_) if !positional.is_empty() => {
+                return Err("A server can't be given along with a config file".to_string())
+            }
+            Some(path) => Source::ConfigFile(path),
+            None if positional.len() > 4 => {
+                return Err(format!("Unexpected argument {:?}", positional[4]))
+            }
+            None if positional.len() < 2 => {
+                return Err("A server and a nickname, or a config file, are required".to_string())
+            }
+            None => {
+                let mut positional = positional.into_iter();
+                Source::Server {
+                    address: positional.next().unwrap_or_default(),
+                    nickname: positional.next().unwrap_or_default(),
+                    username: positional.next(),
+                    realname: positional.next(),
+                }
+            }
+        };
+
+        Ok(Args::Run {
+            source,
+            log_level,
+            record,
+        })
+    }
+}
+
+impl Source {
+    /// Load the config, reporting any problem with it as a message.
+    pub fn load(self) -> Result<Config, String> {
+        match self {
+            Source::ConfigFile(path) => Config::load(path).map_err(|e| e.to_string()),
+            Source::Server {
+                address,
+                nickname,
+                username,
+                realname,
+            } => {
+                let username = username.unwrap_or_else(|| nickname.clone());
+                let realname = realname.unwrap_or_else(|| nickname.clone());
+                Ok(Config {
+                    networks: vec![NetworkConfig {
+                        name: address.clone(),
+                        address,
+                        tls: false,
+                        password: None,
+                        sasl: None,
+                        nickname: nickname
+                            .parse()
+                            .map_err(|_| format!("Invalid nickname {:?}", nickname))?,
+                        alternates: Vec::new(),
+                        username: username
+                            .parse()
+                            .map_err(|_| format!("Invalid username {:?}", username))?,
+                        realname,
+                        channels: Vec::new(),
+                        capabilities: Vec::new(),
+                        flood_limit: None,
+                        auto_away: None,
+                        ctcp_replies: BTreeMap::new(),
+                    }],
+                    chat_log: None,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_args {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn server(
+        address: &str,
+        nickname: &str,
+        username: Option<&str>,
+        realname: Option<&str>,
+    ) -> Args {
+        Args::Run {
+            source: Source::Server {
+                address: address.to_string(),
+                nickname: nickname.to_string(),
+                username: username.map(str::to_string),
+                realname: realname.map(str::to_string),
+            },
+            log_level: LevelFilter::Debug,
+            record: None,
+        }
+    }
+
+    #[test]
+    fn positional() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["irc-server:6667"]).is_err());
+        assert_eq!(
+            Ok(server("irc-server:6667", "potato", None, None)),
+            parse(&["irc-server:6667", "potato"])
+        );
+        assert_eq!(
+            Ok(server("irc:6667", "-nick", Some("user"), Some("Real Name"))),
+            parse(&["--", "irc:6667", "-nick", "user", "Real Name"])
+        );
+        assert!(parse(&["a", "b", "c", "d", "e"]).is_err());
+    }
+
+    #[test]
+    fn options() {
+        let config = Ok(Args::Run {
+            source: Source::ConfigFile("crikey.toml".to_string()),
+            log_level: LevelFilter::Debug,
+            record: None,
+        });
+        assert_eq!(config, parse(&["--config", "crikey.toml"]));
+        assert_eq!(config, parse(&["--config=crikey.toml"]));
+        assert_eq!(config, parse(&["-c", "crikey.toml"]));
+
+        assert_eq!(Ok(Args::Help), parse(&["-h"]));
+        assert_eq!(
+            Ok(Args::Help),
+            parse(&["--config", "crikey.toml", "--help"])
+        );
+        assert_eq!(Ok(Args::Version), parse(&["--version"]));
+
+        assert_eq!(
+            Err("--config needs a file".to_string()),
+            parse(&["--config"])
+        );
+        assert_eq!(
+            Err("Unknown option --verbose".to_string()),
+            parse(&["--verbose"])
+        );
+        assert!(parse(&["--config", "crikey.toml", "irc:6667"]).is_err());
+
+        assert_eq!(
+            Ok(Args::Run {
+                source: Source::ConfigFile("crikey.toml".to_string()),
+                log_level: LevelFilter::Debug,
+                record: Some("sessions".to_string()),
+            }),
+            parse(&["-c", "crikey.toml", "--record", "sessions"])
+        );
+        assert_eq!(Err("-r needs a directory".to_string()), parse(&["-r"]));
+    }
+
+    #[test]
+    fn log_level() {
+        let quiet = Ok(Args::Run {
+            source: Source::ConfigFile("crikey.toml".to_string()),
+            log_level: LevelFilter::Warn,
+            record: None,
+        });
+        assert_eq!(quiet, parse(&["-c", "crikey.toml", "--log-level", "warn"]));
+        assert_eq!(quiet, parse(&["-l", "WARN", "-c", "crikey.toml"]));
+        assert_eq!(quiet, parse(&["--log-level=warn", "-c", "crikey.toml"]));
+
+        assert_eq!(
+            Err("Unknown log level \"loud\"".to_string()),
+            parse(&["--log-level", "loud"])
+        );
+        assert_eq!(Err("-l needs a level".to_string()), parse(&["-l"]));
+    }
+
+    #[test]
+    fn load_server() {
+        let config = match parse(&["irc:6667", "potato"]).unwrap() {
+            Args::Run { source, .. } => source.load().unwrap(),
+            _ => panic!("Expected a source"),
+        };
+        assert_eq!("irc:6667", config.networks[0].name);
+        assert_eq!("potato", config.networks[0].nickname.as_ref());
+        assert_eq!("potato", String::from(config.networks[0].username.clone()));
+        assert_eq!("potato", config.networks[0].realname);
+
+        assert_eq!(
+            Err("Invalid nickname \"3potato\"".to_string()),
+            Source::Server {
+                address: "irc:6667".to_string(),
+                nickname: "3potato".to_string(),
+                username: None,
+                realname: None,
+            }
+            .load()
+        );
+    }
+}
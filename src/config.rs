@@ -0,0 +1,810 @@
+//! The configuration file for the `crikey-irc` binary, which describes the
+//! networks to connect to:
+//!
+//! ```toml
+//! [networks.libera]
+//! address = "irc.libera.chat:6667"
+//! nick = "spudly"
+//! alternates = ["spudly_", "potato"]
+//! channels = ["#crikey", { name = "#secret", key = "hunter2" }]
+//!
+//! [networks.libera.sasl]
+//! account = "spudly"
+//! password = "hunter2"
+//! ```
+//!
+//! Conversations can also be logged to files, with a `[chat_log]` table.
+//!
+//! See `crikey-irc.example.toml` for every option. Passwords can be left out
+//! of the file and set in the environment instead, as `CRIKEY_<NETWORK>_PASSWORD`
+//! and `CRIKEY_<NETWORK>_SASL_PASSWORD`.
+use crikey_irc_client::{AuthToken, AutoAway, ChatLogConfig, FloodLimit, Sasl};
+use crikey_irc_common::{Channel, ChannelKey, Nickname, Username};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use toml::{Table, Value};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Config {
+    /// The networks to connect to, in the order they appear in the file.
+    pub networks: Vec<NetworkConfig>,
+    /// Where to log conversations, if anywhere.
+    pub chat_log: Option<ChatLogConfig>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct NetworkConfig {
+    pub name: String,
+    pub address: String,
+    pub tls: bool,
+    /// The server password, sent with PASS.
+    pub password: Option<String>,
+    pub sasl: Option<SaslConfig>,
+    pub nickname: Nickname,
+    /// Nicknames to try in turn if ours is taken.
+    pub alternates: Vec<Nickname>,
+    pub username: Username,
+    pub realname: String,
+    pub channels: Vec<(Channel, Option<ChannelKey>)>,
+    pub capabilities: Vec<String>,
+    pub flood_limit: Option<FloodLimit>,
+    /// Mark ourselves as away after a period without input.
+    pub auto_away: Option<AutoAway>,
+    /// Replies to CTCP requests, by type, such as "VERSION".
+    pub ctcp_replies: BTreeMap<String, String>,
+}
+
+/// The SASL account to log in to. The password may be left out of the file
+/// and set in the environment instead.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SaslConfig {
+    pub account: String,
+    pub password: Option<String>,
+}
+
+/// A problem with the configuration, along with the key it was found at, such
+/// as `networks.libera.nick`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConfigError {
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(key: &str, message: &str) -> Self {
+        ConfigError {
+            key: Some(key.to_string()),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{}: {}", key, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl Config {
+    /// Read a config file, applying any overrides from the environment.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let raw = fs::read_to_string(&path).map_err(|e| ConfigError {
+            key: None,
+            message: format!("Could not read {}: {}", path.as_ref().display(), e),
+        })?;
+        let mut config: Config = raw.parse()?;
+        config.apply_env(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    /// Override secrets with values from the environment, looked up by name.
+    /// A SASL password without an account logs in as our nickname. Fails if
+    /// a SASL account still has no password.
+    pub fn apply_env<F: Fn(&str) -> Option<String>>(
+        &mut self,
+        lookup: F,
+    ) -> Result<(), ConfigError> {
+        for network in self.networks.iter_mut() {
+            let prefix = env_prefix(&network.name);
+
+            if let Some(password) = lookup(&format!("{}_PASSWORD", prefix)) {
+                network.password = Some(password);
+            }
+
+            if let Some(password) = lookup(&format!("{}_SASL_PASSWORD", prefix)) {
+                match &mut network.sasl {
+                    Some(sasl) => sasl.password = Some(password),
+                    None => {
+                        network.sasl = Some(SaslConfig {
+                            account: String::from(network.nickname.clone()),
+                            password: Some(password),
+                        })
+                    }
+                }
+            }
+
+            if network
+                .sasl
+                .as_ref()
+                .is_some_and(|sasl| sasl.password.is_none())
+            {
+                return Err(ConfigError::new(
+                    &format!("networks.{}.sasl.password", network.name),
+                    &format!("is required, here or in {}_SASL_PASSWORD", prefix),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let mut root = raw.parse::<Table>().map_err(|e| ConfigError {
+            key: None,
+            message: e.to_string().trim_end().to_string(),
+        })?;
+
+        let networks = match root.remove("networks") {
+            Some(Value::Table(networks)) => networks,
+            Some(_) => return Err(ConfigError::new("networks", "expected a table")),
+            None => return Err(ConfigError::new("networks", "no networks are configured")),
+        };
+        let chat_log = match root.remove("chat_log") {
+            Some(Value::Table(table)) => Some(chat_log(table)?),
+            Some(_) => return Err(ConfigError::new("chat_log", "expected a table")),
+            None => None,
+        };
+        unknown_keys("", &root)?;
+        if networks.is_empty() {
+            return Err(ConfigError::new("networks", "no networks are configured"));
+        }
+
+        let networks = networks
+            .into_iter()
+            .map(|(name, network)| match network {
+                Value::Table(network) => NetworkConfig::parse(name, network),
+                _ => Err(ConfigError::new(
+                    &format!("networks.{}", name),
+                    "expected a table",
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Config { networks, chat_log })
+    }
+}
+
+impl NetworkConfig {
+    /// The host part of the address, which the server's TLS certificate must
+    /// be valid for.
+    pub fn host(&self) -> &str {
+        let host = match self.address.rsplit_once(':') {
+            Some((host, _)) => host,
+            None => &self.address,
+        };
+        host.trim_start_matches('[').trim_end_matches(']')
+    }
+
+    /// The `AuthToken` to register with.
+    pub fn auth_token(&self) -> AuthToken {
+        AuthToken {
+            nickname: self.nickname.clone(),
+            username: self.username.clone(),
+            mode: 0,
+            realname: self.realname.clone(),
+            password: self.password.clone(),
+            capabilities: self.capabilities.clone(),
+            sasl: self.sasl.as_ref().and_then(|sasl| {
+                Some(Sasl {
+                    account: sasl.account.clone(),
+                    password: sasl.password.clone()?,
+                })
+            }),
+        }
+    }
+
+    fn parse(name: String, mut table: Table) -> Result<Self, ConfigError> {
+        let path = format!("networks.{}", name);
+        let mut fields = Fields {
+            path: &path,
+            table: &mut table,
+        };
+
+        let address = fields
+            .string("address")?
+            .ok_or_else(|| ConfigError::new(&format!("{}.address", path), "is required"))?;
+        let tls = fields.boolean("tls")?.unwrap_or(false);
+        if tls && !cfg!(feature = "tls") {
+            return Err(ConfigError::new(
+                &format!("{}.tls", path),
+                "crikey-irc was built without TLS support",
+            ));
+        }
+        let password = fields.string("password")?;
+
+        let nickname: Nickname = fields
+            .parsed("nick")?
+            .ok_or_else(|| ConfigError::new(&format!("{}.nick", path), "is required"))?;
+        let alternates = fields.parsed_list("alternates")?;
+        let username = match fields.parsed("username")? {
+            Some(username) => username,
+            None => nickname
+                .as_ref()
+                .parse()
+                .map_err(|_| ConfigError::new(&format!("{}.username", path), "is required"))?,
+        };
+        let realname = fields
+            .string("realname")?
+            .unwrap_or_else(|| String::from(nickname.clone()));
+
+        let channels = fields.channels("channels")?;
+        let capabilities = fields.string_list("capabilities")?;
+        let sasl = fields.sasl("sasl")?;
+        let flood_limit = fields.flood_limit("flood_limit")?;
+        let auto_away = fields.auto_away("auto_away")?;
+        let ctcp_replies = fields.ctcp_replies("ctcp")?;
+        unknown_keys(&path, fields.table)?;
+
+        Ok(NetworkConfig {
+            name,
+            address,
+            tls,
+            password,
+            sasl,
+            nickname,
+            alternates,
+            username,
+            realname,
+            channels,
+            capabilities,
+            flood_limit,
+            auto_away,
+            ctcp_replies,
+        })
+    }
+}
+
+/// Takes typed values out of a table, so that whatever's left over once
+/// everything has been taken is a key we don't recognize.
+struct Fields<'a> {
+    path: &'a str,
+    table: &'a mut Table,
+}
+
+impl Fields<'_> {
+    fn key(&self, key: &str) -> String {
+        format!("{}.{}", self.path, key)
+    }
+
+    fn string(&mut self, key: &str) -> Result<Option<String>, ConfigError> {
+        match self.table.remove(key) {
+            Some(Value::String(value)) => Ok(Some(value)),
+            Some(_) => Err(ConfigError::new(&self.key(key), "expected a string")),
+            None => Ok(None),
+        }
+    }
+
+    fn boolean(&mut self, key: &str) -> Result<Option<bool>, ConfigError> {
+        match self.table.remove(key) {
+            Some(Value::Boolean(value)) => Ok(Some(value)),
+            Some(_) => Err(ConfigError::new(&self.key(key), "expected true or false")),
+            None => Ok(None),
+        }
+    }
+
+    fn seconds(&mut self, key: &str) -> Result<Option<Duration>, ConfigError> {
+        match self.table.remove(key) {
+            Some(Value::Integer(value)) if value >= 0 => {
+                Ok(Some(Duration::from_secs(value as u64)))
+            }
+            Some(_) => Err(ConfigError::new(
+                &self.key(key),
+                "expected a number of seconds",
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn parsed<T: FromStr>(&mut self, key: &str) -> Result<Option<T>, ConfigError> {
+        match self.string(key)? {
+            Some(value) => value.parse().map(Some).map_err(|_| {
+                ConfigError::new(&self.key(key), &format!("invalid value {:?}", value))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    fn list(&mut self, key: &str) -> Result<Vec<Value>, ConfigError> {
+        match self.table.remove(key) {
+            Some(Value::Array(values)) => Ok(values),
+            Some(_) => Err(ConfigError::new(&self.key(key), "expected a list")),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn string_list(&mut self, key: &str) -> Result<Vec<String>, ConfigError> {
+        self.list(key)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| match value {
+                Value::String(value) => Ok(value),
+                _ => Err(ConfigError::new(
+                    &format!("{}[{}]", self.key(key), i),
+                    "expected a string",
+                )),
+            })
+            .collect()
+    }
+
+    fn parsed_list<T: FromStr>(&mut self, key: &str) -> Result<Vec<T>, ConfigError> {
+        self.string_list(key)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                value.parse().map_err(|_| {
+                    ConfigError::new(
+                        &format!("{}[{}]", self.key(key), i),
+                        &format!("invalid value {:?}", value),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn table(&mut self, key: &str) -> Result<Option<Table>, ConfigError> {
+        match self.table.remove(key) {
+            Some(Value::Table(table)) => Ok(Some(table)),
+            Some(_) => Err(ConfigError::new(&self.key(key), "expected a table")),
+            None => Ok(None),
+        }
+    }
+
+    /// Channels are either a name, or a table with a name and a key.
+    fn channels(&mut self, key: &str) -> Result<Vec<(Channel, Option<ChannelKey>)>, ConfigError> {
+        self.list(key)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let path = format!("{}[{}]", self.key(key), i);
+                match value {
+                    Value::String(name) => Ok((parse(&path, &name)?, None)),
+                    Value::Table(mut table) => {
+                        let mut fields = Fields {
+                            path: &path,
+                            table: &mut table,
+                        };
+                        let channel = fields
+                            .parsed("name")?
+                            .ok_or_else(|| ConfigError::new(&fields.key("name"), "is required"))?;
+                        let key = fields.parsed("key")?;
+                        unknown_keys(&path, fields.table)?;
+                        Ok((channel, key))
+                    }
+                    _ => Err(ConfigError::new(
+                        &path,
+                        "expected a channel name or a table",
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    fn sasl(&mut self, key: &str) -> Result<Option<SaslConfig>, ConfigError> {
+        let mut table = match self.table(key)? {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+        let path = self.key(key);
+        let mut fields = Fields {
+            path: &path,
+            table: &mut table,
+        };
+        let account = fields
+            .string("account")?
+            .ok_or_else(|| ConfigError::new(&fields.key("account"), "is required"))?;
+        let password = fields.string("password")?;
+        unknown_keys(&path, fields.table)?;
+        Ok(Some(SaslConfig { account, password }))
+    }
+
+    fn flood_limit(&mut self, key: &str) -> Result<Option<FloodLimit>, ConfigError> {
+        let mut table = match self.table(key)? {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+        let path = self.key(key);
+        let mut fields = Fields {
+            path: &path,
+            table: &mut table,
+        };
+        let messages = match fields.table.remove("messages") {
+            Some(Value::Integer(messages)) if messages > 0 => messages as usize,
+            Some(_) => {
+                return Err(ConfigError::new(
+                    &fields.key("messages"),
+                    "expected a positive number",
+                ))
+            }
+            None => return Err(ConfigError::new(&fields.key("messages"), "is required")),
+        };
+        let period = fields
+            .seconds("period")?
+            .ok_or_else(|| ConfigError::new(&fields.key("period"), "is required"))?;
+        let ignore_for = fields
+            .seconds("ignore_for")?
+            .ok_or_else(|| ConfigError::new(&fields.key("ignore_for"), "is required"))?;
+        unknown_keys(&path, fields.table)?;
+        Ok(Some(FloodLimit {
+            messages,
+            period,
+            ignore_for,
+        }))
+    }
+
+    fn auto_away(&mut self, key: &str) -> Result<Option<AutoAway>, ConfigError> {
+        let mut table = match self.table(key)? {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+        let path = self.key(key);
+        let mut fields = Fields {
+            path: &path,
+            table: &mut table,
+        };
+        let after = fields
+            .seconds("after")?
+            .ok_or_else(|| ConfigError::new(&fields.key("after"), "is required"))?;
+        let message = fields
+            .string("message")?
+            .unwrap_or_else(|| "Away".to_string());
+        unknown_keys(&path, fields.table)?;
+        Ok(Some(AutoAway { after, message }))
+    }
+
+    fn ctcp_replies(&mut self, key: &str) -> Result<BTreeMap<String, String>, ConfigError> {
+        let table = self.table(key)?.unwrap_or_default();
+        let path = self.key(key);
+        table
+            .into_iter()
+            .map(|(ctcp_type, reply)| match reply {
+                Value::String(reply) => Ok((ctcp_type.to_uppercase(), reply)),
+                _ => Err(ConfigError::new(
+                    &format!("{}.{}", path, ctcp_type),
+                    "expected a string",
+                )),
+            })
+            .collect()
+    }
+}
+
+fn chat_log(mut table: Table) -> Result<ChatLogConfig, ConfigError> {
+    let mut fields = Fields {
+        path: "chat_log",
+        table: &mut table,
+    };
+    let default = ChatLogConfig::default();
+    let config = ChatLogConfig {
+        directory: fields
+            .string("directory")?
+            .map_or(default.directory, PathBuf::from),
+        format: fields.parsed("format")?.unwrap_or(default.format),
+        strip_formatting: fields
+            .boolean("strip_formatting")?
+            .unwrap_or(default.strip_formatting),
+    };
+    unknown_keys("chat_log", fields.table)?;
+    Ok(config)
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_| ConfigError::new(key, &format!("invalid value {:?}", value)))
+}
+
+fn unknown_keys(path: &str, table: &Table) -> Result<(), ConfigError> {
+    match table.keys().next() {
+        Some(key) if path.is_empty() => Err(ConfigError::new(key, "unknown key")),
+        Some(key) => Err(ConfigError::new(
+            &format!("{}.{}", path, key),
+            "unknown key",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The prefix of the environment variables for a network, such as
+/// "CRIKEY_LIBERA" for "libera".
+fn env_prefix(network: &str) -> String {
+    let name: String = network
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("CRIKEY_{}", name)
+}
+
+#[cfg(test)]
+mod test_config {
+    use super::*;
+    use crikey_irc_client::LogFormat;
+
+    fn error(raw: &str) -> String {
+        raw.parse::<Config>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn example() {
+        let config: Config = include_str!("../crikey-irc.example.toml").parse().unwrap();
+        assert_eq!(2, config.networks.len());
+
+        let libera = &config.networks[0];
+        assert_eq!("libera", libera.name);
+        assert_eq!("irc.libera.chat:6667", libera.address);
+        assert!(!libera.tls);
+        assert_eq!(None, libera.password);
+        assert_eq!("spudly", libera.nickname.as_ref());
+        assert_eq!(
+            vec!["spudly_", "potato"],
+            libera
+                .alternates
+                .iter()
+                .map(|nickname| nickname.as_ref())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("pjohnson", String::from(libera.username.clone()));
+        assert_eq!("Potato Johnson", libera.realname);
+        assert_eq!(
+            vec![
+                ("#crikey".parse().unwrap(), None),
+                ("#secret".parse().unwrap(), Some("hunter2".parse().unwrap())),
+            ],
+            libera.channels
+        );
+        assert_eq!(
+            vec!["batch", "server-time", "echo-message"],
+            libera.capabilities
+        );
+        assert_eq!(
+            Some(SaslConfig {
+                account: "spudly".to_string(),
+                password: None,
+            }),
+            libera.sasl
+        );
+        assert_eq!(
+            Some(FloodLimit {
+                messages: 5,
+                period: Duration::from_secs(10),
+                ignore_for: Duration::from_secs(300),
+            }),
+            libera.flood_limit
+        );
+        assert_eq!(
+            Some(AutoAway {
+                after: Duration::from_secs(1800),
+                message: "Idle".to_string(),
+            }),
+            libera.auto_away
+        );
+        assert_eq!(
+            Some(&"crikey-irc".to_string()),
+            libera.ctcp_replies.get("VERSION")
+        );
+
+        let local = &config.networks[1];
+        assert_eq!("local", local.name);
+        assert_eq!("spudly", String::from(local.username.clone()));
+        assert_eq!("spudly", local.realname);
+        assert!(local.channels.is_empty());
+        assert_eq!(None, local.sasl);
+        assert_eq!(None, local.flood_limit);
+        assert_eq!(None, local.auto_away);
+
+        assert_eq!(
+            Some(ChatLogConfig {
+                directory: PathBuf::from("logs"),
+                format: LogFormat::Weechat,
+                strip_formatting: true,
+            }),
+            config.chat_log
+        );
+    }
+
+    #[test]
+    fn tls() {
+        let raw =
+            "[networks.local]\naddress = \"irc.example.com:6697\"\nnick = \"spudly\"\ntls = true";
+        if cfg!(feature = "tls") {
+            let config: Config = raw.parse().unwrap();
+            assert!(config.networks[0].tls);
+        } else {
+            assert_eq!(
+                "networks.local.tls: crikey-irc was built without TLS support",
+                error(raw)
+            );
+        }
+    }
+
+    #[test]
+    fn host() {
+        let host = |address: &str| {
+            let raw = format!(
+                "[networks.local]\naddress = \"{}\"\nnick = \"spudly\"",
+                address
+            );
+            let config: Config = raw.parse().unwrap();
+            config.networks[0].host().to_string()
+        };
+        assert_eq!("irc.example.com", host("irc.example.com:6697"));
+        assert_eq!("irc.example.com", host("irc.example.com"));
+        assert_eq!("::1", host("[::1]:6697"));
+    }
+
+    #[test]
+    fn auth_token() {
+        let config: Config = "[networks.local]\naddress = \"127.0.0.1:6667\"\nnick = \"spudly\"\npassword = \"letmein\"\ncapabilities = [\"batch\"]"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            AuthToken {
+                nickname: "spudly".parse().unwrap(),
+                username: "spudly".parse().unwrap(),
+                mode: 0,
+                realname: "spudly".to_string(),
+                password: Some("letmein".to_string()),
+                capabilities: vec!["batch".to_string()],
+                sasl: None,
+            },
+            config.networks[0].auth_token()
+        );
+    }
+
+    #[test]
+    fn environment() {
+        let mut config: Config = "[networks.my-net]\naddress = \"127.0.0.1:6667\"\nnick = \"spudly\"\n[networks.other]\naddress = \"127.0.0.1:6668\"\nnick = \"spudly\"\n[networks.other.sasl]\naccount = \"potato\"\npassword = \"hunter1\""
+            .parse()
+            .unwrap();
+        config
+            .apply_env(|name| match name {
+                "CRIKEY_MY_NET_PASSWORD" => Some("letmein".to_string()),
+                "CRIKEY_MY_NET_SASL_PASSWORD" | "CRIKEY_OTHER_SASL_PASSWORD" => {
+                    Some("hunter2".to_string())
+                }
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(Some("letmein".to_string()), config.networks[0].password);
+        assert_eq!(
+            Some(SaslConfig {
+                account: "spudly".to_string(),
+                password: Some("hunter2".to_string()),
+            }),
+            config.networks[0].sasl
+        );
+        assert_eq!(None, config.networks[1].password);
+        assert_eq!(
+            Some(SaslConfig {
+                account: "potato".to_string(),
+                password: Some("hunter2".to_string()),
+            }),
+            config.networks[1].sasl
+        );
+    }
+
+    #[test]
+    fn sasl_password_required() {
+        let mut config: Config = "[networks.libera]\naddress = \"x:1\"\nnick = \"spudly\"\n[networks.libera.sasl]\naccount = \"spudly\""
+            .parse()
+            .unwrap();
+        assert_eq!(None, config.networks[0].auth_token().sasl);
+        assert_eq!(
+            "networks.libera.sasl.password: is required, here or in CRIKEY_LIBERA_SASL_PASSWORD",
+            config.apply_env(|_| None).unwrap_err().to_string()
+        );
+
+        config
+            .apply_env(|name| match name {
+                "CRIKEY_LIBERA_SASL_PASSWORD" => Some("hunter2".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            Some(Sasl {
+                account: "spudly".to_string(),
+                password: "hunter2".to_string(),
+            }),
+            config.networks[0].auth_token().sasl
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!("networks: no networks are configured", error(""));
+        assert_eq!("networks: no networks are configured", error("[networks]"));
+        assert!(error("[networks.libera").contains("line 1"));
+        assert_eq!(
+            "debug: unknown key",
+            error("debug = true\n[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"")
+        );
+        assert_eq!(
+            "networks.local.address: is required",
+            error("[networks.local]\nnick = \"spudly\"")
+        );
+        assert_eq!(
+            "networks.local.nick: invalid value \"3spudly\"",
+            error("[networks.local]\naddress = \"x:1\"\nnick = \"3spudly\"")
+        );
+        assert_eq!(
+            "networks.local.nickname: unknown key",
+            error("[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"\nnickname = \"potato\"")
+        );
+        assert_eq!(
+            "networks.local.tls: expected true or false",
+            error("[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"\ntls = \"yes\"")
+        );
+        assert_eq!(
+            "networks.local.alternates[1]: invalid value \"-potato\"",
+            error("[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"\nalternates = [\"spudly_\", \"-potato\"]")
+        );
+        assert_eq!(
+            "networks.local.channels[1].key: invalid value \"two words\"",
+            error("[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"\nchannels = [\"#one\", { name = \"#two\", key = \"two words\" }]")
+        );
+        assert_eq!(
+            "networks.local.channels[0]: invalid value \"crikey\"",
+            error(
+                "[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"\nchannels = [\"crikey\"]"
+            )
+        );
+        assert_eq!(
+            "networks.local.sasl.account: is required",
+            error("[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"\n[networks.local.sasl]\npassword = \"hunter2\"")
+        );
+        assert_eq!(
+            "networks.local.flood_limit.period: expected a number of seconds",
+            error("[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"\n[networks.local.flood_limit]\nmessages = 5\nperiod = \"10s\"\nignore_for = 60")
+        );
+        assert_eq!(
+            "networks.local.auto_away.after: is required",
+            error("[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"\n[networks.local.auto_away]\nmessage = \"Idle\"")
+        );
+        assert_eq!(
+            "networks.local.ctcp.VERSION: expected a string",
+            error("[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"\n[networks.local.ctcp]\nVERSION = 1")
+        );
+        assert_eq!(
+            "chat_log.format: invalid value \"html\"",
+            error("[chat_log]\nformat = \"html\"\n[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"")
+        );
+        assert_eq!(
+            "chat_log.dir: unknown key",
+            error("[chat_log]\ndir = \"logs\"\n[networks.local]\naddress = \"x:1\"\nnick = \"spudly\"")
+        );
+    }
+
+    #[test]
+    fn env_prefix() {
+        assert_eq!("CRIKEY_LIBERA", super::env_prefix("libera"));
+        assert_eq!("CRIKEY_MY_NET_2", super::env_prefix("my-net.2"));
+    }
+}
@@ -1,8 +0,0 @@
-pub use self::keyword_list::KeywordList;
-pub use self::stats_query::StatsQuery;
-pub use self::target_mask::{HostMask, ServerMask, TargetMask};
-use super::ParseError;
-
-mod keyword_list;
-mod stats_query;
-mod target_mask;
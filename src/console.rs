@@ -0,0 +1,76 @@
+use crikey_irc_common::{TARGET_PARSE, TARGET_STATE, TARGET_WIRE};
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// Prints log records from the libraries to the terminal, coloured by what
+/// they are: lines received from the server in blue, lines sent in plain
+/// text, changes of state in yellow, and problems in red.
+pub struct Console {
+    level: LevelFilter,
+}
+
+impl Console {
+    /// Install the console as the logger for the rest of the program.
+    pub fn install(level: LevelFilter) -> Result<(), SetLoggerError> {
+        log::set_boxed_logger(Box::new(Console { level }))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl Log for Console {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            println!(
+                "{}",
+                format(record.target(), record.level(), &record.args().to_string())
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn format(target: &str, level: Level, message: &str) -> String {
+    let color = match (target, level) {
+        (_, Level::Error) | (_, Level::Warn) | (TARGET_PARSE, _) => "91",
+        (TARGET_WIRE, _) if message.starts_with("<<") => "94",
+        (TARGET_WIRE, _) => return message.to_string(),
+        (TARGET_STATE, _) => "93",
+        _ => return format!("{} {}: {}", level, target, message),
+    };
+    format!("\x1B[{}m{}\x1B[0m", color, message)
+}
+
+#[cfg(test)]
+mod test_console {
+    use super::*;
+
+    #[test]
+    fn colors() {
+        assert_eq!(
+            "\x1B[94m<< PING irc.example.com\x1B[0m",
+            format(TARGET_WIRE, Level::Debug, "<< PING irc.example.com")
+        );
+        assert_eq!(
+            ">> PONG irc.example.com",
+            format(TARGET_WIRE, Level::Debug, ">> PONG irc.example.com")
+        );
+        assert_eq!(
+            "\x1B[91mDiscarded \"FOO\"\x1B[0m",
+            format(TARGET_PARSE, Level::Warn, "Discarded \"FOO\"")
+        );
+        assert_eq!(
+            "\x1B[93mJoined #channel\x1B[0m",
+            format(TARGET_STATE, Level::Info, "Joined #channel")
+        );
+        assert_eq!(
+            "\x1B[91mThe server stopped responding\x1B[0m",
+            format(TARGET_STATE, Level::Warn, "The server stopped responding")
+        );
+        assert_eq!("INFO other: hello", format("other", Level::Info, "hello"));
+    }
+}
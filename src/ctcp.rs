@@ -0,0 +1,74 @@
+use crikey_irc_common::{Command, KeywordList, Message, MessageBody, Recipient, Sender};
+use std::collections::BTreeMap;
+
+/// The reply to a CTCP request, such as "\x01VERSION\x01", if we have one.
+/// Replies are sent as a NOTICE to whoever asked, and PINGs are always echoed
+/// back.
+pub fn reply(message: &Message, replies: &BTreeMap<String, String>) -> Option<Command> {
+    let text = match &message.body {
+        MessageBody::Command(Command::Privmsg { message, .. }) => message,
+        _ => return None,
+    };
+    let nickname = match &message.sender {
+        Some(Sender::User { nickname, .. }) => nickname.clone(),
+        _ => return None,
+    };
+
+    let ctcp = text.strip_prefix('\x01')?;
+    let ctcp = ctcp.strip_suffix('\x01').unwrap_or(ctcp);
+    let (ctcp_type, args) = match ctcp.find(' ') {
+        Some(index) => (&ctcp[..index], &ctcp[index + 1..]),
+        None => (ctcp, ""),
+    };
+    let ctcp_type = ctcp_type.to_uppercase();
+
+    let reply = match replies.get(&ctcp_type) {
+        Some(reply) => reply,
+        None if ctcp_type == "PING" => args,
+        None => return None,
+    };
+
+    Some(Command::Notice {
+        recipients: KeywordList::from(vec![Recipient::from(nickname)]),
+        message: if reply.is_empty() {
+            format!("\x01{}\x01", ctcp_type)
+        } else {
+            format!("\x01{} {}\x01", ctcp_type, reply)
+        },
+    })
+}
+
+#[cfg(test)]
+mod test_ctcp {
+    use super::*;
+
+    fn reply_to(raw: &str) -> Option<String> {
+        let mut replies = BTreeMap::new();
+        replies.insert("VERSION".to_string(), "crikey-irc".to_string());
+        reply(&raw.parse().unwrap(), &replies).map(String::from)
+    }
+
+    #[test]
+    fn replies() {
+        assert_eq!(
+            Some("NOTICE Wiz :\x01VERSION crikey-irc\x01".to_string()),
+            reply_to(":Wiz!wiz@example.com PRIVMSG spudly :\x01version\x01")
+        );
+        assert_eq!(
+            Some("NOTICE Wiz :\x01PING 1234\x01".to_string()),
+            reply_to(":Wiz!wiz@example.com PRIVMSG #channel :\x01PING 1234\x01")
+        );
+        assert_eq!(
+            None,
+            reply_to(":Wiz!wiz@example.com PRIVMSG spudly :\x01TIME\x01")
+        );
+        assert_eq!(
+            None,
+            reply_to(":Wiz!wiz@example.com PRIVMSG spudly :VERSION")
+        );
+        assert_eq!(
+            None,
+            reply_to(":Wiz!wiz@example.com NOTICE spudly :\x01VERSION\x01")
+        );
+    }
+}
@@ -1,38 +1,158 @@
+//! An IRC library implemented in Rust, split into the following crates:
+//!
+//! - `crikey-irc-common`: parsing and serialization of the protocol, and the
+//!   `Connection` that sends and receives messages. Re-exported as `common`.
+//! - `crikey-irc-client`: the `Client`, which registers with a server and
+//!   keeps track of its state. Re-exported as `client`.
+//! - `crikey-irc-server`: the server side of the protocol, to come.
+//!
+//! The types most applications need are also re-exported here:
+//!
+//! ```no_run
+//! use crikey_irc::{AuthToken, Client, Command};
+//!
+//! let mut client = Client::connect(
+//!     "127.0.0.1:6667",
+//!     AuthToken {
+//!         nickname: "spudly".parse()?,
+//!         username: "pjohnson".parse()?,
+//!         mode: 0,
+//!         realname: "Potato Johnson".to_string(),
+//!         password: None,
+//!         capabilities: Vec::new(),
+//!         sasl: None,
+//!     },
+//! )?;
+//! client.send_command(Command::privmsg("#channel", "Hello there")?).unwrap();
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+pub use self::config::{Config, ConfigError, NetworkConfig, SaslConfig};
+pub use self::console::Console;
+pub use crikey_irc_client as client;
+pub use crikey_irc_client::{
+    AuthToken, ChatLogConfig, ChatLogger, Client, ClientManager, Event, LogFormat, NetworkEvent,
+};
+pub use crikey_irc_common as common;
+pub use crikey_irc_common::{
+    Channel, ChannelKey, Command, Connection, Message, MessageBody, MessageParams, MessageTags,
+    Nickname, ParseError, Recipient, Recorder, Recording, Replay, ReplyType, Sender, Servername,
+    Username,
+};
+use log::warn;
+use std::collections::HashMap;
+use std::fs;
 use std::io;
-use std::net;
+use std::path::Path;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-mod client;
-mod connection;
+pub mod args;
+mod config;
+mod console;
+mod ctcp;
 mod terminal;
 
-pub fn run<A: net::ToSocketAddrs>(
-    addr: A,
-    nickname: String,
-    username: String,
-    realname: String,
-) -> io::Result<()> {
-    let token = client::AuthToken {
-        nickname: nickname.parse().unwrap(),
-        username: username.parse().unwrap(),
-        mode: 0,
-        realname: realname,
-        password: None,
-    };
+/// Connect to every network in the config, printing events as they arrive,
+/// logging conversations if the config says to, and sending whatever is
+/// typed to the current network as a raw command. Typing "/network NAME"
+/// changes the current network. With `record`, each session is saved to
+/// `<network>-<timestamp>.rec` in that directory.
+pub fn run(config: Config, record: Option<&Path>) -> io::Result<()> {
+    let mut manager = ClientManager::new();
+    let mut ctcp_replies = HashMap::new();
+    let mut chat_logs = HashMap::new();
+
+    for network in config.networks {
+        let connected = match record {
+            Some(directory) => {
+                fs::create_dir_all(directory)?;
+                let started = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                let path = directory.join(format!("{}-{}.rec", network.name, started));
+                connect(&network, Some(&Recorder::create(path)?))
+            }
+            None => connect(&network, None),
+        };
+        // One network being unreachable shouldn't stop us using the others.
+        let mut client = match connected {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Could not connect to {}: {}", network.name, e);
+                continue;
+            }
+        };
+        client.set_alternate_nicknames(network.alternates);
+        client.set_flood_limit(network.flood_limit);
+        client.set_auto_away(network.auto_away);
+        for (channel, key) in network.channels {
+            client.join(channel, key)?;
+        }
+        ctcp_replies.insert(network.name.clone(), network.ctcp_replies);
+        if let Some(chat_log) = &config.chat_log {
+            let logger = ChatLogger::new(&network.name, chat_log.clone());
+            chat_logs.insert(network.name.clone(), logger);
+        }
+        manager.add(&network.name, client);
+    }
 
-    let mut client = client::Client::connect(addr, token);
+    let mut current = match manager.networks().next() {
+        Some(network) => network.to_string(),
+        None => return Ok(()),
+    };
 
     let terminal = terminal::Terminal::new(io::stdin());
 
     loop {
-        if client.poll() {
+        // Events such as a network disconnecting arrive without a message
+        // being received, so take them either way.
+        let received = manager.poll();
+        while let Some(NetworkEvent { network, event }) = manager.next_event() {
+            if let Event::Message { message, .. } = &event {
+                if let Some(reply) = ctcp::reply(message, &ctcp_replies[&network]) {
+                    if let Err(e) = manager.send_command(&network, reply) {
+                        warn!("Could not reply to a CTCP request: {}", e);
+                    }
+                }
+            }
+            if let (Some(logger), Some(client)) =
+                (chat_logs.get_mut(&network), manager.get(&network))
+            {
+                if let Err(e) = logger.log(&event, client.nickname()) {
+                    warn!("Could not write to the chat log: {}", e);
+                }
+            }
+            if let Event::Back(summary) = &event {
+                println!(
+                    "\x1B[92m** [{}] Back after {}s. While away:\x1B[0m",
+                    network,
+                    summary.duration.as_secs()
+                );
+                for message in summary.private_messages.iter().chain(&summary.highlights) {
+                    println!("\x1B[92m**   {}\x1B[0m", String::from(message.clone()));
+                }
+                continue;
+            }
+            println!("\x1B[92m** [{}] {:?}\x1B[0m", network, event);
+        }
+        if received {
             continue;
         }
 
         if let Some(mut input) = terminal.read() {
             input.pop(); // trim trailing newline
-            client.send_command_raw(input)?;
+            match input.strip_prefix("/network ").map(str::trim) {
+                Some(network) if manager.get(network).is_some() => current = network.to_string(),
+                Some(network) => println!("Unknown network {:?}", network),
+                None => {
+                    if let Some(client) = manager.get_mut(&current) {
+                        if let Err(e) = client.send_command_raw(input) {
+                            warn!("Could not send to {}: {}", current, e);
+                        }
+                    }
+                }
+            }
         }
 
         thread::sleep(Duration::from_millis(100));
@@ -40,3 +160,27 @@ pub fn run<A: net::ToSocketAddrs>(
 
     //Ok(())
 }
+
+/// Connect to a network, over TLS if its config says to, and recording the
+/// session with `recorder` if there is one.
+fn connect(network: &NetworkConfig, recorder: Option<&Recorder>) -> io::Result<Client> {
+    let address = &network.address[..];
+    #[cfg(feature = "tls")]
+    {
+        if network.tls {
+            return match recorder {
+                Some(recorder) => Client::connect_tls_recorded(
+                    address,
+                    network.host(),
+                    network.auth_token(),
+                    recorder,
+                ),
+                None => Client::connect_tls(address, network.host(), network.auth_token()),
+            };
+        }
+    }
+    match recorder {
+        Some(recorder) => Client::connect_recorded(address, network.auth_token(), recorder),
+        None => Client::connect(address, network.auth_token()),
+    }
+}
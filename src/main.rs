@@ -1,12 +1,36 @@
-use crikey_irc::run;
+use crikey_irc::args::{Args, USAGE};
+use crikey_irc::{run, Console};
 use std::env;
 use std::io;
+use std::path::Path;
+use std::process;
 
 fn main() -> io::Result<()> {
-    let server_addr = env::args().nth(1).unwrap_or("127.0.0.1:6667".to_string());
-    let nick = env::args().nth(2).unwrap_or("spudly".to_string());
-    let username = env::args().nth(3).unwrap_or("pjohnson".to_string());
-    let realname = env::args().nth(4).unwrap_or("Potato Johnson".to_string());
+    let config = match Args::parse(env::args().skip(1)) {
+        Ok(Args::Run {
+            source,
+            log_level,
+            record,
+        }) => {
+            Console::install(log_level).expect("Could not install the logger");
+            source.load().map(|config| (config, record))
+        }
+        Ok(Args::Help) => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Ok(Args::Version) => {
+            println!("crikey-irc {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Err(e) => Err(format!("{}\n\n{}", e, USAGE)),
+    };
 
-    run(server_addr, nick, username, realname)
+    match config {
+        Ok((config, record)) => run(config, record.as_ref().map(Path::new)),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    }
 }
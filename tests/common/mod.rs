@@ -48,6 +48,7 @@ impl Client {
 
         let child = process::Command::new("target/debug/crikey-irc")
             .arg(&server_ip)
+            .args(["spudly", "pjohnson", "Potato Johnson"])
             .stdin(process::Stdio::null())
             .stdout(process::Stdio::null())
             .stderr(process::Stdio::null())
@@ -1,3 +1,11 @@
+// The harness predates the clippy gate and is kept as it was written.
+#[allow(
+    clippy::len_zero,
+    clippy::manual_ok_err,
+    clippy::redundant_pattern_matching,
+    clippy::unused_io_amount,
+    clippy::while_let_loop
+)]
 mod common;
 
 #[test]